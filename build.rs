@@ -0,0 +1,19 @@
+//! Embeds the git revision the binary was built from, recorded with every benchmark run.
+
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+
+    let revision = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+
+    if let Some(revision) = revision.filter(|r| !r.is_empty()) {
+        println!("cargo:rustc-env=SYSPERF_GIT_REVISION={}", revision);
+    }
+}
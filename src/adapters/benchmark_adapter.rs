@@ -34,12 +34,35 @@
 //!    `rwmixread`.
 //! 3. That’s it—`BenchmarkAdapter::run` will automatically pick it up.
 
-use crate::ports::benchmark_port::BenchmarkPort;
+use crate::domain::storage::fio::{FioJobConfig, FioResult, StorageTarget};
+use crate::domain::storage::preflight::{Preflight, PreflightConfig};
+use crate::domain::storage::scratch::{data_files, ScratchFiles, ScratchGuard, STATE_DIR};
+use crate::domain::storage::units::{format_bytes, BlockSizes, KbBase, SizeSpec};
+use crate::ports::benchmark_port::{
+    BenchmarkConfig, BenchmarkMetrics, BenchmarkParams, BenchmarkPort, BenchmarkResult,
+    BenchmarkStopped, BenchmarkTool, FIOParams, IOType, RunControl,
+};
 use crate::ports::log_port::LoggerPort;
 use anyhow::Result;
-use std::path::PathBuf;
-use std::process::Command;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running FIO is checked for its timeout and cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long FIO gets to stop its jobs after SIGTERM before it is killed
+const STOP_GRACE: Duration = Duration::from_secs(10);
+
+/// Data file size of each job in the built-in suite: ~¼ TiB, enough to observe steady-state
+/// behaviour
+const SUITE_SIZE: u64 = 256 << 30;
+
+/// Jobs per workload in the built-in suite
+const SUITE_NUMJOBS: u32 = 16;
 
 /// default FIO benchmark parameters
 const FIO_DEAULT_ARGS: [&str; 8] = [
@@ -66,6 +89,8 @@ pub struct BenchmarkAdapter {
     args: Vec<String>,
     logger: Arc<dyn LoggerPort>,
     benchmark_dir: PathBuf,
+    preflight: Preflight,
+    scratch: ScratchFiles,
 }
 
 /// Configuration for a single FIO test variant
@@ -154,7 +179,27 @@ impl BenchmarkAdapter {
     /// The method builds a dedicated data‑file and JSON result name that embeds both the config
     /// name and a timestamp.  That keeps parallel test runs from stepping on each other and makes
     /// it trivial to correlate `.dat` scratch files with their matching `.json` metrics later on.
-    fn run_benchmark_type(&self, config: &TestConfig) -> Result<()> {
+    /// Once FIO exits, the JSON report is read back and parsed into a [`FioResult`] and the
+    /// `.dat` file is removed.
+    ///
+    /// The data files must fit in the benchmark directory; see [`with_preflight`].
+    ///
+    /// [`with_preflight`]: BenchmarkAdapter::with_preflight
+    fn run_benchmark_type(&self, config: &TestConfig) -> Result<FioResult> {
+        self.preflight.check_direct_io(&self.benchmark_dir)?;
+        let capacity =
+            self.preflight
+                .check_space(&self.benchmark_dir, SUITE_SIZE, SUITE_NUMJOBS, 0)?;
+        if capacity.scaled {
+            self.logger.log_warn(&format!(
+                "Scaling {} from {} to {} per job to fit {}",
+                config.name,
+                format_bytes(SUITE_SIZE),
+                format_bytes(capacity.size),
+                self.benchmark_dir.display()
+            ));
+        }
+
         // file names (unique per run)
         let ts = chrono::Local::now().format("%Y%m%d_%H%M%S");
         let test_file = self
@@ -163,6 +208,7 @@ impl BenchmarkAdapter {
         let results_file = self
             .benchmark_dir
             .join(format!("results_{}_{}.json", config.name, ts));
+        let scratch = self.register_scratch(vec![test_file.clone()])?;
 
         // tuned to saturate NVMe
         let mut args = vec![
@@ -179,12 +225,12 @@ impl BenchmarkAdapter {
                 "--bs=1M".into()
             },
             // Push well beyond page‑cache yet stay inside most NVMe capacities.
-            "--size=256G".into(), // ~¼ TiB – enough to observe steady‑state behaviour
+            format!("--size={}", capacity.size),
             // Fan out across sockets/cores: 16 jobs × 128‑deep iodepth ≈ 2 K outstanding I/Os.
-            "--numjobs=16".into(),  // parallel threads per device
-            "--iodepth=128".into(), // deeper queue for PCIe Gen4/5 SSDs
-            "--runtime=600".into(), // three‑minute window for convergence
-            "--time_based".into(),  // use runtime, ignore size limit once sustained
+            format!("--numjobs={}", SUITE_NUMJOBS), // parallel threads per device
+            "--iodepth=128".into(),                 // deeper queue for PCIe Gen4/5 SSDs
+            "--runtime=600".into(),                 // three‑minute window for convergence
+            "--time_based".into(),                  // use runtime, ignore size limit once sustained
             "--group_reporting".into(),
             format!("--name=fio_{}_nvme", config.name),
             "--output-format=json".into(),
@@ -205,21 +251,84 @@ impl BenchmarkAdapter {
             results_file.display()
         ));
 
-        let output = Command::new("fio")
+        let output = Command::new(&self.command)
             .args(&args)
             .output()
             .map_err(|e| anyhow::anyhow!("Failed to spawn FIO: {}", e))?;
+        self.remove_scratch(scratch);
 
-        if output.status.success() {
-            self.logger
-                .log_info(&format!("✔ {} completed", config.name));
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!(
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
                 "{} failed: {}",
                 config.name,
                 String::from_utf8_lossy(&output.stderr)
-            ))
+            ));
+        }
+
+        let result = self.read_results(&results_file)?;
+        self.logger.log_info(&format!(
+            "✔ {} completed: read {:.0} IOPS / {:.1} MiB/s, write {:.0} IOPS / {:.1} MiB/s",
+            config.name,
+            result.read.iops,
+            result.read.bandwidth_mb,
+            result.write.iops,
+            result.write.bandwidth_mb
+        ));
+        Ok(result)
+    }
+
+    /// Reads and parses a FIO JSON report written via `--output`.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if:
+    /// * The report file cannot be read
+    /// * The report is not valid FIO JSON output
+    fn read_results(&self, results_file: &Path) -> Result<FioResult> {
+        let json = std::fs::read_to_string(results_file).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to read FIO results {}: {}",
+                results_file.display(),
+                e
+            )
+        })?;
+
+        let result = FioResult::from_json(&json)?;
+        if let Some(error) = &result.error {
+            self.logger.log_warn(error);
+        }
+        Ok(result)
+    }
+
+    /// Translates [`FIOParams`] into FIO command‑line arguments.
+    ///
+    /// The report is written to stdout as JSON so it can be returned verbatim as the
+    /// `raw_output` of a [`BenchmarkResult`].
+    fn fio_args(name: &str, params: &FIOParams) -> Vec<String> {
+        vec![
+            format!("--name={}", name),
+            format!("--directory={}", params.directory),
+            "--ioengine=libaio".into(),
+            "--direct=1".into(),
+            format!("--rw={}", Self::fio_rw(&params.io_type)),
+            format!("--bs={}", params.block_size),
+            format!("--size={}", params.size),
+            format!("--numjobs={}", params.num_jobs),
+            format!("--iodepth={}", params.io_depth),
+            format!("--runtime={}", params.runtime),
+            "--time_based".into(),
+            "--group_reporting".into(),
+            "--output-format=json".into(),
+        ]
+    }
+
+    /// Maps an [`IOType`] onto FIO's `--rw` values.
+    fn fio_rw(io_type: &IOType) -> &'static str {
+        match io_type {
+            IOType::SequentialRead => "read",
+            IOType::RandomRead => "randread",
+            IOType::SequentialWrite => "write",
+            IOType::RandomWrite => "randwrite",
         }
     }
 
@@ -234,7 +343,11 @@ impl BenchmarkAdapter {
     /// # Example
     ///
     /// ```
-    /// let logger = Arc::new(MyLogger::new());
+    /// use std::sync::Arc;
+    /// use sysperf_svr::adapters::benchmark_adapter::BenchmarkAdapter;
+    /// use sysperf_svr::adapters::log_adapter::FernLogger;
+    ///
+    /// let logger = Arc::new(FernLogger::new());
     /// let adapter = BenchmarkAdapter::new(
     ///     String::from("fio"),
     ///     vec![String::from("--version")],
@@ -255,7 +368,9 @@ impl BenchmarkAdapter {
             command,
             args,
             logger,
+            scratch: ScratchFiles::new(benchmark_dir.join(STATE_DIR)),
             benchmark_dir,
+            preflight: Preflight::default(),
         }
     }
 
@@ -270,7 +385,11 @@ impl BenchmarkAdapter {
     /// # Example
     ///
     /// ```
-    /// let logger = Arc::new(MyLogger::new());
+    /// use std::sync::Arc;
+    /// use sysperf_svr::adapters::benchmark_adapter::BenchmarkAdapter;
+    /// use sysperf_svr::adapters::log_adapter::FernLogger;
+    ///
+    /// let logger = Arc::new(FernLogger::new());
     /// let fio_adapter = BenchmarkAdapter::new_fio(logger);
     /// ```
     pub fn new_fio(logger: Arc<dyn LoggerPort>) -> Self {
@@ -290,10 +409,187 @@ impl BenchmarkAdapter {
             command: String::from("fio"),
             args,
             logger,
+            scratch: ScratchFiles::new(benchmark_dir.join(STATE_DIR)),
             benchmark_dir,
+            preflight: Preflight::default(),
         }
     }
 
+    /// Overrides the directory in which FIO data and result files are created
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Benchmark directory, typically `storage.test_directory` from the config
+    pub fn with_benchmark_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.benchmark_dir = dir.into();
+        self.scratch = ScratchFiles::new(self.benchmark_dir.join(STATE_DIR));
+        self
+    }
+
+    /// Sets how the built-in suite's data files are checked against the free space of the
+    /// benchmark directory
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Pre-flight settings, typically from `[storage]` in the config
+    pub fn with_preflight(mut self, config: PreflightConfig) -> Self {
+        self.preflight = Preflight::new(config);
+        self
+    }
+
+    /// Registers the data files a run is about to create, so that they are removed however the
+    /// run ends
+    fn register_scratch(&self, files: Vec<PathBuf>) -> Result<ScratchGuard<'_>> {
+        self.scratch.register(files).map_err(|e| {
+            let error_msg = format!(
+                "Failed to track scratch files in {}: {}",
+                self.scratch.state_dir().display(),
+                e
+            );
+            self.logger.log_error(&error_msg);
+            anyhow::anyhow!(error_msg)
+        })
+    }
+
+    /// Removes a run's data files, logging what was freed
+    fn remove_scratch(&self, scratch: ScratchGuard<'_>) {
+        let removed = scratch.remove();
+        if !removed.files.is_empty() {
+            self.logger.log_info(&format!(
+                "Removed {} scratch files ({})",
+                removed.files.len(),
+                format_bytes(removed.bytes)
+            ));
+        }
+        for (file, e) in &removed.errors {
+            self.logger.log_warn(&format!(
+                "Failed to remove scratch file {}: {}",
+                file.display(),
+                e
+            ));
+        }
+    }
+
+    /// Spawns FIO with `args` (which must request JSON output on stdout) and parses its report
+    fn execute_fio(
+        &self,
+        name: &str,
+        args: &[String],
+        control: &RunControl,
+    ) -> Result<BenchmarkResult> {
+        self.logger.log_debug(&format!("FIO args: {:?}", args));
+
+        let mut child = Command::new(&self.command)
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to spawn FIO: {}", e))?;
+
+        // Drain both pipes while waiting so that FIO never blocks on a full pipe
+        let stdout = drain(child.stdout.take());
+        let stderr = drain(child.stderr.take());
+
+        let (status, stopped) = self
+            .wait_fio(name, &mut child, control)
+            .map_err(|e| anyhow::anyhow!("Failed to wait for FIO: {}", e))?;
+        if let Some(reason) = stopped {
+            // FIO's job processes may still hold the pipes, so the output is left behind
+            self.logger.log_error(&format!("{} {}", name, reason));
+            return Err(anyhow::Error::new(reason).context(format!("{} stopped", name)));
+        }
+        let output = std::process::Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        };
+
+        if !output.status.success() {
+            let error_msg = format!(
+                "{} failed: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            self.logger.log_error(&error_msg);
+            return Err(anyhow::anyhow!(error_msg));
+        }
+
+        let raw_output = String::from_utf8_lossy(&output.stdout).to_string();
+        let result = FioResult::from_json(&raw_output)?;
+        if let Some(error) = &result.error {
+            self.logger.log_warn(error);
+        }
+        self.logger.log_info(&format!("✔ {} completed", name));
+
+        Ok(BenchmarkResult {
+            tool: BenchmarkTool::FIO,
+            metrics: BenchmarkMetrics::FIO(result.into()),
+            raw_output,
+            system_metrics: None,
+            run_id: None,
+        })
+    }
+
+    /// Waits for FIO to exit, stopping it once `control` times out or is cancelled
+    ///
+    /// # Returns
+    ///
+    /// * `io::Result<(ExitStatus, Option<BenchmarkStopped>)>` - FIO's exit status and, when it
+    ///   was stopped, why
+    fn wait_fio(
+        &self,
+        name: &str,
+        child: &mut Child,
+        control: &RunControl,
+    ) -> std::io::Result<(ExitStatus, Option<BenchmarkStopped>)> {
+        let started = Instant::now();
+        loop {
+            if let Some(status) = child.try_wait()? {
+                // Ctrl-C reaches FIO too, which may exit before the cancellation is noticed
+                let stopped = control
+                    .is_cancelled()
+                    .then_some(BenchmarkStopped::Cancelled);
+                return Ok((status, stopped));
+            }
+
+            let stopped = if control.is_cancelled() {
+                Some(BenchmarkStopped::Cancelled)
+            } else {
+                control
+                    .timeout()
+                    .filter(|timeout| started.elapsed() >= *timeout)
+                    .map(BenchmarkStopped::TimedOut)
+            };
+            if let Some(reason) = stopped {
+                self.logger
+                    .log_warn(&format!("{} {}; stopping FIO", name, reason));
+                return Ok((Self::stop_fio(child)?, Some(reason)));
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Stops FIO the way Ctrl-C would: SIGTERM lets it stop its job processes and clean up,
+    /// and only if it has not exited after [`STOP_GRACE`] is it killed
+    fn stop_fio(child: &mut Child) -> std::io::Result<ExitStatus> {
+        // SAFETY: kill(2) has no memory-safety preconditions; the PID is our own child, which
+        // has not been reaped yet
+        unsafe {
+            libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+        }
+
+        let deadline = Instant::now() + STOP_GRACE;
+        while Instant::now() < deadline {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        child.kill()?;
+        child.wait()
+    }
+
     /// Formats command output for logging
     ///
     /// # Arguments
@@ -319,44 +615,191 @@ impl BenchmarkPort for BenchmarkAdapter {
     ///
     /// # Returns
     ///
-    /// * `Result<()>` - Success or error with details
+    /// * `Result<Vec<FioResult>>` - Parsed results, one per workload variant
     ///
     /// # Errors
     ///
     /// Returns error if:
     /// * Command execution fails
     /// * Command returns non-zero exit status
-    fn run(&self) -> Result<()> {
+    /// * The JSON report cannot be parsed
+    fn run(&self) -> Result<Vec<FioResult>> {
         self.validate()?;
         self.check_fio_installation()?;
 
+        let mut results = Vec::new();
         for config in Self::get_test_configs() {
-            self.run_benchmark_type(&config)?;
+            results.push(self.run_benchmark_type(&config)?);
         }
 
         self.logger.log_info("All benchmarks completed");
-        Ok(())
+        Ok(results)
+    }
+
+    /// Runs a single benchmark described by a [`BenchmarkConfig`]
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Benchmark name, tool and tool‑specific parameters
+    /// * `control` - Timeout and cancellation; FIO is stopped when either fires
+    ///
+    /// # Returns
+    ///
+    /// * `Result<BenchmarkResult>` - Parsed metrics together with the raw FIO JSON report
+    ///
+    /// # Errors
+    ///
+    /// Returns error if:
+    /// * The config is not a FIO benchmark
+    /// * The block size or size is not a valid FIO size
+    /// * The test directory cannot be created
+    /// * FIO fails or produces unparsable output
+    /// * FIO was stopped, in which case the error carries a [`BenchmarkStopped`]
+    fn run_config(
+        &self,
+        config: &BenchmarkConfig,
+        control: &RunControl,
+    ) -> Result<BenchmarkResult> {
+        let params = match (&config.tool, &config.params) {
+            (BenchmarkTool::FIO, BenchmarkParams::FIO(params)) => params,
+            (tool, _) => {
+                let error_msg = format!(
+                    "Benchmark {} uses {:?}, which this adapter cannot run",
+                    config.name, tool
+                );
+                self.logger.log_error(&error_msg);
+                return Err(anyhow::anyhow!(error_msg));
+            }
+        };
+
+        BlockSizes::parse(&params.block_size, KbBase::default())
+            .and(SizeSpec::parse(&params.size, KbBase::default()))
+            .map_err(|e| {
+                let error_msg = format!("Benchmark {}: {}", config.name, e);
+                self.logger.log_error(&error_msg);
+                anyhow::anyhow!(error_msg)
+            })?;
+
+        std::fs::create_dir_all(&params.directory).map_err(|e| {
+            let error_msg = format!(
+                "Failed to create benchmark directory {}: {}",
+                params.directory, e
+            );
+            self.logger.log_error(&error_msg);
+            anyhow::anyhow!(error_msg)
+        })?;
+
+        let args = Self::fio_args(&config.name, params);
+        self.logger.log_info(&format!(
+            "▶︎ {} ({} {} × {} jobs, iodepth {}, {}s)",
+            config.name,
+            Self::fio_rw(&params.io_type),
+            params.block_size,
+            params.num_jobs,
+            params.io_depth,
+            params.runtime
+        ));
+
+        let job = FioJobConfig {
+            numjobs: params.num_jobs,
+            ..Default::default()
+        };
+        let target = StorageTarget {
+            path: params.directory.clone().into(),
+            target_type: "directory".into(),
+            options: Default::default(),
+        };
+        let scratch = self.register_scratch(data_files(&config.name, &job, &target))?;
+        let result = self.execute_fio(&config.name, &args, control);
+        self.remove_scratch(scratch);
+        result
+    }
+
+    /// Runs a [`FioJobConfig`] against a [`StorageTarget`]
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Job name, used for `--name` and in log output
+    /// * `job` - FIO job parameters, typically a config profile merged with its defaults
+    /// * `target` - Device, file or directory to exercise
+    /// * `control` - Timeout and cancellation; FIO is stopped when either fires
+    ///
+    /// The data files FIO creates are removed once it exits, however the run ends, unless the
+    /// target keeps them for reuse (see [`data_files`]).
+    ///
+    /// # Returns
+    ///
+    /// * `Result<BenchmarkResult>` - Parsed metrics together with the raw FIO JSON report
+    ///
+    /// # Errors
+    ///
+    /// Returns error if:
+    /// * The job's `bs`, `size`, `bssplit` or `kb_base` is invalid
+    /// * A directory target cannot be created
+    /// * FIO fails or produces unparsable output
+    /// * FIO was stopped, in which case the error carries a [`BenchmarkStopped`]
+    fn run_job(
+        &self,
+        name: &str,
+        job: &FioJobConfig,
+        target: &StorageTarget,
+        control: &RunControl,
+    ) -> Result<BenchmarkResult> {
+        job.validate_units().map_err(|e| {
+            let error_msg = format!("Job {}: {}", name, e);
+            self.logger.log_error(&error_msg);
+            anyhow::anyhow!(error_msg)
+        })?;
+
+        if target.is_directory() {
+            std::fs::create_dir_all(&target.path).map_err(|e| {
+                let error_msg = format!(
+                    "Failed to create target directory {}: {}",
+                    target.path.display(),
+                    e
+                );
+                self.logger.log_error(&error_msg);
+                anyhow::anyhow!(error_msg)
+            })?;
+        }
+
+        let mut args = job.to_fio_args(name, target);
+        args.push("--output-format=json".into());
+        self.logger.log_info(&format!(
+            "▶︎ {} ({} {} × {} jobs, iodepth {}) → {}",
+            name,
+            job.rw,
+            job.bs,
+            job.numjobs,
+            job.iodepth,
+            target.path.display()
+        ));
+
+        let scratch = self.register_scratch(data_files(name, job, target))?;
+        let result = self.execute_fio(name, &args, control);
+        self.remove_scratch(scratch);
+        result
     }
 
     /// Checks if FIO is installed
     ///
     /// # Returns
     ///
-    /// * `Result<()>` - Success or error with details
+    /// * `Result<String>` - The installed FIO version (e.g. "fio-3.36")
     ///
     /// # Errors
     ///
     /// Returns error if:
     /// * FIO is not installed
-    fn check_fio_installation(&self) -> Result<()> {
+    fn check_fio_installation(&self) -> Result<String> {
         self.logger.log_debug("Checking FIO installation");
 
-        match Command::new("fio").arg("--version").output() {
+        match Command::new(&self.command).arg("--version").output() {
             Ok(output) => {
                 if output.status.success() {
-                    let version = String::from_utf8_lossy(&output.stdout);
+                    let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
                     self.logger.log_info(&format!("FIO version: {}", version));
-                    Ok(())
+                    Ok(version)
                 } else {
                     let error = String::from_utf8_lossy(&output.stderr);
                     Err(anyhow::anyhow!("FIO check failed: {}", error))
@@ -383,21 +826,13 @@ impl BenchmarkPort for BenchmarkAdapter {
     fn validate(&self) -> Result<()> {
         self.logger.log_debug("Validating benchmark directory");
 
-        // Get project root directory (where Cargo.toml is located)
-        let project_root = std::env::current_dir().map_err(|e| {
-            let error_msg = format!("Could not determine current directory: {}", e);
-            self.logger.log_error(&error_msg);
-            anyhow::anyhow!(error_msg)
-        })?;
-
-        // Create benchmark directory path within project root
-        let benchmark_dir = project_root.join("benchmark");
+        let benchmark_dir = &self.benchmark_dir;
 
         // Create directory if it doesn't exist
         if !benchmark_dir.exists() {
             self.logger
                 .log_info("Benchmark directory does not exist, creating it");
-            std::fs::create_dir_all(&benchmark_dir).map_err(|e| {
+            std::fs::create_dir_all(benchmark_dir).map_err(|e| {
                 let error_msg = format!(
                     "Failed to create benchmark directory {}: {}",
                     benchmark_dir.display(),
@@ -452,22 +887,27 @@ impl BenchmarkPort for BenchmarkAdapter {
         }
     }
 
-    /// Executes FIO benchmark with version check
+    /// Executes the benchmark command with the arguments the adapter was created with
+    ///
+    /// The CLI creates the adapter with `--version`, making this a version check.
     ///
     /// # Returns
     ///
-    /// * `Result<String>` - FIO version string or error
+    /// * `Result<String>` - The command's output, e.g. the FIO version string
     ///
     /// # Errors
     ///
     /// Returns error if:
     /// * FIO command is not available
-    /// * Version check fails
+    /// * The command fails
     fn run_fio(&self) -> Result<String> {
-        self.logger.log_debug("Running FIO version check");
+        self.logger.log_debug(&format!(
+            "Running {} {}",
+            self.command,
+            self.args.join(" ")
+        ));
 
-        // Execute FIO version check
-        let output = match Command::new("fio").arg("--version").output() {
+        let output = match Command::new(&self.command).args(&self.args).output() {
             Ok(output) => output,
             Err(e) => {
                 let error_msg = format!("Failed to execute FIO command: {}", e);
@@ -490,3 +930,95 @@ impl BenchmarkPort for BenchmarkAdapter {
     }
 }
 
+/// Reads `pipe` to the end on a background thread.
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buffer);
+        }
+        buffer
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fio_args_from_params() {
+        let params = FIOParams {
+            directory: "/mnt/benchmark".to_string(),
+            block_size: "64k".to_string(),
+            io_type: IOType::SequentialWrite,
+            size: "1G".to_string(),
+            runtime: 30,
+            num_jobs: 4,
+            io_depth: 32,
+        };
+
+        let args = BenchmarkAdapter::fio_args("fio_test_64k", &params);
+
+        assert!(args.contains(&"--name=fio_test_64k".to_string()));
+        assert!(args.contains(&"--directory=/mnt/benchmark".to_string()));
+        assert!(args.contains(&"--rw=write".to_string()));
+        assert!(args.contains(&"--bs=64k".to_string()));
+        assert!(args.contains(&"--size=1G".to_string()));
+        assert!(args.contains(&"--numjobs=4".to_string()));
+        assert!(args.contains(&"--iodepth=32".to_string()));
+        assert!(args.contains(&"--runtime=30".to_string()));
+        assert!(args.contains(&"--output-format=json".to_string()));
+    }
+
+    #[test]
+    fn test_fio_rw_mapping() {
+        assert_eq!(BenchmarkAdapter::fio_rw(&IOType::SequentialRead), "read");
+        assert_eq!(BenchmarkAdapter::fio_rw(&IOType::RandomRead), "randread");
+        assert_eq!(BenchmarkAdapter::fio_rw(&IOType::SequentialWrite), "write");
+        assert_eq!(BenchmarkAdapter::fio_rw(&IOType::RandomWrite), "randwrite");
+    }
+
+    #[test]
+    fn test_stops_fio_on_timeout_and_cancel() {
+        use crate::adapters::log_adapter::FernLogger;
+        use crate::ports::benchmark_port::BenchmarkStopped;
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stands in for a FIO run that never finishes by itself
+        let dir = tempfile::tempdir().unwrap();
+        let fio = dir.path().join("fio");
+        std::fs::write(&fio, "#!/bin/sh\nexec sleep 60\n").unwrap();
+        std::fs::set_permissions(&fio, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let adapter = BenchmarkAdapter::new(
+            fio.display().to_string(),
+            vec![],
+            Arc::new(FernLogger::new()),
+        )
+        .with_benchmark_dir(dir.path());
+        let job = FioJobConfig::default();
+        let target = StorageTarget::new_file(dir.path().join("data"), None);
+
+        let started = Instant::now();
+        let control = RunControl::new().with_timeout(Duration::from_millis(200));
+        let e = adapter
+            .run_job("slow", &job, &target, &control)
+            .unwrap_err();
+        assert!(matches!(
+            BenchmarkStopped::find(&e),
+            Some(BenchmarkStopped::TimedOut(_))
+        ));
+
+        let control = RunControl::new();
+        let e = thread::scope(|scope| {
+            let run = scope.spawn(|| adapter.run_job("slow", &job, &target, &control));
+            thread::sleep(Duration::from_millis(200));
+            control.cancel();
+            run.join().unwrap().unwrap_err()
+        });
+        assert!(matches!(
+            BenchmarkStopped::find(&e),
+            Some(BenchmarkStopped::Cancelled)
+        ));
+        assert!(started.elapsed() < STOP_GRACE);
+    }
+}
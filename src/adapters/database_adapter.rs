@@ -1,14 +1,81 @@
-use crate::database::{EmbeddedStore, KeyValueStore};
+use crate::database::{DatabaseError, EmbeddedStore, KeyValueStore};
 use crate::ports::database_port::DatabasePort;
+use anyhow::Context;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
+/// How long to wait for another process to release the database
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Adapter exposing the embedded store as a [`DatabasePort`]
+///
+/// The database file is opened on first use, so commands that never touch the database do not
+/// contend for its lock with another running instance.  While another process holds the file,
+/// opening it is retried for up to ten seconds.
 pub struct DatabaseAdapter {
-    store: EmbeddedStore,
+    path: PathBuf,
+    store: OnceLock<EmbeddedStore>,
+    /// Serializes opening; the file cannot be opened twice
+    opening: Mutex<()>,
+    /// Keep the file open between operations
+    keep_open: bool,
 }
 
 impl DatabaseAdapter {
-    pub fn new() -> Self {
+    /// Creates an adapter for the database at `path` (usually `general.database_path`).
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
         Self {
-            store: EmbeddedStore::new(),
+            path: path.into(),
+            store: OnceLock::new(),
+            opening: Mutex::new(()),
+            keep_open: true,
+        }
+    }
+
+    /// Opens the file for every operation and closes it again afterwards.
+    ///
+    /// Long-running processes use this so that other instances can use the database between
+    /// their operations.
+    pub fn close_when_idle(mut self) -> Self {
+        self.keep_open = false;
+        self
+    }
+
+    /// Runs `f` against the opened store.
+    fn with_store<T>(
+        &self,
+        f: impl FnOnce(&EmbeddedStore) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        if let Some(store) = self.store.get() {
+            return f(store);
+        }
+
+        let _opening = self.opening.lock().unwrap_or_else(|e| e.into_inner());
+        if !self.keep_open {
+            return f(&self.open()?);
+        }
+        if let Some(store) = self.store.get() {
+            return f(store);
+        }
+        let store = self.open()?;
+        f(self.store.get_or_init(|| store))
+    }
+
+    /// Opens the file, waiting while another process has it open.
+    fn open(&self) -> anyhow::Result<EmbeddedStore> {
+        let started = Instant::now();
+        loop {
+            match EmbeddedStore::open(&self.path) {
+                Err(DatabaseError::Locked { .. }) if started.elapsed() < LOCK_TIMEOUT => {
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                result => {
+                    return result.with_context(|| {
+                        format!("Failed to open database {}", self.path.display())
+                    })
+                }
+            }
         }
     }
 }
@@ -17,15 +84,39 @@ impl DatabasePort for DatabaseAdapter {}
 
 impl KeyValueStore for DatabaseAdapter {
     fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
-        self.store.get(key)
+        self.with_store(|store| store.get(key))
     }
 
-    fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
-        self.store.set(key, value)
+    fn set(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        self.with_store(|store| store.set(key, value))
     }
 
-    fn delete(&mut self, key: &str) -> anyhow::Result<()> {
-        self.store.delete(key)
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.with_store(|store| store.delete(key))
+    }
+
+    fn list(&self, prefix: &str) -> anyhow::Result<Vec<(String, String)>> {
+        self.with_store(|store| store.list(prefix))
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idle_adapter_shares_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+
+        let idle = DatabaseAdapter::new(&path).close_when_idle();
+        idle.set("runs/a", "1").unwrap();
+
+        // The idle adapter has closed the file, so another opener gets it
+        let other = DatabaseAdapter::new(&path);
+        assert_eq!(other.get("runs/a").unwrap().as_deref(), Some("1"));
+        drop(other);
+
+        assert_eq!(idle.list("runs/").unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,628 @@
+//! InfluxDB Adapter Module
+//!
+//! Implements [`StoragePort`] by exporting metric samples and benchmark runs to an InfluxDB
+//! line protocol endpoint (`[database.remote]`).  The adapter is write-only: queries are served
+//! by InfluxDB itself, and retention of exported points is the bucket's.
+//!
+//! Writes are queued to a background thread that sends them in batches of `batch_size` lines,
+//! or after `flush_interval` seconds for a partial batch.  Requests that fail because the
+//! endpoint is unreachable, overloaded (429) or failing (5xx) are retried with exponential
+//! backoff; batches that still cannot be sent are appended to `buffer_path` and replayed, oldest
+//! first, once the endpoint accepts writes again.  Batches the endpoint rejects as malformed
+//! (other 4xx) are logged and dropped, since resending them cannot succeed.
+//!
+//! Dropping the adapter flushes whatever is still queued.
+
+use crate::config::RemoteConfig;
+use crate::domain::metrics::line_protocol::{run_points, sample_points, Point};
+use crate::domain::metrics::series::MetricKind;
+use crate::domain::storage::history::{HostIdentity, RunFilter, RunRecord};
+use crate::ports::log_port::LoggerPort;
+use crate::ports::storage_port::StoragePort;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Delay before the first retry; doubled for every further retry
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest delay between two retries
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Adapter that exports results to InfluxDB as line protocol
+pub struct InfluxDbAdapter {
+    sender: Option<Sender<Vec<String>>>,
+    worker: Option<JoinHandle<()>>,
+    /// Tags added to every metric sample
+    tags: BTreeMap<String, String>,
+}
+
+/// Why a request failed
+enum SendError {
+    /// The endpoint could not take the batch now; it may succeed later
+    Unavailable(String),
+    /// The endpoint refused the batch itself
+    Rejected(String),
+}
+
+/// State of the background thread that talks to the endpoint
+struct Exporter {
+    agent: ureq::Agent,
+    url: String,
+    query: Vec<(&'static str, String)>,
+    token: Option<String>,
+    batch_size: usize,
+    max_retries: u32,
+    buffer_path: PathBuf,
+    max_buffer_bytes: u64,
+    /// Whether the last request reached the endpoint; while it is down, requests are not
+    /// retried so that queued writes are buffered without delay
+    available: bool,
+    logger: Arc<dyn LoggerPort>,
+}
+
+impl InfluxDbAdapter {
+    /// Creates an adapter for `config` and starts its background sender.
+    ///
+    /// Every exported point is tagged with this host's name.
+    pub fn new(config: &RemoteConfig, logger: Arc<dyn LoggerPort>) -> Self {
+        let mut tags = BTreeMap::new();
+        tags.insert("host".to_string(), HostIdentity::detect().hostname);
+
+        let exporter = Exporter::new(config, logger);
+        let flush_interval = Duration::from_secs(config.flush_interval);
+        let (sender, receiver) = mpsc::channel::<Vec<String>>();
+
+        let worker = thread::spawn(move || {
+            let mut exporter = exporter;
+            let mut batch: Vec<String> = Vec::new();
+            let mut deadline: Option<Instant> = None;
+
+            loop {
+                let received = match deadline {
+                    Some(deadline) => {
+                        receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                    None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(lines) => {
+                        batch.extend(lines);
+                        deadline.get_or_insert_with(|| Instant::now() + flush_interval);
+                        if batch.len() >= exporter.batch_size {
+                            exporter.flush(std::mem::take(&mut batch));
+                            deadline = None;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        exporter.flush(std::mem::take(&mut batch));
+                        deadline = None;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        exporter.flush(batch);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self {
+            sender: Some(sender),
+            worker: Some(worker),
+            tags,
+        }
+    }
+
+    /// Queues points for export.
+    fn export(&self, points: Vec<Point>) -> Result<()> {
+        let lines: Vec<String> = points.iter().filter_map(Point::to_line).collect();
+        if lines.is_empty() {
+            return Ok(());
+        }
+        self.sender
+            .as_ref()
+            .and_then(|sender| sender.send(lines).ok())
+            .ok_or_else(|| anyhow!("InfluxDB exporter has stopped"))
+    }
+}
+
+impl Drop for InfluxDbAdapter {
+    fn drop(&mut self) {
+        // Closing the channel makes the worker flush what is queued and exit
+        self.sender.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl StoragePort for InfluxDbAdapter {
+    /// Exports a sample stored under `<metric>/<timestamp_ms>`.
+    ///
+    /// `value` is either the collector output or a record with the output under `data`.
+    fn store_metrics(&self, key: &str, value: &Value) -> Result<()> {
+        let mut segments = key.split('/');
+        let kind: MetricKind = segments
+            .next()
+            .unwrap_or_default()
+            .parse()
+            .map_err(|e| anyhow!("Cannot export metrics key {}: {}", key, e))?;
+        let timestamp_ms = segments
+            .next_back()
+            .and_then(|ts| ts.parse().ok())
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+        let data = value.get("data").unwrap_or(value);
+
+        self.export(sample_points(kind, timestamp_ms, data, &self.tags))
+    }
+
+    fn retrieve_metrics(&self, _key: &str) -> Result<Option<Value>> {
+        Ok(None)
+    }
+
+    fn metric_keys(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn delete_metrics(&self, _key: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn save_run(&self, run: &RunRecord) -> Result<()> {
+        self.export(run_points(run))
+    }
+
+    fn get_run(&self, _id: &str) -> Result<Option<RunRecord>> {
+        Ok(None)
+    }
+
+    fn list_runs(&self, _filter: &RunFilter) -> Result<Vec<RunRecord>> {
+        Ok(Vec::new())
+    }
+
+    fn delete_run(&self, _id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    fn set_pinned(&self, _id: &str, _pinned: bool) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+impl Exporter {
+    fn new(config: &RemoteConfig, logger: Arc<dyn LoggerPort>) -> Self {
+        let base = config.url.trim_end_matches('/');
+        let (url, mut query) = match (&config.bucket, &config.database) {
+            (Some(bucket), _) => {
+                let mut query = vec![("bucket", bucket.clone())];
+                if let Some(org) = &config.org {
+                    query.push(("org", org.clone()));
+                }
+                (format!("{}/api/v2/write", base), query)
+            }
+            (None, database) => (
+                format!("{}/write", base),
+                vec![("db", database.clone().unwrap_or_default())],
+            ),
+        };
+        query.push(("precision", "ns".to_string()));
+
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(config.timeout))
+                .build(),
+            url,
+            query,
+            token: config.token.clone(),
+            batch_size: config.batch_size.max(1),
+            max_retries: config.max_retries,
+            buffer_path: config.buffer_path.clone(),
+            max_buffer_bytes: config.max_buffer_mb * 1024 * 1024,
+            available: true,
+            logger,
+        }
+    }
+
+    /// Sends `lines` after any previously buffered lines, buffering what cannot be sent.
+    fn flush(&mut self, lines: Vec<String>) {
+        if lines.is_empty() {
+            return;
+        }
+        // Buffered lines go first so that points reach the endpoint in order
+        if !self.replay_buffer() {
+            self.buffer(&lines);
+            return;
+        }
+
+        for (i, chunk) in lines.chunks(self.batch_size).enumerate() {
+            match self.send(&chunk.join("\n")) {
+                Ok(()) => {}
+                Err(SendError::Rejected(e)) => self.logger.log_error(&format!(
+                    "InfluxDB rejected {} lines, dropping them: {}",
+                    chunk.len(),
+                    e
+                )),
+                Err(SendError::Unavailable(e)) => {
+                    self.logger.log_warn(&format!(
+                        "InfluxDB write to {} failed, buffering to {}: {}",
+                        self.url,
+                        self.buffer_path.display(),
+                        e
+                    ));
+                    self.buffer(&lines[i * self.batch_size..]);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Sends the buffered lines.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the buffer is now empty, `false` if the endpoint is still unavailable.
+    fn replay_buffer(&mut self) -> bool {
+        let content = match fs::read_to_string(&self.buffer_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return true,
+            Err(e) => {
+                self.logger.log_warn(&format!(
+                    "Failed to read InfluxDB buffer {}: {}",
+                    self.buffer_path.display(),
+                    e
+                ));
+                return true;
+            }
+        };
+        let lines: Vec<&str> = content.lines().filter(|l| !l.is_empty()).collect();
+
+        for (i, chunk) in lines.chunks(self.batch_size).enumerate() {
+            match self.send(&chunk.join("\n")) {
+                Ok(()) => {}
+                Err(SendError::Rejected(e)) => self.logger.log_error(&format!(
+                    "InfluxDB rejected {} buffered lines, dropping them: {}",
+                    chunk.len(),
+                    e
+                )),
+                Err(SendError::Unavailable(_)) => {
+                    let remaining = lines[i * self.batch_size..].join("\n") + "\n";
+                    if let Err(e) = fs::write(&self.buffer_path, remaining) {
+                        self.logger.log_warn(&format!(
+                            "Failed to rewrite InfluxDB buffer {}: {}",
+                            self.buffer_path.display(),
+                            e
+                        ));
+                    }
+                    return false;
+                }
+            }
+        }
+
+        if !lines.is_empty() {
+            self.logger
+                .log_info(&format!("Sent {} buffered lines to InfluxDB", lines.len()));
+        }
+        let _ = fs::remove_file(&self.buffer_path);
+        true
+    }
+
+    /// Appends lines to the buffer file, unless that would exceed `max_buffer_mb`.
+    fn buffer(&self, lines: &[String]) {
+        let data = lines.join("\n") + "\n";
+        let size = fs::metadata(&self.buffer_path)
+            .map(|m| m.len())
+            .unwrap_or(0);
+        if size + data.len() as u64 > self.max_buffer_bytes {
+            self.logger.log_error(&format!(
+                "InfluxDB buffer {} is full, dropping {} lines",
+                self.buffer_path.display(),
+                lines.len()
+            ));
+            return;
+        }
+
+        if let Some(parent) = self.buffer_path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let appended = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.buffer_path)
+            .and_then(|mut file| file.write_all(data.as_bytes()));
+        if let Err(e) = appended {
+            self.logger.log_error(&format!(
+                "Failed to buffer {} lines to {}: {}",
+                lines.len(),
+                self.buffer_path.display(),
+                e
+            ));
+        }
+    }
+
+    /// Posts one batch, retrying while the endpoint is unavailable.
+    fn send(&mut self, body: &str) -> Result<(), SendError> {
+        let retries = if self.available { self.max_retries } else { 0 };
+        let mut attempt = 0;
+        loop {
+            match self.post(body) {
+                Err(SendError::Unavailable(e)) if attempt < retries => {
+                    self.logger.log_debug(&format!(
+                        "InfluxDB write failed ({}), retry {} of {}",
+                        e,
+                        attempt + 1,
+                        retries
+                    ));
+                    thread::sleep(backoff(attempt));
+                    attempt += 1;
+                }
+                result => {
+                    self.available = !matches!(result, Err(SendError::Unavailable(_)));
+                    return result;
+                }
+            }
+        }
+    }
+
+    fn post(&self, body: &str) -> Result<(), SendError> {
+        let mut request = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "text/plain; charset=utf-8");
+        for (key, value) in &self.query {
+            request = request.query(key, value);
+        }
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Token {}", token));
+        }
+
+        match request.send_string(body) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(code, _)) if code == 429 || code >= 500 => {
+                Err(SendError::Unavailable(format!("HTTP {}", code)))
+            }
+            Err(ureq::Error::Status(code, response)) => Err(SendError::Rejected(format!(
+                "HTTP {}: {}",
+                code,
+                response.into_string().unwrap_or_default().trim()
+            ))),
+            Err(ureq::Error::Transport(e)) => Err(SendError::Unavailable(e.to_string())),
+        }
+    }
+}
+
+/// Delay before retry number `attempt` (counting from 0), capped at [`MAX_BACKOFF`].
+fn backoff(attempt: u32) -> Duration {
+    RETRY_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::log_adapter::FernLogger;
+    use crate::domain::storage::fio::{FioResult, StorageTarget};
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::sync::Mutex;
+
+    /// A request received by [`MockEndpoint`]
+    struct Request {
+        target: String,
+        authorization: Option<String>,
+        body: String,
+    }
+
+    /// Local HTTP listener that records requests and answers with a settable status
+    struct MockEndpoint {
+        url: String,
+        status: Arc<AtomicU16>,
+        requests: Arc<Mutex<Vec<Request>>>,
+    }
+
+    impl MockEndpoint {
+        fn start(status: u16) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let status = Arc::new(AtomicU16::new(status));
+            let requests = Arc::new(Mutex::new(Vec::new()));
+
+            let (answer, recorded) = (status.clone(), requests.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else { break };
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                    let mut target = String::new();
+                    reader.read_line(&mut target).unwrap();
+                    let target = target.split(' ').nth(1).unwrap_or("").to_string();
+
+                    let (mut length, mut authorization) = (0, None);
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        let header = header.trim_end();
+                        if header.is_empty() {
+                            break;
+                        }
+                        let (name, value) = header.split_once(": ").unwrap();
+                        match name.to_ascii_lowercase().as_str() {
+                            "content-length" => length = value.parse().unwrap(),
+                            "authorization" => authorization = Some(value.to_string()),
+                            _ => {}
+                        }
+                    }
+                    let mut body = vec![0; length];
+                    reader.read_exact(&mut body).unwrap();
+
+                    recorded.lock().unwrap().push(Request {
+                        target,
+                        authorization,
+                        body: String::from_utf8(body).unwrap(),
+                    });
+                    let response = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        answer.load(Ordering::SeqCst)
+                    );
+                    let _ = stream.write_all(response.as_bytes());
+                }
+            });
+
+            Self {
+                url,
+                status,
+                requests,
+            }
+        }
+
+        /// Every line received, in order.
+        fn lines(&self) -> Vec<String> {
+            self.requests
+                .lock()
+                .unwrap()
+                .iter()
+                .flat_map(|r| r.body.lines().map(String::from).collect::<Vec<_>>())
+                .collect()
+        }
+    }
+
+    fn config(endpoint: &MockEndpoint, buffer: &std::path::Path) -> RemoteConfig {
+        RemoteConfig {
+            enabled: true,
+            url: endpoint.url.clone(),
+            bucket: Some("sysperf".into()),
+            org: Some("lab".into()),
+            token: Some("secret".into()),
+            flush_interval: 60,
+            max_retries: 0,
+            timeout: 5,
+            buffer_path: buffer.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    fn logger() -> Arc<dyn LoggerPort> {
+        Arc::new(FernLogger::new())
+    }
+
+    #[test]
+    fn test_batches_samples_and_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoint = MockEndpoint::start(204);
+        let config = RemoteConfig {
+            batch_size: 2,
+            ..config(&endpoint, &dir.path().join("buffer.lp"))
+        };
+
+        let adapter = InfluxDbAdapter::new(&config, logger());
+        for ts in [1000, 2000, 3000] {
+            let record = json!({"metric": "loadavg", "data": [{"load_1": 0.5}]});
+            adapter
+                .store_metrics(&format!("loadavg/{}", ts), &record)
+                .unwrap();
+        }
+        let mut run = RunRecord::new(
+            "job",
+            &StorageTarget::new_file("/tmp/x", None),
+            FioResult::default(),
+        );
+        run.target = Some("nvme_direct".into());
+        run.profile = Some("randread_4k".into());
+        adapter.save_run(&run).unwrap();
+        assert!(adapter.store_metrics("disk/1000", &json!({})).is_err());
+        drop(adapter);
+
+        let requests = endpoint.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].body.lines().count(), 2);
+        assert_eq!(
+            requests[0].target,
+            "/api/v2/write?bucket=sysperf&org=lab&precision=ns"
+        );
+        assert_eq!(requests[0].authorization.as_deref(), Some("Token secret"));
+        drop(requests);
+
+        let lines = endpoint.lines();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("sysperf_loadavg,host="));
+        assert!(lines[0].ends_with(" load_1=0.5 1000000000"));
+        assert!(lines[3].starts_with("sysperf_benchmark,"));
+        assert!(lines[3].contains(",profile=randread_4k,target=nvme_direct "));
+        assert!(!dir.path().join("buffer.lp").exists());
+    }
+
+    #[test]
+    fn test_buffers_while_down_and_replays_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let buffer = dir.path().join("spool/buffer.lp");
+        let endpoint = MockEndpoint::start(503);
+        let sample = |ts: i64| json!({"data": {"load_1": ts}});
+
+        let adapter = InfluxDbAdapter::new(
+            &RemoteConfig {
+                max_retries: 1,
+                ..config(&endpoint, &buffer)
+            },
+            logger(),
+        );
+        adapter.store_metrics("loadavg/1", &sample(1)).unwrap();
+        drop(adapter);
+
+        // One attempt and one retry, then the line waits on disk
+        assert_eq!(endpoint.requests.lock().unwrap().len(), 2);
+        assert_eq!(fs::read_to_string(&buffer).unwrap().lines().count(), 1);
+
+        endpoint.status.store(204, Ordering::SeqCst);
+        endpoint.requests.lock().unwrap().clear();
+        let adapter = InfluxDbAdapter::new(&config(&endpoint, &buffer), logger());
+        adapter.store_metrics("loadavg/2", &sample(2)).unwrap();
+        drop(adapter);
+
+        let lines = endpoint.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" 1000000"));
+        assert!(lines[1].ends_with(" 2000000"));
+        assert!(!buffer.exists());
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        assert_eq!(backoff(0), RETRY_BACKOFF);
+        assert_eq!(backoff(2), RETRY_BACKOFF * 4);
+        assert_eq!(backoff(40), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_rejected_batches_are_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let buffer = dir.path().join("buffer.lp");
+        let endpoint = MockEndpoint::start(400);
+        let config = RemoteConfig {
+            bucket: None,
+            database: Some("sysperf".into()),
+            max_retries: 3,
+            ..config(&endpoint, &buffer)
+        };
+
+        let adapter = InfluxDbAdapter::new(&config, logger());
+        adapter
+            .store_metrics("mem/1", &json!({"data": {"free_memory": 1}}))
+            .unwrap();
+        drop(adapter);
+
+        let requests = endpoint.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].target, "/write?db=sysperf&precision=ns");
+        assert!(!buffer.exists());
+    }
+}
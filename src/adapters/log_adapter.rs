@@ -18,6 +18,7 @@ pub struct FernLogger;
 unsafe impl Sync for FernLogger {}
 
 // Implement the Send trait for the FernLogger struct.
+#[allow(clippy::new_without_default)]
 impl FernLogger {
     /// Creates a new instance of `FernLogger`.
     ///
@@ -94,6 +95,7 @@ impl LoggerPort for FernLogger {
 /// # Returns
 ///
 /// * `FernLogger` - The initialized `FernLogger` instance.
+#[allow(clippy::needless_borrows_for_generic_args)]
 pub fn init(
     log_dir_path: &str,
     level_filter: LevelFilter,
@@ -175,7 +177,7 @@ pub fn init(
         .chain(info_dispatch)
         .chain(debug_dispatch)
         .chain(trace_dispatch)
-        .chain(std::io::stdout()); // Also log to standard output.
+        .chain(std::io::stderr()); // Also log to standard error, leaving stdout to command output.
 
     // Apply the combined logger configuration.
     combined_config
@@ -1,31 +1,159 @@
+//! Metrics Adapter Module
+//!
+//! Implements [`MetricsPort`] on top of the collectors in [`crate::domain::metrics`].  The
+//! collectors are async while the port is synchronous, so the adapter drives them on a small
+//! runtime of its own, or on the caller's runtime when it is already inside one.  Each
+//! collector's typed output is serialized to JSON before it crosses the port.
+
+use crate::domain::metrics::cpuinfo::CpuInfoCollector;
+use crate::domain::metrics::iostat::{IostatCollector, IostatConfig};
+use crate::domain::metrics::loadavg::{LoadavgCollector, LoadavgConfig};
+use crate::domain::metrics::meminfo::MemInfoCollector;
+use crate::domain::metrics::mpstat::{MpstatCollector, MpstatConfig};
+use crate::domain::metrics::vmstat::{VmstatCollector, VmstatConfig};
 use crate::ports::metrics_port::MetricsPort;
-use anyhow::Result;
-use std::collections::HashMap;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::runtime::{Handle, Runtime, RuntimeFlavor};
 
+/// Adapter that collects system metrics through the domain collectors
 pub struct MetricsAdapter {
-    metrics: HashMap<String, f64>,
+    /// Built on first use outside of a runtime; never created (and so never dropped) inside one
+    runtime: OnceLock<Runtime>,
+    mpstat: MpstatConfig,
+    vmstat: VmstatConfig,
+    iostat: IostatConfig,
+    loadavg: LoadavgConfig,
+}
+
+impl Default for MetricsAdapter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MetricsAdapter {
+    /// Creates an adapter taking one sample per call over the collectors' default interval.
     pub fn new() -> Self {
         Self {
-            metrics: HashMap::new(),
+            runtime: OnceLock::new(),
+            mpstat: MpstatConfig::default(),
+            vmstat: VmstatConfig::default(),
+            iostat: IostatConfig::default(),
+            loadavg: LoadavgConfig {
+                count: Some(1),
+                ..Default::default()
+            },
         }
     }
+
+    /// Sets the interval over which rate-based metrics (mpstat, vmstat, iostat) are sampled.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.mpstat.interval = interval;
+        self.vmstat.interval = interval;
+        self.iostat.interval = interval;
+        self.loadavg.interval = interval;
+        self
+    }
+
+    /// The adapter's own runtime, built on first use.
+    fn runtime(&self) -> Result<&Runtime> {
+        if let Some(runtime) = self.runtime.get() {
+            return Ok(runtime);
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .context("Failed to build the metrics runtime")?;
+        Ok(self.runtime.get_or_init(|| runtime))
+    }
+
+    /// Runs a collector future to completion and serializes its output.
+    fn collect<T, E, F>(&self, name: &str, future: F) -> Result<serde_json::Value>
+    where
+        T: Serialize,
+        E: std::error::Error + Send + Sync + 'static,
+        F: Future<Output = Result<T, E>>,
+    {
+        let output = match Handle::try_current() {
+            // Blocking a current-thread runtime from inside itself would deadlock
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| handle.block_on(future))
+            }
+            Ok(_) => anyhow::bail!(
+                "cannot collect {} from inside a current-thread runtime",
+                name
+            ),
+            Err(_) => self.runtime()?.block_on(future),
+        }
+        .with_context(|| format!("Failed to collect {}", name))?;
+
+        serde_json::to_value(output).with_context(|| format!("Failed to serialize {}", name))
+    }
 }
 
 impl MetricsPort for MetricsAdapter {
     fn collect_memoryinfo(&self) -> Result<serde_json::Value> {
-        Ok(serde_json::Value::Null)
+        let collector = MemInfoCollector::new();
+        self.collect("memory info", collector.collect())
     }
+
     fn collect_cpuinfo(&self) -> Result<serde_json::Value> {
-        Ok(serde_json::Value::Null)
+        let collector = CpuInfoCollector::new();
+        self.collect("CPU info", collector.collect())
     }
+
     fn collect_vmstat(&self) -> Result<serde_json::Value> {
-        Ok(serde_json::Value::Null)
+        let collector = VmstatCollector::new(self.vmstat.clone());
+        self.collect("vmstat", collector.collect())
     }
 
     fn collect_mpstat(&self) -> Result<serde_json::Value> {
-        Ok(serde_json::Value::Null)
+        let collector = MpstatCollector::new(self.mpstat.clone());
+        self.collect("mpstat", collector.collect())
+    }
+
+    fn collect_iostat(&self) -> Result<serde_json::Value> {
+        let collector = IostatCollector::new(self.iostat.clone());
+        self.collect("iostat", collector.collect())
+    }
+
+    fn collect_loadavg(&self) -> Result<serde_json::Value> {
+        let collector = LoadavgCollector::new(self.loadavg.clone());
+        self.collect("load average", collector.collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collect_serializes_typed_output() {
+        let adapter = MetricsAdapter::new().with_interval(Duration::from_millis(50));
+
+        let mpstat = adapter.collect_mpstat().unwrap();
+        assert!(mpstat
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|cpu| cpu["cpu_id"] == "all"));
+
+        let loadavg = adapter.collect_loadavg().unwrap();
+        assert!(loadavg[0]["load_1"].as_f64().unwrap() >= 0.0);
+
+        let vmstat = adapter.collect_vmstat().unwrap();
+        assert!(vmstat[0]["memory"]["free_kb"].is_u64());
+
+        assert!(adapter.collect_memoryinfo().unwrap().is_object());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_collect_inside_runtime() {
+        let adapter = MetricsAdapter::new().with_interval(Duration::from_millis(50));
+        assert!(adapter.collect_iostat().unwrap().is_array());
     }
 }
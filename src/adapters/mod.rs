@@ -1,4 +1,6 @@
 pub mod benchmark_adapter;
 pub mod database_adapter;
+pub mod influxdb_adapter;
 pub mod log_adapter;
 pub mod metrics_adapter;
+pub mod storage_adapter;
@@ -0,0 +1,445 @@
+//! Storage Adapter Module
+//!
+//! Implements [`StoragePort`] on top of the key-value [`DatabasePort`].  Records are stored as
+//! JSON under three namespaces:
+//!
+//! - `metrics/<key>` - individual metric samples
+//! - `runs/<run id>` - run records without their system metrics
+//! - `run_metrics/<run id>` - the system metrics recorded during a run
+//! - `index/latest_runs` - the ID of the newest run of every job
+//!
+//! Keeping the (potentially large) metric series apart from the run records lets history
+//! listings scan only the records.  The latest-run index lets the metrics endpoint load one
+//! record per job instead of every stored run; it is rebuilt from the runs when missing.
+//!
+//! [`MirroredStorage`] layers export targets such as InfluxDB on top of the embedded store.
+
+use crate::database::{KeyValueStore, Namespace};
+use crate::domain::metrics::series::MetricSeries;
+use crate::domain::storage::history::{latest_per_job, JobKey, RunFilter, RunRecord};
+use crate::ports::database_port::DatabasePort;
+use crate::ports::log_port::LoggerPort;
+use crate::ports::storage_port::StoragePort;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+const METRICS: &str = "metrics";
+const RUNS: &str = "runs";
+const RUN_METRICS: &str = "run_metrics";
+const INDEX: &str = "index";
+const LATEST_RUNS: &str = "latest_runs";
+
+/// Entry of the latest-run index
+#[derive(Debug, Serialize, Deserialize)]
+struct LatestRun {
+    job: JobKey,
+    id: String,
+    timestamp: i64,
+}
+
+impl LatestRun {
+    fn new(run: &RunRecord) -> Self {
+        Self {
+            job: run.job(),
+            id: run.id.clone(),
+            timestamp: run.timestamp,
+        }
+    }
+
+    /// Whether `run` is at least as new as this entry, in the order [`RunFilter`] lists runs.
+    fn is_superseded_by(&self, run: &RunRecord) -> bool {
+        (run.timestamp, run.id.as_str()) >= (self.timestamp, self.id.as_str())
+    }
+}
+
+/// Adapter that stores metrics and benchmark history in the embedded database
+pub struct StorageAdapter {
+    db: Arc<dyn DatabasePort>,
+}
+
+impl StorageAdapter {
+    /// Creates an adapter storing into `db`.
+    pub fn new(db: Arc<dyn DatabasePort>) -> Self {
+        Self { db }
+    }
+
+    fn namespace(&self, name: &str) -> Namespace<'_, dyn DatabasePort> {
+        Namespace::new(self.db.as_ref(), name)
+    }
+
+    /// Loads the latest-run index, building it from the stored runs if there is none yet.
+    fn latest_index(&self) -> Result<Vec<LatestRun>> {
+        match self.namespace(INDEX).get(LATEST_RUNS)? {
+            Some(index) => serde_json::from_str(&index).context("Corrupt latest-run index"),
+            None => self.rebuild_latest_index(),
+        }
+    }
+
+    fn rebuild_latest_index(&self) -> Result<Vec<LatestRun>> {
+        let index: Vec<LatestRun> = latest_per_job(self.list_runs(&RunFilter::default())?)
+            .iter()
+            .map(LatestRun::new)
+            .collect();
+        self.store_latest_index(&index)?;
+        Ok(index)
+    }
+
+    fn store_latest_index(&self, index: &[LatestRun]) -> Result<()> {
+        self.namespace(INDEX)
+            .set(LATEST_RUNS, &serde_json::to_string(index)?)
+    }
+}
+
+impl StoragePort for StorageAdapter {
+    fn store_metrics(&self, key: &str, value: &Value) -> Result<()> {
+        self.namespace(METRICS).set(key, &value.to_string())
+    }
+
+    fn retrieve_metrics(&self, key: &str) -> Result<Option<Value>> {
+        self.namespace(METRICS)
+            .get(key)?
+            .map(|value| serde_json::from_str(&value))
+            .transpose()
+            .with_context(|| format!("Corrupt metrics entry {}", key))
+    }
+
+    fn metric_keys(&self) -> Result<Vec<String>> {
+        Ok(self
+            .namespace(METRICS)
+            .list("")?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect())
+    }
+
+    fn delete_metrics(&self, key: &str) -> Result<()> {
+        self.namespace(METRICS).delete(key)
+    }
+
+    fn save_run(&self, run: &RunRecord) -> Result<()> {
+        // Metrics first, so that a stored run never points at missing metrics
+        if let Some(series) = &run.system_metrics {
+            let series = serde_json::to_string(series)?;
+            self.namespace(RUN_METRICS).set(&run.id, &series)?;
+        }
+
+        let mut record = serde_json::to_value(run)?;
+        if let Some(record) = record.as_object_mut() {
+            record.remove("system_metrics");
+        }
+        self.namespace(RUNS).set(&run.id, &record.to_string())?;
+
+        let mut index = self.latest_index()?;
+        let job = run.job();
+        match index.iter_mut().find(|entry| entry.job == job) {
+            Some(entry) if entry.is_superseded_by(run) => *entry = LatestRun::new(run),
+            Some(_) => return Ok(()),
+            None => index.push(LatestRun::new(run)),
+        }
+        self.store_latest_index(&index)
+    }
+
+    fn get_run(&self, id: &str) -> Result<Option<RunRecord>> {
+        let Some(record) = self.namespace(RUNS).get(id)? else {
+            return Ok(None);
+        };
+        let mut run: RunRecord =
+            serde_json::from_str(&record).with_context(|| format!("Corrupt run {}", id))?;
+
+        if let Some(series) = self.namespace(RUN_METRICS).get(id)? {
+            let series: MetricSeries = serde_json::from_str(&series)
+                .with_context(|| format!("Corrupt system metrics for run {}", id))?;
+            run.system_metrics = Some(series);
+        }
+        Ok(Some(run))
+    }
+
+    fn list_runs(&self, filter: &RunFilter) -> Result<Vec<RunRecord>> {
+        let mut runs = Vec::new();
+        for (id, record) in self.namespace(RUNS).list("")? {
+            let run: RunRecord =
+                serde_json::from_str(&record).with_context(|| format!("Corrupt run {}", id))?;
+            runs.push(run);
+        }
+        Ok(filter.apply(runs))
+    }
+
+    fn latest_runs(&self) -> Result<Vec<RunRecord>> {
+        let runs = self.namespace(RUNS);
+        let mut latest = Vec::new();
+        for entry in self.latest_index()? {
+            if let Some(record) = runs.get(&entry.id)? {
+                let run: RunRecord = serde_json::from_str(&record)
+                    .with_context(|| format!("Corrupt run {}", entry.id))?;
+                latest.push(run);
+            }
+        }
+        Ok(RunFilter::default().apply(latest))
+    }
+
+    fn delete_run(&self, id: &str) -> Result<()> {
+        self.namespace(RUNS).delete(id)?;
+        self.namespace(RUN_METRICS).delete(id)?;
+
+        // The job's previous run becomes its latest; only a full scan can tell which one that is
+        if self.latest_index()?.iter().any(|entry| entry.id == id) {
+            self.rebuild_latest_index()?;
+        }
+        Ok(())
+    }
+
+    fn set_pinned(&self, id: &str, pinned: bool) -> Result<bool> {
+        let runs = self.namespace(RUNS);
+        let Some(record) = runs.get(id)? else {
+            return Ok(false);
+        };
+        // Update the record in place; the system metrics are stored apart and stay untouched
+        let mut record: Value =
+            serde_json::from_str(&record).with_context(|| format!("Corrupt run {}", id))?;
+        record["pinned"] = Value::Bool(pinned);
+        runs.set(id, &record.to_string())?;
+        Ok(true)
+    }
+}
+
+/// Adapter that keeps results in a primary store and copies new ones to mirrors
+///
+/// Reads, deletes and pins only touch the primary; mirrors such as the InfluxDB exporter apply
+/// their own retention.  A mirror failing to take a write is logged and does not fail it.
+pub struct MirroredStorage {
+    primary: Arc<dyn StoragePort>,
+    mirrors: Vec<Arc<dyn StoragePort>>,
+    logger: Arc<dyn LoggerPort>,
+}
+
+impl MirroredStorage {
+    /// Creates an adapter storing into `primary` and copying writes to `mirrors`.
+    pub fn new(
+        primary: Arc<dyn StoragePort>,
+        mirrors: Vec<Arc<dyn StoragePort>>,
+        logger: Arc<dyn LoggerPort>,
+    ) -> Self {
+        Self {
+            primary,
+            mirrors,
+            logger,
+        }
+    }
+
+    fn mirror<F>(&self, what: &str, write: F)
+    where
+        F: Fn(&dyn StoragePort) -> Result<()>,
+    {
+        for mirror in &self.mirrors {
+            if let Err(e) = write(mirror.as_ref()) {
+                self.logger
+                    .log_warn(&format!("Failed to mirror {}: {:#}", what, e));
+            }
+        }
+    }
+}
+
+impl StoragePort for MirroredStorage {
+    fn store_metrics(&self, key: &str, value: &Value) -> Result<()> {
+        self.primary.store_metrics(key, value)?;
+        self.mirror(key, |mirror| mirror.store_metrics(key, value));
+        Ok(())
+    }
+
+    fn retrieve_metrics(&self, key: &str) -> Result<Option<Value>> {
+        self.primary.retrieve_metrics(key)
+    }
+
+    fn metric_keys(&self) -> Result<Vec<String>> {
+        self.primary.metric_keys()
+    }
+
+    fn delete_metrics(&self, key: &str) -> Result<()> {
+        self.primary.delete_metrics(key)
+    }
+
+    fn save_run(&self, run: &RunRecord) -> Result<()> {
+        self.primary.save_run(run)?;
+        self.mirror(&format!("run {}", run.id), |mirror| mirror.save_run(run));
+        Ok(())
+    }
+
+    fn get_run(&self, id: &str) -> Result<Option<RunRecord>> {
+        self.primary.get_run(id)
+    }
+
+    fn list_runs(&self, filter: &RunFilter) -> Result<Vec<RunRecord>> {
+        self.primary.list_runs(filter)
+    }
+
+    fn latest_runs(&self) -> Result<Vec<RunRecord>> {
+        self.primary.latest_runs()
+    }
+
+    fn delete_run(&self, id: &str) -> Result<()> {
+        self.primary.delete_run(id)
+    }
+
+    fn set_pinned(&self, id: &str, pinned: bool) -> Result<bool> {
+        self.primary.set_pinned(id, pinned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::database_adapter::DatabaseAdapter;
+    use crate::domain::metrics::series::MetricKind;
+    use crate::domain::storage::fio::{FioResult, StorageTarget};
+    use serde_json::json;
+
+    fn run(profile: &str, timestamp: i64) -> RunRecord {
+        let mut run = RunRecord::new(
+            "job",
+            &StorageTarget::new_file("/tmp/x", None),
+            FioResult::default(),
+        );
+        run.profile = Some(profile.into());
+        run.timestamp = timestamp;
+        run
+    }
+
+    #[test]
+    fn test_runs_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageAdapter::new(Arc::new(DatabaseAdapter::new(dir.path().join("db"))));
+
+        let mut with_metrics = run("randread_4k", 100);
+        let mut series = MetricSeries::new(1000, 0);
+        series.push(MetricKind::Loadavg, 1000, json!([{"load_1": 0.5}]));
+        with_metrics.system_metrics = Some(series);
+        storage.save_run(&with_metrics).unwrap();
+        storage.save_run(&run("seqwrite_1m", 200)).unwrap();
+
+        let loaded = storage.get_run(&with_metrics.id).unwrap().unwrap();
+        assert_eq!(loaded.profile.as_deref(), Some("randread_4k"));
+        assert_eq!(loaded.system_metrics.unwrap().len(), 1);
+
+        let listed = storage.list_runs(&RunFilter::default()).unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].timestamp, 200);
+        assert!(listed.iter().all(|r| r.system_metrics.is_none()));
+
+        let filter = RunFilter {
+            profile: Some("randread_4k".into()),
+            ..Default::default()
+        };
+        assert_eq!(storage.list_runs(&filter).unwrap().len(), 1);
+
+        storage.delete_run(&with_metrics.id).unwrap();
+        assert!(storage.get_run(&with_metrics.id).unwrap().is_none());
+        assert_eq!(storage.list_runs(&filter).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_latest_runs_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageAdapter::new(Arc::new(DatabaseAdapter::new(dir.path().join("db"))));
+
+        let old = run("randread_4k", 100);
+        let new = run("randread_4k", 200);
+        let other = run("seqwrite_1m", 150);
+        storage.save_run(&new).unwrap();
+        storage.save_run(&old).unwrap();
+        storage.save_run(&other).unwrap();
+
+        let ids = |runs: Vec<RunRecord>| runs.into_iter().map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(storage.latest_runs().unwrap()),
+            vec![new.id.clone(), other.id.clone()]
+        );
+
+        // Deleting a job's latest run falls back to its previous one
+        storage.delete_run(&new.id).unwrap();
+        assert_eq!(
+            ids(storage.latest_runs().unwrap()),
+            vec![other.id.clone(), old.id.clone()]
+        );
+
+        // A store written before the index existed gets it built on first use
+        storage.namespace(INDEX).delete(LATEST_RUNS).unwrap();
+        assert_eq!(
+            ids(storage.latest_runs().unwrap()),
+            vec![other.id.clone(), old.id.clone()]
+        );
+    }
+
+    #[test]
+    fn test_metrics_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageAdapter::new(Arc::new(DatabaseAdapter::new(dir.path().join("db"))));
+
+        storage
+            .store_metrics("mpstat/1000", &json!({"usr": 1.5}))
+            .unwrap();
+        assert_eq!(
+            storage.retrieve_metrics("mpstat/1000").unwrap(),
+            Some(json!({"usr": 1.5}))
+        );
+        assert_eq!(storage.retrieve_metrics("mpstat/2000").unwrap(), None);
+
+        storage.store_metrics("iostat/1000", &json!([])).unwrap();
+        assert_eq!(
+            storage.metric_keys().unwrap(),
+            vec!["iostat/1000".to_string(), "mpstat/1000".to_string()]
+        );
+        storage.delete_metrics("mpstat/1000").unwrap();
+        assert_eq!(
+            storage.metric_keys().unwrap(),
+            vec!["iostat/1000".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_pinning_keeps_metrics() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = StorageAdapter::new(Arc::new(DatabaseAdapter::new(dir.path().join("db"))));
+
+        let mut baseline = run("randread_4k", 100);
+        baseline.system_metrics = Some(MetricSeries::new(1000, 0));
+        storage.save_run(&baseline).unwrap();
+
+        assert!(storage.set_pinned(&baseline.id, true).unwrap());
+        assert!(!storage.set_pinned("missing", true).unwrap());
+
+        let loaded = storage.get_run(&baseline.id).unwrap().unwrap();
+        assert!(loaded.pinned);
+        assert!(loaded.system_metrics.is_some());
+
+        storage.set_pinned(&baseline.id, false).unwrap();
+        assert!(!storage.get_run(&baseline.id).unwrap().unwrap().pinned);
+    }
+
+    #[test]
+    fn test_mirrored_writes_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let primary: Arc<dyn StoragePort> = Arc::new(StorageAdapter::new(Arc::new(
+            DatabaseAdapter::new(dir.path().join("primary")),
+        )));
+        let mirror: Arc<dyn StoragePort> = Arc::new(StorageAdapter::new(Arc::new(
+            DatabaseAdapter::new(dir.path().join("mirror")),
+        )));
+        let storage = MirroredStorage::new(
+            primary.clone(),
+            vec![mirror.clone()],
+            Arc::new(crate::adapters::log_adapter::FernLogger::new()),
+        );
+
+        let run = run("randread_4k", 100);
+        storage.save_run(&run).unwrap();
+        storage.store_metrics("mpstat/1000", &json!({})).unwrap();
+        assert!(mirror.get_run(&run.id).unwrap().is_some());
+        assert_eq!(mirror.metric_keys().unwrap().len(), 1);
+
+        storage.delete_run(&run.id).unwrap();
+        assert!(primary.get_run(&run.id).unwrap().is_none());
+        assert!(mirror.get_run(&run.id).unwrap().is_some());
+    }
+}
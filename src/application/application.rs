@@ -2,14 +2,33 @@
 //! This module serves as the primary orchestrator for the system performance testing application.
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::ports::benchmark_port::BenchmarkPort;
+use super::recorder::{MetricsRecorder, Recording};
+use crate::config::Config;
+use crate::domain::storage::fio::{FioJobConfig, FioResult, StorageTarget};
+use crate::domain::storage::history::{RunFilter, RunRecord};
+use crate::domain::storage::preflight::Preflight;
+use crate::domain::storage::retention::{PruneReport, RetentionPolicy};
+use crate::domain::storage::safety::SafetyGuard;
+use crate::domain::storage::scratch::{self, CleanReport, ScratchFiles, REUSE_OPTION, STATE_DIR};
+use crate::domain::storage::steady_state::{self, SteadyState, SteadyStateReport};
+use crate::domain::storage::sweep::{SweepPlan, SweepResults};
+use crate::domain::storage::units::{format_bytes, ByteSize};
+use crate::ports::benchmark_port::{
+    BenchmarkConfig, BenchmarkMetrics, BenchmarkParams, BenchmarkPort, BenchmarkResult,
+    BenchmarkStopped, RunControl,
+};
 use crate::ports::database_port::DatabasePort;
 use crate::ports::log_port::LoggerPort;
 use crate::ports::metrics_port::MetricsPort;
+use crate::ports::storage_port::StoragePort;
 
 /// Main application struct that coordinates all core functionality.
 ///
+/// The loaded [`Config`] travels with the application so that use-cases can read the test
+/// directory, database path and timeouts without re-parsing the file.
+///
 /// # Type Parameters
 /// * `DB` - Database adapter type that implements DatabasePort
 /// * `B`  - Benchmark adapter type that implements benchmark_port
@@ -20,29 +39,642 @@ use crate::ports::metrics_port::MetricsPort;
 /// All type parameters must implement their respective port traits, enabling
 /// dependency inversion and making the application independent of specific implementations.
 pub struct Application {
+    pub config: Config,
     pub db: Arc<dyn DatabasePort>,
     pub benchmark: Arc<dyn BenchmarkPort>,
     pub metrics: Arc<dyn MetricsPort>,
+    pub storage: Arc<dyn StoragePort>,
     pub logger: Arc<dyn LoggerPort>,
 }
 
 impl Application {
     pub fn new(
+        config: Config,
         db: Arc<dyn DatabasePort>,
         benchmark: Arc<dyn BenchmarkPort>,
         metrics: Arc<dyn MetricsPort>,
+        storage: Arc<dyn StoragePort>,
         logger: Arc<dyn LoggerPort>,
     ) -> Self {
         Self {
+            config,
             db,
             benchmark,
             metrics,
+            storage,
             logger,
         }
     }
 
-    pub fn run_benchmark(&self) -> anyhow::Result<()> {
-        self.benchmark.run()?;
+    pub fn run_benchmark(&self) -> anyhow::Result<Vec<FioResult>> {
+        self.benchmark.run()
+    }
+
+    /// Returns a control that times benchmarks out after `storage.default_timeout`.
+    pub fn run_control(&self) -> RunControl {
+        RunControl::new().with_timeout(Duration::from_secs(self.config.storage.default_timeout))
+    }
+
+    /// Runs a configured FIO profile against a configured storage target.
+    ///
+    /// The profile from `[storage.fio.profiles.<profile>]` is layered over
+    /// `[storage.fio.defaults]` and bound to the `[[storage.targets]]` entry named `target`.
+    /// Without a target the benchmark runs in `storage.test_directory`.  FIO is stopped when
+    /// `control` times out or is cancelled.  Successful runs are saved to the run history.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the profile or target is unknown, if the target fails
+    /// [`check_target`](Self::check_target) or [`preflight`](Self::preflight), or if FIO fails.
+    pub fn run_profile(
+        &self,
+        profile: &str,
+        target: Option<&str>,
+        control: &RunControl,
+    ) -> anyhow::Result<BenchmarkResult> {
+        let job = self.config.job_for_profile(profile)?;
+        let storage_target = self.resolve_target(target)?;
+        self.check_target(target, &storage_target, job.rw.writes(), control)?;
+        let job = self.preflight(&job, &storage_target)?;
+
+        let name = match target {
+            Some(target) => format!("{}_{}", profile, target),
+            None => profile.to_string(),
+        };
+        self.logger.log_info(&format!(
+            "Running profile {} against {}",
+            profile,
+            storage_target.path.display()
+        ));
+        let mut result = self.run_recorded(|| {
+            self.benchmark
+                .run_job(&name, &job, &storage_target, control)
+        })?;
+        self.save_run(
+            &mut result,
+            &name,
+            Some(profile),
+            target,
+            &storage_target,
+            Some(&job),
+        );
+        self.auto_prune();
+        Ok(result)
+    }
+
+    /// Runs an ad-hoc benchmark configuration, recording system metrics while it runs, and
+    /// saves it to the run history.  FIO is stopped when `control` times out or is cancelled.
+    pub fn run_config(
+        &self,
+        config: &BenchmarkConfig,
+        control: &RunControl,
+    ) -> anyhow::Result<BenchmarkResult> {
+        let mut result = self.run_recorded(|| self.benchmark.run_config(config, control))?;
+        if let BenchmarkParams::FIO(params) = &config.params {
+            let storage_target = StorageTarget {
+                path: params.directory.clone().into(),
+                target_type: "directory".into(),
+                options: Default::default(),
+            };
+            self.save_run(&mut result, &config.name, None, None, &storage_target, None);
+        }
+        self.auto_prune();
+        Ok(result)
+    }
+
+    /// Runs every point of `plan` against a configured storage target.
+    ///
+    /// Points run one after another, each under `control`'s timeout; a point that fails its
+    /// [`preflight`](Self::preflight), fails or times out is recorded in the results and the
+    /// sweep carries on with the next one.  Every
+    /// successful point is saved to the run history under `profile`.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the target is unknown or fails
+    /// [`check_target`](Self::check_target), or if `control` is cancelled; points that
+    /// finished before the cancellation stay in the run history.
+    pub fn run_sweep(
+        &self,
+        profile: &str,
+        plan: &SweepPlan,
+        target: Option<&str>,
+        control: &RunControl,
+    ) -> anyhow::Result<SweepResults> {
+        let storage_target = self.resolve_target(target)?;
+        let name = match target {
+            Some(target) => format!("{}_{}", profile, target),
+            None => profile.to_string(),
+        };
+        let points = plan.points();
+        let writes = points.iter().any(|point| point.config.rw.writes());
+        self.check_target(target, &storage_target, writes, control)?;
+        let mut results = SweepResults::default();
+
+        self.logger.log_info(&format!(
+            "Running sweep {} ({} points) against {}",
+            name,
+            points.len(),
+            storage_target.path.display()
+        ));
+
+        for (i, point) in points.into_iter().enumerate() {
+            self.logger
+                .log_info(&format!("Sweep point {}: {}", i + 1, point.key));
+            let job_name = format!("{}_{}", name, point.key.suffix());
+
+            let result = self
+                .preflight(&point.config, &storage_target)
+                .and_then(|job| {
+                    self.run_recorded(|| {
+                        self.benchmark
+                            .run_job(&job_name, &job, &storage_target, control)
+                    })
+                });
+            match result {
+                Ok(mut result) => {
+                    self.save_run(
+                        &mut result,
+                        &job_name,
+                        Some(profile),
+                        target,
+                        &storage_target,
+                        Some(&point.config),
+                    );
+                    match result.metrics {
+                        BenchmarkMetrics::FIO(fio) => results.record_with_metrics(
+                            point.key,
+                            *fio.details,
+                            result.system_metrics,
+                        ),
+                        _ => results
+                            .record_error(point.key, "benchmark did not return FIO results".into()),
+                    }
+                }
+                Err(e) if BenchmarkStopped::find(&e) == Some(BenchmarkStopped::Cancelled) => {
+                    self.logger
+                        .log_warn(&format!("Sweep {} cancelled at point {}", name, point.key));
+                    return Err(e);
+                }
+                Err(e) => {
+                    self.logger
+                        .log_error(&format!("Sweep point {} failed: {}", point.key, e));
+                    results.record_error(point.key, e.to_string());
+                }
+            }
+        }
+
+        self.auto_prune();
+        Ok(results)
+    }
+
+    /// Preconditions a configured storage target and measures a profile on it until steady
+    /// state, following the SNIA PTS and `[storage.fio.steady_state]`.
+    ///
+    /// Unless `precondition` is off, the range the profile tests is first written twice over
+    /// sequentially (see [`steady_state::precondition_job`]).  The profile then runs in rounds
+    /// of `round_runtime` seconds until the measurement window is steady or `max_rounds` have
+    /// run.  Each round is saved to the run history as `<name>_round<n>`.
+    ///
+    /// Data files are kept between the preconditioning and the rounds, which must all run on
+    /// the same preconditioned data, and removed afterwards unless the target reuses its files.
+    /// Every round is subject to `control`'s timeout; preconditioning, which writes the range
+    /// twice and can take hours on a large device, is only limited by `precondition_timeout`.
+    /// Cancelling `control` stops either.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the profile or target is unknown, if the target fails
+    /// [`check_target`](Self::check_target) or [`preflight`](Self::preflight), or if
+    /// preconditioning or a round fails or is stopped; rounds that finished stay in the run
+    /// history.
+    pub fn run_steady_state(
+        &self,
+        profile: &str,
+        target: Option<&str>,
+        control: &RunControl,
+    ) -> anyhow::Result<SteadyStateReport> {
+        let settings = &self.config.storage.fio.steady_state;
+        let mut job = self.config.job_for_profile(profile)?;
+        job.extra_options.insert("time_based".into(), "true".into());
+        job.extra_options
+            .insert("runtime".into(), settings.round_runtime.to_string());
+
+        let storage_target = self.resolve_target(target)?;
+        let writes = settings.precondition || job.rw.writes();
+        self.check_target(target, &storage_target, writes, control)?;
+        let job = self.preflight(&job, &storage_target)?;
+
+        let name = match target {
+            Some(target) => format!("{}_{}", profile, target),
+            None => profile.to_string(),
+        };
+
+        // Every run uses the same name, so FIO finds the files preconditioning laid out
+        let mut held_target = storage_target.clone();
+        held_target
+            .options
+            .insert(REUSE_OPTION.into(), "true".into());
+        // Removed once the rounds are over, or when they fail
+        let scratch = ScratchFiles::new(self.config.storage.test_directory.join(STATE_DIR));
+        let files = scratch.register(scratch::data_files(&name, &job, &storage_target))?;
+
+        let precondition = if settings.precondition {
+            let precondition_job = steady_state::precondition_job(&job, &storage_target);
+            self.logger.log_info(&format!(
+                "Preconditioning {}: {}× sequential {} writes",
+                storage_target.path.display(),
+                steady_state::PRECONDITION_LOOPS,
+                precondition_job.bs
+            ));
+            let mut precondition_control = control.clone().without_timeout();
+            if let Some(timeout) = settings.precondition_timeout {
+                precondition_control =
+                    precondition_control.with_timeout(Duration::from_secs(timeout));
+            }
+            let result = self.benchmark.run_job(
+                &name,
+                &precondition_job,
+                &held_target,
+                &precondition_control,
+            )?;
+            match result.metrics {
+                BenchmarkMetrics::FIO(fio) => Some(fio.details.combined()),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let criteria = settings.to_criteria();
+        let metric = criteria.metric;
+        let mut tracker = SteadyState::new(criteria);
+        while tracker.needs_round() {
+            let round = tracker.rounds().len() + 1;
+            let round_name = format!("{}_round{}", name, round);
+            let mut result =
+                self.run_recorded(|| self.benchmark.run_job(&name, &job, &held_target, control))?;
+            self.save_run(
+                &mut result,
+                &round_name,
+                Some(profile),
+                target,
+                &storage_target,
+                Some(&job),
+            );
+            let BenchmarkMetrics::FIO(fio) = &result.metrics else {
+                anyhow::bail!("Round {} did not return FIO results", round);
+            };
+
+            let stats = fio.details.combined();
+            let value = metric.value(&stats);
+            match tracker.record(stats, result.run_id.clone()) {
+                Some(window) => self.logger.log_info(&format!(
+                    "Round {}: {:.1} {}; rounds {}-{} excursion {:.1}%, slope excursion {:.1}%",
+                    round,
+                    value,
+                    metric.unit(),
+                    window.first_round,
+                    window.last_round,
+                    window.data_excursion,
+                    window.slope_excursion
+                )),
+                None => self.logger.log_info(&format!(
+                    "Round {}: {:.1} {}",
+                    round,
+                    value,
+                    metric.unit()
+                )),
+            }
+        }
+
+        if tracker.is_steady() {
+            self.logger.log_info(&format!(
+                "{} reached steady state after {} rounds",
+                name,
+                tracker.rounds().len()
+            ));
+        } else {
+            self.logger.log_warn(&format!(
+                "{} did not reach steady state in {} rounds",
+                name,
+                tracker.rounds().len()
+            ));
+        }
+
+        // Nothing was registered if the target keeps its files
+        let removed = files.remove();
+        if !removed.files.is_empty() {
+            self.logger.log_info(&format!(
+                "Removed {} scratch files ({})",
+                removed.files.len(),
+                format_bytes(removed.bytes)
+            ));
+        }
+
+        self.auto_prune();
+        Ok(tracker.into_report(precondition))
+    }
+
+    /// Removes runs and metric samples that `policy` expires, keeping pinned runs.
+    ///
+    /// With `dry_run` nothing is deleted and the report lists what would be.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the stored results cannot be listed or deleted; runs deleted
+    /// before the failure stay deleted.
+    pub fn prune(&self, policy: RetentionPolicy, dry_run: bool) -> anyhow::Result<PruneReport> {
+        let now = chrono::Utc::now().timestamp();
+        let mut report = PruneReport {
+            cutoff: policy.cutoff(now),
+            dry_run,
+            ..Default::default()
+        };
+        let Some(cutoff) = report.cutoff else {
+            return Ok(report);
+        };
+
+        let old = self.storage.list_runs(&RunFilter {
+            until: Some(cutoff),
+            ..Default::default()
+        })?;
+        (report.runs, report.pinned_kept) = policy.expired_runs(old, now);
+        report.metrics = self
+            .storage
+            .metric_keys()?
+            .into_iter()
+            .filter(|key| policy.metric_expired(key, now))
+            .collect();
+
+        if !dry_run {
+            for run in &report.runs {
+                self.storage.delete_run(&run.id)?;
+            }
+            for key in &report.metrics {
+                self.storage.delete_metrics(key)?;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Removes FIO scratch files that runs left behind in `storage.test_directory`.
+    ///
+    /// Files of processes that are still running are left alone; see
+    /// [`find_orphans`](scratch::find_orphans).  With `dry_run` nothing is deleted and the
+    /// report lists what would be.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the test directory cannot be read.  Files that cannot be
+    /// removed are listed in the report instead.
+    pub fn clean(&self, dry_run: bool) -> anyhow::Result<CleanReport> {
+        let dir = &self.config.storage.test_directory;
+        let orphans = scratch::find_orphans(&dir.join(scratch::STATE_DIR), dir).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to look for scratch files in {}: {}",
+                dir.display(),
+                e
+            )
+        })?;
+
+        let mut report = CleanReport {
+            bytes: orphans.files.iter().map(|f| f.bytes).sum(),
+            files: orphans.files.clone(),
+            live_processes: orphans.live_processes,
+            dry_run,
+            ..Default::default()
+        };
+        if !dry_run {
+            let removed = scratch::remove_orphans(&orphans);
+            for (file, e) in &removed.errors {
+                self.logger.log_warn(&format!(
+                    "Failed to remove scratch file {}: {}",
+                    file.display(),
+                    e
+                ));
+                report.errors.push(format!("{}: {}", file.display(), e));
+            }
+            self.logger.log_info(&format!(
+                "Removed {} scratch files ({})",
+                removed.files.len(),
+                format_bytes(removed.bytes)
+            ));
+        }
+        Ok(report)
+    }
+
+    /// Returns the newest stored run of every benchmark job.
+    ///
+    /// A job is a run name on one host, target and profile; older runs of the same job are
+    /// left out.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the stored runs cannot be listed.
+    pub fn latest_runs(&self) -> anyhow::Result<Vec<RunRecord>> {
+        self.storage.latest_runs()
+    }
+
+    /// Applies `[database]` retention after a benchmark when `auto_prune` is set.
+    ///
+    /// Failing to prune is logged but does not fail the benchmark.
+    fn auto_prune(&self) {
+        let config = &self.config.database;
+        if !config.auto_prune || config.retention_days == 0 {
+            return;
+        }
+
+        match self.prune(RetentionPolicy::new(config.retention_days), false) {
+            Ok(report) if !report.runs.is_empty() || !report.metrics.is_empty() => {
+                self.logger.log_info(&format!(
+                    "Pruned {} runs and {} metric samples older than {} days",
+                    report.runs.len(),
+                    report.metrics.len(),
+                    config.retention_days
+                ))
+            }
+            Ok(_) => {}
+            Err(e) => self
+                .logger
+                .log_warn(&format!("Failed to prune stored results: {:#}", e)),
+        }
+    }
+
+    /// Runs a benchmark while the `[metrics]` recorder samples system metrics in the
+    /// background, and attaches the series to the result.
+    ///
+    /// Recording is best effort: if the recorder cannot start the benchmark still runs.
+    fn run_recorded<F>(&self, run: F) -> anyhow::Result<BenchmarkResult>
+    where
+        F: FnOnce() -> anyhow::Result<BenchmarkResult>,
+    {
+        let recording = self.start_recording();
+        let result = run();
+        let series = recording.map(Recording::stop);
+
+        let mut result = result?;
+        if let Some(series) = series {
+            self.logger.log_info(&format!(
+                "Recorded {} metric samples over {:.1}s",
+                series.len(),
+                series.duration_secs()
+            ));
+            for error in &series.errors {
+                self.logger
+                    .log_warn(&format!("Metric recording failed: {}", error));
+            }
+            result.system_metrics = Some(series);
+        }
+        Ok(result)
+    }
+
+    /// Saves a successful FIO run to the history and stores its ID in `result.run_id`.
+    ///
+    /// Failing to save is logged but does not fail the benchmark.
+    fn save_run(
+        &self,
+        result: &mut BenchmarkResult,
+        name: &str,
+        profile: Option<&str>,
+        target: Option<&str>,
+        storage_target: &StorageTarget,
+        job: Option<&FioJobConfig>,
+    ) {
+        let BenchmarkMetrics::FIO(fio) = &result.metrics else {
+            return;
+        };
+
+        let mut run = RunRecord::new(name, storage_target, (*fio.details).clone());
+        run.profile = profile.map(String::from);
+        run.target = target.map(String::from);
+        run.job = job.cloned();
+        run.system_metrics = result.system_metrics.clone();
+        if run.fio_version.is_none() {
+            run.fio_version = self.benchmark.check_fio_installation().ok();
+        }
+
+        match self.storage.save_run(&run) {
+            Ok(()) => {
+                self.logger.log_info(&format!("Saved run {}", run.id));
+                result.run_id = Some(run.id);
+            }
+            Err(e) => self
+                .logger
+                .log_warn(&format!("Failed to save run {}: {:#}", run.id, e)),
+        }
+    }
+
+    fn start_recording(&self) -> Option<Recording> {
+        let config = &self.config.metrics;
+        if !config.record_benchmarks || config.collect.is_empty() {
+            return None;
+        }
+
+        let recorder = MetricsRecorder::new(
+            self.metrics.clone(),
+            config.collect.clone(),
+            Duration::from_secs(config.interval),
+        );
+        match recorder.start() {
+            Ok(recording) => Some(recording),
+            Err(e) => {
+                self.logger
+                    .log_warn(&format!("Failed to start metric recording: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Checks that a workload may run on `storage_target`, the target named `target`.
+    ///
+    /// Workloads that write to a raw device are refused while the device is in use, and
+    /// otherwise need `control` to acknowledge the data loss; see [`SafetyGuard`].
+    ///
+    /// # Errors
+    ///
+    /// Will return a [`SafetyError`](crate::domain::storage::safety::SafetyError) when the
+    /// workload may not run.
+    pub fn check_target(
+        &self,
+        target: Option<&str>,
+        storage_target: &StorageTarget,
+        writes: bool,
+        control: &RunControl,
+    ) -> anyhow::Result<()> {
+        let name = target.unwrap_or("the test directory");
+        let report = SafetyGuard::new().check(
+            name,
+            storage_target,
+            writes,
+            control.data_loss_acknowledged(),
+        )?;
+        if let Some(report) = report.filter(|_| writes) {
+            for content in &report.contents {
+                self.logger.log_warn(&format!(
+                    "Overwriting {} on {} ({})",
+                    content,
+                    name,
+                    storage_target.path.display()
+                ));
+            }
+        }
         Ok(())
     }
+
+    /// Checks that `job`'s data files fit on `storage_target` and that the target supports its
+    /// I/O mode, following the `[storage]` pre-flight settings.
+    ///
+    /// # Returns
+    ///
+    /// `job`, with its size scaled down when it does not fit and `storage.scale_to_fit` is set.
+    ///
+    /// # Errors
+    ///
+    /// Will return a [`PreflightError`](crate::domain::storage::preflight::PreflightError)
+    /// when the job cannot run.
+    pub fn preflight(
+        &self,
+        job: &FioJobConfig,
+        storage_target: &StorageTarget,
+    ) -> anyhow::Result<FioJobConfig> {
+        let mut job = job.clone();
+        match Preflight::new(self.config.preflight()).check(&job, storage_target)? {
+            Some(capacity) if capacity.scaled => {
+                self.logger.log_warn(&format!(
+                    "Scaling size from {} to {} per job to fit the {} free on {}",
+                    job.size,
+                    format_bytes(capacity.size),
+                    format_bytes(capacity.available),
+                    storage_target.path.display()
+                ));
+                // Written in the job's own units: under kb_base=1000, "M" is 10^6 bytes
+                job.size = ByteSize::from_bytes(capacity.size).to_fio(job.kb_base()?);
+            }
+            Some(capacity) => self.logger.log_debug(&format!(
+                "{} needs {} of the {} available",
+                storage_target.path.display(),
+                format_bytes(capacity.required),
+                format_bytes(capacity.available)
+            )),
+            None => {}
+        }
+        Ok(job)
+    }
+
+    /// Resolves a `[[storage.targets]]` name, defaulting to `storage.test_directory`.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if no target is named `target`.
+    pub fn resolve_target(&self, target: Option<&str>) -> anyhow::Result<StorageTarget> {
+        Ok(match target {
+            Some(name) => self.config.storage_target(name)?,
+            None => StorageTarget {
+                path: self.config.storage.test_directory.clone(),
+                target_type: "directory".into(),
+                options: Default::default(),
+            },
+        })
+    }
 }
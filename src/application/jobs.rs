@@ -0,0 +1,843 @@
+//! Benchmark jobs submitted to a long-running server.
+//!
+//! [`JobQueue::submit`] queues a [`JobSpec`] — a profile and, optionally, a storage target — and
+//! returns at once.  [`JobQueue::work`] runs queued jobs through [`Application::run_profile`],
+//! so results land in the run history exactly as they do for `sysperf-svr benchmark --profile`.
+//!
+//! Scheduling follows `[storage]`:
+//!
+//! - at most `max_concurrent_tests` jobs run at a time;
+//! - two jobs never run on the same disk at once (see [`StorageTarget::device_key`]); a job
+//!   waiting for its disk does not hold up later jobs on other disks;
+//! - a job is stopped after its own timeout, or `default_timeout` seconds.
+//!
+//! Jobs can be cancelled whether queued or running.  Every job keeps the log lines emitted
+//! while it runs; clients follow them with [`JobQueue::read_log`].
+//!
+//! Jobs live in memory only: the queue and the job logs are lost when the server stops, while
+//! the runs they produced stay in the database.
+//!
+//! [`StorageTarget::device_key`]: crate::domain::storage::fio::StorageTarget::device_key
+
+use super::Application;
+use crate::domain::storage::history::new_run_id;
+use crate::ports::benchmark_port::{BenchmarkStopped, RunControl};
+use crate::ports::log_port::LoggerPort;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Finished jobs kept for clients to query; older ones are forgotten first
+const MAX_FINISHED_JOBS: usize = 1000;
+
+/// What a job runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobSpec {
+    /// Profile from `[storage.fio.profiles]`
+    pub profile: String,
+    /// Target from `[[storage.targets]]`; the test directory when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Seconds after which the job is stopped; `storage.default_timeout` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+/// Where a job is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    TimedOut,
+    Cancelled,
+}
+
+impl JobState {
+    /// Whether the job has stopped running, successfully or not.
+    pub fn is_finished(self) -> bool {
+        !matches!(self, JobState::Queued | JobState::Running)
+    }
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Succeeded => "succeeded",
+            JobState::Failed => "failed",
+            JobState::TimedOut => "timed out",
+            JobState::Cancelled => "cancelled",
+        };
+        write!(f, "{}", state)
+    }
+}
+
+/// Status of a submitted job
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    /// Job ID, in the same form as run IDs
+    pub id: String,
+    #[serde(flatten)]
+    pub spec: JobSpec,
+    pub state: JobState,
+    /// Unix time the job was submitted
+    pub submitted_at: i64,
+    /// Unix time the job started running
+    pub started_at: Option<i64>,
+    /// Unix time the job finished
+    pub finished_at: Option<i64>,
+    /// ID of the saved run, once the job has succeeded
+    pub run_id: Option<String>,
+    /// Why the job failed, timed out or was cancelled
+    pub error: Option<String>,
+}
+
+/// Why a job cannot be cancelled
+#[derive(Debug, Error)]
+pub enum JobError {
+    #[error("No job with ID {0}")]
+    NotFound(String),
+    #[error("Job {id} has already finished: {state}")]
+    Finished { id: String, state: JobState },
+}
+
+/// Log lines of one job
+#[derive(Default)]
+struct JobLog {
+    state: Mutex<LogState>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct LogState {
+    lines: Vec<String>,
+    finished: bool,
+}
+
+impl JobLog {
+    fn push(&self, line: String) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.lines.push(line);
+        self.changed.notify_all();
+    }
+
+    fn finish(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.finished = true;
+        self.changed.notify_all();
+    }
+}
+
+thread_local! {
+    /// Log of the job running on this thread
+    static CURRENT_LOG: RefCell<Option<Arc<JobLog>>> = const { RefCell::new(None) };
+}
+
+/// Logger that also appends every message to the log of the job running on the calling thread
+///
+/// Wrap the process logger in it before building the adapters, so that what the benchmark
+/// adapter and the application log while a job runs shows up in that job's log.
+#[derive(Debug)]
+pub struct JobLogger {
+    inner: Arc<dyn LoggerPort>,
+}
+
+impl JobLogger {
+    /// Creates a logger forwarding to `inner`.
+    pub fn new(inner: Arc<dyn LoggerPort>) -> Self {
+        Self { inner }
+    }
+
+    fn capture(level: &str, message: &str) {
+        CURRENT_LOG.with(|current| {
+            if let Some(log) = current.borrow().as_ref() {
+                log.push(format!(
+                    "{} [{}] - {}",
+                    Local::now().format("%Y-%m-%dT%H:%M:%S"),
+                    level,
+                    message
+                ));
+            }
+        });
+    }
+}
+
+impl LoggerPort for JobLogger {
+    fn log_info(&self, message: &str) {
+        Self::capture("INFO", message);
+        self.inner.log_info(message);
+    }
+
+    fn log_warn(&self, message: &str) {
+        Self::capture("WARN", message);
+        self.inner.log_warn(message);
+    }
+
+    fn log_error(&self, message: &str) {
+        Self::capture("ERROR", message);
+        self.inner.log_error(message);
+    }
+
+    fn log_debug(&self, message: &str) {
+        Self::capture("DEBUG", message);
+        self.inner.log_debug(message);
+    }
+
+    fn log_trace(&self, message: &str) {
+        self.inner.log_trace(message);
+    }
+}
+
+/// Queue of submitted jobs, started in submission order as workers and disks free up
+#[derive(Default)]
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+    /// Raw-device targets that jobs may write to, destroying the data on them
+    data_loss_allowed: HashSet<String>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Jobs in submission order
+    jobs: Vec<Entry>,
+    /// Disks with a job running on them
+    busy: HashSet<String>,
+    closed: bool,
+}
+
+struct Entry {
+    job: Job,
+    log: Arc<JobLog>,
+    control: RunControl,
+    /// Disk the job runs on
+    device: String,
+}
+
+/// A job a worker has taken off the queue
+struct Started {
+    job: Job,
+    log: Arc<JobLog>,
+    control: RunControl,
+    device: String,
+}
+
+impl QueueState {
+    fn find(&mut self, id: &str) -> Option<&mut Entry> {
+        self.jobs.iter_mut().find(|entry| entry.job.id == id)
+    }
+}
+
+impl JobQueue {
+    /// Creates an empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets jobs run write workloads on the raw-device `targets`.
+    ///
+    /// This is the operator's decision, made when the queue is created; submitters cannot
+    /// acknowledge the data loss themselves.
+    pub fn allow_data_loss(mut self, targets: impl IntoIterator<Item = String>) -> Self {
+        self.data_loss_allowed.extend(targets);
+        self
+    }
+
+    /// Queues `spec` and returns the new job.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the profile or target is not configured, or the job fails
+    /// [`Application::check_target`] or [`Application::preflight`], so that mistakes are
+    /// reported to the submitter rather than when the job runs.
+    pub fn submit(&self, app: &Application, mut spec: JobSpec) -> anyhow::Result<Job> {
+        let timeout = *spec
+            .timeout
+            .get_or_insert(app.config.storage.default_timeout);
+        if timeout == 0 {
+            anyhow::bail!("timeout must be greater than 0 seconds");
+        }
+        let mut control = RunControl::new().with_timeout(Duration::from_secs(timeout));
+        if let Some(target) = &spec.target {
+            if self.data_loss_allowed.contains(target) {
+                control = control.acknowledge_data_loss();
+            }
+        }
+
+        let fio_job = app.config.job_for_profile(&spec.profile)?;
+        let storage_target = app.resolve_target(spec.target.as_deref())?;
+        app.check_target(
+            spec.target.as_deref(),
+            &storage_target,
+            fio_job.rw.writes(),
+            &control,
+        )?;
+        app.preflight(&fio_job, &storage_target)?;
+        let device = storage_target.device_key();
+
+        let now = chrono::Utc::now();
+        let job = Job {
+            id: new_run_id(now),
+            spec,
+            state: JobState::Queued,
+            submitted_at: now.timestamp(),
+            started_at: None,
+            finished_at: None,
+            run_id: None,
+            error: None,
+        };
+
+        let mut state = self.lock();
+        app.logger.log_info(&format!(
+            "Queued job {}: profile {} on {}",
+            job.id,
+            job.spec.profile,
+            job.spec.target.as_deref().unwrap_or("the test directory")
+        ));
+        state.jobs.push(Entry {
+            job: job.clone(),
+            log: Arc::default(),
+            control,
+            device,
+        });
+
+        let finished = state
+            .jobs
+            .iter()
+            .filter(|entry| entry.job.state.is_finished())
+            .count();
+        if finished > MAX_FINISHED_JOBS {
+            let mut excess = finished - MAX_FINISHED_JOBS;
+            state.jobs.retain(|entry| {
+                let forget = excess > 0 && entry.job.state.is_finished();
+                excess -= usize::from(forget);
+                !forget
+            });
+        }
+
+        self.changed.notify_all();
+        Ok(job)
+    }
+
+    /// Returns the job with ID `id`.
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.lock().find(id).map(|entry| entry.job.clone())
+    }
+
+    /// Returns every known job, newest first.
+    pub fn list(&self) -> Vec<Job> {
+        self.lock()
+            .jobs
+            .iter()
+            .rev()
+            .map(|entry| entry.job.clone())
+            .collect()
+    }
+
+    /// Cancels job `id`.
+    ///
+    /// A queued job is cancelled at once.  A running job keeps the `running` state until FIO
+    /// has stopped, which can take a few seconds.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if there is no such job or it has already finished.
+    pub fn cancel(&self, id: &str) -> Result<Job, JobError> {
+        let mut state = self.lock();
+        let entry = state
+            .find(id)
+            .ok_or_else(|| JobError::NotFound(id.to_string()))?;
+        match entry.job.state {
+            JobState::Queued => {
+                entry.job.state = JobState::Cancelled;
+                entry.job.finished_at = Some(chrono::Utc::now().timestamp());
+                entry.job.error = Some("Cancelled before it started".into());
+                entry.log.finish();
+            }
+            JobState::Running => entry.control.cancel(),
+            state => {
+                return Err(JobError::Finished {
+                    id: id.to_string(),
+                    state,
+                })
+            }
+        }
+        Ok(entry.job.clone())
+    }
+
+    /// Returns the log lines of job `id` from line `from` on, and whether the log is complete.
+    ///
+    /// When there are no new lines yet and the job has not finished, waits up to `wait` for
+    /// some to arrive.  Returns `None` for an unknown job.
+    pub fn read_log(&self, id: &str, from: usize, wait: Duration) -> Option<(Vec<String>, bool)> {
+        let log = self.lock().find(id).map(|entry| entry.log.clone())?;
+
+        let deadline = Instant::now() + wait;
+        let mut state = log.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.lines.len() <= from && !state.finished {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            state = log
+                .changed
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+
+        let lines = state.lines.get(from..).unwrap_or_default().to_vec();
+        Some((lines, state.finished))
+    }
+
+    /// Runs queued jobs until [`close`](Self::close) is called.
+    ///
+    /// Up to `storage.max_concurrent_tests` jobs run at once, each on its own thread; a job
+    /// that fails is recorded and the next one starts.
+    pub fn work(&self, app: &Application) {
+        let workers = app.config.storage.max_concurrent_tests.max(1);
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    while let Some(started) = self.next() {
+                        self.run(app, started);
+                    }
+                });
+            }
+        });
+    }
+
+    /// Stops [`work`](Self::work): running jobs are cancelled, and jobs still queued will
+    /// never run, so they are failed.
+    pub fn close(&self) {
+        let mut state = self.lock();
+        state.closed = true;
+        let now = chrono::Utc::now().timestamp();
+        for entry in state.jobs.iter_mut() {
+            match entry.job.state {
+                JobState::Queued => {
+                    entry.job.state = JobState::Failed;
+                    entry.job.finished_at = Some(now);
+                    entry.job.error = Some("The server stopped before the job started".into());
+                    entry.log.finish();
+                }
+                JobState::Running => entry.control.cancel(),
+                _ => {}
+            }
+        }
+        self.changed.notify_all();
+    }
+
+    /// Waits for the oldest queued job whose disk is free, and marks it running.
+    fn next(&self) -> Option<Started> {
+        let mut state = self.lock();
+        loop {
+            if state.closed {
+                return None;
+            }
+            let QueueState { jobs, busy, .. } = &mut *state;
+            if let Some(entry) = jobs
+                .iter_mut()
+                .find(|entry| entry.job.state == JobState::Queued && !busy.contains(&entry.device))
+            {
+                busy.insert(entry.device.clone());
+                entry.job.state = JobState::Running;
+                entry.job.started_at = Some(chrono::Utc::now().timestamp());
+                return Some(Started {
+                    job: entry.job.clone(),
+                    log: entry.log.clone(),
+                    control: entry.control.clone(),
+                    device: entry.device.clone(),
+                });
+            }
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn run(&self, app: &Application, started: Started) {
+        let Started {
+            job,
+            log,
+            control,
+            device,
+        } = started;
+        CURRENT_LOG.with(|current| *current.borrow_mut() = Some(log.clone()));
+        app.logger.log_info(&format!(
+            "Job {} started: profile {} on {} ({})",
+            job.id,
+            job.spec.profile,
+            job.spec.target.as_deref().unwrap_or("the test directory"),
+            device
+        ));
+
+        let outcome = app.run_profile(&job.spec.profile, job.spec.target.as_deref(), &control);
+        let state = match &outcome {
+            Ok(_) => JobState::Succeeded,
+            Err(e) => match BenchmarkStopped::find(e) {
+                Some(BenchmarkStopped::TimedOut(_)) => JobState::TimedOut,
+                Some(BenchmarkStopped::Cancelled) => JobState::Cancelled,
+                None => JobState::Failed,
+            },
+        };
+        match &outcome {
+            Ok(result) => app.logger.log_info(&format!(
+                "Job {} succeeded{}",
+                job.id,
+                result
+                    .run_id
+                    .as_deref()
+                    .map(|id| format!(" as run {}", id))
+                    .unwrap_or_default()
+            )),
+            Err(e) => app
+                .logger
+                .log_error(&format!("Job {} {}: {:#}", job.id, state, e)),
+        }
+        CURRENT_LOG.with(|current| *current.borrow_mut() = None);
+
+        let mut queue = self.lock();
+        queue.busy.remove(&device);
+        if let Some(entry) = queue.find(&job.id) {
+            entry.job.state = state;
+            entry.job.finished_at = Some(chrono::Utc::now().timestamp());
+            match outcome {
+                Ok(result) => entry.job.run_id = result.run_id,
+                Err(e) => entry.job.error = Some(format!("{:#}", e)),
+            }
+        }
+        log.finish();
+        self.changed.notify_all();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::database_adapter::DatabaseAdapter;
+    use crate::adapters::log_adapter::FernLogger;
+    use crate::adapters::metrics_adapter::MetricsAdapter;
+    use crate::adapters::storage_adapter::StorageAdapter;
+    use crate::config::Config;
+    use crate::domain::storage::fio::{FioJobConfig, FioResult, StorageTarget};
+    use crate::ports::benchmark_port::{
+        BenchmarkConfig, BenchmarkMetrics, BenchmarkPort, BenchmarkResult, BenchmarkTool,
+    };
+    use anyhow::{anyhow, Result};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Succeeds for every job except those named "broken"; jobs named "slow" run until they
+    /// are stopped
+    struct FakeFio {
+        logger: Arc<dyn LoggerPort>,
+        running: AtomicUsize,
+        most_running: AtomicUsize,
+    }
+
+    impl BenchmarkPort for FakeFio {
+        fn run(&self) -> Result<Vec<FioResult>> {
+            Ok(Vec::new())
+        }
+        fn run_config(
+            &self,
+            _config: &BenchmarkConfig,
+            _control: &RunControl,
+        ) -> Result<BenchmarkResult> {
+            Err(anyhow!("not supported"))
+        }
+        fn run_job(
+            &self,
+            name: &str,
+            _job: &FioJobConfig,
+            _target: &StorageTarget,
+            control: &RunControl,
+        ) -> Result<BenchmarkResult> {
+            self.logger.log_info(&format!("fio {}", name));
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_running.fetch_max(running, Ordering::SeqCst);
+
+            let started = Instant::now();
+            let outcome = loop {
+                if name.starts_with("broken") {
+                    break Err(anyhow!("fio exited with status 1"));
+                }
+                if control.is_cancelled() {
+                    break Err(BenchmarkStopped::Cancelled.into());
+                }
+                match control.timeout() {
+                    Some(timeout) if started.elapsed() >= timeout => {
+                        break Err(BenchmarkStopped::TimedOut(timeout).into())
+                    }
+                    _ if !name.starts_with("slow")
+                        && started.elapsed() >= Duration::from_millis(200) =>
+                    {
+                        break Ok(BenchmarkResult {
+                            tool: BenchmarkTool::FIO,
+                            metrics: BenchmarkMetrics::FIO(FioResult::default().into()),
+                            raw_output: String::new(),
+                            system_metrics: None,
+                            run_id: None,
+                        })
+                    }
+                    _ => thread::sleep(Duration::from_millis(10)),
+                }
+            };
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            outcome
+        }
+        fn run_fio(&self) -> Result<String> {
+            Ok(String::new())
+        }
+        fn validate(&self) -> Result<()> {
+            Ok(())
+        }
+        fn run_command(&self, _command: &str, _args: &str) -> Result<String> {
+            Ok(String::new())
+        }
+        fn check_fio_installation(&self) -> Result<String> {
+            Ok("fio-3.36".into())
+        }
+    }
+
+    /// An application with the profiles quick, broken and slow, the targets `a` and `b` on the
+    /// disk holding `dir`, the target `other` elsewhere, and the raw device `raw`
+    fn app(dir: &Path, max_concurrent_tests: u32) -> (Application, Arc<FakeFio>) {
+        let config = Config::parse(
+            &format!(
+                "[storage]\ntest_directory = \"{dir}\"\nmax_concurrent_tests = {max_concurrent_tests}\n\
+                 [[storage.targets]]\nname = \"a\"\npath = \"{dir}/a\"\ntype = \"file\"\n\
+                 [[storage.targets]]\nname = \"b\"\npath = \"{dir}/b\"\ntype = \"file\"\n\
+                 [[storage.targets]]\nname = \"other\"\npath = \"/dev/null\"\ntype = \"file\"\n\
+                 [[storage.targets]]\nname = \"raw\"\npath = \"{dir}/raw\"\ntype = \"block_device\"\n\
+                 [storage.fio.defaults]\nsize = \"1M\"\n\
+                 [storage.fio.profiles.quick]\nrw = \"randread\"\n\
+                 [storage.fio.profiles.broken]\nrw = \"randwrite\"\n\
+                 [storage.fio.profiles.slow]\nrw = \"read\"\n\
+                 [metrics]\nrecord_benchmarks = false\n",
+                dir = dir.display(),
+            ),
+            Path::new("t.toml"),
+        )
+        .unwrap();
+        let logger: Arc<dyn LoggerPort> = Arc::new(JobLogger::new(Arc::new(FernLogger::new())));
+        let fio = Arc::new(FakeFio {
+            logger: logger.clone(),
+            running: AtomicUsize::new(0),
+            most_running: AtomicUsize::new(0),
+        });
+        let db = Arc::new(DatabaseAdapter::new(dir.join("db")));
+        let app = Application::new(
+            config,
+            db.clone(),
+            fio.clone(),
+            Arc::new(MetricsAdapter::new()),
+            Arc::new(StorageAdapter::new(db)),
+            logger,
+        );
+        (app, fio)
+    }
+
+    fn spec(profile: &str, target: Option<&str>) -> JobSpec {
+        JobSpec {
+            profile: profile.into(),
+            target: target.map(String::from),
+            timeout: None,
+        }
+    }
+
+    /// Waits until job `id` is in `state`.
+    fn wait_for(queue: &JobQueue, id: &str, state: JobState) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while queue.get(id).unwrap().state != state {
+            assert!(
+                Instant::now() < deadline,
+                "job {} never became {}",
+                id,
+                state
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_runs_jobs_in_order_and_keeps_their_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, _) = app(dir.path(), 1);
+
+        let queue = JobQueue::new();
+        let quick = queue.submit(&app, spec("quick", None)).unwrap();
+        let broken = queue.submit(&app, spec("broken", None)).unwrap();
+        assert_eq!(queue.get(&quick.id).unwrap().state, JobState::Queued);
+        assert_eq!(queue.list()[0].id, broken.id);
+        assert_eq!(quick.spec.timeout, Some(3600));
+        assert!(queue.submit(&app, spec("nope", None)).is_err());
+        assert!(queue.submit(&app, spec("quick", Some("nope"))).is_err());
+
+        thread::scope(|scope| {
+            scope.spawn(|| queue.work(&app));
+            // Follow the second job's log until it is complete
+            let mut lines = Vec::new();
+            loop {
+                let (new, finished) = queue
+                    .read_log(&broken.id, lines.len(), Duration::from_secs(5))
+                    .unwrap();
+                lines.extend(new);
+                if finished {
+                    break;
+                }
+            }
+            queue.close();
+
+            assert!(lines.iter().any(|l| l.ends_with("[INFO] - fio broken")));
+            assert!(lines.last().unwrap().contains("[ERROR] - Job"));
+        });
+
+        let quick = queue.get(&quick.id).unwrap();
+        assert_eq!(quick.state, JobState::Succeeded);
+        let run_id = quick.run_id.unwrap();
+        assert_eq!(
+            app.storage
+                .get_run(&run_id)
+                .unwrap()
+                .unwrap()
+                .profile
+                .as_deref(),
+            Some("quick")
+        );
+        let (lines, finished) = queue.read_log(&quick.id, 0, Duration::ZERO).unwrap();
+        assert!(finished);
+        assert!(lines.iter().any(|l| l.ends_with("- fio quick")));
+        assert!(lines
+            .last()
+            .unwrap()
+            .ends_with(&format!("succeeded as run {}", run_id)));
+
+        let broken = queue.get(&broken.id).unwrap();
+        assert_eq!(broken.state, JobState::Failed);
+        assert!(broken.error.unwrap().contains("fio exited with status 1"));
+        assert!(queue.read_log("unknown", 0, Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_never_runs_two_jobs_on_one_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, fio) = app(dir.path(), 4);
+
+        let queue = JobQueue::new();
+        let slow = queue.submit(&app, spec("slow", Some("a"))).unwrap();
+        let same_disk = queue.submit(&app, spec("quick", Some("b"))).unwrap();
+        let other_disk = queue.submit(&app, spec("quick", Some("other"))).unwrap();
+
+        thread::scope(|scope| {
+            scope.spawn(|| queue.work(&app));
+
+            // The job on the other disk overtakes the one waiting for the slow job's disk
+            wait_for(&queue, &other_disk.id, JobState::Succeeded);
+            assert_eq!(queue.get(&same_disk.id).unwrap().state, JobState::Queued);
+
+            let cancelled = queue.cancel(&slow.id).unwrap();
+            assert_eq!(cancelled.state, JobState::Running);
+            wait_for(&queue, &same_disk.id, JobState::Succeeded);
+            queue.close();
+        });
+
+        let slow = queue.get(&slow.id).unwrap();
+        assert_eq!(slow.state, JobState::Cancelled);
+        assert!(slow.error.unwrap().contains("cancelled"));
+        assert_eq!(fio.most_running.load(Ordering::SeqCst), 2);
+        assert!(matches!(
+            queue.cancel(&slow.id),
+            Err(JobError::Finished {
+                state: JobState::Cancelled,
+                ..
+            })
+        ));
+        assert!(matches!(queue.cancel("nope"), Err(JobError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_times_out_and_cancels_queued_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, _) = app(dir.path(), 1);
+
+        let queue = JobQueue::new();
+        let mut slow = spec("slow", None);
+        slow.timeout = Some(1);
+        let slow = queue.submit(&app, slow).unwrap();
+        let queued = queue.submit(&app, spec("quick", None)).unwrap();
+        let mut zero = spec("quick", None);
+        zero.timeout = Some(0);
+        assert!(queue.submit(&app, zero).is_err());
+
+        assert_eq!(queue.cancel(&queued.id).unwrap().state, JobState::Cancelled);
+        thread::scope(|scope| {
+            scope.spawn(|| queue.work(&app));
+            wait_for(&queue, &slow.id, JobState::TimedOut);
+            queue.close();
+        });
+
+        let slow = queue.get(&slow.id).unwrap();
+        assert!(slow
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("timed out after 1s"));
+        assert_eq!(
+            serde_json::to_value(&slow).unwrap()["state"],
+            serde_json::json!("timed_out")
+        );
+        let queued = queue.get(&queued.id).unwrap();
+        assert_eq!(queued.state, JobState::Cancelled);
+        assert!(queued.started_at.is_none());
+    }
+
+    #[test]
+    fn test_close_fails_queued_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let (app, _) = app(dir.path(), 1);
+        let queue = JobQueue::new();
+        let job = queue.submit(&app, spec("quick", Some("a"))).unwrap();
+
+        // Writing to a raw device needs the operator to allow the data loss
+        let e = queue.submit(&app, spec("broken", Some("raw"))).unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("data loss on raw has not been allowed"));
+        let unsafe_spec =
+            r#"{"profile": "broken", "target": "raw", "i_understand_data_loss": true}"#;
+        assert!(serde_json::from_str::<JobSpec>(unsafe_spec).is_err());
+        let allowed = JobQueue::new().allow_data_loss(["raw".to_string()]);
+        allowed.submit(&app, spec("broken", Some("raw"))).unwrap();
+        allowed.close();
+        queue.close();
+
+        let job = queue.get(&job.id).unwrap();
+        assert_eq!(job.state, JobState::Failed);
+        assert!(job.finished_at.is_some());
+        assert_eq!(
+            serde_json::to_value(&job).unwrap()["target"],
+            serde_json::json!("a")
+        );
+        assert!(
+            queue
+                .read_log(&job.id, 0, Duration::from_secs(5))
+                .unwrap()
+                .1
+        );
+    }
+}
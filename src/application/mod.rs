@@ -1,2 +1,6 @@
+#[allow(clippy::module_inception)]
 pub mod application;
+pub mod jobs;
+pub mod recorder;
+pub mod sampler;
 pub use application::Application;
@@ -0,0 +1,280 @@
+//! Background recording of system metrics while a benchmark runs.
+//!
+//! [`MetricsRecorder::start`] spawns a tokio task that samples the selected metrics through the
+//! [`MetricsPort`] once per interval until the returned [`Recording`] is stopped, at which point
+//! the collected [`MetricSeries`] is handed back.  The task runs on the caller's multi-thread
+//! runtime when there is one, and on a small runtime owned by the recording otherwise, so the
+//! synchronous benchmark runners can be wrapped without becoming async themselves.
+
+use crate::domain::metrics::series::{MetricKind, MetricSeries};
+use crate::ports::metrics_port::MetricsPort;
+use anyhow::{Context, Result};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+use tokio::runtime::{Handle, Runtime, RuntimeFlavor};
+use tokio::sync::oneshot;
+use tokio::task;
+use tokio::time::{self, MissedTickBehavior};
+
+/// Samples system metrics in the background at a fixed interval
+pub struct MetricsRecorder {
+    metrics: Arc<dyn MetricsPort>,
+    collect: Vec<MetricKind>,
+    interval: Duration,
+}
+
+/// A recording in progress; stop it to obtain the samples
+///
+/// Dropping a recording without calling [`Recording::stop`] ends the background task and
+/// discards its samples.
+pub struct Recording {
+    stop: Option<oneshot::Sender<()>>,
+    done: mpsc::Receiver<MetricSeries>,
+    /// Present when no suitable runtime was running at start
+    runtime: Option<Runtime>,
+}
+
+impl MetricsRecorder {
+    /// Creates a recorder sampling `collect` every `interval`.
+    ///
+    /// Rate-based metrics (mpstat, vmstat, iostat) are averaged over the window the
+    /// [`MetricsPort`] is configured with; when that window is longer than `interval`, samples
+    /// are simply taken back to back.
+    pub fn new(
+        metrics: Arc<dyn MetricsPort>,
+        collect: Vec<MetricKind>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            metrics,
+            collect,
+            interval,
+        }
+    }
+
+    /// Starts sampling in the background.
+    ///
+    /// The first sample is taken immediately.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if no runtime is running and one cannot be created
+    pub fn start(&self) -> Result<Recording> {
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let (done_tx, done_rx) = mpsc::channel();
+        let task = record(
+            self.metrics.clone(),
+            self.collect.clone(),
+            self.interval,
+            stop_rx,
+        );
+        let task = async move {
+            // The receiver is gone when the recording was dropped
+            let _ = done_tx.send(task.await);
+        };
+
+        let runtime = match Handle::try_current() {
+            // Collectors block in spawn_blocking, which a current-thread runtime cannot host
+            // alongside a blocked caller
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                handle.spawn(task);
+                None
+            }
+            _ => {
+                let runtime = tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .thread_name("metrics-recorder")
+                    .enable_all()
+                    .build()
+                    .context("Failed to build the metrics recorder runtime")?;
+                runtime.spawn(task);
+                Some(runtime)
+            }
+        };
+
+        Ok(Recording {
+            stop: Some(stop_tx),
+            done: done_rx,
+            runtime,
+        })
+    }
+}
+
+impl Recording {
+    /// Stops sampling and returns the recorded series.
+    ///
+    /// A sample that is in flight is allowed to finish, so this blocks for at most one
+    /// sampling window.
+    pub fn stop(mut self) -> MetricSeries {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        self.done.recv().unwrap_or_default()
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        // Dropping the sender stops the task if stop() was never called
+        self.stop.take();
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Samples `collect` every `interval` until `stop` fires or is dropped.
+async fn record(
+    metrics: Arc<dyn MetricsPort>,
+    collect: Vec<MetricKind>,
+    interval: Duration,
+    mut stop: oneshot::Receiver<()>,
+) -> MetricSeries {
+    let mut series = MetricSeries::new(interval.as_millis() as u64, now_ms());
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = &mut stop => break,
+            _ = ticker.tick() => {}
+        }
+
+        // As collect_all, but on the blocking pool so the collectors can reach this runtime
+        let samples: Vec<_> = collect
+            .iter()
+            .map(|&kind| {
+                let metrics = metrics.clone();
+                (kind, task::spawn_blocking(move || metrics.collect(kind)))
+            })
+            .collect();
+
+        for (kind, sample) in samples {
+            let error = match sample.await {
+                Ok(Ok(data)) => {
+                    series.push(kind, now_ms(), data);
+                    continue;
+                }
+                Ok(Err(e)) => format!("{}: {:#}", kind, e),
+                Err(e) => format!("{}: {}", kind, e),
+            };
+            if !series.errors.contains(&error) {
+                series.errors.push(error);
+            }
+        }
+    }
+
+    series.finished_ms = now_ms();
+    series
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::metrics_adapter::MetricsAdapter;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Returns an increasing counter for every metric; fails iostat
+    #[derive(Default)]
+    struct CountingMetrics {
+        calls: AtomicU32,
+    }
+
+    impl CountingMetrics {
+        fn next(&self) -> Result<Value> {
+            Ok(json!(self.calls.fetch_add(1, Ordering::SeqCst)))
+        }
+    }
+
+    impl MetricsPort for CountingMetrics {
+        fn collect_mpstat(&self) -> Result<Value> {
+            self.next()
+        }
+        fn collect_vmstat(&self) -> Result<Value> {
+            self.next()
+        }
+        fn collect_cpuinfo(&self) -> Result<Value> {
+            self.next()
+        }
+        fn collect_memoryinfo(&self) -> Result<Value> {
+            self.next()
+        }
+        fn collect_iostat(&self) -> Result<Value> {
+            anyhow::bail!("no devices")
+        }
+        fn collect_loadavg(&self) -> Result<Value> {
+            self.next()
+        }
+    }
+
+    fn recorder(collect: Vec<MetricKind>) -> MetricsRecorder {
+        MetricsRecorder::new(
+            Arc::new(CountingMetrics::default()),
+            collect,
+            Duration::from_millis(20),
+        )
+    }
+
+    #[test]
+    fn test_records_until_stopped() {
+        let recording = recorder(vec![
+            MetricKind::Mpstat,
+            MetricKind::Mem,
+            MetricKind::Iostat,
+        ])
+        .start()
+        .unwrap();
+        std::thread::sleep(Duration::from_millis(150));
+        let series = recording.stop();
+
+        let mpstat = series.samples_of(MetricKind::Mpstat).count();
+        assert!(mpstat >= 3, "only {} mpstat samples", mpstat);
+        assert_eq!(series.samples_of(MetricKind::Mem).count(), mpstat);
+        assert_eq!(series.samples_of(MetricKind::Iostat).count(), 0);
+        assert_eq!(series.errors, vec!["iostat: no devices".to_string()]);
+        assert_eq!(series.interval_ms, 20);
+        assert!(series.finished_ms >= series.started_ms + 150);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_records_on_current_runtime() {
+        let recording = recorder(vec![MetricKind::Loadavg]).start().unwrap();
+        assert!(recording.runtime.is_none());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let series = task::spawn_blocking(move || recording.stop())
+            .await
+            .unwrap();
+        assert!(!series.is_empty());
+    }
+
+    #[test]
+    fn test_records_through_metrics_adapter() {
+        let adapter = MetricsAdapter::new().with_interval(Duration::from_millis(50));
+        let recording = MetricsRecorder::new(
+            Arc::new(adapter),
+            vec![MetricKind::Mpstat, MetricKind::Loadavg],
+            Duration::from_millis(50),
+        )
+        .start()
+        .unwrap();
+        std::thread::sleep(Duration::from_millis(120));
+        let series = recording.stop();
+
+        assert!(series.errors.is_empty(), "{:?}", series.errors);
+        assert!(series.samples_of(MetricKind::Mpstat).count() >= 2);
+        assert!(series.samples_of(MetricKind::Loadavg).count() >= 2);
+    }
+
+    #[test]
+    fn test_drop_without_stop() {
+        let recording = recorder(vec![MetricKind::Loadavg]).start().unwrap();
+        drop(recording);
+    }
+}
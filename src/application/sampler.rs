@@ -0,0 +1,183 @@
+//! Continuous sampling of the latest system metrics.
+//!
+//! Where the [`recorder`](super::recorder) keeps every sample of a benchmark, a
+//! [`MetricsSampler`] keeps only the most recent sample of each metric, for as long as the
+//! process runs.  Long-running modes such as `serve` read [`Sampling::latest`] whenever a client
+//! asks, without waiting for the collectors' sampling window.
+
+use crate::domain::metrics::series::{MetricKind, MetricSample};
+use crate::ports::metrics_port::{collect_all, MetricsPort};
+use std::collections::HashMap;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Samples system metrics in the background, keeping the latest value of each
+pub struct MetricsSampler {
+    metrics: Arc<dyn MetricsPort>,
+    collect: Vec<MetricKind>,
+    interval: Duration,
+}
+
+/// Sampling in progress; dropping it stops the background thread
+pub struct Sampling {
+    state: Arc<Mutex<SamplerState>>,
+    stop: Option<mpsc::Sender<()>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+struct SamplerState {
+    latest: HashMap<MetricKind, MetricSample>,
+    errors: HashMap<MetricKind, u64>,
+}
+
+impl MetricsSampler {
+    /// Creates a sampler collecting `collect` every `interval`.
+    pub fn new(
+        metrics: Arc<dyn MetricsPort>,
+        collect: Vec<MetricKind>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            metrics,
+            collect,
+            interval,
+        }
+    }
+
+    /// Starts sampling on a background thread; the first sample is taken immediately.
+    pub fn start(&self) -> Sampling {
+        let state = Arc::new(Mutex::new(SamplerState::default()));
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let metrics = self.metrics.clone();
+        let collect = self.collect.clone();
+        let interval = self.interval;
+        let shared = state.clone();
+        let worker = thread::spawn(move || loop {
+            let started = Instant::now();
+
+            let results = collect_all(metrics.as_ref(), &collect);
+
+            let timestamp_ms = chrono::Utc::now().timestamp_millis();
+            {
+                let mut state = shared.lock().unwrap_or_else(|e| e.into_inner());
+                for (kind, result) in results {
+                    match result {
+                        Ok(data) => {
+                            state.latest.insert(
+                                kind,
+                                MetricSample {
+                                    metric: kind,
+                                    timestamp_ms,
+                                    data,
+                                },
+                            );
+                        }
+                        Err(_) => *state.errors.entry(kind).or_default() += 1,
+                    }
+                }
+            }
+
+            match stopped.recv_timeout(interval.saturating_sub(started.elapsed())) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => break,
+            }
+        });
+
+        Sampling {
+            state,
+            stop: Some(stop),
+            worker: Some(worker),
+        }
+    }
+}
+
+impl Sampling {
+    /// The most recent sample of every metric collected successfully so far.
+    pub fn latest(&self) -> Vec<MetricSample> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut latest: Vec<MetricSample> = state.latest.values().cloned().collect();
+        latest.sort_by_key(|sample| sample.metric.as_str());
+        latest
+    }
+
+    /// Number of failed collections of each metric since sampling started.
+    pub fn errors(&self) -> HashMap<MetricKind, u64> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.errors.clone()
+    }
+}
+
+impl Drop for Sampling {
+    fn drop(&mut self) {
+        // Closing the channel wakes the worker, which exits after any sample in flight
+        self.stop.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Result};
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Returns an increasing counter for loadavg; fails everything else
+    #[derive(Default)]
+    struct CountingMetrics {
+        calls: AtomicU32,
+    }
+
+    impl MetricsPort for CountingMetrics {
+        fn collect_mpstat(&self) -> Result<Value> {
+            Err(anyhow!("no mpstat"))
+        }
+        fn collect_vmstat(&self) -> Result<Value> {
+            Err(anyhow!("no vmstat"))
+        }
+        fn collect_cpuinfo(&self) -> Result<Value> {
+            Err(anyhow!("no cpuinfo"))
+        }
+        fn collect_memoryinfo(&self) -> Result<Value> {
+            Err(anyhow!("no meminfo"))
+        }
+        fn collect_iostat(&self) -> Result<Value> {
+            Err(anyhow!("no iostat"))
+        }
+        fn collect_loadavg(&self) -> Result<Value> {
+            Ok(json!(self.calls.fetch_add(1, Ordering::SeqCst)))
+        }
+    }
+
+    #[test]
+    fn test_keeps_latest_sample_and_counts_errors() {
+        let metrics = Arc::new(CountingMetrics::default());
+        let sampler = MetricsSampler::new(
+            metrics.clone(),
+            vec![MetricKind::Loadavg, MetricKind::Iostat],
+            Duration::from_millis(20),
+        );
+
+        let sampling = sampler.start();
+        thread::sleep(Duration::from_millis(150));
+        let latest = sampling.latest();
+        let errors = sampling.errors();
+        drop(sampling);
+
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].metric, MetricKind::Loadavg);
+        assert!(latest[0].data.as_u64().unwrap() >= 2);
+        assert!(errors[&MetricKind::Iostat] >= 3);
+
+        // Sampling has stopped
+        let calls = metrics.calls.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(60));
+        assert_eq!(metrics.calls.load(Ordering::SeqCst), calls);
+    }
+}
@@ -1,4 +1,8 @@
-use clap::{Parser, Subcommand};
+use crate::domain::storage::units::{BlockSizes, KbBase};
+use crate::ports::metrics_port::MetricKind;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::net::SocketAddr;
+use std::time::Duration;
 
 /// System performance benchmarking and metrics collection tool
 #[derive(Parser)]
@@ -27,11 +31,525 @@ pub enum Commands {
         /// Specify which benchmark to run
         #[arg(short, long)]
         tool: Option<String>,
+
+        /// Run a FIO profile from [storage.fio.profiles]
+        #[arg(short, long)]
+        profile: Option<String>,
+
+        /// Storage target from [[storage.targets]] to run the profile against
+        #[arg(long, requires = "profile")]
+        target: Option<String>,
+
+        /// Sweep the profile over the axes in [storage.fio.sweep]
+        #[arg(long, requires = "profile")]
+        sweep: bool,
+
+        /// Block sizes to sweep, overriding the config (e.g. 4k,64k,1m)
+        #[arg(long, value_delimiter = ',', requires = "sweep", value_parser = parse_block_size)]
+        bs: Vec<String>,
+
+        /// Queue depths to sweep, overriding the config (e.g. 1,8,32)
+        #[arg(
+            long,
+            value_delimiter = ',',
+            requires = "sweep",
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        iodepth: Vec<u32>,
+
+        /// Job counts to sweep, overriding the config (e.g. 1,4,8)
+        #[arg(
+            long,
+            value_delimiter = ',',
+            requires = "sweep",
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        numjobs: Vec<u32>,
+
+        /// Precondition the target and run the profile in rounds until steady state, as set
+        /// in [storage.fio.steady_state]
+        #[arg(long, requires = "profile", conflicts_with = "sweep")]
+        steady_state: bool,
+
+        /// Skip preconditioning, for a target that is preconditioned already
+        #[arg(long, requires = "steady_state")]
+        no_precondition: bool,
+
+        /// Allow write workloads on this raw-device target, destroying the data on it
+        #[arg(
+            long = "i-understand-data-loss",
+            value_name = "TARGET",
+            value_delimiter = ',',
+            requires = "profile"
+        )]
+        i_understand_data_loss: Vec<String>,
     },
     /// Collects system metrics
     Collect {
-        /// Specify which metric to collect
+        /// Metrics to collect: cpu, mem, vmstat, mpstat, iostat, loadavg [default: mpstat,mem,loadavg]
+        #[arg(short, long, value_delimiter = ',')]
+        metric: Vec<MetricKind>,
+
+        /// Time between samples (e.g. 500ms, 1s, 2m)
+        #[arg(short, long, default_value = "1s", value_parser = parse_interval)]
+        interval: Duration,
+
+        /// Number of samples to take; runs until interrupted when omitted
         #[arg(short, long)]
-        metric: Option<String>,
+        count: Option<u32>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+
+        /// Store every sample in the database
+        #[arg(long)]
+        persist: bool,
+    },
+    /// Lists stored benchmark runs, newest first
+    History {
+        /// Show one run in full, including the system metrics recorded during it
+        #[arg(long, conflicts_with_all = ["host", "target", "profile", "since", "until", "limit"])]
+        id: Option<String>,
+
+        /// Only runs taken on this host
+        #[arg(long)]
+        host: Option<String>,
+
+        /// Only runs against this storage target
+        #[arg(long)]
+        target: Option<String>,
+
+        /// Only runs of this profile
+        #[arg(short, long)]
+        profile: Option<String>,
+
+        /// Only runs at or after this time (YYYY-MM-DD, RFC 3339, or an age such as 7d)
+        #[arg(long, value_parser = parse_time)]
+        since: Option<i64>,
+
+        /// Only runs before this time (YYYY-MM-DD, RFC 3339, or an age such as 7d)
+        #[arg(long, value_parser = parse_time)]
+        until: Option<i64>,
+
+        /// Show at most this many runs
+        #[arg(short, long)]
+        limit: Option<usize>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Manages the stored results
+    Db {
+        #[command(subcommand)]
+        command: DbCommands,
+    },
+    /// Removes FIO scratch files left behind by runs that crashed or were killed
+    Clean {
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Serves live system metrics, benchmark jobs and results over HTTP
+    Serve {
+        /// Address to serve the job API and Prometheus metrics (at /metrics) on
+        #[arg(
+            long,
+            value_name = "ADDR",
+            default_value = "0.0.0.0:9105",
+            visible_alias = "metrics-addr"
+        )]
+        listen: SocketAddr,
+
+        /// Metrics to sample: cpu, mem, vmstat, mpstat, iostat, loadavg [default: mpstat,mem,vmstat,iostat,loadavg]
+        #[arg(short, long, value_delimiter = ',')]
+        metric: Vec<MetricKind>,
+
+        /// Time between samples (e.g. 500ms, 1s, 2m)
+        #[arg(short, long, default_value = "5s", value_parser = parse_interval)]
+        interval: Duration,
+
+        /// Allow submitted write workloads on this raw-device target, destroying the data on it
+        #[arg(
+            long = "i-understand-data-loss",
+            value_name = "TARGET",
+            value_delimiter = ','
+        )]
+        i_understand_data_loss: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbCommands {
+    /// Removes runs and metric samples older than [database] retention_days; pinned runs are kept
+    Prune {
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Use this many days instead of retention_days
+        #[arg(long, value_name = "DAYS", value_parser = clap::value_parser!(u32).range(1..))]
+        older_than: Option<u32>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Pins a run, such as a baseline, so that it is never pruned
+    Pin {
+        /// Run ID, as shown by `history`
+        id: String,
     },
+    /// Unpins a run so that retention applies to it again
+    Unpin {
+        /// Run ID, as shown by `history`
+        id: String,
+    },
+}
+
+/// How collected samples are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns for humans
+    Table,
+    /// A single JSON array, streamed as samples arrive
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// Checks that a block size such as "4k" or "1m" is one FIO accepts.
+fn parse_block_size(value: &str) -> Result<String, String> {
+    match BlockSizes::parse(value, KbBase::default()) {
+        Ok(_) if !value.trim().is_empty() => Ok(value.trim().to_string()),
+        Ok(_) => Err("block size must not be empty".into()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parses an interval such as "500ms", "1s", "2m" or "1h"; a bare number means seconds.
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid interval \"{}\"", value))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        other => {
+            return Err(format!(
+                "unknown interval unit \"{}\"; use ms, s, m or h",
+                other
+            ))
+        }
+    };
+
+    if seconds <= 0.0 {
+        return Err("interval must be greater than zero".into());
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// Parses a point in time into a Unix timestamp.
+///
+/// Accepts a date ("2024-06-01", midnight UTC), an RFC 3339 timestamp, a Unix timestamp, or an
+/// age relative to now such as "90m", "12h", "7d" or "2w".
+fn parse_time(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    if let Ok(timestamp) = value.parse::<i64>() {
+        return Ok(timestamp);
+    }
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.timestamp());
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_time(chrono::NaiveTime::MIN).and_utc().timestamp());
+    }
+
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => {
+            return Err(format!(
+                "invalid time \"{}\"; use YYYY-MM-DD, RFC 3339 or an age such as 7d",
+                value
+            ))
+        }
+    };
+    let number: i64 = number
+        .parse()
+        .map_err(|_| format!("invalid time \"{}\"", value))?;
+    Ok(chrono::Utc::now().timestamp() - number * seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("1s").unwrap(), Duration::from_secs(1));
+        assert_eq!(parse_interval("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_interval("2m").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_interval("1.5").unwrap(), Duration::from_millis(1500));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("5d").is_err());
+        assert!(parse_interval("fast").is_err());
+    }
+
+    #[test]
+    fn test_collect_arguments() {
+        let cli = Cli::try_parse_from([
+            "sysperf-svr",
+            "collect",
+            "--metric",
+            "cpu,mem,iostat",
+            "--interval",
+            "250ms",
+            "--count",
+            "60",
+            "--format",
+            "ndjson",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Some(Commands::Collect {
+                metric,
+                interval,
+                count,
+                format,
+                persist,
+            }) => {
+                assert_eq!(
+                    metric,
+                    vec![MetricKind::Cpu, MetricKind::Mem, MetricKind::Iostat]
+                );
+                assert_eq!(interval, Duration::from_millis(250));
+                assert_eq!(count, Some(60));
+                assert_eq!(format, OutputFormat::Ndjson);
+                assert!(!persist);
+            }
+            _ => panic!("expected the collect subcommand"),
+        }
+
+        assert!(Cli::try_parse_from(["sysperf-svr", "collect", "--metric", "disk"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("2024-06-01").unwrap(), 1717200000);
+        assert_eq!(parse_time("2024-06-01T12:00:00Z").unwrap(), 1717243200);
+        assert_eq!(parse_time("2024-06-01T14:00:00+02:00").unwrap(), 1717243200);
+        assert_eq!(parse_time("1717243200").unwrap(), 1717243200);
+
+        let week_ago = chrono::Utc::now().timestamp() - 7 * 86400;
+        assert!((parse_time("7d").unwrap() - week_ago).abs() <= 1);
+        assert!(parse_time("7y").is_err());
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_db_prune_arguments() {
+        let cli = Cli::try_parse_from(["sysperf-svr", "db", "prune", "--dry-run"]).unwrap();
+        match cli.command {
+            Some(Commands::Db {
+                command:
+                    DbCommands::Prune {
+                        dry_run,
+                        older_than,
+                        ..
+                    },
+            }) => {
+                assert!(dry_run);
+                assert_eq!(older_than, None);
+            }
+            _ => panic!("expected db prune"),
+        }
+
+        assert!(Cli::try_parse_from(["sysperf-svr", "db", "prune", "--older-than", "0"]).is_err());
+        assert!(Cli::try_parse_from(["sysperf-svr", "db", "pin"]).is_err());
+    }
+
+    #[test]
+    fn test_data_loss_acknowledgement() {
+        let cli = Cli::try_parse_from([
+            "sysperf-svr",
+            "benchmark",
+            "--profile",
+            "randwrite_4k",
+            "--target",
+            "nvme_direct",
+            "--i-understand-data-loss",
+            "nvme_direct",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Benchmark {
+                i_understand_data_loss,
+                ..
+            }) => assert_eq!(i_understand_data_loss, vec!["nvme_direct"]),
+            _ => panic!("expected benchmark"),
+        }
+
+        assert!(Cli::try_parse_from([
+            "sysperf-svr",
+            "benchmark",
+            "--i-understand-data-loss",
+            "nvme_direct"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_sweep_overrides() {
+        let cli = Cli::try_parse_from([
+            "sysperf-svr",
+            "benchmark",
+            "-p",
+            "x",
+            "--sweep",
+            "--bs",
+            "4k,1m",
+            "--iodepth",
+            "1,32",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Benchmark { bs, iodepth, .. }) => {
+                assert_eq!(bs, vec!["4k", "1m"]);
+                assert_eq!(iodepth, vec![1, 32]);
+            }
+            _ => panic!("expected benchmark"),
+        }
+
+        for (flag, value) in [
+            ("--iodepth", "0"),
+            ("--numjobs", "4,0"),
+            ("--bs", "4q"),
+            ("--bs", "4k,,8k"),
+        ] {
+            let args = [
+                "sysperf-svr",
+                "benchmark",
+                "-p",
+                "x",
+                "--sweep",
+                flag,
+                value,
+            ];
+            assert!(Cli::try_parse_from(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_steady_state_arguments() {
+        let cli = Cli::try_parse_from([
+            "sysperf-svr",
+            "benchmark",
+            "--profile",
+            "nvme_randrw",
+            "--steady-state",
+            "--no-precondition",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Benchmark {
+                steady_state,
+                no_precondition,
+                ..
+            }) => assert!(steady_state && no_precondition),
+            _ => panic!("expected benchmark"),
+        }
+
+        for args in [
+            &["sysperf-svr", "benchmark", "--steady-state"][..],
+            &[
+                "sysperf-svr",
+                "benchmark",
+                "-p",
+                "x",
+                "--steady-state",
+                "--sweep",
+            ],
+            &["sysperf-svr", "benchmark", "-p", "x", "--no-precondition"],
+        ] {
+            assert!(Cli::try_parse_from(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_clean_arguments() {
+        let cli = Cli::try_parse_from(["sysperf-svr", "clean", "--dry-run", "-f", "json"]).unwrap();
+        match cli.command {
+            Some(Commands::Clean { dry_run, format }) => {
+                assert!(dry_run);
+                assert_eq!(format, OutputFormat::Json);
+            }
+            _ => panic!("expected clean"),
+        }
+    }
+
+    #[test]
+    fn test_serve_arguments() {
+        let cli = Cli::try_parse_from(["sysperf-svr", "serve"]).unwrap();
+        match cli.command {
+            Some(Commands::Serve {
+                listen,
+                metric,
+                interval,
+                i_understand_data_loss,
+            }) => {
+                assert_eq!(listen, "0.0.0.0:9105".parse().unwrap());
+                assert!(metric.is_empty());
+                assert_eq!(interval, Duration::from_secs(5));
+                assert!(i_understand_data_loss.is_empty());
+            }
+            _ => panic!("expected serve"),
+        }
+
+        for flag in ["--listen", "--metrics-addr"] {
+            match Cli::try_parse_from(["sysperf-svr", "serve", flag, "127.0.0.1:9200"]) {
+                Ok(Cli {
+                    command: Some(Commands::Serve { listen, .. }),
+                    ..
+                }) => assert_eq!(listen.port(), 9200),
+                _ => panic!("expected serve {}", flag),
+            }
+        }
+        assert!(
+            Cli::try_parse_from(["sysperf-svr", "serve", "--metrics-addr", "localhost"]).is_err()
+        );
+
+        match Cli::try_parse_from(["sysperf-svr", "serve", "--i-understand-data-loss", "a,b"]) {
+            Ok(Cli {
+                command:
+                    Some(Commands::Serve {
+                        i_understand_data_loss,
+                        ..
+                    }),
+                ..
+            }) => assert_eq!(i_understand_data_loss, vec!["a", "b"]),
+            _ => panic!("expected serve"),
+        }
+    }
 }
@@ -1,15 +1,43 @@
+use super::cli_struct::OutputFormat;
+use super::interrupt::{self, InterruptWatch};
+use super::output::{self, SampleWriter};
 use crate::application::Application;
+use crate::domain::metrics::series::MetricSeries;
+use crate::domain::storage::history::RunFilter;
+use crate::domain::storage::units::format_bytes;
+use crate::domain::storage::retention::RetentionPolicy;
+use crate::domain::storage::saturation::KneeCriteria;
+use crate::domain::storage::sweep::SweepResults;
 use crate::ports::benchmark_port::{
-    BenchmarkConfig, BenchmarkParams, BenchmarkTool, FIOParams, IOType,
+    BenchmarkConfig, BenchmarkMetrics, BenchmarkParams, BenchmarkTool, FIOMetrics, FIOParams,
+    IOType, RunControl,
 };
+use crate::ports::metrics_port::{collect_all, selected_metrics, MetricKind};
+use crate::server::Server;
 use anyhow::Result;
 use colored::*;
 use dialoguer::{theme::ColorfulTheme, Select};
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 const BLOCK_SIZES: &[&str] = &[
     "4k", "8k", "16k", "32k", "64k", "128k", "256k", "512k", "1m",
 ];
-const BENCHMARK_DIR: &str = "/mnt/benchmark";
+
+/// Metrics collected when none are selected
+const DEFAULT_METRICS: &[MetricKind] = &[MetricKind::Mpstat, MetricKind::Mem, MetricKind::Loadavg];
+
+/// Metrics `serve` samples when none are selected
+const SERVE_METRICS: &[MetricKind] = &[
+    MetricKind::Mpstat,
+    MetricKind::Mem,
+    MetricKind::Vmstat,
+    MetricKind::Iostat,
+    MetricKind::Loadavg,
+];
 
 pub fn run_benchmark(app: &mut Application, tool: &Option<String>) -> Result<()> {
     let logger = app.logger.clone();
@@ -40,6 +68,8 @@ fn run_fio_benchmark(app: &mut Application) -> Result<()> {
     }
 
     println!("Running FIO benchmarks with multiple block sizes...");
+    // Ctrl-C or SIGTERM stops the running block size, so that its scratch files are removed
+    let (control, _watch) = run_control(app, &None, &[]);
 
     // Validate benchmark directory exists
     if let Err(e) = app.benchmark.validate() {
@@ -57,7 +87,7 @@ fn run_fio_benchmark(app: &mut Application) -> Result<()> {
             name: format!("fio_test_{}", bs),
             tool: BenchmarkTool::FIO,
             params: BenchmarkParams::FIO(FIOParams {
-                directory: BENCHMARK_DIR.to_string(),
+                directory: app.config.storage.test_directory.display().to_string(),
                 block_size: bs.to_string(),
                 io_type: IOType::RandomRead,
                 size: "1G".to_string(),
@@ -67,19 +97,17 @@ fn run_fio_benchmark(app: &mut Application) -> Result<()> {
             }),
         };
 
-        match app.benchmark.run() {
-            Ok(_) => {
+        match app.run_config(&config, &control) {
+            Ok(result) => {
                 println!("✓ Block size {} completed successfully", bs.green());
-
-                // Collect and display system metrics
-                if let Ok(cpu_info) = app.metrics.collect_cpuinfo() {
-                    println!("  CPU Usage: {:#?}", cpu_info);
+                if let BenchmarkMetrics::FIO(fio) = &result.metrics {
+                    print_fio_summary(fio);
                 }
-                if let Ok(memory_info) = app.metrics.collect_memoryinfo() {
-                    println!("  Memory Usage: {:#?}", memory_info);
-                }
-                if let Ok(vmstat) = app.metrics.collect_vmstat() {
-                    println!("  VM Stats: {:#?}", vmstat);
+
+                // Show what the system did during the run, or a snapshot when nothing was recorded
+                match &result.system_metrics {
+                    Some(series) => print_recorded_metrics(series),
+                    None => print_system_metrics(app),
                 }
             }
             Err(e) => {
@@ -88,12 +116,321 @@ fn run_fio_benchmark(app: &mut Application) -> Result<()> {
                     .log_error(&format!("Benchmark failed for block size {}: {}", bs, e));
             }
         }
+        if interrupt::interrupted() {
+            println!(
+                "{}",
+                "Interrupted; skipping the remaining block sizes".yellow()
+            );
+            return Ok(());
+        }
     }
 
     println!("\nAll FIO benchmarks completed!");
     Ok(())
 }
 
+/// Returns the control for a benchmark on `target`, acknowledging data loss when the target is
+/// listed in `acknowledged`.  Ctrl-C cancels the benchmark, so that its scratch files are
+/// removed, for as long as the returned watch is kept.
+fn run_control(
+    app: &Application,
+    target: &Option<String>,
+    acknowledged: &[String],
+) -> (RunControl, InterruptWatch) {
+    let control = app.run_control();
+    let control = match target {
+        Some(target) if acknowledged.contains(target) => control.acknowledge_data_loss(),
+        _ => control,
+    };
+    handle_interrupts(app);
+    let watch = interrupt::cancel_on_interrupt(&control);
+    (control, watch)
+}
+
+/// Lets Ctrl-C stop benchmarks cleanly instead of killing the process.
+fn handle_interrupts(app: &Application) {
+    if let Err(e) = interrupt::install() {
+        app.logger.log_warn(&format!(
+            "Failed to handle Ctrl-C; it will leave scratch files: {}",
+            e
+        ));
+    }
+}
+
+pub fn run_profile(
+    app: &mut Application,
+    profile: &str,
+    target: &Option<String>,
+    acknowledged: &[String],
+) -> Result<()> {
+    println!(
+        "Running FIO profile {} against {}...",
+        profile.blue(),
+        target.as_deref().unwrap_or("the test directory").blue()
+    );
+
+    let (control, _watch) = run_control(app, target, acknowledged);
+    match app.run_profile(profile, target.as_deref(), &control) {
+        Ok(result) => {
+            println!("✓ Profile {} completed successfully", profile.green());
+            if let BenchmarkMetrics::FIO(fio) = &result.metrics {
+                print_fio_summary(fio);
+            }
+            if let Some(series) = &result.system_metrics {
+                print_recorded_metrics(series);
+            }
+            if let Some(id) = &result.run_id {
+                println!("  Saved as run {}", id.blue());
+            }
+            Ok(())
+        }
+        Err(e) => {
+            app.logger
+                .log_error(&format!("Profile {} failed: {}", profile, e));
+            // Scripts and schedulers rely on the exit status
+            Err(e.context(format!("Profile {} failed", profile)))
+        }
+    }
+}
+
+pub fn run_sweep(
+    app: &mut Application,
+    profile: &str,
+    target: &Option<String>,
+    acknowledged: &[String],
+) -> Result<()> {
+    let plan = match app.config.sweep_plan(profile) {
+        Ok(plan) => plan,
+        Err(e) => {
+            app.logger
+                .log_error(&format!("Sweep for {} failed: {}", profile, e));
+            return Err(anyhow::Error::new(e).context(format!("Sweep for {} failed", profile)));
+        }
+    };
+
+    println!(
+        "Sweeping FIO profile {} over {} points against {}...",
+        profile.blue(),
+        plan.points().len(),
+        target.as_deref().unwrap_or("the test directory").blue()
+    );
+
+    let (control, _watch) = run_control(app, target, acknowledged);
+    match app.run_sweep(profile, &plan, target.as_deref(), &control) {
+        Ok(results) => {
+            println!("\n{}", results.table());
+            print_saturation(&results, target.as_deref().unwrap_or("test directory"));
+            Ok(())
+        }
+        Err(e) => {
+            app.logger
+                .log_error(&format!("Sweep for {} failed: {}", profile, e));
+            Err(e.context(format!("Sweep for {} failed", profile)))
+        }
+    }
+}
+
+pub fn run_steady_state(
+    app: &mut Application,
+    profile: &str,
+    target: &Option<String>,
+    acknowledged: &[String],
+) -> Result<()> {
+    let settings = &app.config.storage.fio.steady_state;
+    println!(
+        "Measuring FIO profile {} against {} until steady state ({}up to {} rounds of {}s)...",
+        profile.blue(),
+        target.as_deref().unwrap_or("the test directory").blue(),
+        if settings.precondition { "after preconditioning, " } else { "" },
+        settings.max_rounds,
+        settings.round_runtime
+    );
+
+    let (control, _watch) = run_control(app, target, acknowledged);
+    match app.run_steady_state(profile, target.as_deref(), &control) {
+        Ok(report) => {
+            if let Some(stats) = &report.precondition {
+                println!(
+                    "  Preconditioned at {:.1} MiB/s ({})",
+                    stats.bandwidth_mb,
+                    format_bytes(stats.io_bytes)
+                );
+            }
+            println!("\n{}", report.table());
+            let metric = report.criteria.metric;
+            match &report.window {
+                Some(window) if report.steady => println!(
+                    "✓ Steady state in rounds {}-{}: {:.1} {} (excursion {:.1}%, slope excursion {:.1}%)",
+                    window.first_round,
+                    window.last_round,
+                    window.average,
+                    metric.unit(),
+                    window.data_excursion,
+                    window.slope_excursion
+                ),
+                Some(window) => println!(
+                    "{} Not steady after {} rounds: excursion {:.1}% (max {}%), slope excursion {:.1}% (max {}%)",
+                    "✗".red(),
+                    report.rounds.len(),
+                    window.data_excursion,
+                    report.criteria.max_data_excursion,
+                    window.slope_excursion,
+                    report.criteria.max_slope_excursion
+                ),
+                None => println!(
+                    "{} Not steady: {} rounds are fewer than the {}-round window",
+                    "✗".red(),
+                    report.rounds.len(),
+                    report.criteria.window
+                ),
+            }
+            Ok(())
+        }
+        Err(e) => {
+            app.logger
+                .log_error(&format!("Steady-state run of {} failed: {}", profile, e));
+            Err(e.context(format!("Steady-state run of {} failed", profile)))
+        }
+    }
+}
+
+fn print_system_metrics(app: &Application) {
+    match app.metrics.collect_mpstat() {
+        Ok(cpus) => {
+            if let Some(all) = cpus
+                .as_array()
+                .and_then(|cpus| cpus.iter().find(|cpu| cpu["cpu_id"] == "all"))
+            {
+                println!(
+                    "  CPU Usage: usr {:.1}%  sys {:.1}%  iowait {:.1}%  idle {:.1}%",
+                    all["usr"].as_f64().unwrap_or_default(),
+                    all["sys"].as_f64().unwrap_or_default(),
+                    all["iowait"].as_f64().unwrap_or_default(),
+                    all["idle"].as_f64().unwrap_or_default()
+                );
+            }
+        }
+        Err(e) => println!("  CPU Usage: unavailable ({})", e),
+    }
+
+    match app.metrics.collect_memoryinfo() {
+        Ok(memory) => {
+            let mib = |key: &str| memory[key].as_u64().unwrap_or_default() / (1024 * 1024);
+            println!(
+                "  Memory Usage: {} MiB used of {} MiB ({} MiB available)",
+                mib("used_memory"),
+                mib("total_memory"),
+                mib("available_memory")
+            );
+        }
+        Err(e) => println!("  Memory Usage: unavailable ({})", e),
+    }
+
+    match app.metrics.collect_vmstat() {
+        Ok(vmstat) => {
+            let vm = &vmstat[0];
+            println!(
+                "  VM Stats: r {}  b {}  si {} KiB/s  so {} KiB/s  bi {}/s  bo {}/s  cs {}/s",
+                vm["procs"]["running"],
+                vm["procs"]["blocked"],
+                vm["swap"]["kb_in_per_sec"],
+                vm["swap"]["kb_out_per_sec"],
+                vm["io"]["blocks_in_per_sec"],
+                vm["io"]["blocks_out_per_sec"],
+                vm["system"]["context_switches_per_sec"]
+            );
+        }
+        Err(e) => println!("  VM Stats: unavailable ({})", e),
+    }
+}
+
+/// Prints mean and peak figures from the metrics recorded during a benchmark.
+fn print_recorded_metrics(series: &MetricSeries) {
+    println!(
+        "  Recorded {} samples over {:.1}s",
+        series.len(),
+        series.duration_secs()
+    );
+
+    let all_cpus = |data: &Value, key: &str| {
+        data.as_array()?
+            .iter()
+            .find(|cpu| cpu["cpu_id"] == "all")
+            .and_then(|cpu| cpu[key].as_f64())
+    };
+    if let Some((mean, max)) = series.mean_max(MetricKind::Mpstat, |d| {
+        Some(all_cpus(d, "usr")? + all_cpus(d, "sys")?)
+    }) {
+        println!("  CPU busy: mean {:.1}%  peak {:.1}%", mean, max);
+    }
+    if let Some((mean, max)) = series.mean_max(MetricKind::Mpstat, |d| all_cpus(d, "iowait")) {
+        println!("  CPU iowait: mean {:.1}%  peak {:.1}%", mean, max);
+    }
+
+    let devices: Vec<String> = series
+        .samples_of(MetricKind::Iostat)
+        .flat_map(|s| s.data.as_array().cloned().unwrap_or_default())
+        .filter_map(|dev| dev["device"].as_str().map(String::from))
+        .fold(Vec::new(), |mut devices, dev| {
+            if !devices.contains(&dev) {
+                devices.push(dev);
+            }
+            devices
+        });
+    for device in devices {
+        let util = |data: &Value| {
+            data.as_array()?
+                .iter()
+                .find(|dev| dev["device"] == device.as_str())
+                .and_then(|dev| dev["util"].as_f64())
+        };
+        if let Some((mean, max)) = series.mean_max(MetricKind::Iostat, util) {
+            println!("  {} util: mean {:.1}%  peak {:.1}%", device, mean, max);
+        }
+    }
+
+    if let Some((_, max)) = series.mean_max(MetricKind::Mem, |d| d["used_memory"].as_f64()) {
+        println!("  Memory used: peak {:.0} MiB", max / (1024.0 * 1024.0));
+    }
+    if let Some((_, max)) = series.mean_max(MetricKind::Loadavg, |d| d[0]["load_1"].as_f64()) {
+        println!("  Load average (1m): peak {:.2}", max);
+    }
+}
+
+fn print_saturation(results: &SweepResults, device: &str) {
+    let criteria = KneeCriteria::default();
+
+    for (bs, numjobs) in results.groups() {
+        // A single depth for this pair means iodepth was not swept
+        let Ok(report) = results.saturation(&bs, numjobs, &criteria) else {
+            continue;
+        };
+        let point = report.recommended();
+        let knee = match report.knee_iodepth {
+            Some(knee) => format!("saturates above QD {}", knee),
+            None => "did not saturate".to_string(),
+        };
+        println!(
+            "{} (bs={}, numjobs={}): {}; recommended QD {} ({:.0} IOPS, {:.1} µs p99, peak {:.0} IOPS)",
+            device,
+            bs,
+            numjobs,
+            knee,
+            report.recommended_iodepth.to_string().green(),
+            point.iops,
+            point.lat_usec_p99,
+            report.peak_iops
+        );
+    }
+}
+
+fn print_fio_summary(fio: &FIOMetrics) {
+    println!(
+        "  IOPS: {:.0}  Bandwidth: {:.1} MiB/s  Latency: {:.1} µs",
+        fio.iops, fio.bandwidth, fio.latency
+    );
+}
+
 pub fn run_interactive(app: &mut Application) -> Result<()> {
     let options = vec!["FIO Benchmark", "System Metrics", "Exit"];
 
@@ -105,13 +442,219 @@ pub fn run_interactive(app: &mut Application) -> Result<()> {
 
     match selection {
         0 => run_fio_benchmark(app),
-        1 => collect_metrics(app, &None),
+        1 => collect_metrics(
+            app,
+            &[],
+            Duration::from_secs(1),
+            Some(1),
+            OutputFormat::Table,
+            false,
+        ),
         _ => Ok(()),
     }
 }
 
-pub fn collect_metrics(app: &mut Application, metric: &Option<String>) -> Result<()> {
-    println!("Collecting metrics...");
+/// Samples the selected metrics `count` times (forever when `None`), one tick per `interval`.
+///
+/// Every metric in a tick is collected concurrently with [`collect_all`].  Samples are streamed to stdout in `format` and, with `persist`, stored
+/// under `<metric>/<timestamp_ms>`.
+pub fn collect_metrics(
+    app: &mut Application,
+    metrics: &[MetricKind],
+    interval: Duration,
+    count: Option<u32>,
+    format: OutputFormat,
+    persist: bool,
+) -> Result<()> {
+    let selected = selected_metrics(DEFAULT_METRICS, metrics);
+
+    let mut writer = SampleWriter::new(format, selected.len());
+    let mut sample = 0;
+    while count.is_none_or(|count| sample < count) {
+        let started = Instant::now();
+        let now = chrono::Utc::now();
+
+        for (kind, result) in collect_all(app.metrics.as_ref(), &selected) {
+            match result {
+                Ok(data) => {
+                    let record = output::record(kind, sample, now.timestamp(), data);
+                    writer.write(&record);
+                    if persist {
+                        let key = format!("{}/{}", kind, now.timestamp_millis());
+                        if let Err(e) = app.storage.store_metrics(&key, &record) {
+                            app.logger
+                                .log_error(&format!("Failed to persist {} sample: {}", kind, e));
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("✗ {}: {:#}", kind, e);
+                    app.logger
+                        .log_error(&format!("Failed to collect {}: {:#}", kind, e));
+                }
+            }
+        }
+
+        sample += 1;
+        if count.is_none_or(|count| sample < count) {
+            thread::sleep(interval.saturating_sub(started.elapsed()));
+        }
+    }
+
+    writer.finish();
     Ok(())
 }
 
+/// Prints the stored runs matching `filter`.
+pub fn list_runs(app: &mut Application, filter: &RunFilter, format: OutputFormat) -> Result<()> {
+    let runs = app.storage.list_runs(filter)?;
+    match format {
+        OutputFormat::Table if runs.is_empty() => println!("No runs found"),
+        OutputFormat::Table => print!("{}", output::runs_table(&runs)),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&runs)?),
+        OutputFormat::Ndjson => {
+            for run in &runs {
+                println!("{}", serde_json::to_string(run)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints one stored run in full.
+pub fn show_run(app: &mut Application, id: &str, format: OutputFormat) -> Result<()> {
+    let Some(run) = app.storage.get_run(id)? else {
+        anyhow::bail!("No run with ID {}", id);
+    };
+    match format {
+        OutputFormat::Table => {
+            print!("{}", output::runs_table(std::slice::from_ref(&run)));
+            if let Some(series) = &run.system_metrics {
+                print_recorded_metrics(series);
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&run)?),
+        OutputFormat::Ndjson => println!("{}", serde_json::to_string(&run)?),
+    }
+    Ok(())
+}
+
+/// Prunes stored results older than `older_than` days, or `[database] retention_days`.
+pub fn prune(
+    app: &mut Application,
+    older_than: Option<u32>,
+    dry_run: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let days = older_than.unwrap_or(app.config.database.retention_days);
+    let report = app.prune(RetentionPolicy::new(days), dry_run)?;
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Ndjson => println!("{}", serde_json::to_string(&report)?),
+        OutputFormat::Table => {
+            let Some(cutoff) = report.cutoff else {
+                println!("Retention is disabled (retention_days = 0); nothing to prune");
+                return Ok(());
+            };
+            let cutoff = chrono::DateTime::from_timestamp(cutoff, 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_default();
+
+            if dry_run && !report.runs.is_empty() {
+                print!("{}", output::runs_table(&report.runs));
+            }
+            println!(
+                "{} {} runs and {} metric samples from before {}",
+                if dry_run { "Would prune" } else { "Pruned" },
+                report.runs.len(),
+                report.metrics.len(),
+                cutoff
+            );
+            if report.pinned_kept > 0 {
+                println!("Kept {} pinned runs", report.pinned_kept);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pins or unpins a stored run.
+pub fn pin_run(app: &mut Application, id: &str, pinned: bool) -> Result<()> {
+    if !app.storage.set_pinned(id, pinned)? {
+        anyhow::bail!("No run with ID {}", id);
+    }
+    println!("{} run {}", if pinned { "Pinned" } else { "Unpinned" }, id);
+    Ok(())
+}
+
+/// Removes the FIO scratch files that crashed or killed runs left behind.
+pub fn clean(app: &mut Application, dry_run: bool, format: OutputFormat) -> Result<()> {
+    let report = app.clean(dry_run)?;
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Ndjson => println!("{}", serde_json::to_string(&report)?),
+        OutputFormat::Table => {
+            for file in &report.files {
+                println!("{:>10}  {}", format_bytes(file.bytes), file.path.display());
+            }
+            println!(
+                "{} {} scratch files ({})",
+                if dry_run { "Would remove" } else { "Removed" },
+                report.files.len() - report.errors.len(),
+                format_bytes(report.bytes)
+            );
+            for error in &report.errors {
+                println!("✗ {}", error.red());
+            }
+            if report.live_processes > 0 {
+                println!(
+                    "Left the files of {} running benchmark processes alone",
+                    report.live_processes
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Serves the job API and Prometheus metrics on `addr` until the process is interrupted.
+///
+/// Jobs may write to the raw-device targets in `data_loss_allowed`, destroying their data.
+pub fn serve(
+    app: &mut Application,
+    addr: SocketAddr,
+    metrics: &[MetricKind],
+    interval: Duration,
+    data_loss_allowed: &[String],
+) -> Result<()> {
+    for target in data_loss_allowed {
+        app.resolve_target(Some(target))?;
+        app.logger
+            .log_warn(&format!("Jobs may destroy the data on target {}", target));
+    }
+
+    let selected = selected_metrics(SERVE_METRICS, metrics);
+    let server =
+        Server::bind(app, addr, selected, interval)?.allow_data_loss(data_loss_allowed.to_vec());
+    let addr = server.local_addr().unwrap_or(addr);
+    app.logger.log_info(&format!("Listening on {}", addr));
+    println!("{} http://{}", "Listening on".green().bold(), addr);
+    println!("  Jobs:    http://{}/jobs", addr);
+    println!("  Metrics: http://{}/metrics", addr);
+
+    // Ctrl-C stops the server, cancelling running jobs so that their scratch files are removed
+    handle_interrupts(app);
+    let stopped = AtomicBool::new(false);
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            interrupt::wait_until(|| stopped.load(Ordering::SeqCst));
+            server.stop();
+        });
+        server.run();
+        stopped.store(true, Ordering::SeqCst);
+    });
+    app.logger.log_info("Server stopped");
+    Ok(())
+}
@@ -0,0 +1,82 @@
+//! Ctrl-C and SIGTERM handling
+//!
+//! Left alone, SIGINT and SIGTERM kill the process on the spot and FIO's data files stay
+//! behind.  Once [`install`]ed, the first signal only sets a flag: benchmarks watched with
+//! [`cancel_on_interrupt`] are cancelled, their scratch files are removed and the command
+//! returns.  A second signal exits at once.
+
+use crate::ports::benchmark_port::RunControl;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How often watchers check for a signal
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        // SAFETY: _exit is async-signal-safe
+        unsafe { libc::_exit(128 + signal) }
+    }
+}
+
+/// Handles SIGINT and SIGTERM for the rest of the process.
+///
+/// # Errors
+///
+/// Will return an error if a handler cannot be installed.
+pub fn install() -> io::Result<()> {
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: on_signal only stores to an atomic and calls async-signal-safe functions
+        let previous =
+            unsafe { libc::signal(signal, on_signal as *const () as libc::sighandler_t) };
+        if previous == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Whether SIGINT or SIGTERM has been received.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Stops watching for a signal when dropped; see [`cancel_on_interrupt`]
+#[must_use = "the control is only watched while this is alive"]
+pub struct InterruptWatch {
+    done: Arc<AtomicBool>,
+}
+
+impl Drop for InterruptWatch {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Cancels `control` if the process is interrupted before the returned watch is dropped.
+///
+/// The watching thread exits with the watch, so keep it alive for as long as the run lasts.
+pub fn cancel_on_interrupt(control: &RunControl) -> InterruptWatch {
+    let done = Arc::new(AtomicBool::new(false));
+    let control = control.clone();
+    let finished = done.clone();
+    thread::spawn(move || {
+        wait_until(|| finished.load(Ordering::SeqCst));
+        if interrupted() {
+            control.cancel();
+        }
+    });
+    InterruptWatch { done }
+}
+
+/// Blocks until the process is interrupted or `done` returns true.
+pub fn wait_until<F: Fn() -> bool>(done: F) {
+    while !interrupted() && !done() {
+        thread::sleep(POLL_INTERVAL);
+    }
+}
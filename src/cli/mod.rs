@@ -1,39 +1,58 @@
 mod cli_struct;
 mod commands;
+mod interrupt;
+mod output;
 
 use std::sync::Arc;
-use self::cli_struct::{Cli, Commands};
+use std::time::Duration;
+use self::cli_struct::{Cli, Commands, DbCommands};
 use crate::ports::{
     database_port::DatabasePort,
     metrics_port::MetricsPort,
     benchmark_port::BenchmarkPort,
     log_port::LoggerPort,
+    storage_port::StoragePort,
 };
 use crate::adapters::{
     database_adapter::DatabaseAdapter,
+    influxdb_adapter::InfluxDbAdapter,
     metrics_adapter::MetricsAdapter,
     benchmark_adapter::BenchmarkAdapter,
     log_adapter::init,
+    storage_adapter::{MirroredStorage, StorageAdapter},
 };
 use crate::application::Application;
+use crate::application::jobs::JobLogger;
+use crate::config::Config;
+use crate::domain::storage::history::RunFilter;
 use anyhow::Result;
 use clap::Parser;
 use log::LevelFilter;
 
+#[allow(clippy::to_string_in_format_args)]
 pub fn run() -> Result<()> {
     let cli = Cli::parse();
 
+    // Load configuration before anything else so the logger can honour it
+    let config = Config::load_or_default(cli.config.as_deref())?;
+
+    // --debug overrides the configured log level
     let log_level = match cli.debug {
-        0 => LevelFilter::Info,
+        0 => config.log_level(),
         1 => LevelFilter::Debug,
         _ => LevelFilter::Trace,
     };
 
     // Create logger
-    let logger: Arc<dyn LoggerPort> = Arc::new(
-        init("logs", log_level)
+    let log_directory = config.general.log_directory.display().to_string();
+    let mut logger: Arc<dyn LoggerPort> = Arc::new(
+        init(&log_directory, log_level)
             .map_err(|e| anyhow::anyhow!("Failed to initialize logger: {}", e))?,
     );
+    if let Some(Commands::Serve { .. }) = &cli.command {
+        // Copy what is logged while a submitted job runs into that job's log
+        logger = Arc::new(JobLogger::new(logger));
+    }
     
     let logger_clone = logger.clone();
     logger_clone.log_info("CLI application starting...");
@@ -42,30 +61,133 @@ pub fn run() -> Result<()> {
     }
    
     // Create adapters as trait objects
-    let db: Arc<dyn DatabasePort> = Arc::new(DatabaseAdapter::new());
-    let benchmark: Arc<dyn BenchmarkPort> = Arc::new(BenchmarkAdapter::new(
-        String::from("fio"),
-        vec![String::from("--version")],
-        logger.clone(),
-    ));
-    let metrics: Arc<dyn MetricsPort> = Arc::new(MetricsAdapter::new());
+    let db: Arc<dyn DatabasePort> = match &cli.command {
+        // A server runs indefinitely; leave the database to benchmarks between scrapes
+        Some(Commands::Serve { .. }) => {
+            Arc::new(DatabaseAdapter::new(&config.general.database_path).close_when_idle())
+        }
+        _ => Arc::new(DatabaseAdapter::new(&config.general.database_path)),
+    };
+    let benchmark: Arc<dyn BenchmarkPort> = Arc::new(
+        BenchmarkAdapter::new(
+            String::from("fio"),
+            vec![String::from("--version")],
+            logger.clone(),
+        )
+        .with_benchmark_dir(&config.storage.test_directory)
+        .with_preflight(config.preflight()),
+    );
+    let metrics: Arc<dyn MetricsPort> = match &cli.command {
+        Some(Commands::Collect { interval, .. } | Commands::Serve { interval, .. }) => {
+            Arc::new(MetricsAdapter::new().with_interval(*interval))
+        }
+        // Benchmarks sample over the recorder's interval
+        _ => Arc::new(
+            MetricsAdapter::new().with_interval(Duration::from_secs(config.metrics.interval)),
+        ),
+    };
+
+    let mut storage: Arc<dyn StoragePort> = Arc::new(StorageAdapter::new(db.clone()));
+    if config.database.remote.enabled {
+        logger.log_info(&format!("Exporting results to {}", config.database.remote.url));
+        let remote: Arc<dyn StoragePort> =
+            Arc::new(InfluxDbAdapter::new(&config.database.remote, logger.clone()));
+        storage = Arc::new(MirroredStorage::new(storage, vec![remote], logger.clone()));
+    }
 
     // Create application with port interfaces
     let mut app = Application::new(
+        config,
         db,
         benchmark,
         metrics,
+        storage,
         logger
     );
 
     match &cli.command {
-        Some(Commands::Benchmark { tool }) => {
+        Some(Commands::Benchmark {
+            profile: Some(profile),
+            target,
+            sweep: true,
+            bs,
+            iodepth,
+            numjobs,
+            i_understand_data_loss,
+            ..
+        }) => {
+            app.logger.log_info(&format!("Running benchmark sweep for profile: {}", profile));
+            if !bs.is_empty() {
+                app.config.storage.fio.sweep.block_sizes = bs.clone();
+            }
+            if !iodepth.is_empty() {
+                app.config.storage.fio.sweep.iodepth = iodepth.clone();
+            }
+            if !numjobs.is_empty() {
+                app.config.storage.fio.sweep.numjobs = numjobs.clone();
+            }
+            // The overrides replace values the config was validated with
+            app.config.validate()?;
+            commands::run_sweep(&mut app, profile, target, i_understand_data_loss)?;
+        }
+        Some(Commands::Benchmark {
+            profile: Some(profile),
+            target,
+            steady_state: true,
+            no_precondition,
+            i_understand_data_loss,
+            ..
+        }) => {
+            app.logger.log_info(&format!("Running steady-state benchmark for profile: {}", profile));
+            if *no_precondition {
+                app.config.storage.fio.steady_state.precondition = false;
+            }
+            commands::run_steady_state(&mut app, profile, target, i_understand_data_loss)?;
+        }
+        Some(Commands::Benchmark { profile: Some(profile), target, i_understand_data_loss, .. }) => {
+            app.logger.log_info(&format!("Running benchmark profile: {}", profile));
+            commands::run_profile(&mut app, profile, target, i_understand_data_loss)?;
+        }
+        Some(Commands::Benchmark { tool, .. }) => {
             app.logger.log_info(&format!("Running benchmark with tool: {}", tool.as_deref().unwrap_or("default")));
             commands::run_benchmark(&mut app, tool)?;
         }
-        Some(Commands::Collect { metric }) => {
-            app.logger.log_info(&format!("Collecting metrics: {}", metric.as_deref().unwrap_or("default")));
-            commands::collect_metrics(&mut app, metric)?;
+        Some(Commands::Collect { metric, interval, count, format, persist }) => {
+            let names: Vec<&str> = metric.iter().map(|m| m.as_str()).collect();
+            app.logger.log_info(&format!(
+                "Collecting metrics: {}",
+                if names.is_empty() { "default".to_string() } else { names.join(",") }
+            ));
+            commands::collect_metrics(&mut app, metric, *interval, *count, *format, *persist)?;
+        }
+        Some(Commands::History { id: Some(id), format, .. }) => {
+            commands::show_run(&mut app, id, *format)?;
+        }
+        Some(Commands::History { host, target, profile, since, until, limit, format, .. }) => {
+            let filter = RunFilter {
+                host: host.clone(),
+                target: target.clone(),
+                profile: profile.clone(),
+                since: *since,
+                until: *until,
+                limit: *limit,
+            };
+            commands::list_runs(&mut app, &filter, *format)?;
+        }
+        Some(Commands::Db { command: DbCommands::Prune { dry_run, older_than, format } }) => {
+            commands::prune(&mut app, *older_than, *dry_run, *format)?;
+        }
+        Some(Commands::Db { command: DbCommands::Pin { id } }) => {
+            commands::pin_run(&mut app, id, true)?;
+        }
+        Some(Commands::Db { command: DbCommands::Unpin { id } }) => {
+            commands::pin_run(&mut app, id, false)?;
+        }
+        Some(Commands::Clean { dry_run, format }) => {
+            commands::clean(&mut app, *dry_run, *format)?;
+        }
+        Some(Commands::Serve { listen, metric, interval, i_understand_data_loss }) => {
+            commands::serve(&mut app, *listen, metric, *interval, i_understand_data_loss)?;
         }
         None => {
             app.logger.log_info("Starting interactive mode");
@@ -73,6 +195,12 @@ pub fn run() -> Result<()> {
         }
     }
 
+    // A server stops on Ctrl-C; anything else that was interrupted did not finish
+    if interrupt::interrupted() && !matches!(&cli.command, Some(Commands::Serve { .. })) {
+        app.logger.log_warn("Interrupted");
+        anyhow::bail!("Interrupted");
+    }
+
     app.logger.log_info("CLI application completed successfully");
     Ok(())
 }
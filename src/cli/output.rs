@@ -0,0 +1,339 @@
+//! Rendering of collected metric samples and stored runs.
+
+use super::cli_struct::OutputFormat;
+use crate::domain::storage::history::RunRecord;
+use crate::ports::metrics_port::MetricKind;
+use serde_json::{json, Value};
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Writes samples to stdout in the selected format
+pub struct SampleWriter {
+    format: OutputFormat,
+    repeat_headers: bool,
+    written: usize,
+    headers_shown: Vec<MetricKind>,
+}
+
+impl SampleWriter {
+    /// Creates a writer; table headers are repeated for every sample when more than one metric
+    /// is shown, so that interleaved tables stay readable.
+    pub fn new(format: OutputFormat, metrics: usize) -> Self {
+        Self {
+            format,
+            repeat_headers: metrics > 1,
+            written: 0,
+            headers_shown: Vec::new(),
+        }
+    }
+
+    /// Writes one sample of one metric.
+    pub fn write(&mut self, record: &Value) {
+        match self.format {
+            OutputFormat::Ndjson => println!("{}", record),
+            OutputFormat::Json => {
+                let prefix = if self.written == 0 { "[\n" } else { ",\n" };
+                let pretty = serde_json::to_string_pretty(record).unwrap_or_default();
+                print!("{}{}", prefix, pretty);
+            }
+            OutputFormat::Table => self.write_table(record),
+        }
+        self.written += 1;
+    }
+
+    /// Closes the output (ends the JSON array).
+    pub fn finish(&mut self) {
+        if self.format == OutputFormat::Json {
+            if self.written == 0 {
+                println!("[]");
+            } else {
+                println!("\n]");
+            }
+        }
+    }
+
+    fn write_table(&mut self, record: &Value) {
+        let Some(kind) = record["metric"].as_str().and_then(|m| m.parse().ok()) else {
+            return;
+        };
+        let (headers, rows) = table(kind, &record["data"]);
+
+        let show_header = self.repeat_headers || !self.headers_shown.contains(&kind);
+        if show_header {
+            if self.repeat_headers {
+                let time = chrono::DateTime::from_timestamp(
+                    record["timestamp"].as_i64().unwrap_or_default(),
+                    0,
+                )
+                .map(|t| t.format("%H:%M:%S").to_string())
+                .unwrap_or_default();
+                println!("\n{} {}", time, kind);
+            }
+            println!("{}", format_row(&headers));
+            self.headers_shown.push(kind);
+        }
+        for row in rows {
+            println!("{}", format_row(&row));
+        }
+    }
+}
+
+/// Wraps collected data with the metric name, sample number and timestamp.
+pub fn record(kind: MetricKind, sample: u32, timestamp: i64, data: Value) -> Value {
+    json!({
+        "metric": kind.as_str(),
+        "sample": sample,
+        "timestamp": timestamp,
+        "data": data,
+    })
+}
+
+/// Renders runs as a table, one line per run; pinned run IDs are marked with `*`.
+pub fn runs_table(runs: &[RunRecord]) -> String {
+    let mut table = format!(
+        "{:<26} {:<16} {:<12} {:<14} {:<18} {:<9} {:>6} {:>3} {:>4} {:>10} {:>9} {:>10}\n",
+        "ID",
+        "Time (UTC)",
+        "Host",
+        "Target",
+        "Profile",
+        "rw",
+        "bs",
+        "QD",
+        "jobs",
+        "IOPS",
+        "MiB/s",
+        "lat µs"
+    );
+    for run in runs {
+        let time = chrono::DateTime::from_timestamp(run.timestamp, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let job = |f: fn(&crate::domain::storage::fio::FioJobConfig) -> String| {
+            run.job.as_ref().map(f).unwrap_or_else(|| "-".to_string())
+        };
+        let io = run.result.combined();
+        table.push_str(&format!(
+            "{:<26} {:<16} {:<12} {:<14} {:<18} {:<9} {:>6} {:>3} {:>4} {:>10.0} {:>9.1} {:>10.1}\n",
+            format!("{}{}", run.id, if run.pinned { "*" } else { "" }),
+            time,
+            run.hostname,
+            run.target.as_deref().unwrap_or("-"),
+            run.profile.as_deref().unwrap_or(&run.name),
+            job(|j| j.rw.to_string()),
+            job(|j| j.bs.clone()),
+            job(|j| j.iodepth.to_string()),
+            job(|j| j.numjobs.to_string()),
+            io.iops,
+            io.bandwidth_mb,
+            io.lat_usec
+        ));
+    }
+    table
+}
+
+fn format_row<S: AsRef<str>>(cells: &[S]) -> String {
+    cells
+        .iter()
+        .enumerate()
+        .map(|(i, cell)| {
+            if i == 0 {
+                format!("{:<10}", cell.as_ref())
+            } else {
+                format!("{:>10}", cell.as_ref())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end()
+        .to_string()
+}
+
+/// Column headers and rows for one sample of a metric.
+fn table(kind: MetricKind, data: &Value) -> (Vec<String>, Vec<Vec<String>>) {
+    let num = |v: &Value, key: &str, precision: usize| match v[key].as_f64() {
+        Some(n) => format!("{:.*}", precision, n),
+        None => "-".to_string(),
+    };
+    let mib = |v: &Value, key: &str| match v[key].as_f64() {
+        Some(n) => format!("{:.0}", n / MIB),
+        None => "-".to_string(),
+    };
+    let text = |v: &Value, key: &str| match &v[key] {
+        Value::String(s) => s.clone(),
+        Value::Null => "-".to_string(),
+        other => other.to_string(),
+    };
+    let headers = |names: &[&str]| names.iter().map(|s| s.to_string()).collect();
+    let records = |data: &Value| data.as_array().cloned().unwrap_or_default();
+
+    match kind {
+        MetricKind::Cpu => (
+            headers(&["sockets", "cores", "threads", "model"]),
+            vec![vec![
+                text(data, "num_sockets"),
+                text(data, "total_cores"),
+                text(data, "total_threads"),
+                text(data, "model_name"),
+            ]],
+        ),
+        MetricKind::Mem => (
+            headers(&[
+                "total_MiB",
+                "used",
+                "free",
+                "avail",
+                "buffers",
+                "cached",
+                "dirty",
+                "swap_used",
+            ]),
+            vec![vec![
+                mib(data, "total_memory"),
+                mib(data, "used_memory"),
+                mib(data, "free_memory"),
+                mib(data, "available_memory"),
+                mib(data, "buffers"),
+                mib(data, "cached"),
+                mib(data, "dirty"),
+                mib(data, "swap_used"),
+            ]],
+        ),
+        MetricKind::Vmstat => (
+            headers(&[
+                "r", "b", "swpd", "free", "buff", "cache", "si", "so", "bi", "bo", "in", "cs",
+                "us", "sy", "id", "wa", "st",
+            ]),
+            records(data)
+                .iter()
+                .map(|vm| {
+                    let (procs, memory, swap) = (&vm["procs"], &vm["memory"], &vm["swap"]);
+                    let (io, system, cpu) = (&vm["io"], &vm["system"], &vm["cpu"]);
+                    vec![
+                        text(procs, "running"),
+                        text(procs, "blocked"),
+                        text(memory, "swap_used_kb"),
+                        text(memory, "free_kb"),
+                        text(memory, "buffer_kb"),
+                        text(memory, "cache_kb"),
+                        num(swap, "kb_in_per_sec", 0),
+                        num(swap, "kb_out_per_sec", 0),
+                        num(io, "blocks_in_per_sec", 0),
+                        num(io, "blocks_out_per_sec", 0),
+                        num(system, "interrupts_per_sec", 0),
+                        num(system, "context_switches_per_sec", 0),
+                        num(cpu, "user", 0),
+                        num(cpu, "system", 0),
+                        num(cpu, "idle", 0),
+                        num(cpu, "iowait", 0),
+                        num(cpu, "steal", 0),
+                    ]
+                })
+                .collect(),
+        ),
+        MetricKind::Mpstat => (
+            headers(&[
+                "CPU", "%usr", "%nice", "%sys", "%iowait", "%irq", "%soft", "%steal", "%guest",
+                "%gnice", "%idle",
+            ]),
+            records(data)
+                .iter()
+                .map(|cpu| {
+                    let mut row = vec![text(cpu, "cpu_id")];
+                    for key in [
+                        "usr", "nice", "sys", "iowait", "irq", "soft", "steal", "guest", "gnice",
+                        "idle",
+                    ] {
+                        row.push(num(cpu, key, 2));
+                    }
+                    row
+                })
+                .collect(),
+        ),
+        MetricKind::Iostat => (
+            headers(&[
+                "Device",
+                "tps",
+                "kB_read/s",
+                "kB_wrtn/s",
+                "rareq-sz",
+                "wareq-sz",
+                "aqu-sz",
+                "await",
+                "%util",
+            ]),
+            records(data)
+                .iter()
+                .map(|dev| {
+                    vec![
+                        text(dev, "device"),
+                        num(dev, "tps", 2),
+                        num(dev, "kb_read_per_sec", 2),
+                        num(dev, "kb_wrtn_per_sec", 2),
+                        num(dev, "rareq_sz", 2),
+                        num(dev, "wareq_sz", 2),
+                        num(dev, "aqu_sz", 2),
+                        num(dev, "await_ms", 2),
+                        num(dev, "util", 2),
+                    ]
+                })
+                .collect(),
+        ),
+        MetricKind::Loadavg => (
+            headers(&["load1", "load5", "load15", "running", "total", "last_pid"]),
+            records(data)
+                .iter()
+                .map(|load| {
+                    vec![
+                        num(load, "load_1", 2),
+                        num(load, "load_5", 2),
+                        num(load, "load_15", 2),
+                        text(load, "running_tasks"),
+                        text(load, "total_tasks"),
+                        text(load, "last_pid"),
+                    ]
+                })
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables() {
+        let mpstat = json!([
+            {"cpu_id": "all", "usr": 12.5, "nice": 0.0, "sys": 3.25, "iowait": 1.0, "irq": 0.0,
+             "soft": 0.5, "steal": 0.0, "guest": 0.0, "gnice": 0.0, "idle": 82.75, "timestamp": 0}
+        ]);
+        let (headers, rows) = table(MetricKind::Mpstat, &mpstat);
+        assert_eq!(headers.len(), rows[0].len());
+        assert_eq!(rows[0][0], "all");
+        assert_eq!(rows[0][1], "12.50");
+        assert_eq!(rows[0][10], "82.75");
+
+        let mem = json!({"total_memory": 8u64 << 30, "used_memory": 2u64 << 30});
+        let (headers, rows) = table(MetricKind::Mem, &mem);
+        assert_eq!(headers.len(), rows[0].len());
+        assert_eq!(rows[0][0], "8192");
+        assert_eq!(rows[0][1], "2048");
+        assert_eq!(rows[0][2], "-");
+
+        let (_, rows) = table(MetricKind::Iostat, &json!([]));
+        assert!(rows.is_empty());
+    }
+
+    #[test]
+    fn test_record_and_row() {
+        let record = record(MetricKind::Loadavg, 3, 1700000000, json!([]));
+        assert_eq!(record["metric"], "loadavg");
+        assert_eq!(record["sample"], 3);
+
+        assert_eq!(
+            format_row(&["all", "1.00"]),
+            format!("{:<10} {:>10}", "all", "1.00")
+        );
+    }
+}
@@ -0,0 +1,1198 @@
+//! Typed configuration loaded from `config.toml`.
+//!
+//! The configuration is layered: every section has built-in defaults, the TOML file overrides
+//! whichever keys it sets, and command-line flags (e.g. `--debug`) take precedence over both.
+//! Unknown keys are rejected so that typos surface as errors instead of being silently ignored.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysperf_svr::config::Config;
+//!
+//! let config = Config::load("config.toml").unwrap();
+//! println!("Tests run in {}", config.storage.test_directory.display());
+//! for target in &config.storage.targets {
+//!     println!("{} -> {}", target.name, target.path.display());
+//! }
+//! ```
+
+use crate::domain::metrics::series::MetricKind;
+use crate::domain::storage::fio::{FioJobConfig, StorageTarget, UnknownFioValue};
+use crate::domain::storage::preflight::PreflightConfig;
+use crate::domain::storage::steady_state::{SteadyStateCriteria, TrackingMetric};
+use crate::domain::storage::sweep::{SweepAxes, SweepPlan};
+use crate::domain::storage::units::{BlockSizes, BsSplit, KbBase, SizeSpec, UnitError};
+use log::LevelFilter;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Name of the configuration file picked up from the working directory when `--config` is not
+/// given.
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Target types understood by the storage benchmarks.
+const TARGET_TYPES: &[&str] = &["block_device", "device_mapper", "filesystem", "file"];
+
+/// Errors that can occur while loading the configuration
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// The configuration file could not be read
+    #[error("Failed to read config file {path}: {message}")]
+    ReadError { path: PathBuf, message: String },
+
+    /// The file is not valid TOML or does not match the expected schema
+    #[error("Failed to parse config file {path}: {message}")]
+    ParseError { path: PathBuf, message: String },
+
+    /// An inline table spans multiple lines, which TOML does not allow
+    #[error(
+        "{path}:{line}: inline table `{key}` spans multiple lines, which TOML does not allow; \
+         put it on one line or use a [{table}] section instead"
+    )]
+    MultilineInlineTable {
+        path: PathBuf,
+        line: usize,
+        key: String,
+        table: String,
+    },
+
+    /// A value parsed but failed validation
+    #[error("Invalid value for `{key}`: {message}")]
+    InvalidValue { key: String, message: String },
+}
+
+/// Complete application configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Logging and persistence settings
+    pub general: GeneralConfig,
+    /// Storage benchmark settings
+    pub storage: StorageConfig,
+    /// System metrics recorded during benchmarks
+    pub metrics: MetricsConfig,
+    /// Stored results
+    pub database: DatabaseConfig,
+    /// HTTP server settings
+    pub server: ServerConfig,
+}
+
+/// The `[general]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneralConfig {
+    /// Directory where log files are written
+    pub log_directory: PathBuf,
+    /// Minimum log level: Debug, Info, Warn, Trace or Error
+    pub log_level: String,
+    /// Path of the embedded database
+    pub database_path: PathBuf,
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        Self {
+            log_directory: PathBuf::from("logs"),
+            log_level: String::from("Info"),
+            database_path: PathBuf::from("./database.db"),
+        }
+    }
+}
+
+/// The `[storage]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Directory in which FIO creates its data files
+    pub test_directory: PathBuf,
+    /// Maximum number of benchmarks that may run at the same time
+    pub max_concurrent_tests: u32,
+    /// Default per-test timeout in seconds
+    pub default_timeout: u64,
+    /// Scale FIO's per-job size down to the free space instead of refusing jobs that do not fit
+    pub scale_to_fit: bool,
+    /// Percentage of a filesystem left free after FIO has created its data files
+    pub reserve_percent: u32,
+    /// Named storage targets (`[[storage.targets]]`)
+    pub targets: Vec<TargetConfig>,
+    /// FIO profiles and defaults (`[storage.fio]`)
+    pub fio: FioConfig,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            test_directory: PathBuf::from("./benchmark"),
+            max_concurrent_tests: 1,
+            default_timeout: 3600,
+            scale_to_fit: false,
+            reserve_percent: 5,
+            targets: Vec::new(),
+            fio: FioConfig::default(),
+        }
+    }
+}
+
+/// The `[metrics]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Record a time series of system metrics while every benchmark runs
+    pub record_benchmarks: bool,
+    /// Seconds between samples
+    pub interval: u64,
+    /// Metrics to record: cpu, mem, vmstat, mpstat, iostat or loadavg
+    pub collect: Vec<MetricKind>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            record_benchmarks: true,
+            interval: 1,
+            collect: vec![
+                MetricKind::Mpstat,
+                MetricKind::Mem,
+                MetricKind::Iostat,
+                MetricKind::Loadavg,
+            ],
+        }
+    }
+}
+
+/// The `[database]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Days to keep runs and metric samples; 0 keeps them forever. Pinned runs are always kept.
+    pub retention_days: u32,
+    /// Prune expired results after every benchmark instead of only on `db prune`
+    pub auto_prune: bool,
+    /// Time-series database that results are also exported to (`[database.remote]`)
+    pub remote: RemoteConfig,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: 30,
+            auto_prune: true,
+            remote: RemoteConfig::default(),
+        }
+    }
+}
+
+/// The `[database.remote]` section
+///
+/// Metric samples and benchmark summaries are written as InfluxDB line protocol.  InfluxDB 2
+/// endpoints are addressed by `bucket` (and `org`), InfluxDB 1 endpoints by `database`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemoteConfig {
+    /// Export to the remote database
+    pub enabled: bool,
+    /// Kind of remote database; only "influxdb" is supported
+    #[serde(rename = "type")]
+    pub remote_type: String,
+    /// Base URL, e.g. "http://metrics-db:8086"
+    pub url: String,
+    /// InfluxDB 2 bucket
+    pub bucket: Option<String>,
+    /// InfluxDB 2 organization
+    pub org: Option<String>,
+    /// API token, sent as `Authorization: Token <token>`
+    pub token: Option<String>,
+    /// InfluxDB 1 database
+    pub database: Option<String>,
+    /// Lines sent per request
+    pub batch_size: usize,
+    /// Seconds a partial batch waits before it is sent
+    pub flush_interval: u64,
+    /// Retries of a failed request before its batch is buffered to disk (at most 10)
+    pub max_retries: u32,
+    /// Request timeout in seconds
+    pub timeout: u64,
+    /// File holding batches that could not be sent; replayed once the endpoint is back
+    pub buffer_path: PathBuf,
+    /// Largest size the buffer file may grow to, in MiB
+    pub max_buffer_mb: u64,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            remote_type: String::from("influxdb"),
+            url: String::from("http://localhost:8086"),
+            bucket: None,
+            org: None,
+            token: None,
+            database: None,
+            batch_size: 500,
+            flush_interval: 10,
+            max_retries: 3,
+            timeout: 10,
+            buffer_path: PathBuf::from("./remote_buffer.lp"),
+            max_buffer_mb: 64,
+        }
+    }
+}
+
+/// The `[server]` section, used by `serve`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Bearer token required to submit and cancel jobs; without one the job API is read-only
+    pub api_token: Option<String>,
+}
+
+/// A single `[[storage.targets]]` entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetConfig {
+    /// Unique name used to refer to the target
+    pub name: String,
+    /// Device, mount point or file path
+    pub path: PathBuf,
+    /// One of "block_device", "device_mapper", "filesystem" or "file"
+    #[serde(rename = "type")]
+    pub target_type: String,
+    /// Target-specific options; scalar values are kept in their string form
+    #[serde(default, deserialize_with = "scalar_map")]
+    pub options: HashMap<String, String>,
+}
+
+/// The `[storage.fio]` section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FioConfig {
+    /// Named job profiles (`[storage.fio.profiles.<name>]`)
+    pub profiles: BTreeMap<String, FioProfileConfig>,
+    /// Options applied to every profile (`[storage.fio.defaults]`)
+    pub defaults: FioProfileConfig,
+    /// Parameter sweep axes (`[storage.fio.sweep]`)
+    pub sweep: SweepConfig,
+    /// Preconditioning and steady-state criteria (`[storage.fio.steady_state]`)
+    pub steady_state: SteadyStateConfig,
+}
+
+/// The `[storage.fio.sweep]` section.
+///
+/// Each axis overrides the matching profile value; an empty list keeps the profile's value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SweepConfig {
+    /// Block sizes to sweep
+    pub block_sizes: Vec<String>,
+    /// Queue depths to sweep
+    pub iodepth: Vec<u32>,
+    /// Job counts to sweep
+    pub numjobs: Vec<u32>,
+    /// Skip points with more than this many I/Os in flight (`iodepth × numjobs`)
+    pub max_outstanding_ios: Option<u32>,
+    /// Run at most this many points
+    pub limit: Option<usize>,
+}
+
+impl Default for SweepConfig {
+    fn default() -> Self {
+        Self {
+            block_sizes: vec!["4k".into(), "8k".into(), "64k".into(), "1m".into()],
+            iodepth: vec![1, 8, 16, 32, 64],
+            numjobs: vec![1, 4, 8],
+            max_outstanding_ios: None,
+            limit: None,
+        }
+    }
+}
+
+/// The `[storage.fio.steady_state]` section.
+///
+/// Defaults follow the SNIA PTS: a window of 5 rounds, 20% data excursion, 10% slope
+/// excursion and at most 25 rounds of one minute.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SteadyStateConfig {
+    /// Write the test range twice sequentially before the first round
+    pub precondition: bool,
+    /// Seconds preconditioning may take; unlimited when unset, since writing a large device
+    /// twice takes hours
+    pub precondition_timeout: Option<u64>,
+    /// Value tracked across rounds: "iops", "bandwidth" or "latency"
+    pub metric: TrackingMetric,
+    /// Rounds in the measurement window
+    pub window: usize,
+    /// Largest spread between the rounds of the window, in percent of its average
+    pub max_data_excursion: f64,
+    /// Largest change of the window's best-fit line, in percent of its average
+    pub max_slope_excursion: f64,
+    /// Rounds after which to give up on reaching steady state
+    pub max_rounds: u32,
+    /// Length of each round in seconds
+    pub round_runtime: u64,
+}
+
+impl Default for SteadyStateConfig {
+    fn default() -> Self {
+        let criteria = SteadyStateCriteria::default();
+        Self {
+            precondition: true,
+            precondition_timeout: None,
+            metric: criteria.metric,
+            window: criteria.window,
+            max_data_excursion: criteria.max_data_excursion,
+            max_slope_excursion: criteria.max_slope_excursion,
+            max_rounds: criteria.max_rounds,
+            round_runtime: 60,
+        }
+    }
+}
+
+/// A FIO job profile as written in the config file.
+///
+/// Every field is optional so that profiles can be layered on top of
+/// `[storage.fio.defaults]`; keys without a dedicated field are kept verbatim in `extra`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FioProfileConfig {
+    /// Human-readable description of the profile
+    pub description: Option<String>,
+    /// I/O engine (e.g. "libaio", "io_uring")
+    pub ioengine: Option<String>,
+    /// I/O pattern (e.g. "randrw", "read")
+    pub rw: Option<String>,
+    /// Block size (e.g. "4k", "4k,128k")
+    pub bs: Option<String>,
+    /// Size per job (e.g. "10G")
+    pub size: Option<String>,
+    /// Number of parallel jobs
+    pub numjobs: Option<u32>,
+    /// I/O queue depth
+    pub iodepth: Option<u32>,
+    /// Use O_DIRECT
+    pub direct: Option<bool>,
+    /// Use buffered I/O
+    pub buffered: Option<bool>,
+    /// Percentage of reads for mixed workloads
+    pub rwmixread: Option<u32>,
+    /// Any other FIO option, in its string form
+    #[serde(flatten, deserialize_with = "scalar_map")]
+    pub extra: BTreeMap<String, String>,
+}
+
+/// A scalar TOML value accepted in free-form option tables
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScalarValue {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl ScalarValue {
+    fn into_string(self) -> String {
+        match self {
+            ScalarValue::Bool(v) => v.to_string(),
+            ScalarValue::Integer(v) => v.to_string(),
+            ScalarValue::Float(v) => v.to_string(),
+            ScalarValue::String(v) => v,
+        }
+    }
+}
+
+/// Deserializes a table of scalars into a map of strings.
+fn scalar_map<'de, D, M>(deserializer: D) -> Result<M, D::Error>
+where
+    D: Deserializer<'de>,
+    M: FromIterator<(String, String)>,
+{
+    let raw: BTreeMap<String, ScalarValue> = BTreeMap::deserialize(deserializer)?;
+    Ok(raw.into_iter().map(|(k, v)| (k, v.into_string())).collect())
+}
+
+impl Config {
+    /// Loads and validates the configuration at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if:
+    /// - The file cannot be read
+    /// - The file is not valid TOML or contains unknown keys
+    /// - A value fails validation
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| ConfigError::ReadError {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        Self::parse(&content, path)
+    }
+
+    /// Resolves the configuration to use for this invocation.
+    ///
+    /// An explicit path must exist; otherwise `config.toml` in the working directory is used
+    /// when present, falling back to built-in defaults.
+    pub fn load_or_default(path: Option<&Path>) -> Result<Self, ConfigError> {
+        match path {
+            Some(path) => Self::load(path),
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => Self::load(DEFAULT_CONFIG_FILE),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Parses and validates configuration text; `path` is only used in diagnostics.
+    pub fn parse(content: &str, path: &Path) -> Result<Self, ConfigError> {
+        let config: Config = toml::from_str(content).map_err(|e| {
+            find_multiline_inline_table(content, path).unwrap_or_else(|| ConfigError::ParseError {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks value ranges and cross-references that serde cannot express.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid =
+            |key: String, message: String| Err(ConfigError::InvalidValue { key, message });
+
+        if parse_level(&self.general.log_level).is_none() {
+            return invalid(
+                "general.log_level".into(),
+                format!(
+                    "\"{}\" is not one of Debug, Info, Warn, Trace or Error",
+                    self.general.log_level
+                ),
+            );
+        }
+        if self.storage.max_concurrent_tests == 0 {
+            return invalid(
+                "storage.max_concurrent_tests".into(),
+                "must be at least 1".into(),
+            );
+        }
+        if self.storage.default_timeout == 0 {
+            return invalid(
+                "storage.default_timeout".into(),
+                "must be greater than 0 seconds".into(),
+            );
+        }
+        if self.storage.reserve_percent >= 100 {
+            return invalid(
+                "storage.reserve_percent".into(),
+                "must be less than 100".into(),
+            );
+        }
+
+        if self.metrics.interval == 0 {
+            return invalid(
+                "metrics.interval".into(),
+                "must be greater than 0 seconds".into(),
+            );
+        }
+
+        if self.database.remote.enabled {
+            self.database.remote.validate("database.remote")?;
+        }
+
+        if let Some(token) = &self.server.api_token {
+            if token.trim().is_empty() || token.contains(char::is_whitespace) {
+                return invalid(
+                    "server.api_token".into(),
+                    "must be non-empty and contain no whitespace".into(),
+                );
+            }
+        }
+
+        let mut names = HashSet::new();
+        for (i, target) in self.storage.targets.iter().enumerate() {
+            let key = format!("storage.targets[{}]", i);
+            if target.name.trim().is_empty() {
+                return invalid(format!("{}.name", key), "must not be empty".into());
+            }
+            if !names.insert(target.name.as_str()) {
+                return invalid(
+                    format!("{}.name", key),
+                    format!("duplicate target name \"{}\"", target.name),
+                );
+            }
+            if target.path.as_os_str().is_empty() {
+                return invalid(format!("{}.path", key), "must not be empty".into());
+            }
+            if !TARGET_TYPES.contains(&target.target_type.as_str()) {
+                return invalid(
+                    format!("{}.type", key),
+                    format!(
+                        "\"{}\" is not one of {}",
+                        target.target_type,
+                        TARGET_TYPES.join(", ")
+                    ),
+                );
+            }
+        }
+
+        let defaults = &self.storage.fio.defaults;
+        defaults.validate("storage.fio.defaults")?;
+        defaults.validate_units("storage.fio.defaults")?;
+        self.storage.fio.sweep.validate("storage.fio.sweep")?;
+        self.storage
+            .fio
+            .steady_state
+            .validate("storage.fio.steady_state")?;
+        for (name, profile) in &self.storage.fio.profiles {
+            let key = format!("storage.fio.profiles.{}", name);
+            profile.validate(&key)?;
+            // Sizes are read with the kb_base the merged job ends up with
+            profile.merged_over(defaults).validate_units(&key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Log level from `general.log_level`.
+    pub fn log_level(&self) -> LevelFilter {
+        parse_level(&self.general.log_level).unwrap_or(LevelFilter::Info)
+    }
+
+    /// Looks up a target by name.
+    pub fn target(&self, name: &str) -> Option<&TargetConfig> {
+        self.storage.targets.iter().find(|t| t.name == name)
+    }
+
+    /// Resolves a named profile into a runnable job, layered over `[storage.fio.defaults]`.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the profile does not exist or names an unknown `rw` pattern.
+    pub fn job_for_profile(&self, name: &str) -> Result<FioJobConfig, ConfigError> {
+        let key = format!("storage.fio.profiles.{}", name);
+        let profile = self.storage.fio.profiles.get(name).ok_or_else(|| {
+            let available: Vec<&str> = self
+                .storage
+                .fio
+                .profiles
+                .keys()
+                .map(String::as_str)
+                .collect();
+            ConfigError::InvalidValue {
+                key: key.clone(),
+                message: format!("no such profile; available: {}", available.join(", ")),
+            }
+        })?;
+
+        profile
+            .merged_over(&self.storage.fio.defaults)
+            .to_job_config(&key)
+    }
+
+    /// Builds a sweep of `[storage.fio.sweep]` around a named profile.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the profile cannot be resolved (see [`Config::job_for_profile`]).
+    pub fn sweep_plan(&self, profile: &str) -> Result<SweepPlan, ConfigError> {
+        let base = self.job_for_profile(profile)?;
+        Ok(self.storage.fio.sweep.to_plan(base))
+    }
+
+    /// Pre-flight settings from `[storage]`.
+    pub fn preflight(&self) -> PreflightConfig {
+        PreflightConfig {
+            scale_to_fit: self.storage.scale_to_fit,
+            reserve_percent: self.storage.reserve_percent,
+        }
+    }
+
+    /// Resolves a named target into a [`StorageTarget`].
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if no target has that name.
+    pub fn storage_target(&self, name: &str) -> Result<StorageTarget, ConfigError> {
+        self.target(name)
+            .map(TargetConfig::to_storage_target)
+            .ok_or_else(|| {
+                let available: Vec<&str> = self
+                    .storage
+                    .targets
+                    .iter()
+                    .map(|t| t.name.as_str())
+                    .collect();
+                ConfigError::InvalidValue {
+                    key: "storage.targets".into(),
+                    message: format!(
+                        "no target named \"{}\"; available: {}",
+                        name,
+                        available.join(", ")
+                    ),
+                }
+            })
+    }
+}
+
+impl TargetConfig {
+    /// Converts the target into the domain representation used by the FIO runner.
+    pub fn to_storage_target(&self) -> StorageTarget {
+        StorageTarget {
+            path: self.path.clone(),
+            target_type: self.target_type.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl FioProfileConfig {
+    /// Layers this profile on top of `defaults`; keys set in the profile win.
+    pub fn merged_over(&self, defaults: &FioProfileConfig) -> FioProfileConfig {
+        let mut extra = defaults.extra.clone();
+        extra.extend(self.extra.clone());
+
+        FioProfileConfig {
+            description: self.description.clone().or(defaults.description.clone()),
+            ioengine: self.ioengine.clone().or(defaults.ioengine.clone()),
+            rw: self.rw.clone().or(defaults.rw.clone()),
+            bs: self.bs.clone().or(defaults.bs.clone()),
+            size: self.size.clone().or(defaults.size.clone()),
+            numjobs: self.numjobs.or(defaults.numjobs),
+            iodepth: self.iodepth.or(defaults.iodepth),
+            direct: self.direct.or(defaults.direct),
+            buffered: self.buffered.or(defaults.buffered),
+            rwmixread: self.rwmixread.or(defaults.rwmixread),
+            extra,
+        }
+    }
+
+    /// Builds a [`FioJobConfig`], using [`FioJobConfig::default`] for unset fields.
+    ///
+    /// `key` is the config path of the profile and is only used in error messages.
+    pub fn to_job_config(&self, key: &str) -> Result<FioJobConfig, ConfigError> {
+        let invalid = |field: &str, message: String| ConfigError::InvalidValue {
+            key: format!("{}.{}", key, field),
+            message,
+        };
+        let defaults = FioJobConfig::default();
+
+        Ok(FioJobConfig {
+            ioengine: match &self.ioengine {
+                Some(engine) => engine
+                    .parse()
+                    .map_err(|e: UnknownFioValue| invalid("ioengine", e.to_string()))?,
+                None => defaults.ioengine,
+            },
+            rw: match &self.rw {
+                Some(rw) => rw
+                    .parse()
+                    .map_err(|e: UnknownFioValue| invalid("rw", e.to_string()))?,
+                None => defaults.rw,
+            },
+            bs: self.bs.clone().unwrap_or(defaults.bs),
+            size: self.size.clone().unwrap_or(defaults.size),
+            numjobs: self.numjobs.unwrap_or(defaults.numjobs),
+            iodepth: self.iodepth.unwrap_or(defaults.iodepth),
+            direct: self.direct.unwrap_or(defaults.direct),
+            buffered: self.buffered.unwrap_or(defaults.buffered),
+            rwmixread: self.rwmixread,
+            extra_options: self.extra.clone().into_iter().collect(),
+        })
+    }
+
+    fn validate(&self, key: &str) -> Result<(), ConfigError> {
+        let invalid = |field: &str, message: &str| {
+            Err(ConfigError::InvalidValue {
+                key: format!("{}.{}", key, field),
+                message: message.to_string(),
+            })
+        };
+
+        if self.rwmixread.is_some_and(|mix| mix > 100) {
+            return invalid("rwmixread", "must be between 0 and 100");
+        }
+        if self.numjobs == Some(0) {
+            return invalid("numjobs", "must be at least 1");
+        }
+        if self.iodepth == Some(0) {
+            return invalid("iodepth", "must be at least 1");
+        }
+        Ok(())
+    }
+
+    /// Checks that `bs`, `size`, `bssplit` and `kb_base` are valid FIO sizes.
+    fn validate_units(&self, key: &str) -> Result<(), ConfigError> {
+        let invalid = |field: &str, error: UnitError| ConfigError::InvalidValue {
+            key: format!("{}.{}", key, field),
+            message: error.to_string(),
+        };
+
+        let kb_base = match self.extra.get("kb_base") {
+            Some(base) => base.parse().map_err(|e| invalid("kb_base", e))?,
+            None => KbBase::default(),
+        };
+        if let Some(bs) = &self.bs {
+            BlockSizes::parse(bs, kb_base).map_err(|e| invalid("bs", e))?;
+        }
+        if let Some(size) = self.size.as_deref().filter(|s| !s.trim().is_empty()) {
+            SizeSpec::parse(size, kb_base).map_err(|e| invalid("size", e))?;
+        }
+        if let Some(split) = self.extra.get("bssplit") {
+            BsSplit::parse(split, kb_base).map_err(|e| invalid("bssplit", e))?;
+        }
+        Ok(())
+    }
+}
+
+impl RemoteConfig {
+    fn validate(&self, key: &str) -> Result<(), ConfigError> {
+        let invalid = |field: &str, message: &str| {
+            Err(ConfigError::InvalidValue {
+                key: format!("{}.{}", key, field),
+                message: message.to_string(),
+            })
+        };
+
+        if self.remote_type != "influxdb" {
+            return invalid("type", "only \"influxdb\" is supported");
+        }
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return invalid("url", "must be an http:// or https:// URL");
+        }
+        if self.bucket.is_some() == self.database.is_some() {
+            return invalid(
+                "bucket",
+                "set either bucket (InfluxDB 2) or database (InfluxDB 1)",
+            );
+        }
+        if self.batch_size == 0 {
+            return invalid("batch_size", "must be at least 1");
+        }
+        if self.flush_interval == 0 {
+            return invalid("flush_interval", "must be greater than 0 seconds");
+        }
+        if self.timeout == 0 {
+            return invalid("timeout", "must be greater than 0 seconds");
+        }
+        if self.max_retries > 10 {
+            return invalid("max_retries", "must be at most 10");
+        }
+        Ok(())
+    }
+}
+
+impl SweepConfig {
+    /// Builds a [`SweepPlan`] around `base` with this section's axes, filter and limit.
+    pub fn to_plan(&self, base: FioJobConfig) -> SweepPlan {
+        let mut plan = SweepPlan::new(
+            base,
+            SweepAxes {
+                block_sizes: self.block_sizes.clone(),
+                iodepths: self.iodepth.clone(),
+                numjobs: self.numjobs.clone(),
+            },
+        );
+        if let Some(max) = self.max_outstanding_ios {
+            plan = plan.max_outstanding_ios(max);
+        }
+        if let Some(limit) = self.limit {
+            plan = plan.limit(limit);
+        }
+        plan
+    }
+
+    fn validate(&self, key: &str) -> Result<(), ConfigError> {
+        let invalid = |field: &str, message: &str| {
+            Err(ConfigError::InvalidValue {
+                key: format!("{}.{}", key, field),
+                message: message.to_string(),
+            })
+        };
+
+        if self.block_sizes.iter().any(|bs| bs.trim().is_empty()) {
+            return invalid("block_sizes", "must not contain empty entries");
+        }
+        if let Some(error) = self
+            .block_sizes
+            .iter()
+            .find_map(|bs| BlockSizes::parse(bs, KbBase::default()).err())
+        {
+            return invalid("block_sizes", &error.to_string());
+        }
+        if self.iodepth.contains(&0) {
+            return invalid("iodepth", "values must be at least 1");
+        }
+        if self.numjobs.contains(&0) {
+            return invalid("numjobs", "values must be at least 1");
+        }
+        if self.limit == Some(0) {
+            return invalid("limit", "must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+impl SteadyStateConfig {
+    /// The steady-state criteria of this section.
+    pub fn to_criteria(&self) -> SteadyStateCriteria {
+        SteadyStateCriteria {
+            metric: self.metric,
+            window: self.window,
+            max_data_excursion: self.max_data_excursion,
+            max_slope_excursion: self.max_slope_excursion,
+            max_rounds: self.max_rounds,
+        }
+    }
+
+    fn validate(&self, key: &str) -> Result<(), ConfigError> {
+        let invalid = |field: &str, message: &str| {
+            Err(ConfigError::InvalidValue {
+                key: format!("{}.{}", key, field),
+                message: message.to_string(),
+            })
+        };
+
+        if self.window < 2 {
+            return invalid("window", "must be at least 2 rounds");
+        }
+        if (self.max_rounds as usize) < self.window {
+            return invalid("max_rounds", "must be at least the window size");
+        }
+        if self.max_data_excursion.is_nan() || self.max_data_excursion <= 0.0 {
+            return invalid("max_data_excursion", "must be greater than 0 percent");
+        }
+        if self.max_slope_excursion.is_nan() || self.max_slope_excursion <= 0.0 {
+            return invalid("max_slope_excursion", "must be greater than 0 percent");
+        }
+        if self.round_runtime == 0 {
+            return invalid("round_runtime", "must be greater than 0 seconds");
+        }
+        if self.precondition_timeout == Some(0) {
+            return invalid("precondition_timeout", "must be greater than 0 seconds");
+        }
+        Ok(())
+    }
+}
+
+fn parse_level(level: &str) -> Option<LevelFilter> {
+    match level.to_ascii_lowercase().as_str() {
+        "error" => Some(LevelFilter::Error),
+        "warn" => Some(LevelFilter::Warn),
+        "info" => Some(LevelFilter::Info),
+        "debug" => Some(LevelFilter::Debug),
+        "trace" => Some(LevelFilter::Trace),
+        _ => None,
+    }
+}
+
+/// Looks for `key = {` lines whose inline table is not closed on the same line.
+///
+/// TOML's own error for this case ("expected `}`") does not say what is wrong, so the loader
+/// reports the offending key and the section form to use instead.
+fn find_multiline_inline_table(content: &str, path: &Path) -> Option<ConfigError> {
+    let mut table = String::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let (line, open_braces) = scan_line(line);
+        let line = line.trim();
+
+        if let Some(header) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+            table = header.trim().to_string();
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            table = header.trim().to_string();
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if value.trim().starts_with('{') && open_braces > 0 {
+            let key = key.trim().to_string();
+            let table = if table.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", table, key)
+            };
+            return Some(ConfigError::MultilineInlineTable {
+                path: path.to_path_buf(),
+                line: i + 1,
+                key,
+                table,
+            });
+        }
+    }
+
+    None
+}
+
+/// Splits a TOML line at its comment, if any, and counts the braces it leaves open.
+///
+/// A `#` or brace inside a basic (`"..."`) or literal (`'...'`) string is part of the value.
+fn scan_line(line: &str) -> (&str, i32) {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut depth = 0;
+
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '#' => return (&line[..i], depth),
+                _ => {}
+            },
+        }
+    }
+    (line, depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shipped_config() {
+        let config = Config::parse(include_str!("../../config.toml"), Path::new("config.toml"))
+            .expect("shipped config.toml must load");
+
+        assert_eq!(config.general.log_level, "Debug");
+        assert_eq!(config.storage.max_concurrent_tests, 4);
+        assert_eq!(config.storage.targets.len(), 4);
+
+        let nvme = config.target("nvme_direct").unwrap();
+        assert_eq!(nvme.target_type, "block_device");
+        assert_eq!(nvme.options["rotational"], "false");
+
+        let profile = &config.storage.fio.profiles["filesystem_test"];
+        assert_eq!(profile.bs.as_deref(), Some("16k"));
+        assert_eq!(profile.extra["verify"], "md5");
+        assert_eq!(config.storage.fio.defaults.extra["runtime"], "60");
+        let steady = config.storage.fio.steady_state.to_criteria();
+        assert_eq!(steady, SteadyStateCriteria::default());
+
+        assert!(config.metrics.record_benchmarks);
+        assert_eq!(config.metrics.interval, 1);
+        assert_eq!(config.metrics.collect[2], MetricKind::Iostat);
+
+        assert_eq!(config.database.retention_days, 30);
+        assert!(config.database.auto_prune);
+        assert!(!config.database.remote.enabled);
+        assert_eq!(config.database.remote.bucket.as_deref(), Some("sysperf"));
+    }
+
+    #[test]
+    fn test_defaults_when_sections_missing() {
+        let config =
+            Config::parse("[general]\nlog_level = \"warn\"\n", Path::new("t.toml")).unwrap();
+
+        assert_eq!(config.log_level(), LevelFilter::Warn);
+        assert_eq!(config.storage.default_timeout, 3600);
+        assert_eq!(config.preflight(), PreflightConfig::default());
+        assert!(config.storage.targets.is_empty());
+        assert_eq!(config.database.retention_days, 30);
+    }
+
+    #[test]
+    fn test_multiline_inline_table() {
+        let content = "[[storage.targets]]\nname = \"a\"\npath = \"/dev/a\"\ntype = \"file\"\noptions = {\n    x = 1\n}\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+
+        match err {
+            ConfigError::MultilineInlineTable {
+                line, key, table, ..
+            } => {
+                assert_eq!(line, 5);
+                assert_eq!(key, "options");
+                assert_eq!(table, "storage.targets.options");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+
+        // A '#' or brace inside a string is not a comment or a table
+        let content = "[[storage.targets]]\nname = \"a\"\npath = \"/mnt/#1\"\ntype = \"file\"\noptions = { label = \"#2 {\" }\n";
+        assert!(Config::parse(content, Path::new("t.toml")).is_ok());
+        let content = "[[storage.targets]]\nname = \"a\"\npath = \"/mnt/#1\"\ntype = \"file\"\noptions = { label = \"}#\",\n    x = 1 }\n";
+        match Config::parse(content, Path::new("t.toml")).unwrap_err() {
+            ConfigError::MultilineInlineTable { line, .. } => assert_eq!(line, 5),
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+
+    #[test]
+    fn test_validation_names_offending_key() {
+        let content = "[storage]\nmax_concurrent_tests = 0\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("storage.max_concurrent_tests"));
+
+        let content = "[storage]\nreserve_percent = 100\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("storage.reserve_percent"));
+
+        let content = "[storage.fio.profiles.bad]\nrwmixread = 150\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("storage.fio.profiles.bad.rwmixread"));
+
+        let content = "[storage.fio.profiles.bad]\nbs = \"4x\"\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("storage.fio.profiles.bad.bs"));
+
+        let content = "[storage.fio.defaults]\nsize = \"150%\"\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("storage.fio.defaults.size"));
+
+        let content = "[storage.fio.defaults]\nkb_base = 512\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("storage.fio.defaults.kb_base"));
+
+        let content = "[storage.fio.profiles.mix]\nbssplit = \"4k/50:64k/40\"\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("storage.fio.profiles.mix.bssplit"));
+
+        let content = "[storage.fio.steady_state]\nwindow = 5\nmax_rounds = 3\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("storage.fio.steady_state.max_rounds"));
+
+        let content = "[storage.fio.steady_state]\nprecondition_timeout = 0\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("storage.fio.steady_state.precondition_timeout"));
+
+        let content = "[metrics]\ninterval = 0\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("metrics.interval"));
+
+        let content = "[metrics]\ncollect = [\"disk\"]\n";
+        assert!(Config::parse(content, Path::new("t.toml")).is_err());
+
+        let content = "[database.remote]\nenabled = true\nurl = \"metrics-db:8086\"\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("database.remote.url"));
+
+        let content = "[database.remote]\nenabled = true\nbucket = \"b\"\ndatabase = \"d\"\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("database.remote.bucket"));
+
+        let content = "[server]\napi_token = \"\"\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("server.api_token"));
+
+        let content = "[database.remote]\nenabled = true\nbucket = \"b\"\nmax_retries = 40\n";
+        let err = Config::parse(content, Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("database.remote.max_retries"));
+    }
+
+    #[test]
+    fn test_profile_merged_with_defaults() {
+        let config =
+            Config::parse(include_str!("../../config.toml"), Path::new("config.toml")).unwrap();
+
+        let job = config.job_for_profile("filesystem_test").unwrap();
+        assert_eq!(job.ioengine.as_str(), "libaio");
+        assert_eq!(job.rw.as_str(), "randrw");
+        assert_eq!(job.numjobs, 8);
+        assert_eq!(job.rwmixread, Some(70));
+        assert_eq!(job.extra_options["verify"], "md5");
+        assert_eq!(job.extra_options["runtime"], "60");
+        assert_eq!(job.extra_options["time_based"], "true");
+
+        let target = config.storage_target("xfs_mount").unwrap();
+        assert!(target.is_directory());
+        assert!(config.job_for_profile("missing").is_err());
+        assert!(config.storage_target("missing").is_err());
+    }
+
+    #[test]
+    fn test_shipped_profiles_render_known_fio_options() {
+        // Options the shipped profiles may pass to FIO; FIO rejects the whole job on any other
+        const FIO_OPTIONS: &[&str] = &[
+            "bs",
+            "direct",
+            "buffered",
+            "group_reporting",
+            "iodepth",
+            "ioengine",
+            "log_avg_msec",
+            "numjobs",
+            "randrepeat",
+            "runtime",
+            "rw",
+            "rwmixread",
+            "size",
+            "time_based",
+            "verify",
+            "write_bw_log",
+            "write_iops_log",
+            "write_lat_log",
+        ];
+
+        let config =
+            Config::parse(include_str!("../../config.toml"), Path::new("config.toml")).unwrap();
+        let target = StorageTarget::new_device("/dev/null", None);
+        for profile in config.storage.fio.profiles.keys() {
+            let job = config.job_for_profile(profile).unwrap();
+            for arg in job.to_fio_args(profile, &target) {
+                let option = arg.trim_start_matches("--");
+                let option = option.split_once('=').map_or(option, |(key, _)| key);
+                if option == "name" || option == "filename" {
+                    continue;
+                }
+                assert!(
+                    FIO_OPTIONS.contains(&option),
+                    "profile {} passes unknown FIO option {}",
+                    profile,
+                    arg
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_sweep_plan_from_config() {
+        let content = r#"
+[storage.fio.profiles.qd]
+rw = "randread"
+bs = "4k"
+numjobs = 2
+
+[storage.fio.sweep]
+block_sizes = ["4k", "128k"]
+iodepth = [1, 32]
+numjobs = []
+max_outstanding_ios = 32
+"#;
+        let config = Config::parse(content, Path::new("t.toml")).unwrap();
+        let points = config.sweep_plan("qd").unwrap().points();
+
+        let keys: Vec<String> = points.iter().map(|p| p.key.suffix()).collect();
+        assert_eq!(keys, vec!["bs4k_qd1_j2", "bs128k_qd1_j2"]);
+        assert!(points.iter().all(|p| p.config.rw.as_str() == "randread"));
+
+        let err =
+            Config::parse("[storage.fio.sweep]\niodepth = [0]\n", Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("storage.fio.sweep.iodepth"));
+
+        let err = Config::parse(
+            "[storage.fio.sweep]\nblock_sizes = [\"4k\", \"1.5m\"]\n",
+            Path::new("t.toml"),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("storage.fio.sweep.block_sizes"));
+    }
+
+    #[test]
+    fn test_unknown_key_rejected() {
+        let err = Config::parse("[general]\nlog_dir = \"x\"\n", Path::new("t.toml")).unwrap_err();
+        assert!(err.to_string().contains("log_dir"));
+    }
+}
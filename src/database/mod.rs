@@ -1,28 +1,335 @@
+//! Embedded key-value storage.
+//!
+//! [`EmbeddedStore`] persists string values in a single file (by default
+//! `general.database_path`).  Every write is its own transaction that is flushed to disk before
+//! it returns, and the file is never left half-written: after a crash or power loss the store
+//! reopens with the last committed state.
+//!
+//! Keys are `/`-separated paths such as `metrics/mpstat/1718000000000`; the first segment is the
+//! key's namespace.  [`Namespace`] scopes any [`KeyValueStore`] to one namespace so that callers
+//! can use short keys without colliding with each other.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysperf_svr::database::{EmbeddedStore, KeyValueStore, Namespace};
+//!
+//! let store = EmbeddedStore::open("database.db").unwrap();
+//! let runs = Namespace::new(&store, "runs");
+//! runs.set("2024-06-01T12:00:00Z", "{\"iops\": 120000}").unwrap();
+//!
+//! for (key, value) in runs.list("").unwrap() {
+//!     println!("{} = {}", key, value);
+//! }
+//! ```
+
 use anyhow::Result;
+use redb::{Database, TableDefinition};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Separator between key segments
+pub const KEY_SEPARATOR: char = '/';
+
+/// The single table holding every key
+const ENTRIES: TableDefinition<&str, &str> = TableDefinition::new("entries");
+
+/// Errors raised by the embedded store
+#[derive(Debug, Error)]
+pub enum DatabaseError {
+    /// The database file could not be created or opened
+    #[error("Failed to open database {path}: {message}")]
+    OpenError { path: PathBuf, message: String },
+
+    /// Another process has the database open
+    #[error("Database {path} is in use by another process")]
+    Locked { path: PathBuf },
+
+    /// A read or write transaction failed
+    #[error("Database {operation} failed: {message}")]
+    StorageError {
+        operation: &'static str,
+        message: String,
+    },
+
+    /// The key is empty or malformed
+    #[error("Invalid key \"{key}\": {reason}")]
+    InvalidKey { key: String, reason: &'static str },
+}
 
 pub trait KeyValueStore {
     fn get(&self, key: &str) -> Result<Option<String>>;
-    fn set(&mut self, key: &str, value: &str) -> Result<()>;
-    fn delete(&mut self, key: &str) -> Result<()>;
+    fn set(&self, key: &str, value: &str) -> Result<()>;
+    fn delete(&self, key: &str) -> Result<()>;
+
+    /// Returns every entry whose key starts with `prefix`, ordered by key.
+    fn list(&self, prefix: &str) -> Result<Vec<(String, String)>>;
 }
 
-pub struct EmbeddedStore;
+/// File-backed store built on a copy-on-write B-tree
+pub struct EmbeddedStore {
+    db: Database,
+    path: PathBuf,
+}
 
 impl EmbeddedStore {
-    pub fn new() -> Self {
-        EmbeddedStore
+    /// Opens the store at `path`, creating the file and its parent directories if needed.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if:
+    /// - The parent directory cannot be created
+    /// - The file is not a database or is corrupt
+    /// - Another process has the file open ([`DatabaseError::Locked`])
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        let path = path.as_ref();
+        let open_error = |message: String| DatabaseError::OpenError {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| open_error(e.to_string()))?;
+        }
+        let db = Database::create(path).map_err(|e| match e {
+            redb::DatabaseError::DatabaseAlreadyOpen => DatabaseError::Locked {
+                path: path.to_path_buf(),
+            },
+            e => open_error(e.to_string()),
+        })?;
+
+        // Create the table up front so that reads never see it missing
+        let txn = db.begin_write().map_err(|e| open_error(e.to_string()))?;
+        txn.open_table(ENTRIES)
+            .map_err(|e| open_error(e.to_string()))?;
+        txn.commit().map_err(|e| open_error(e.to_string()))?;
+
+        Ok(Self {
+            db,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Path of the database file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Runs a write transaction and commits it durably.
+    fn write<F>(&self, operation: &'static str, f: F) -> Result<(), DatabaseError>
+    where
+        F: FnOnce(&mut redb::Table<&str, &str>) -> Result<(), redb::StorageError>,
+    {
+        let error = |message: String| DatabaseError::StorageError { operation, message };
+
+        let txn = self.db.begin_write().map_err(|e| error(e.to_string()))?;
+        {
+            let mut table = txn.open_table(ENTRIES).map_err(|e| error(e.to_string()))?;
+            f(&mut table).map_err(|e| error(e.to_string()))?;
+        }
+        txn.commit().map_err(|e| error(e.to_string()))
+    }
+
+    /// Opens the table for reading.
+    fn read(
+        &self,
+        operation: &'static str,
+    ) -> Result<redb::ReadOnlyTable<&'static str, &'static str>, DatabaseError> {
+        let error = |message: String| DatabaseError::StorageError { operation, message };
+
+        let txn = self.db.begin_read().map_err(|e| error(e.to_string()))?;
+        txn.open_table(ENTRIES).map_err(|e| error(e.to_string()))
     }
 }
 
 impl KeyValueStore for EmbeddedStore {
-    // Implement the methods (for now, just return dummy values)
-    fn get(&self, _key: &str) -> Result<Option<String>> {
-        Ok(None)
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        validate_key(key)?;
+        let table = self.read("get")?;
+        let value = table
+            .get(key)
+            .map_err(|e| DatabaseError::StorageError {
+                operation: "get",
+                message: e.to_string(),
+            })?
+            .map(|v| v.value().to_string());
+        Ok(value)
     }
-    fn set(&mut self, _key: &str, _value: &str) -> Result<()> {
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        validate_key(key)?;
+        self.write("set", |table| table.insert(key, value).map(|_| ()))?;
         Ok(())
     }
-    fn delete(&mut self, _key: &str) -> Result<()> {
+
+    fn delete(&self, key: &str) -> Result<()> {
+        validate_key(key)?;
+        self.write("delete", |table| table.remove(key).map(|_| ()))?;
         Ok(())
     }
+
+    fn list(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        let table = self.read("list")?;
+        let error = |e: redb::StorageError| DatabaseError::StorageError {
+            operation: "list",
+            message: e.to_string(),
+        };
+
+        let mut entries = Vec::new();
+        for entry in table.range(prefix..).map_err(error)? {
+            let (key, value) = entry.map_err(error)?;
+            if !key.value().starts_with(prefix) {
+                break;
+            }
+            entries.push((key.value().to_string(), value.value().to_string()));
+        }
+        Ok(entries)
+    }
+}
+
+/// A view of a store in which every key lives under `<namespace>/`
+///
+/// Keys passed in and returned by the view are relative to the namespace.
+pub struct Namespace<'a, S: KeyValueStore + ?Sized> {
+    store: &'a S,
+    prefix: String,
+}
+
+impl<'a, S: KeyValueStore + ?Sized> Namespace<'a, S> {
+    /// Scopes `store` to `namespace`, which may itself contain `/`.
+    pub fn new(store: &'a S, namespace: &str) -> Self {
+        Self {
+            store,
+            prefix: format!("{}{}", namespace.trim_matches(KEY_SEPARATOR), KEY_SEPARATOR),
+        }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+}
+
+impl<S: KeyValueStore + ?Sized> KeyValueStore for Namespace<'_, S> {
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        self.store.get(&self.key(key))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.store.set(&self.key(key), value)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        self.store.delete(&self.key(key))
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .store
+            .list(&self.key(prefix))?
+            .into_iter()
+            .map(|(key, value)| (key[self.prefix.len()..].to_string(), value))
+            .collect())
+    }
+}
+
+/// Rejects keys that would be ambiguous once namespaced.
+fn validate_key(key: &str) -> Result<(), DatabaseError> {
+    let invalid = |reason| {
+        Err(DatabaseError::InvalidKey {
+            key: key.to_string(),
+            reason,
+        })
+    };
+
+    if key.is_empty() {
+        return invalid("key is empty");
+    }
+    if key.split(KEY_SEPARATOR).any(str::is_empty) {
+        return invalid("key has an empty segment");
+    }
+    if key.chars().any(char::is_control) {
+        return invalid("key contains control characters");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_survive_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/sysperf.db");
+
+        {
+            let store = EmbeddedStore::open(&path).unwrap();
+            store.set("runs/a", "1").unwrap();
+            store.set("runs/b", "2").unwrap();
+            store.set("runs/b", "3").unwrap();
+            store.delete("runs/a").unwrap();
+            store.delete("runs/missing").unwrap();
+        }
+
+        let store = EmbeddedStore::open(&path).unwrap();
+        assert_eq!(store.get("runs/a").unwrap(), None);
+        assert_eq!(store.get("runs/b").unwrap().as_deref(), Some("3"));
+        assert_eq!(store.path(), path);
+    }
+
+    #[test]
+    fn test_namespaces_and_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EmbeddedStore::open(dir.path().join("db")).unwrap();
+
+        let metrics = Namespace::new(&store, "metrics");
+        let runs = Namespace::new(&store, "runs/");
+        metrics.set("mpstat/2", "b").unwrap();
+        metrics.set("mpstat/1", "a").unwrap();
+        metrics.set("iostat/1", "c").unwrap();
+        runs.set("1", "run").unwrap();
+
+        assert_eq!(
+            metrics.list("mpstat/").unwrap(),
+            vec![
+                ("mpstat/1".to_string(), "a".to_string()),
+                ("mpstat/2".to_string(), "b".to_string())
+            ]
+        );
+        assert_eq!(metrics.list("").unwrap().len(), 3);
+        assert_eq!(store.get("runs/1").unwrap().as_deref(), Some("run"));
+        assert_eq!(runs.get("mpstat/1").unwrap(), None);
+        assert_eq!(store.list("").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EmbeddedStore::open(dir.path().join("db")).unwrap();
+
+        for key in ["", "/runs", "runs/", "runs//1", "runs/\n"] {
+            assert!(store.set(key, "x").is_err(), "accepted {:?}", key);
+        }
+        assert!(Namespace::new(&store, "runs").set("", "x").is_err());
+    }
+
+    #[test]
+    fn test_open_rejects_garbage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+        std::fs::write(&path, "not a database").unwrap();
+
+        let err = EmbeddedStore::open(&path).err().unwrap();
+        assert!(matches!(err, DatabaseError::OpenError { .. }));
+    }
+
+    #[test]
+    fn test_open_twice_is_locked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db");
+
+        let _store = EmbeddedStore::open(&path).unwrap();
+        let err = EmbeddedStore::open(&path).err().unwrap();
+        assert!(matches!(err, DatabaseError::Locked { .. }));
+    }
 }
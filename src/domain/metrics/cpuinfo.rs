@@ -295,6 +295,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::len_zero)]
     fn test_parse_cpuinfo() {
         let collector = CpuInfoCollector::new();
         let sample_content = r#"
@@ -1,8 +1,9 @@
-//! I/O statistics collection using iostat
+//! I/O statistics collection from `/proc/diskstats`
 //!
-//! This module provides functionality to collect and parse I/O statistics
-//! using the iostat command-line tool. It supports both system-wide and per-device
-//! statistics collection with configurable sampling intervals.
+//! This module samples the kernel's block device counters in `/proc/diskstats` and turns the
+//! difference between two snapshots into the same figures `iostat -x` reports: transfers per
+//! second, read/write throughput, average request sizes, queue length, await and utilisation.
+//! No sysstat installation is required.
 //!
 //! # Example
 //!
@@ -14,9 +15,10 @@
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     let config = IostatConfig {
 //!         interval: Duration::from_secs(1),
-//!         count: Some(10),
+//!         count: Some(2),
 //!         per_device: true,
 //!         include_extended: true,
+//!         devices: Vec::new(),
 //!     };
 //!
 //!     let collector = IostatCollector::new(config);
@@ -32,18 +34,22 @@
 //! ```
 
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+/// Size of a sector as counted in `/proc/diskstats`, independent of the device's block size
+const SECTOR_SIZE: f64 = 512.0;
+
 /// Errors that can occur during iostat operations
 #[derive(Debug, Error)]
 pub enum IostatError {
-    /// Error executing the iostat command
-    #[error("Failed to execute iostat command: {0}")]
+    /// Error reading /proc/diskstats
+    #[error("Failed to read disk statistics: {0}")]
     ExecutionError(String),
 
-    /// Error parsing iostat command output
-    #[error("Failed to parse iostat output: {0}")]
+    /// Error parsing /proc/diskstats content
+    #[error("Failed to parse disk statistics: {0}")]
     ParseError(String),
 
     /// Invalid interval specification
@@ -57,7 +63,7 @@ pub struct DeviceStats {
     /// Device name ("all" for system-wide or device name)
     pub device: String,
 
-    /// Transfers per second
+    /// Transfers (reads, writes and discards) per second
     pub tps: f64,
 
     /// Kilobytes read per second
@@ -66,20 +72,23 @@ pub struct DeviceStats {
     /// Kilobytes written per second
     pub kb_wrtn_per_sec: f64,
 
-    /// Average read request size in sectors
+    /// Average read request size in kilobytes
     pub rareq_sz: f64,
 
-    /// Average write request size in sectors
+    /// Average write request size in kilobytes
     pub wareq_sz: f64,
 
     /// Average queue length
     pub aqu_sz: f64,
 
-    /// Average service time in milliseconds
+    /// Average time in milliseconds for requests to be served, including time in queue
     pub await_ms: f64,
 
-    /// Percentage of CPU time during which I/O requests were issued
+    /// Percentage of elapsed time during which the device had I/O in flight
     pub util: f64,
+
+    /// Timestamp at the end of the sampling interval
+    pub timestamp: i64,
 }
 
 /// Configuration options for iostat collection
@@ -88,14 +97,18 @@ pub struct IostatConfig {
     /// Time interval between samples
     pub interval: Duration,
 
-    /// Number of samples to collect (None for continuous collection)
+    /// Number of samples to collect (None collects a single sample)
     pub count: Option<u32>,
 
     /// Whether to collect statistics for each device separately
     pub per_device: bool,
 
-    /// Whether to include extended statistics
+    /// Whether to include extended statistics (request sizes, queue length, await, %util)
     pub include_extended: bool,
+
+    /// Devices to report (e.g. "nvme0n1"); empty reports every device that has seen I/O
+    #[serde(default)]
+    pub devices: Vec<String>,
 }
 
 impl Default for IostatConfig {
@@ -105,24 +118,238 @@ impl Default for IostatConfig {
             count: Some(1),
             per_device: true,
             include_extended: true,
+            devices: Vec::new(),
         }
     }
 }
 
-/// Collector for I/O statistics using iostat
+/// Raw counters for one device, as found in a `/proc/diskstats` line
+///
+/// Times are in milliseconds and sizes in 512-byte sectors.  Kernels before 4.18 do not report
+/// discard counters, which are then left at zero.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiskCounters {
+    /// Device name
+    pub device: String,
+    /// Whether the device is a partition of another device
+    pub partition: bool,
+    /// Reads completed
+    pub reads: u64,
+    /// Reads merged
+    pub reads_merged: u64,
+    /// Sectors read
+    pub sectors_read: u64,
+    /// Time spent reading
+    pub read_ticks: u64,
+    /// Writes completed
+    pub writes: u64,
+    /// Writes merged
+    pub writes_merged: u64,
+    /// Sectors written
+    pub sectors_written: u64,
+    /// Time spent writing
+    pub write_ticks: u64,
+    /// I/Os currently in progress
+    pub in_flight: u64,
+    /// Time during which I/O was in progress
+    pub io_ticks: u64,
+    /// Weighted time spent doing I/O (sum of per-request times)
+    pub time_in_queue: u64,
+    /// Discards completed
+    pub discards: u64,
+    /// Sectors discarded
+    pub sectors_discarded: u64,
+    /// Time spent discarding
+    pub discard_ticks: u64,
+}
+
+impl DiskCounters {
+    /// Parses one line of `/proc/diskstats`.
+    ///
+    /// `partition` is not part of the line and is left false.
+    pub fn parse_line(line: &str) -> Result<Self, IostatError> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 14 {
+            return Err(IostatError::ParseError(format!(
+                "expected at least 14 fields, got {}: {}",
+                fields.len(),
+                line.trim()
+            )));
+        }
+
+        let value = |index: usize| -> Result<u64, IostatError> {
+            match fields.get(index) {
+                Some(field) => field.parse().map_err(|e| {
+                    IostatError::ParseError(format!(
+                        "invalid counter {} for {}: {}",
+                        field, fields[2], e
+                    ))
+                }),
+                None => Ok(0),
+            }
+        };
+
+        Ok(Self {
+            device: fields[2].to_string(),
+            partition: false,
+            reads: value(3)?,
+            reads_merged: value(4)?,
+            sectors_read: value(5)?,
+            read_ticks: value(6)?,
+            writes: value(7)?,
+            writes_merged: value(8)?,
+            sectors_written: value(9)?,
+            write_ticks: value(10)?,
+            in_flight: value(11)?,
+            io_ticks: value(12)?,
+            time_in_queue: value(13)?,
+            discards: value(14)?,
+            sectors_discarded: value(16)?,
+            discard_ticks: value(17)?,
+        })
+    }
+
+    /// Whether the device has completed any I/O since boot.
+    pub fn has_activity(&self) -> bool {
+        self.reads + self.writes + self.discards > 0
+    }
+
+    /// Counter increase from `prev` to `self`; a counter that went backwards counts as zero.
+    fn delta(&self, prev: &DiskCounters) -> DiskCounters {
+        DiskCounters {
+            device: self.device.clone(),
+            partition: self.partition,
+            reads: self.reads.saturating_sub(prev.reads),
+            reads_merged: self.reads_merged.saturating_sub(prev.reads_merged),
+            sectors_read: self.sectors_read.saturating_sub(prev.sectors_read),
+            read_ticks: self.read_ticks.saturating_sub(prev.read_ticks),
+            writes: self.writes.saturating_sub(prev.writes),
+            writes_merged: self.writes_merged.saturating_sub(prev.writes_merged),
+            sectors_written: self.sectors_written.saturating_sub(prev.sectors_written),
+            write_ticks: self.write_ticks.saturating_sub(prev.write_ticks),
+            in_flight: self.in_flight,
+            io_ticks: self.io_ticks.saturating_sub(prev.io_ticks),
+            time_in_queue: self.time_in_queue.saturating_sub(prev.time_in_queue),
+            discards: self.discards.saturating_sub(prev.discards),
+            sectors_discarded: self
+                .sectors_discarded
+                .saturating_sub(prev.sectors_discarded),
+            discard_ticks: self.discard_ticks.saturating_sub(prev.discard_ticks),
+        }
+    }
+
+    fn add(&mut self, other: &DiskCounters) {
+        self.reads += other.reads;
+        self.reads_merged += other.reads_merged;
+        self.sectors_read += other.sectors_read;
+        self.read_ticks += other.read_ticks;
+        self.writes += other.writes;
+        self.writes_merged += other.writes_merged;
+        self.sectors_written += other.sectors_written;
+        self.write_ticks += other.write_ticks;
+        self.in_flight += other.in_flight;
+        self.io_ticks += other.io_ticks;
+        self.time_in_queue += other.time_in_queue;
+        self.discards += other.discards;
+        self.sectors_discarded += other.sectors_discarded;
+        self.discard_ticks += other.discard_ticks;
+    }
+}
+
+/// Parses the full content of `/proc/diskstats`.
+///
+/// `is_partition` is asked for every device name so callers can mark partitions, which are
+/// left out of the system-wide totals to avoid counting their I/O twice.
+pub fn parse_diskstats(
+    content: &str,
+    is_partition: impl Fn(&str) -> bool,
+) -> Result<Vec<DiskCounters>, IostatError> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut counters = DiskCounters::parse_line(line)?;
+            counters.partition = is_partition(&counters.device);
+            Ok(counters)
+        })
+        .collect()
+}
+
+impl DeviceStats {
+    /// Computes iostat-style figures from two snapshots of the same device.
+    ///
+    /// With `extended` false only tps and throughput are filled in.
+    pub fn from_delta(
+        prev: &DiskCounters,
+        curr: &DiskCounters,
+        elapsed: Duration,
+        extended: bool,
+    ) -> Self {
+        Self::from_counters(&curr.delta(prev), elapsed, extended)
+    }
+
+    fn from_counters(delta: &DiskCounters, elapsed: Duration, extended: bool) -> Self {
+        let secs = elapsed.as_secs_f64();
+        let ms = secs * 1000.0;
+        let per_sec = |v: u64| if secs > 0.0 { v as f64 / secs } else { 0.0 };
+        let ratio = |num: f64, den: u64| if den > 0 { num / den as f64 } else { 0.0 };
+
+        let kb_read = delta.sectors_read as f64 * SECTOR_SIZE / 1024.0;
+        let kb_written = delta.sectors_written as f64 * SECTOR_SIZE / 1024.0;
+        let ios = delta.reads + delta.writes + delta.discards;
+
+        let mut stats = Self {
+            device: delta.device.clone(),
+            tps: per_sec(ios),
+            kb_read_per_sec: if secs > 0.0 { kb_read / secs } else { 0.0 },
+            kb_wrtn_per_sec: if secs > 0.0 { kb_written / secs } else { 0.0 },
+            rareq_sz: 0.0,
+            wareq_sz: 0.0,
+            aqu_sz: 0.0,
+            await_ms: 0.0,
+            util: 0.0,
+            timestamp: chrono::Utc::now().timestamp(),
+        };
+
+        if extended {
+            let ticks = delta.read_ticks + delta.write_ticks + delta.discard_ticks;
+            stats.rareq_sz = ratio(kb_read, delta.reads);
+            stats.wareq_sz = ratio(kb_written, delta.writes);
+            stats.await_ms = ratio(ticks as f64, ios);
+            if ms > 0.0 {
+                stats.aqu_sz = delta.time_in_queue as f64 / ms;
+                stats.util = (delta.io_ticks as f64 / ms * 100.0).min(100.0);
+            }
+        }
+
+        stats
+    }
+}
+
+/// Collector for I/O statistics sampled from /proc/diskstats
 #[derive(Debug)]
 pub struct IostatCollector {
     config: IostatConfig,
+    proc_diskstats_path: String,
+    sys_block_path: String,
 }
 
 impl IostatCollector {
     /// Creates a new IostatCollector with the specified configuration
     pub fn new(config: IostatConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            proc_diskstats_path: "/proc/diskstats".to_string(),
+            sys_block_path: "/sys/class/block".to_string(),
+        }
     }
 
     /// Collects I/O statistics according to the configured options
     ///
+    /// Takes a snapshot, then one more per sample after each interval, and reports the rates
+    /// between consecutive snapshots.  Samples are returned in order, each with one entry per
+    /// device (or a single "all" entry when `per_device` is false).
+    ///
     /// # Returns
     ///
     /// Returns a Result containing a vector of DeviceStats on success, or
@@ -131,56 +358,139 @@ impl IostatCollector {
     /// # Errors
     ///
     /// Will return an error if:
-    /// - The iostat command fails to execute
-    /// - The command output cannot be parsed
-    /// - No valid statistics are found in the output
+    /// - The interval or sample count is zero
+    /// - /proc/diskstats cannot be read
+    /// - /proc/diskstats cannot be parsed
     pub async fn collect(&self) -> Result<Vec<DeviceStats>, IostatError> {
-        let output = self.execute_iostat().await?;
-        self.parse_iostat_output(&output)
-    }
+        if self.config.interval.is_zero() {
+            return Err(IostatError::InvalidInterval(
+                "interval must be greater than zero".into(),
+            ));
+        }
+        let samples = self.config.count.unwrap_or(1);
+        if samples == 0 {
+            return Err(IostatError::InvalidInterval(
+                "count must be at least 1".into(),
+            ));
+        }
 
-    /// Executes the iostat command with the configured options
-    async fn execute_iostat(&self) -> Result<String, IostatError> {
-        let mut cmd = tokio::process::Command::new("iostat");
+        let mut prev = self.read_diskstats().await?;
+        let mut prev_at = Instant::now();
+        let mut stats = Vec::new();
 
-        // Add -x flag for extended statistics if configured
-        if self.config.include_extended {
-            cmd.arg("-x");
-        }
+        for _ in 0..samples {
+            tokio::time::sleep(self.config.interval).await;
+            let curr = self.read_diskstats().await?;
+            let now = Instant::now();
 
-        cmd.arg(self.config.interval.as_secs().to_string());
-        if let Some(count) = self.config.count {
-            cmd.arg(count.to_string());
+            stats.extend(self.sample(&prev, &curr, now - prev_at));
+            prev = curr;
+            prev_at = now;
         }
 
-        let output = cmd
-            .output()
+        Ok(stats)
+    }
+
+    /// Reads and parses a snapshot of /proc/diskstats
+    async fn read_diskstats(&self) -> Result<Vec<DiskCounters>, IostatError> {
+        let content = tokio::fs::read_to_string(&self.proc_diskstats_path)
             .await
             .map_err(|e| IostatError::ExecutionError(e.to_string()))?;
 
-        String::from_utf8(output.stdout).map_err(|e| IostatError::ExecutionError(e.to_string()))
+        parse_diskstats(&content, |device| {
+            Path::new(&self.sys_block_path)
+                .join(device)
+                .join("partition")
+                .exists()
+        })
     }
 
-    /// Parses the raw iostat output into DeviceStats structs
-    fn parse_iostat_output(&self, output: &str) -> Result<Vec<DeviceStats>, IostatError> {
-        // Implementation would parse the iostat output format
-        // This is a placeholder that should be implemented based on the
-        // actual iostat output format
-        todo!("Implement iostat output parsing")
+    /// Turns two snapshots into per-device or system-wide statistics
+    fn sample(
+        &self,
+        prev: &[DiskCounters],
+        curr: &[DiskCounters],
+        elapsed: Duration,
+    ) -> Vec<DeviceStats> {
+        let extended = self.config.include_extended;
+        let pairs = curr
+            .iter()
+            .filter_map(|c| prev.iter().find(|p| p.device == c.device).map(|p| (p, c)));
+
+        if self.config.per_device {
+            return pairs
+                .filter(|(_, c)| self.wanted(c))
+                .map(|(p, c)| DeviceStats::from_delta(p, c, elapsed, extended))
+                .collect();
+        }
+
+        // Partitions and stacked devices (device-mapper, md) repeat I/O already counted on the
+        // disks below them, so the total only covers whole physical disks.
+        let mut total = DiskCounters {
+            device: "all".to_string(),
+            ..Default::default()
+        };
+        let mut disks = 0;
+        for (p, c) in pairs {
+            if self.wanted(c) && !c.partition && !is_stacked(&c.device) {
+                total.add(&c.delta(p));
+                disks += 1;
+            }
+        }
+
+        let mut all = DeviceStats::from_counters(&total, elapsed, extended);
+        if disks > 0 {
+            all.util /= disks as f64;
+        }
+        vec![all]
     }
+
+    /// Whether a device should be reported
+    fn wanted(&self, counters: &DiskCounters) -> bool {
+        if self.config.devices.is_empty() {
+            counters.has_activity()
+        } else {
+            self.config.devices.contains(&counters.device)
+        }
+    }
+}
+
+/// Device-mapper and software RAID devices, whose I/O is also counted on their members
+fn is_stacked(device: &str) -> bool {
+    device.starts_with("dm-") || device.starts_with("md")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const BEFORE: &str = "\
+ 259       0 nvme0n1 1000 10 80000 500 2000 20 160000 3000 0 2500 3500 0 0 0 0 0 0
+ 259       1 nvme0n1p1 1000 10 80000 500 2000 20 160000 3000 0 2500 3500 0 0 0 0 0 0
+   7       0 loop0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+ 253       0 dm-0 900 0 70000 450 1900 0 150000 2900 0 2400 3350
+";
+
+    // One second later: 200 reads of 8 KiB and 100 writes of 64 KiB on nvme0n1
+    const AFTER: &str = "\
+ 259       0 nvme0n1 1200 10 83200 700 2100 20 172800 3400 2 3000 4100 0 0 0 0 0 0
+ 259       1 nvme0n1p1 1200 10 83200 700 2100 20 172800 3400 2 3000 4100 0 0 0 0 0 0
+   7       0 loop0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+ 253       0 dm-0 1100 0 73200 650 2000 0 164800 3300 2 2900 3950
+";
+
+    fn snapshot(content: &str) -> Vec<DiskCounters> {
+        parse_diskstats(content, |device| device.ends_with("p1")).unwrap()
+    }
+
     #[tokio::test]
     async fn test_iostat_collector() {
         let config = IostatConfig {
-            interval: Duration::from_secs(1),
+            interval: Duration::from_millis(100),
             count: Some(1),
             per_device: true,
             include_extended: true,
+            devices: Vec::new(),
         };
 
         let collector = IostatCollector::new(config);
@@ -190,4 +500,76 @@ mod tests {
         let stats = result.unwrap();
         assert!(!stats.is_empty());
     }
+
+    #[test]
+    fn test_parse_diskstats() {
+        let counters = snapshot(BEFORE);
+        assert_eq!(counters.len(), 4);
+        assert_eq!(counters[0].device, "nvme0n1");
+        assert_eq!(counters[0].sectors_written, 160000);
+        assert!(counters[1].partition);
+        assert!(!counters[2].has_activity());
+
+        // Pre-4.18 kernels stop after the weighted queue time
+        assert_eq!(counters[3].time_in_queue, 3350);
+        assert_eq!(counters[3].discards, 0);
+
+        assert!(DiskCounters::parse_line("8 0 sda 1 2 3").is_err());
+        assert!(DiskCounters::parse_line("8 0 sda 1 2 3 x 5 6 7 8 9 10 11").is_err());
+    }
+
+    #[test]
+    fn test_device_stats_from_delta() {
+        let before = snapshot(BEFORE);
+        let after = snapshot(AFTER);
+        let stats = DeviceStats::from_delta(&before[0], &after[0], Duration::from_secs(1), true);
+
+        assert_eq!(stats.device, "nvme0n1");
+        assert_eq!(stats.tps, 300.0);
+        assert_eq!(stats.kb_read_per_sec, 1600.0);
+        assert_eq!(stats.kb_wrtn_per_sec, 6400.0);
+        assert_eq!(stats.rareq_sz, 8.0);
+        assert_eq!(stats.wareq_sz, 64.0);
+        assert_eq!(stats.await_ms, 2.0);
+        assert_eq!(stats.aqu_sz, 0.6);
+        assert_eq!(stats.util, 50.0);
+
+        let basic = DeviceStats::from_delta(&before[0], &after[0], Duration::from_secs(1), false);
+        assert_eq!(basic.tps, 300.0);
+        assert_eq!(basic.util, 0.0);
+    }
+
+    #[test]
+    fn test_sample_filters_and_totals() {
+        let before = snapshot(BEFORE);
+        let after = snapshot(AFTER);
+        let elapsed = Duration::from_secs(1);
+
+        let collector = IostatCollector::new(IostatConfig::default());
+        let devices: Vec<String> = collector
+            .sample(&before, &after, elapsed)
+            .into_iter()
+            .map(|s| s.device)
+            .collect();
+        assert_eq!(devices, vec!["nvme0n1", "nvme0n1p1", "dm-0"]);
+
+        let collector = IostatCollector::new(IostatConfig {
+            devices: vec!["dm-0".into()],
+            ..Default::default()
+        });
+        let stats = collector.sample(&before, &after, elapsed);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].device, "dm-0");
+
+        // Only the whole disk counts towards the total
+        let collector = IostatCollector::new(IostatConfig {
+            per_device: false,
+            ..Default::default()
+        });
+        let stats = collector.sample(&before, &after, elapsed);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].device, "all");
+        assert_eq!(stats[0].tps, 300.0);
+        assert_eq!(stats[0].util, 50.0);
+    }
 }
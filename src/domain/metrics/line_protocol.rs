@@ -0,0 +1,377 @@
+//! InfluxDB line protocol encoding
+//!
+//! Converts collected metric samples and benchmark runs into [`Point`]s and renders them as
+//! line protocol (`measurement,tag=value field=value timestamp`).  Measurements are prefixed
+//! with `sysperf_`; collector output is flattened so that string values become tags (e.g. the
+//! device of an iostat row) and numbers become fields.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sysperf_svr::domain::metrics::line_protocol::Point;
+//!
+//! let point = Point::new("sysperf_loadavg", 1_700_000_000_000_000_000)
+//!     .tag("host", "node 1")
+//!     .field("load_1", 0.5)
+//!     .field("total_tasks", 82i64);
+//! assert_eq!(
+//!     point.to_line().unwrap(),
+//!     "sysperf_loadavg,host=node\\ 1 load_1=0.5,total_tasks=82i 1700000000000000000"
+//! );
+//! ```
+
+use super::series::MetricKind;
+use crate::domain::storage::fio::IoStats;
+use crate::domain::storage::history::RunRecord;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Prefix of every measurement written by this tool
+pub const MEASUREMENT_PREFIX: &str = "sysperf_";
+
+/// Measurement holding one summary point per benchmark run
+pub const BENCHMARK_MEASUREMENT: &str = "sysperf_benchmark";
+
+const NANOS_PER_MILLI: i64 = 1_000_000;
+const NANOS_PER_SECOND: i64 = 1_000_000_000;
+
+/// Value of a line protocol field
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Float(f64),
+    Integer(i64),
+    Bool(bool),
+    String(String),
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::Float(value)
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::Integer(value)
+    }
+}
+
+impl From<u32> for FieldValue {
+    fn from(value: u32) -> Self {
+        FieldValue::Integer(value.into())
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::Bool(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::String(value.to_string())
+    }
+}
+
+/// A single line protocol point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub measurement: String,
+    pub tags: BTreeMap<String, String>,
+    pub fields: BTreeMap<String, FieldValue>,
+    /// Unix timestamp in nanoseconds
+    pub timestamp_ns: i64,
+}
+
+impl Point {
+    /// Creates a point without tags or fields.
+    pub fn new(measurement: &str, timestamp_ns: i64) -> Self {
+        Self {
+            measurement: measurement.to_string(),
+            tags: BTreeMap::new(),
+            fields: BTreeMap::new(),
+            timestamp_ns,
+        }
+    }
+
+    /// Adds a tag; empty values are skipped since line protocol cannot express them.
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        if !key.is_empty() && !value.is_empty() {
+            self.tags.insert(key.to_string(), value.to_string());
+        }
+        self
+    }
+
+    /// Adds a field; non-finite floats are skipped since line protocol cannot express them.
+    pub fn field(mut self, key: &str, value: impl Into<FieldValue>) -> Self {
+        let value = value.into();
+        if !key.is_empty() && !matches!(value, FieldValue::Float(f) if !f.is_finite()) {
+            self.fields.insert(key.to_string(), value);
+        }
+        self
+    }
+
+    /// Renders the point as one line of line protocol, or `None` if it has no fields.
+    pub fn to_line(&self) -> Option<String> {
+        if self.fields.is_empty() {
+            return None;
+        }
+
+        let mut line = escape(&self.measurement, &[',', ' ']);
+        for (key, value) in &self.tags {
+            line.push(',');
+            line.push_str(&escape(key, &[',', '=', ' ']));
+            line.push('=');
+            line.push_str(&escape(value, &[',', '=', ' ']));
+        }
+
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    FieldValue::Float(v) => format!("{:?}", v),
+                    FieldValue::Integer(v) => format!("{}i", v),
+                    FieldValue::Bool(v) => v.to_string(),
+                    FieldValue::String(v) => format!("\"{}\"", escape(v, &['"'])),
+                };
+                format!("{}={}", escape(key, &[',', '=', ' ']), value)
+            })
+            .collect();
+        line.push(' ');
+        line.push_str(&fields.join(","));
+        line.push(' ');
+        line.push_str(&self.timestamp_ns.to_string());
+        Some(line)
+    }
+}
+
+/// Converts one collector sample into points tagged with `tags`.
+///
+/// Array data yields one point per element (e.g. one per CPU or device).  Top-level strings
+/// become tags, numbers and booleans become fields, and nested objects are flattened into
+/// `outer_inner` field names; everything else is dropped.
+pub fn sample_points(
+    kind: MetricKind,
+    timestamp_ms: i64,
+    data: &Value,
+    tags: &BTreeMap<String, String>,
+) -> Vec<Point> {
+    let measurement = format!("{}{}", MEASUREMENT_PREFIX, kind);
+    let timestamp_ns = timestamp_ms * NANOS_PER_MILLI;
+
+    let rows: Vec<&Map<String, Value>> = match data {
+        Value::Array(rows) => rows.iter().filter_map(Value::as_object).collect(),
+        Value::Object(row) => vec![row],
+        _ => Vec::new(),
+    };
+
+    rows.into_iter()
+        .map(|row| {
+            let mut point = Point::new(&measurement, timestamp_ns);
+            for (key, value) in tags {
+                point = point.tag(key, value);
+            }
+            // The collectors' own timestamps duplicate the point's
+            for (key, value) in row.iter().filter(|(key, _)| *key != "timestamp") {
+                match value {
+                    Value::String(s) => point = point.tag(key, s),
+                    _ => add_fields(&mut point, key, value),
+                }
+            }
+            point
+        })
+        .filter(|point| !point.fields.is_empty())
+        .collect()
+}
+
+/// Converts a run into a summary point plus the system metrics recorded during it, all tagged
+/// with the run's host, target and profile.
+pub fn run_points(run: &RunRecord) -> Vec<Point> {
+    let tags = run_tags(run);
+
+    let mut summary = Point::new(BENCHMARK_MEASUREMENT, run.timestamp * NANOS_PER_SECOND);
+    for (key, value) in &tags {
+        summary = summary.tag(key, value);
+    }
+    summary = summary.tag("name", &run.name);
+    if let Some(job) = &run.job {
+        summary = summary
+            .tag("rw", job.rw.as_str())
+            .tag("bs", &job.bs)
+            .tag("ioengine", job.ioengine.as_str())
+            .field("iodepth", job.iodepth)
+            .field("numjobs", job.numjobs);
+    }
+    summary = summary
+        .field("run_id", run.id.as_str())
+        .field("usr_cpu", run.result.usr_cpu)
+        .field("sys_cpu", run.result.sys_cpu);
+    summary = io_fields(summary, "read", &run.result.read);
+    summary = io_fields(summary, "write", &run.result.write);
+    summary = io_fields(summary, "total", &run.result.combined());
+
+    let mut points = vec![summary];
+    if let Some(series) = &run.system_metrics {
+        for sample in &series.samples {
+            points.extend(sample_points(
+                sample.metric,
+                sample.timestamp_ms,
+                &sample.data,
+                &tags,
+            ));
+        }
+    }
+    points
+}
+
+/// Host, target and profile tags of a run.
+pub fn run_tags(run: &RunRecord) -> BTreeMap<String, String> {
+    let mut tags = BTreeMap::new();
+    tags.insert("host".to_string(), run.hostname.clone());
+    if let Some(target) = &run.target {
+        tags.insert("target".to_string(), target.clone());
+    }
+    if let Some(profile) = &run.profile {
+        tags.insert("profile".to_string(), profile.clone());
+    }
+    tags
+}
+
+fn io_fields(point: Point, prefix: &str, stats: &IoStats) -> Point {
+    point
+        .field(&format!("{}_iops", prefix), stats.iops)
+        .field(&format!("{}_bw_mb", prefix), stats.bandwidth_mb)
+        .field(&format!("{}_lat_usec", prefix), stats.lat_usec)
+        .field(&format!("{}_lat_usec_p99", prefix), stats.lat_usec_p99)
+        .field(&format!("{}_lat_usec_max", prefix), stats.lat_usec_max)
+}
+
+fn add_fields(point: &mut Point, key: &str, value: &Value) {
+    match value {
+        Value::Bool(b) => {
+            point.fields.insert(key.to_string(), FieldValue::Bool(*b));
+        }
+        Value::Number(n) => {
+            let value = match (n.as_i64(), n.as_f64()) {
+                (Some(i), _) => FieldValue::Integer(i),
+                (None, Some(f)) if f.is_finite() => FieldValue::Float(f),
+                _ => return,
+            };
+            point.fields.insert(key.to_string(), value);
+        }
+        Value::Object(inner) => {
+            for (inner_key, inner_value) in inner {
+                add_fields(point, &format!("{}_{}", key, inner_key), inner_value);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Backslash-escapes `special` characters and backslashes.
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        // Line protocol has no escape for newlines; the space that replaces them may need one
+        let c = if c == '\n' || c == '\r' { ' ' } else { c };
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::storage::fio::{FioJobConfig, FioResult, StorageTarget};
+    use serde_json::json;
+
+    #[test]
+    fn test_line_escaping_and_types() {
+        let line = Point::new("sysperf test", 1000)
+            .tag("host", "a,b=c")
+            .tag("empty", "")
+            .field("msg", "say \"hi\"")
+            .field("ok", true)
+            .field("n", 3i64)
+            .field("x", 1.0)
+            .field("nan", f64::NAN)
+            .to_line()
+            .unwrap();
+        assert_eq!(
+            line,
+            r#"sysperf\ test,host=a\,b\=c msg="say \"hi\"",n=3i,ok=true,x=1.0 1000"#
+        );
+        assert_eq!(Point::new("m", 0).tag("a", "b").to_line(), None);
+
+        let line = Point::new("m", 0)
+            .tag("host", "node\n1")
+            .field("msg", "a\nb")
+            .to_line()
+            .unwrap();
+        assert_eq!(line, r#"m,host=node\ 1 msg="a b" 0"#);
+    }
+
+    #[test]
+    fn test_sample_points_flatten_rows() {
+        let tags = BTreeMap::from([("host".to_string(), "node1".to_string())]);
+        let data = json!([
+            {"device": "nvme0n1", "util": 87.5, "tps": 1200, "per_cpu": [1, 2], "timestamp": 1},
+            {"device": "sda", "util": 0.0, "tps": 0}
+        ]);
+        let points = sample_points(MetricKind::Iostat, 1_700_000_000_000, &data, &tags);
+
+        assert_eq!(points.len(), 2);
+        assert_eq!(
+            points[0].to_line().unwrap(),
+            "sysperf_iostat,device=nvme0n1,host=node1 tps=1200i,util=87.5 1700000000000000000"
+        );
+
+        let data = json!([{"cpu": {"user": 1.5, "idle": 98.5}, "procs": {"running": 2}}]);
+        let points = sample_points(MetricKind::Vmstat, 0, &data, &tags);
+        let fields: Vec<&str> = points[0].fields.keys().map(String::as_str).collect();
+        assert_eq!(fields, vec!["cpu_idle", "cpu_user", "procs_running"]);
+    }
+
+    #[test]
+    fn test_run_points_tag_host_target_profile() {
+        let mut run = RunRecord::new(
+            "randread_4k_nvme",
+            &StorageTarget::new_file("/dev/nvme0n1", None),
+            FioResult::default(),
+        );
+        run.hostname = "node1".into();
+        run.target = Some("nvme_direct".into());
+        run.profile = Some("randread_4k".into());
+        run.timestamp = 1_700_000_000;
+        run.job = Some(FioJobConfig::default());
+        run.result.read.iops = 250000.0;
+
+        let mut series = crate::domain::metrics::series::MetricSeries::new(1000, 0);
+        series.push(
+            MetricKind::Loadavg,
+            1_700_000_000_500,
+            json!([{"load_1": 2.5}]),
+        );
+        run.system_metrics = Some(series);
+
+        let points = run_points(&run);
+        assert_eq!(points.len(), 2);
+
+        let summary = &points[0];
+        assert_eq!(summary.measurement, BENCHMARK_MEASUREMENT);
+        assert_eq!(summary.tags["target"], "nvme_direct");
+        assert_eq!(summary.tags["profile"], "randread_4k");
+        assert_eq!(summary.fields["read_iops"], FieldValue::Float(250000.0));
+        assert_eq!(summary.timestamp_ns, 1_700_000_000 * NANOS_PER_SECOND);
+
+        assert_eq!(
+            points[1].to_line().unwrap(),
+            "sysperf_loadavg,host=node1,profile=randread_4k,target=nvme_direct load_1=2.5 1700000000500000000"
+        );
+    }
+}
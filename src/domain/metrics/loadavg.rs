@@ -15,9 +15,11 @@
 //!     let collector = LoadavgCollector::new(config);
 //!     let stats = collector.collect().await?;
 //!
-//!     println!("1 min load average: {}", stats.load_1);
-//!     println!("5 min load average: {}", stats.load_5);
-//!     println!("15 min load average: {}", stats.load_15);
+//!     for stat in stats {
+//!         println!("1 min load average: {}", stat.load_1);
+//!         println!("5 min load average: {}", stat.load_5);
+//!         println!("15 min load average: {}", stat.load_15);
+//!     }
 //!     Ok(())
 //! }
 //! ```
@@ -1,6 +1,9 @@
 pub mod cpuinfo;
 pub mod iostat;
+pub mod line_protocol;
 pub mod loadavg;
 pub mod meminfo;
 pub mod mpstat;
+pub mod prometheus;
+pub mod series;
 pub mod vmstat;
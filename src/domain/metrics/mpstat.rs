@@ -1,22 +1,25 @@
-//! CPU performance statistics collection using mpstat
+//! CPU performance statistics collection
 //!
-//! This module provides functionality to collect and parse CPU performance statistics
-//! using the mpstat command-line tool. It supports both system-wide and per-CPU statistics
-//! collection with configurable sampling intervals.
+//! This module provides functionality to collect CPU performance statistics in the same form
+//! as `mpstat -P ALL`. Two backends are available: the default samples `/proc/stat` twice per
+//! interval and computes the percentages itself, so it works on images without sysstat; the
+//! other runs the mpstat command-line tool and parses its output. Both support system-wide
+//! and per-CPU statistics with configurable sampling intervals.
 //!
 //! # Example
 //!
 //! ```rust
-//! use sysperf_svr::domain::metrics::mpstat::{MpstatCollector, MpstatConfig};
+//! use sysperf_svr::domain::metrics::mpstat::{MpstatBackend, MpstatCollector, MpstatConfig};
 //! use std::time::Duration;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     let config = MpstatConfig {
 //!         interval: Duration::from_secs(1),
-//!         count: Some(10),
+//!         count: Some(2),
 //!         per_cpu: true,
 //!         include_idle: true,
+//!         backend: MpstatBackend::ProcStat,
 //!     };
 //!
 //!     let collector = MpstatCollector::new(config);
@@ -42,6 +45,10 @@ pub enum MpstatError {
     #[error("Failed to execute mpstat command: {0}")]
     ExecutionError(String),
 
+    /// Error reading /proc/stat
+    #[error("Failed to read CPU statistics: {0}")]
+    ReadError(String),
+
     /// Error parsing mpstat command output
     #[error("Failed to parse mpstat output: {0}")]
     ParseError(String),
@@ -91,13 +98,26 @@ pub struct CpuStats {
     pub timestamp: i64,
 }
 
+/// Source of the CPU statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MpstatBackend {
+    /// Sample /proc/stat directly
+    #[default]
+    #[serde(rename = "proc", alias = "procstat")]
+    ProcStat,
+    /// Run the sysstat `mpstat` tool
+    Mpstat,
+}
+
 /// Configuration options for mpstat collection
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MpstatConfig {
     /// Time interval between samples
     pub interval: Duration,
 
-    /// Number of samples to collect (None for continuous collection)
+    /// Number of samples to collect (None for continuous collection with the mpstat backend,
+    /// a single sample with the /proc/stat backend)
     pub count: Option<u32>,
 
     /// Whether to collect statistics for each CPU separately
@@ -105,6 +125,10 @@ pub struct MpstatConfig {
 
     /// Whether to include idle time statistics
     pub include_idle: bool,
+
+    /// Where the statistics come from
+    #[serde(default)]
+    pub backend: MpstatBackend,
 }
 
 impl Default for MpstatConfig {
@@ -114,14 +138,140 @@ impl Default for MpstatConfig {
             count: Some(1),
             per_cpu: true,
             include_idle: true,
+            backend: MpstatBackend::default(),
         }
     }
 }
 
-/// Collector for CPU performance statistics using mpstat
+/// Cumulative time counters for one CPU, as found in a `/proc/stat` line
+///
+/// Values are in clock ticks (USER_HZ).  The kernel already counts guest time in `user` and
+/// guest_nice time in `nice`.  Fields missing on older kernels are left at zero.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CpuTimes {
+    /// CPU identifier ("all" for the aggregate `cpu` line, otherwise the CPU number)
+    pub cpu_id: String,
+    /// Time in user mode
+    pub user: u64,
+    /// Time in user mode with low priority
+    pub nice: u64,
+    /// Time in system mode
+    pub system: u64,
+    /// Idle time
+    pub idle: u64,
+    /// Time waiting for I/O to complete
+    pub iowait: u64,
+    /// Time servicing hardware interrupts
+    pub irq: u64,
+    /// Time servicing software interrupts
+    pub softirq: u64,
+    /// Time stolen by the hypervisor
+    pub steal: u64,
+    /// Time running a guest
+    pub guest: u64,
+    /// Time running a niced guest
+    pub guest_nice: u64,
+}
+
+impl CpuTimes {
+    /// Parses one `cpu` or `cpuN` line of `/proc/stat`.
+    pub fn parse_line(line: &str) -> Result<Self, MpstatError> {
+        let mut parts = line.split_whitespace();
+        let label = parts.next().unwrap_or_default();
+        let cpu_id = match label.strip_prefix("cpu") {
+            Some("") => "all".to_string(),
+            Some(n) if n.chars().all(|c| c.is_ascii_digit()) => n.to_string(),
+            _ => return Err(MpstatError::ParseError(format!("Not a CPU line: {}", line))),
+        };
+
+        let values = parts
+            .map(|v| v.parse::<u64>())
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(|e| MpstatError::ParseError(format!("Invalid {} counter: {}", label, e)))?;
+        if values.len() < 4 {
+            return Err(MpstatError::ParseError(format!(
+                "Invalid line format: {}",
+                line
+            )));
+        }
+        let value = |i: usize| values.get(i).copied().unwrap_or(0);
+
+        Ok(Self {
+            cpu_id,
+            user: value(0),
+            nice: value(1),
+            system: value(2),
+            idle: value(3),
+            iowait: value(4),
+            irq: value(5),
+            softirq: value(6),
+            steal: value(7),
+            guest: value(8),
+            guest_nice: value(9),
+        })
+    }
+
+    /// Total accounted time; guest time is already part of user and nice.
+    pub fn total(&self) -> u64 {
+        self.user
+            + self.nice
+            + self.system
+            + self.idle
+            + self.iowait
+            + self.irq
+            + self.softirq
+            + self.steal
+    }
+}
+
+/// Parses the `cpu` lines of `/proc/stat`, ignoring every other line.
+pub fn parse_proc_stat(content: &str) -> Result<Vec<CpuTimes>, MpstatError> {
+    let cpus: Vec<CpuTimes> = content
+        .lines()
+        .filter(|line| line.starts_with("cpu"))
+        .map(CpuTimes::parse_line)
+        .collect::<Result<_, _>>()?;
+
+    if cpus.is_empty() {
+        return Err(MpstatError::ParseError("No CPU lines found".into()));
+    }
+    Ok(cpus)
+}
+
+impl CpuStats {
+    /// Computes mpstat percentages from two snapshots of the same CPU.
+    ///
+    /// As in mpstat, `usr` and `nice` exclude guest time, which is reported separately.
+    /// Counters that went backwards (e.g. after a CPU was hot-plugged) count as zero.
+    pub fn from_delta(prev: &CpuTimes, curr: &CpuTimes, timestamp: i64) -> Self {
+        let delta = |c: u64, p: u64| c.saturating_sub(p) as f64;
+        let guest = delta(curr.guest, prev.guest);
+        let gnice = delta(curr.guest_nice, prev.guest_nice);
+        let total = delta(curr.total(), prev.total());
+        let pct = |v: f64| if total > 0.0 { v / total * 100.0 } else { 0.0 };
+
+        Self {
+            cpu_id: curr.cpu_id.clone(),
+            usr: pct((delta(curr.user, prev.user) - guest).max(0.0)),
+            nice: pct((delta(curr.nice, prev.nice) - gnice).max(0.0)),
+            sys: pct(delta(curr.system, prev.system)),
+            iowait: pct(delta(curr.iowait, prev.iowait)),
+            irq: pct(delta(curr.irq, prev.irq)),
+            soft: pct(delta(curr.softirq, prev.softirq)),
+            steal: pct(delta(curr.steal, prev.steal)),
+            guest: pct(guest),
+            gnice: pct(gnice),
+            idle: pct(delta(curr.idle, prev.idle)),
+            timestamp,
+        }
+    }
+}
+
+/// Collector for CPU performance statistics
 #[derive(Debug)]
 pub struct MpstatCollector {
     config: MpstatConfig,
+    proc_stat_path: String,
 }
 
 impl MpstatCollector {
@@ -140,7 +290,10 @@ impl MpstatCollector {
     /// let collector = MpstatCollector::new(config);
     /// ```
     pub fn new(config: MpstatConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            proc_stat_path: "/proc/stat".to_string(),
+        }
     }
 
     /// Collects CPU statistics according to the configured options
@@ -153,12 +306,65 @@ impl MpstatCollector {
     /// # Errors
     ///
     /// Will return an error if:
-    /// - The mpstat command fails to execute
+    /// - The interval or sample count is zero
+    /// - /proc/stat cannot be read or parsed (/proc/stat backend)
+    /// - The mpstat command fails to execute (mpstat backend)
     /// - The command output cannot be parsed
     /// - No valid statistics are found in the output
     pub async fn collect(&self) -> Result<Vec<CpuStats>, MpstatError> {
-        let output = self.execute_mpstat().await?;
-        self.parse_mpstat_output(&output)
+        match self.config.backend {
+            MpstatBackend::ProcStat => self.collect_proc_stat().await,
+            MpstatBackend::Mpstat => {
+                let output = self.execute_mpstat().await?;
+                self.parse_mpstat_output(&output)
+            }
+        }
+    }
+
+    /// Samples /proc/stat once per interval and reports the percentages between samples
+    async fn collect_proc_stat(&self) -> Result<Vec<CpuStats>, MpstatError> {
+        if self.config.interval.is_zero() {
+            return Err(MpstatError::InvalidInterval(
+                "interval must be greater than zero".into(),
+            ));
+        }
+        let samples = self.config.count.unwrap_or(1);
+        if samples == 0 {
+            return Err(MpstatError::InvalidInterval(
+                "count must be at least 1".into(),
+            ));
+        }
+
+        let mut prev = self.read_proc_stat().await?;
+        let mut stats = Vec::new();
+        for _ in 0..samples {
+            tokio::time::sleep(self.config.interval).await;
+            let curr = self.read_proc_stat().await?;
+            stats.extend(self.sample(&prev, &curr, chrono::Utc::now().timestamp()));
+            prev = curr;
+        }
+
+        Ok(stats)
+    }
+
+    /// Reads and parses a snapshot of /proc/stat
+    async fn read_proc_stat(&self) -> Result<Vec<CpuTimes>, MpstatError> {
+        let content = tokio::fs::read_to_string(&self.proc_stat_path)
+            .await
+            .map_err(|e| MpstatError::ReadError(e.to_string()))?;
+        parse_proc_stat(&content)
+    }
+
+    /// Turns two /proc/stat snapshots into "all" and, if configured, per-CPU statistics
+    fn sample(&self, prev: &[CpuTimes], curr: &[CpuTimes], timestamp: i64) -> Vec<CpuStats> {
+        curr.iter()
+            .filter(|c| self.config.per_cpu || c.cpu_id == "all")
+            .filter_map(|c| {
+                prev.iter()
+                    .find(|p| p.cpu_id == c.cpu_id)
+                    .map(|p| CpuStats::from_delta(p, c, timestamp))
+            })
+            .collect()
     }
 
     /// Executes the mpstat command with the configured options
@@ -230,20 +436,23 @@ impl MpstatCollector {
     /// Returns a Result containing a CpuStats instance on success,
     /// or an MpstatError on failure
     fn parse_stat_line(&self, line: &str, timestamp: i64) -> Result<CpuStats, MpstatError> {
-        let parts: Vec<&str> = line.split_whitespace().collect();
+        let mut parts: Vec<&str> = line.split_whitespace().collect();
 
-        if parts.len() < 12 {
+        // 12-hour locales print "12:00:00 PM", 24-hour ones just "12:00:00"
+        if parts.get(1).is_some_and(|p| *p == "AM" || *p == "PM") {
+            parts.remove(1);
+        }
+        // Line up the columns as "<time> <AM/PM> <cpu> ..." so the indices below hold
+        parts.insert(1, "");
+
+        if parts.len() < 13 {
             return Err(MpstatError::ParseError(format!(
                 "Invalid line format: {}",
                 line
             )));
         }
 
-        let cpu_id = if parts[2] == "all" {
-            "all".to_string()
-        } else {
-            parts[2].to_string()
-        };
+        let cpu_id = parts[2].to_string();
 
         Ok(CpuStats {
             cpu_id,
@@ -293,6 +502,7 @@ mod tests {
             count: Some(1),
             per_cpu: true,
             include_idle: true,
+            backend: MpstatBackend::ProcStat,
         };
 
         let collector = MpstatCollector::new(config);
@@ -316,5 +526,98 @@ mod tests {
         assert_eq!(stat.cpu_id, "all");
         assert_eq!(stat.usr, 2.34);
         assert_eq!(stat.idle, 95.52);
+
+        let line = "12:00:00     0    2.34    0.00    1.23    0.45    0.12    0.34    0.00    0.00    0.00   95.52";
+        let stat = collector.parse_stat_line(line, timestamp).unwrap();
+        assert_eq!(stat.cpu_id, "0");
+        assert_eq!(stat.idle, 95.52);
+        assert!(collector
+            .parse_stat_line("12:00:00 all 1.0", timestamp)
+            .is_err());
+    }
+
+    const BEFORE: &str = "\
+cpu  1000 100 500 8000 200 10 20 30 50 5
+cpu0 500 50 250 4000 100 5 10 15 50 5
+cpu1 500 50 250 4000 100 5 10 15 0 0
+intr 12345 0 0
+ctxt 67890
+";
+
+    // 1000 ticks later per CPU; cpu0 spent 100 of its 300 user ticks running a guest
+    const AFTER: &str = "\
+cpu  1400 120 600 9350 300 10 50 30 150 5
+cpu0 800 50 300 4630 100 5 30 15 150 5
+cpu1 600 70 300 4720 200 5 20 15 0 0
+intr 23456 0 0
+ctxt 78901
+";
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let cpus = parse_proc_stat(BEFORE).unwrap();
+        assert_eq!(cpus.len(), 3);
+        assert_eq!(cpus[0].cpu_id, "all");
+        assert_eq!(cpus[1].cpu_id, "0");
+        assert_eq!(cpus[0].guest_nice, 5);
+
+        // Kernels older than 2.6.11 only report four counters
+        let old = CpuTimes::parse_line("cpu3 1 2 3 4").unwrap();
+        assert_eq!(old.cpu_id, "3");
+        assert_eq!(old.steal, 0);
+
+        assert!(CpuTimes::parse_line("cpu0 1 2").is_err());
+        assert!(parse_proc_stat("intr 1 2 3\n").is_err());
+    }
+
+    #[test]
+    fn test_cpu_stats_from_delta() {
+        let before = parse_proc_stat(BEFORE).unwrap();
+        let after = parse_proc_stat(AFTER).unwrap();
+
+        let cpu0 = CpuStats::from_delta(&before[1], &after[1], 0);
+        assert_eq!(cpu0.usr, 20.0);
+        assert_eq!(cpu0.guest, 10.0);
+        assert_eq!(cpu0.sys, 5.0);
+        assert_eq!(cpu0.soft, 2.0);
+        assert_eq!(cpu0.idle, 63.0);
+
+        let cpu1 = CpuStats::from_delta(&before[2], &after[2], 0);
+        assert_eq!(cpu1.nice, 2.0);
+        assert_eq!(cpu1.iowait, 10.0);
+        let sum = cpu1.usr
+            + cpu1.nice
+            + cpu1.sys
+            + cpu1.iowait
+            + cpu1.irq
+            + cpu1.soft
+            + cpu1.steal
+            + cpu1.guest
+            + cpu1.gnice
+            + cpu1.idle;
+        assert!((sum - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sample_per_cpu_and_all() {
+        let before = parse_proc_stat(BEFORE).unwrap();
+        let after = parse_proc_stat(AFTER).unwrap();
+
+        let collector = MpstatCollector::new(MpstatConfig::default());
+        let ids: Vec<String> = collector
+            .sample(&before, &after, 0)
+            .into_iter()
+            .map(|s| s.cpu_id)
+            .collect();
+        assert_eq!(ids, vec!["all", "0", "1"]);
+
+        let collector = MpstatCollector::new(MpstatConfig {
+            per_cpu: false,
+            ..Default::default()
+        });
+        let stats = collector.sample(&before, &after, 0);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].usr, 15.0);
+        assert_eq!(stats[0].idle, 67.5);
     }
 }
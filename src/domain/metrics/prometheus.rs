@@ -0,0 +1,248 @@
+//! Prometheus text exposition format
+//!
+//! [`Exposition`] collects gauges and counters and renders them in the text format Prometheus
+//! scrapes.  Line protocol [`Point`]s from the collectors and benchmark runs map onto it
+//! directly: every numeric field becomes the metric `<measurement>_<field>`, labelled with the
+//! point's tags.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sysperf_svr::domain::metrics::prometheus::{Exposition, MetricType};
+//! use std::collections::BTreeMap;
+//!
+//! let mut exposition = Exposition::new();
+//! let labels = BTreeMap::from([("host".to_string(), "node1".to_string())]);
+//! exposition.add("sysperf_up", "Whether the exporter is running", MetricType::Gauge, &labels, 1.0);
+//! let text = exposition.render();
+//! assert!(text.starts_with("# HELP sysperf_up Whether the exporter is running\n"));
+//! assert!(text.contains("# TYPE sysperf_up gauge\n"));
+//! assert!(text.ends_with("sysperf_up{host=\"node1\"} 1\n"));
+//! ```
+
+use super::line_protocol::{FieldValue, Point, MEASUREMENT_PREFIX};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Kind of a metric family
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    Gauge,
+    Counter,
+}
+
+impl fmt::Display for MetricType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricType::Gauge => write!(f, "gauge"),
+            MetricType::Counter => write!(f, "counter"),
+        }
+    }
+}
+
+/// All samples of one metric name
+#[derive(Debug, Clone)]
+struct MetricFamily {
+    help: String,
+    metric_type: MetricType,
+    /// Values keyed by their label set
+    samples: BTreeMap<BTreeMap<String, String>, f64>,
+}
+
+/// A set of metric families ready to be rendered
+#[derive(Debug, Clone, Default)]
+pub struct Exposition {
+    families: BTreeMap<String, MetricFamily>,
+}
+
+impl Exposition {
+    /// Creates an empty exposition.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one sample.
+    ///
+    /// Names and label names are sanitized to Prometheus' character set.  The first sample of a
+    /// name fixes its help and type, and the first value of a label set wins, so that callers
+    /// can add newest values first.
+    pub fn add(
+        &mut self,
+        name: &str,
+        help: &str,
+        metric_type: MetricType,
+        labels: &BTreeMap<String, String>,
+        value: f64,
+    ) {
+        let family = self
+            .families
+            .entry(sanitize(name))
+            .or_insert_with(|| MetricFamily {
+                help: help.to_string(),
+                metric_type,
+                samples: BTreeMap::new(),
+            });
+        let labels = labels
+            .iter()
+            .map(|(key, value)| (sanitize(key), value.clone()))
+            .collect();
+        family.samples.entry(labels).or_insert(value);
+    }
+
+    /// Adds every numeric field of `points` as a gauge.
+    ///
+    /// `help` receives the measurement without its `sysperf_` prefix and the field name.
+    pub fn add_points(&mut self, points: &[Point], help: impl Fn(&str, &str) -> String) {
+        for point in points {
+            let source = point
+                .measurement
+                .strip_prefix(MEASUREMENT_PREFIX)
+                .unwrap_or(&point.measurement);
+            for (field, value) in &point.fields {
+                let value = match value {
+                    FieldValue::Float(v) => *v,
+                    FieldValue::Integer(v) => *v as f64,
+                    FieldValue::Bool(v) => f64::from(u8::from(*v)),
+                    FieldValue::String(_) => continue,
+                };
+                self.add(
+                    &format!("{}_{}", point.measurement, field),
+                    &help(source, field),
+                    MetricType::Gauge,
+                    &point.tags,
+                    value,
+                );
+            }
+        }
+    }
+
+    /// Number of metric families.
+    pub fn len(&self) -> usize {
+        self.families.len()
+    }
+
+    /// Whether nothing has been added.
+    pub fn is_empty(&self) -> bool {
+        self.families.is_empty()
+    }
+
+    /// Renders the exposition in the Prometheus text format (version 0.0.4).
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, family) in &self.families {
+            out.push_str(&format!("# HELP {} {}\n", name, escape_help(&family.help)));
+            out.push_str(&format!("# TYPE {} {}\n", name, family.metric_type));
+            for (labels, value) in &family.samples {
+                out.push_str(name);
+                if !labels.is_empty() {
+                    let labels: Vec<String> = labels
+                        .iter()
+                        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+                        .collect();
+                    out.push_str(&format!("{{{}}}", labels.join(",")));
+                }
+                out.push_str(&format!(" {}\n", format_value(*value)));
+            }
+        }
+        out
+    }
+}
+
+/// Replaces characters outside `[a-zA-Z0-9_]` with `_` and avoids a leading digit.
+fn sanitize(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label(value: &str) -> String {
+    escape_help(value).replace('"', "\\\"")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_escapes_and_orders() {
+        let mut exposition = Exposition::new();
+        let labels = |device: &str| BTreeMap::from([("device".to_string(), device.to_string())]);
+
+        exposition.add(
+            "sysperf_iostat_util",
+            "util",
+            MetricType::Gauge,
+            &labels("sdb"),
+            3.0,
+        );
+        exposition.add(
+            "sysperf_iostat_util",
+            "ignored",
+            MetricType::Gauge,
+            &labels("sda"),
+            1.5,
+        );
+        exposition.add(
+            "sysperf_iostat_util",
+            "util",
+            MetricType::Gauge,
+            &labels("sda"),
+            9.0,
+        );
+        exposition.add(
+            "sysperf errors.total",
+            "a \"b\"\nc",
+            MetricType::Counter,
+            &labels("x\"y"),
+            f64::INFINITY,
+        );
+
+        assert_eq!(
+            exposition.render(),
+            "# HELP sysperf_errors_total a \"b\"\\nc\n\
+             # TYPE sysperf_errors_total counter\n\
+             sysperf_errors_total{device=\"x\\\"y\"} +Inf\n\
+             # HELP sysperf_iostat_util util\n\
+             # TYPE sysperf_iostat_util gauge\n\
+             sysperf_iostat_util{device=\"sda\"} 1.5\n\
+             sysperf_iostat_util{device=\"sdb\"} 3\n"
+        );
+    }
+
+    #[test]
+    fn test_points_become_gauges() {
+        let point = Point::new("sysperf_mpstat", 0)
+            .tag("cpu_id", "all")
+            .field("usr", 12.5)
+            .field("busy", true)
+            .field("label", "skipped");
+
+        let mut exposition = Exposition::new();
+        exposition.add_points(&[point], |source, field| format!("{} {}", source, field));
+
+        assert_eq!(exposition.len(), 2);
+        let text = exposition.render();
+        assert!(text.contains("# HELP sysperf_mpstat_usr mpstat usr\n"));
+        assert!(text.contains("sysperf_mpstat_usr{cpu_id=\"all\"} 12.5\n"));
+        assert!(text.contains("sysperf_mpstat_busy{cpu_id=\"all\"} 1\n"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Time series of system metric samples
+//!
+//! A [`MetricSeries`] holds the samples taken while something else was running, typically a
+//! benchmark, so that CPU, memory, device and load figures can be inspected over its whole
+//! lifetime rather than only at the end.  Samples keep the collectors' serialized output as
+//! JSON so the series can be stored and reloaded without knowing every collector's type.
+//!
+//! # Example
+//!
+//! ```rust
+//! use serde_json::json;
+//! use sysperf_svr::domain::metrics::series::{MetricKind, MetricSeries};
+//!
+//! let mut series = MetricSeries::new(1000, 0);
+//! series.push(MetricKind::Mpstat, 1000, json!([{"cpu_id": "all", "iowait": 4.0}]));
+//! series.push(MetricKind::Mpstat, 2000, json!([{"cpu_id": "all", "iowait": 12.0}]));
+//!
+//! let iowait: Vec<f64> = series
+//!     .samples_of(MetricKind::Mpstat)
+//!     .filter_map(|s| s.data[0]["iowait"].as_f64())
+//!     .collect();
+//! assert_eq!(iowait, vec![4.0, 12.0]);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// A kind of system metric that can be collected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricKind {
+    /// CPU model and topology (`/proc/cpuinfo`)
+    Cpu,
+    /// Memory usage (`/proc/meminfo`)
+    Mem,
+    /// Processes, memory, swap, I/O and CPU activity
+    Vmstat,
+    /// Per-CPU utilisation
+    Mpstat,
+    /// Per-device I/O statistics
+    Iostat,
+    /// Load averages
+    Loadavg,
+}
+
+impl MetricKind {
+    /// Every metric, in display order.
+    pub const ALL: [MetricKind; 6] = [
+        MetricKind::Cpu,
+        MetricKind::Mem,
+        MetricKind::Vmstat,
+        MetricKind::Mpstat,
+        MetricKind::Iostat,
+        MetricKind::Loadavg,
+    ];
+
+    /// Name used on the command line and in stored keys.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricKind::Cpu => "cpu",
+            MetricKind::Mem => "mem",
+            MetricKind::Vmstat => "vmstat",
+            MetricKind::Mpstat => "mpstat",
+            MetricKind::Iostat => "iostat",
+            MetricKind::Loadavg => "loadavg",
+        }
+    }
+}
+
+impl fmt::Display for MetricKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for MetricKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cpu" | "cpuinfo" => Ok(MetricKind::Cpu),
+            "mem" | "memory" | "meminfo" => Ok(MetricKind::Mem),
+            "vmstat" => Ok(MetricKind::Vmstat),
+            "mpstat" => Ok(MetricKind::Mpstat),
+            "iostat" => Ok(MetricKind::Iostat),
+            "loadavg" | "load" => Ok(MetricKind::Loadavg),
+            other => Err(format!(
+                "unknown metric \"{}\"; expected one of cpu, mem, vmstat, mpstat, iostat, loadavg",
+                other
+            )),
+        }
+    }
+}
+
+/// One collected sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricSample {
+    /// Metric the sample belongs to
+    pub metric: MetricKind,
+    /// Unix timestamp in milliseconds at which the sample was taken
+    pub timestamp_ms: i64,
+    /// Serialized collector output
+    pub data: Value,
+}
+
+/// Samples of one or more metrics taken at a regular interval
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricSeries {
+    /// Requested time between samples in milliseconds
+    pub interval_ms: u64,
+    /// Unix timestamp in milliseconds at which recording started
+    pub started_ms: i64,
+    /// Unix timestamp in milliseconds at which recording stopped
+    pub finished_ms: i64,
+    /// Samples in the order they were taken
+    pub samples: Vec<MetricSample>,
+    /// Distinct collection failure messages
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl MetricSeries {
+    /// Creates an empty series starting at `started_ms`.
+    pub fn new(interval_ms: u64, started_ms: i64) -> Self {
+        Self {
+            interval_ms,
+            started_ms,
+            finished_ms: started_ms,
+            samples: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Appends a sample.
+    pub fn push(&mut self, metric: MetricKind, timestamp_ms: i64, data: Value) {
+        self.samples.push(MetricSample {
+            metric,
+            timestamp_ms,
+            data,
+        });
+        self.finished_ms = self.finished_ms.max(timestamp_ms);
+    }
+
+    /// Samples of a single metric, oldest first.
+    pub fn samples_of(&self, metric: MetricKind) -> impl Iterator<Item = &MetricSample> {
+        self.samples.iter().filter(move |s| s.metric == metric)
+    }
+
+    /// Number of samples across all metrics.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Whether no sample was recorded.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Recording time in seconds.
+    pub fn duration_secs(&self) -> f64 {
+        (self.finished_ms - self.started_ms).max(0) as f64 / 1000.0
+    }
+
+    /// Mean and maximum of a value extracted from every sample of `metric`.
+    ///
+    /// # Returns
+    ///
+    /// * `Option<(f64, f64)>` - `(mean, max)`, or `None` when no sample yields a value
+    pub fn mean_max<F>(&self, metric: MetricKind, value: F) -> Option<(f64, f64)>
+    where
+        F: Fn(&Value) -> Option<f64>,
+    {
+        let values: Vec<f64> = self
+            .samples_of(metric)
+            .filter_map(|s| value(&s.data))
+            .collect();
+        if values.is_empty() {
+            return None;
+        }
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let max = values.iter().copied().fold(f64::MIN, f64::max);
+        Some((mean, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_metric_kind_names() {
+        for kind in MetricKind::ALL {
+            assert_eq!(kind.as_str().parse::<MetricKind>(), Ok(kind));
+            assert_eq!(
+                serde_json::to_value(kind).unwrap(),
+                Value::String(kind.to_string())
+            );
+        }
+        assert_eq!("memory".parse::<MetricKind>(), Ok(MetricKind::Mem));
+        assert!("disk".parse::<MetricKind>().is_err());
+    }
+
+    #[test]
+    fn test_series_statistics() {
+        let mut series = MetricSeries::new(1000, 10_000);
+        for (i, util) in [20.0, 80.0, 50.0].into_iter().enumerate() {
+            let ts = 11_000 + i as i64 * 1000;
+            series.push(
+                MetricKind::Iostat,
+                ts,
+                json!([{"device": "sda", "util": util}]),
+            );
+            series.push(MetricKind::Loadavg, ts, json!([{"load_1": 1.0}]));
+        }
+
+        assert_eq!(series.len(), 6);
+        assert_eq!(series.samples_of(MetricKind::Iostat).count(), 3);
+        assert_eq!(series.duration_secs(), 3.0);
+
+        let (mean, max) = series
+            .mean_max(MetricKind::Iostat, |d| d[0]["util"].as_f64())
+            .unwrap();
+        assert_eq!(mean, 50.0);
+        assert_eq!(max, 80.0);
+        assert!(series.mean_max(MetricKind::Mpstat, |_| Some(1.0)).is_none());
+    }
+}
@@ -1,28 +1,31 @@
-//! Virtual Memory Statistics collection using vmstat
+//! Virtual Memory Statistics collection
 //!
-//! This module provides functionality to collect and parse system statistics
-//! using the vmstat command-line tool. It provides information about system memory,
-//! processes, paging, block I/O, and CPU activity.
+//! This module provides functionality to collect system statistics in the same form as
+//! `vmstat`: processes, memory, swap activity, block I/O, interrupts, context switches and
+//! CPU activity. The default backend computes them from `/proc/stat`, `/proc/vmstat` and
+//! `/proc/meminfo` deltas without needing procps installed; the other runs the vmstat
+//! command-line tool and parses its output.
 //!
 //! # Example
 //!
 //! ```rust
-//! use sysperf_svr::domain::metrics::vmstat::{VmstatCollector, VmstatConfig};
+//! use sysperf_svr::domain::metrics::vmstat::{VmstatBackend, VmstatCollector, VmstatConfig};
 //! use std::time::Duration;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     let config = VmstatConfig {
 //!         interval: Duration::from_secs(1),
-//!         count: Some(10),
+//!         count: Some(2),
+//!         backend: VmstatBackend::Proc,
 //!     };
 //!
 //!     let collector = VmstatCollector::new(config);
 //!     let stats = collector.collect().await?;
 //!
 //!     for stat in stats {
-//!         println!("Memory: {}MB free, Processes: {} running, {} blocked",
-//!             stat.memory.free_mb,
+//!         println!("Memory: {} KiB free, Processes: {} running, {} blocked",
+//!             stat.memory.free_kb,
 //!             stat.procs.running,
 //!             stat.procs.blocked);
 //!     }
@@ -31,8 +34,10 @@
 //! }
 //! ```
 
+use super::mpstat::CpuTimes;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 /// Errors that can occur during vmstat operations
@@ -42,6 +47,10 @@ pub enum VmstatError {
     #[error("Failed to execute vmstat command: {0}")]
     ExecutionError(String),
 
+    /// Error reading /proc/stat, /proc/vmstat or /proc/meminfo
+    #[error("Failed to read system statistics: {0}")]
+    ReadError(String),
+
     /// Error parsing vmstat command output
     #[error("Failed to parse vmstat output: {0}")]
     ParseError(String),
@@ -60,36 +69,36 @@ pub struct ProcessStats {
     pub blocked: u32,
 }
 
-/// Memory statistics in megabytes
+/// Memory statistics in kibibytes, as vmstat reports them
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryStats {
-    /// Free memory in MB
-    pub free_mb: u64,
-    /// Buffer memory in MB
-    pub buffer_mb: u64,
-    /// Cache memory in MB
-    pub cache_mb: u64,
-    /// Swap memory used in MB
-    pub swap_used_mb: u64,
-    /// Free swap memory in MB
-    pub swap_free_mb: u64,
+    /// Free memory in KiB
+    pub free_kb: u64,
+    /// Buffer memory in KiB
+    pub buffer_kb: u64,
+    /// Cache memory (page cache plus reclaimable slab) in KiB
+    pub cache_kb: u64,
+    /// Swap memory used in KiB
+    pub swap_used_kb: u64,
+    /// Free swap memory in KiB; None when the backend does not report it (vmstat)
+    pub swap_free_kb: Option<u64>,
 }
 
 /// Swap activity statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SwapStats {
-    /// Pages swapped in per second
-    pub pages_in_per_sec: f64,
-    /// Pages swapped out per second
-    pub pages_out_per_sec: f64,
+    /// Memory swapped in from disk, in KiB per second
+    pub kb_in_per_sec: f64,
+    /// Memory swapped out to disk, in KiB per second
+    pub kb_out_per_sec: f64,
 }
 
 /// I/O statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IoStats {
-    /// Blocks received from device per second
+    /// 1 KiB blocks received from block devices per second
     pub blocks_in_per_sec: f64,
-    /// Blocks sent to device per second
+    /// 1 KiB blocks sent to block devices per second
     pub blocks_out_per_sec: f64,
 }
 
@@ -105,14 +114,17 @@ pub struct SystemStats {
 /// CPU utilization percentages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CpuStats {
-    /// Percentage of CPU time spent in user space
+    /// Percentage of CPU time spent in user space, including nice and guest time
     pub user: f64,
-    /// Percentage of CPU time spent in system space
+    /// Percentage of CPU time spent in system space, including interrupt handling
     pub system: f64,
     /// Percentage of CPU time spent idle
     pub idle: f64,
     /// Percentage of CPU time spent waiting for I/O
     pub iowait: f64,
+    /// Percentage of CPU time stolen by the hypervisor
+    #[serde(default)]
+    pub steal: f64,
 }
 
 /// Complete system statistics from vmstat
@@ -134,13 +146,28 @@ pub struct VmstatData {
     pub timestamp: i64,
 }
 
+/// Source of the statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VmstatBackend {
+    /// Sample /proc/stat, /proc/vmstat and /proc/meminfo directly
+    #[default]
+    Proc,
+    /// Run the procps `vmstat` tool
+    Vmstat,
+}
+
 /// Configuration options for vmstat collection
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VmstatConfig {
     /// Time interval between samples
     pub interval: Duration,
-    /// Number of samples to collect (None for continuous collection)
+    /// Number of samples to collect (None for continuous collection with the vmstat backend,
+    /// a single sample with the /proc backend)
     pub count: Option<u32>,
+    /// Where the statistics come from
+    #[serde(default)]
+    pub backend: VmstatBackend,
 }
 
 impl Default for VmstatConfig {
@@ -148,20 +175,202 @@ impl Default for VmstatConfig {
         Self {
             interval: Duration::from_secs(1),
             count: Some(1),
+            backend: VmstatBackend::default(),
+        }
+    }
+}
+
+/// Counters from one snapshot of `/proc/stat`, `/proc/vmstat` and `/proc/meminfo`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VmstatSnapshot {
+    /// Aggregate CPU times from the `cpu` line of /proc/stat
+    pub cpu: CpuTimes,
+    /// Interrupts serviced since boot
+    pub interrupts: u64,
+    /// Context switches since boot
+    pub context_switches: u64,
+    /// Processes currently runnable
+    pub procs_running: u32,
+    /// Processes currently blocked on I/O
+    pub procs_blocked: u32,
+    /// KiB paged in from block devices since boot
+    pub pgpgin: u64,
+    /// KiB paged out to block devices since boot
+    pub pgpgout: u64,
+    /// Pages swapped in since boot
+    pub pswpin: u64,
+    /// Pages swapped out since boot
+    pub pswpout: u64,
+    /// Free memory in KiB
+    pub mem_free_kb: u64,
+    /// Buffer memory in KiB
+    pub buffers_kb: u64,
+    /// Page cache in KiB
+    pub cached_kb: u64,
+    /// Reclaimable slab in KiB
+    pub sreclaimable_kb: u64,
+    /// Total swap in KiB
+    pub swap_total_kb: u64,
+    /// Free swap in KiB
+    pub swap_free_kb: u64,
+}
+
+impl VmstatSnapshot {
+    /// Builds a snapshot from the contents of `/proc/stat`, `/proc/vmstat` and `/proc/meminfo`.
+    pub fn parse(stat: &str, vmstat: &str, meminfo: &str) -> Result<Self, VmstatError> {
+        let mut snapshot = Self::default();
+
+        let mut found_cpu = false;
+        for line in stat.lines() {
+            let mut parts = line.split_whitespace();
+            let value = |v: Option<&str>| -> Result<u64, VmstatError> {
+                v.unwrap_or_default().parse().map_err(|e| {
+                    VmstatError::ParseError(format!("Invalid /proc/stat line {}: {}", line, e))
+                })
+            };
+
+            match parts.next() {
+                Some("cpu") => {
+                    snapshot.cpu = CpuTimes::parse_line(line)
+                        .map_err(|e| VmstatError::ParseError(e.to_string()))?;
+                    found_cpu = true;
+                }
+                Some("intr") => snapshot.interrupts = value(parts.next())?,
+                Some("ctxt") => snapshot.context_switches = value(parts.next())?,
+                Some("procs_running") => snapshot.procs_running = value(parts.next())? as u32,
+                Some("procs_blocked") => snapshot.procs_blocked = value(parts.next())? as u32,
+                _ => {}
+            }
+        }
+        if !found_cpu {
+            return Err(VmstatError::ParseError(
+                "No cpu line found in /proc/stat".into(),
+            ));
+        }
+
+        let counters = parse_key_values(vmstat, "/proc/vmstat")?;
+        let counter = |key: &str| counters.get(key).copied().unwrap_or(0);
+        snapshot.pgpgin = counter("pgpgin");
+        snapshot.pgpgout = counter("pgpgout");
+        snapshot.pswpin = counter("pswpin");
+        snapshot.pswpout = counter("pswpout");
+
+        let memory = parse_key_values(meminfo, "/proc/meminfo")?;
+        let required = |key: &str| {
+            memory
+                .get(key)
+                .copied()
+                .ok_or_else(|| VmstatError::ParseError(format!("Missing {} in /proc/meminfo", key)))
+        };
+        snapshot.mem_free_kb = required("MemFree")?;
+        snapshot.buffers_kb = required("Buffers")?;
+        snapshot.cached_kb = required("Cached")?;
+        snapshot.sreclaimable_kb = memory.get("SReclaimable").copied().unwrap_or(0);
+        snapshot.swap_total_kb = required("SwapTotal")?;
+        snapshot.swap_free_kb = required("SwapFree")?;
+
+        Ok(snapshot)
+    }
+}
+
+/// Parses `key value` (/proc/vmstat) or `Key: value kB` (/proc/meminfo) lines.
+fn parse_key_values(content: &str, source: &str) -> Result<HashMap<String, u64>, VmstatError> {
+    let mut values = HashMap::new();
+    for line in content.lines() {
+        let mut parts = line.split_whitespace();
+        let (Some(key), Some(value)) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        let value = value.parse().map_err(|e| {
+            VmstatError::ParseError(format!("Invalid {} line {}: {}", source, line, e))
+        })?;
+        values.insert(key.trim_end_matches(':').to_string(), value);
+    }
+    Ok(values)
+}
+
+impl VmstatData {
+    /// Computes vmstat figures from two snapshots taken `elapsed` apart.
+    ///
+    /// Rates come from the counter deltas and memory figures from `curr`.  `page_kb` is the
+    /// page size in KiB, used to convert swapped pages to KiB.
+    pub fn from_delta(
+        prev: &VmstatSnapshot,
+        curr: &VmstatSnapshot,
+        elapsed: Duration,
+        page_kb: u64,
+        timestamp: i64,
+    ) -> Self {
+        let secs = elapsed.as_secs_f64();
+        let rate = |c: u64, p: u64| {
+            if secs > 0.0 {
+                c.saturating_sub(p) as f64 / secs
+            } else {
+                0.0
+            }
+        };
+
+        let (c, p) = (&curr.cpu, &prev.cpu);
+        let ticks = |c: u64, p: u64| c.saturating_sub(p) as f64;
+        let total = ticks(c.total(), p.total());
+        let pct = |v: f64| if total > 0.0 { v / total * 100.0 } else { 0.0 };
+
+        Self {
+            procs: ProcessStats {
+                running: curr.procs_running,
+                blocked: curr.procs_blocked,
+            },
+            memory: MemoryStats {
+                free_kb: curr.mem_free_kb,
+                buffer_kb: curr.buffers_kb,
+                cache_kb: curr.cached_kb + curr.sreclaimable_kb,
+                swap_used_kb: curr.swap_total_kb.saturating_sub(curr.swap_free_kb),
+                swap_free_kb: Some(curr.swap_free_kb),
+            },
+            swap: SwapStats {
+                kb_in_per_sec: rate(curr.pswpin, prev.pswpin) * page_kb as f64,
+                kb_out_per_sec: rate(curr.pswpout, prev.pswpout) * page_kb as f64,
+            },
+            io: IoStats {
+                blocks_in_per_sec: rate(curr.pgpgin, prev.pgpgin),
+                blocks_out_per_sec: rate(curr.pgpgout, prev.pgpgout),
+            },
+            system: SystemStats {
+                interrupts_per_sec: rate(curr.interrupts, prev.interrupts),
+                context_switches_per_sec: rate(curr.context_switches, prev.context_switches),
+            },
+            cpu: CpuStats {
+                user: pct(ticks(c.user, p.user) + ticks(c.nice, p.nice)),
+                system: pct(ticks(c.system, p.system)
+                    + ticks(c.irq, p.irq)
+                    + ticks(c.softirq, p.softirq)),
+                idle: pct(ticks(c.idle, p.idle)),
+                iowait: pct(ticks(c.iowait, p.iowait)),
+                steal: pct(ticks(c.steal, p.steal)),
+            },
+            timestamp,
         }
     }
 }
 
-/// Collector for virtual memory and system statistics using vmstat
+/// Collector for virtual memory and system statistics
 #[derive(Debug)]
 pub struct VmstatCollector {
     config: VmstatConfig,
+    proc_stat_path: String,
+    proc_vmstat_path: String,
+    proc_meminfo_path: String,
 }
 
 impl VmstatCollector {
     /// Creates a new VmstatCollector with the specified configuration
     pub fn new(config: VmstatConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            proc_stat_path: "/proc/stat".to_string(),
+            proc_vmstat_path: "/proc/vmstat".to_string(),
+            proc_meminfo_path: "/proc/meminfo".to_string(),
+        }
     }
 
     /// Collects system statistics according to the configured options
@@ -174,19 +383,81 @@ impl VmstatCollector {
     /// # Errors
     ///
     /// Will return an error if:
-    /// - The vmstat command fails to execute
+    /// - The interval or sample count is zero (/proc backend)
+    /// - The /proc files cannot be read or parsed (/proc backend)
+    /// - The vmstat command fails to execute (vmstat backend)
     /// - The command output cannot be parsed
     /// - No valid statistics are found in the output
     pub async fn collect(&self) -> Result<Vec<VmstatData>, VmstatError> {
-        let output = self.execute_vmstat().await?;
-        self.parse_vmstat_output(&output)
+        match self.config.backend {
+            VmstatBackend::Proc => self.collect_proc().await,
+            VmstatBackend::Vmstat => {
+                let output = self.execute_vmstat().await?;
+                self.parse_vmstat_output(&output)
+            }
+        }
+    }
+
+    /// Samples the /proc files once per interval and reports the rates between samples
+    async fn collect_proc(&self) -> Result<Vec<VmstatData>, VmstatError> {
+        if self.config.interval.is_zero() {
+            return Err(VmstatError::InvalidInterval(
+                "interval must be greater than zero".into(),
+            ));
+        }
+        let samples = self.config.count.unwrap_or(1);
+        if samples == 0 {
+            return Err(VmstatError::InvalidInterval(
+                "count must be at least 1".into(),
+            ));
+        }
+
+        let page_kb = page_size_kb();
+        let mut prev = self.read_snapshot().await?;
+        let mut prev_at = Instant::now();
+        let mut stats = Vec::new();
+
+        for _ in 0..samples {
+            tokio::time::sleep(self.config.interval).await;
+            let curr = self.read_snapshot().await?;
+            let now = Instant::now();
+
+            stats.push(VmstatData::from_delta(
+                &prev,
+                &curr,
+                now - prev_at,
+                page_kb,
+                chrono::Utc::now().timestamp(),
+            ));
+            prev = curr;
+            prev_at = now;
+        }
+
+        Ok(stats)
+    }
+
+    /// Reads one snapshot of the /proc files
+    async fn read_snapshot(&self) -> Result<VmstatSnapshot, VmstatError> {
+        let read = |path: &str| {
+            let path = path.to_string();
+            async move {
+                tokio::fs::read_to_string(&path)
+                    .await
+                    .map_err(|e| VmstatError::ReadError(format!("{}: {}", path, e)))
+            }
+        };
+
+        let stat = read(&self.proc_stat_path).await?;
+        let vmstat = read(&self.proc_vmstat_path).await?;
+        let meminfo = read(&self.proc_meminfo_path).await?;
+        VmstatSnapshot::parse(&stat, &vmstat, &meminfo)
     }
 
     /// Executes the vmstat command with the configured options
     async fn execute_vmstat(&self) -> Result<String, VmstatError> {
         let mut cmd = tokio::process::Command::new("vmstat");
 
-        // Add -n flag to disable header in output
+        // Add -n flag to print the header only once
         cmd.arg("-n");
 
         cmd.arg(self.config.interval.as_secs().to_string());
@@ -203,15 +474,17 @@ impl VmstatCollector {
     }
 
     /// Parses the raw vmstat output into VmstatData structs
+    ///
+    /// Header lines are skipped.  Note that vmstat's first sample is the average since boot.
     fn parse_vmstat_output(&self, output: &str) -> Result<Vec<VmstatData>, VmstatError> {
         let mut stats = Vec::new();
         let timestamp = chrono::Utc::now().timestamp();
 
-        for line in output.lines().skip(2) {
-            // Skip headers
+        for line in output.lines() {
             let fields: Vec<&str> = line.split_whitespace().collect();
 
-            if fields.len() < 17 {
+            // Header lines start with "procs" or "r"
+            if fields.len() < 16 || fields[0].parse::<u64>().is_err() {
                 continue;
             }
 
@@ -221,21 +494,22 @@ impl VmstatCollector {
                 })
             };
 
+            // r b swpd free buff cache si so bi bo in cs us sy id wa [st [gu]]
             let stat = VmstatData {
                 procs: ProcessStats {
                     running: parse_num(0)? as u32,
                     blocked: parse_num(1)? as u32,
                 },
                 memory: MemoryStats {
-                    free_mb: parse_num(3)? as u64,
-                    buffer_mb: parse_num(4)? as u64,
-                    cache_mb: parse_num(5)? as u64,
-                    swap_used_mb: parse_num(2)? as u64,
-                    swap_free_mb: parse_num(3)? as u64,
+                    free_kb: parse_num(3)? as u64,
+                    buffer_kb: parse_num(4)? as u64,
+                    cache_kb: parse_num(5)? as u64,
+                    swap_used_kb: parse_num(2)? as u64,
+                    swap_free_kb: None,
                 },
                 swap: SwapStats {
-                    pages_in_per_sec: parse_num(6)?,
-                    pages_out_per_sec: parse_num(7)?,
+                    kb_in_per_sec: parse_num(6)?,
+                    kb_out_per_sec: parse_num(7)?,
                 },
                 io: IoStats {
                     blocks_in_per_sec: parse_num(8)?,
@@ -250,6 +524,11 @@ impl VmstatCollector {
                     system: parse_num(13)?,
                     idle: parse_num(14)?,
                     iowait: parse_num(15)?,
+                    steal: if fields.len() > 16 {
+                        parse_num(16)?
+                    } else {
+                        0.0
+                    },
                 },
                 timestamp,
             };
@@ -265,15 +544,78 @@ impl VmstatCollector {
     }
 }
 
+/// Page size in KiB, used to convert swapped pages
+fn page_size_kb() -> u64 {
+    // SAFETY: sysconf has no preconditions and only reads a system constant
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if size > 0 {
+        size as u64 / 1024
+    } else {
+        4
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const STAT_BEFORE: &str = "\
+cpu  1000 100 500 8000 200 10 20 30 50 5
+cpu0 1000 100 500 8000 200 10 20 30 50 5
+intr 100000 20 30
+ctxt 500000
+btime 1700000000
+processes 4000
+procs_running 3
+procs_blocked 0
+";
+
+    const STAT_AFTER: &str = "\
+cpu  1150 150 580 8660 250 10 30 30 100 5
+cpu0 1150 150 580 8660 250 10 30 30 100 5
+intr 102000 20 30
+ctxt 504000
+btime 1700000000
+processes 4010
+procs_running 2
+procs_blocked 1
+";
+
+    const VMSTAT_BEFORE: &str = "\
+nr_free_pages 195479
+pgpgin 1000
+pgpgout 2000
+pswpin 10
+pswpout 20
+";
+
+    const VMSTAT_AFTER: &str = "\
+nr_free_pages 195000
+pgpgin 1400
+pgpgout 10000
+pswpin 14
+pswpout 60
+";
+
+    const MEMINFO: &str = "\
+MemTotal:        8000000 kB
+MemFree:          781916 kB
+MemAvailable:    5000000 kB
+Buffers:          195988 kB
+Cached:           457784 kB
+SwapCached:            0 kB
+SReclaimable:      42216 kB
+SwapTotal:       2097148 kB
+SwapFree:        2000000 kB
+HugePages_Total:       0
+";
+
     #[tokio::test]
     async fn test_vmstat_collector() {
         let config = VmstatConfig {
             interval: Duration::from_secs(1),
             count: Some(1),
+            backend: VmstatBackend::Proc,
         };
 
         let collector = VmstatCollector::new(config);
@@ -290,9 +632,56 @@ mod tests {
         let sample_output = "
 procs -----------memory---------- ---swap-- -----io---- --system-- -----cpu------
  r  b   swpd   free   buff  cache   si   so    bi    bo   in   cs us sy id wa st
- 1  0      0 781916 195988 457784    0    0     0     0   95  142  1  1 98  0  0
+ 1  0   1024 781916 195988 457784    0    0     0     0   95  142  1  1 98  0  0
 ";
         let result = collector.parse_vmstat_output(sample_output);
         assert!(result.is_ok());
+
+        let stats = result.unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].memory.swap_used_kb, 1024);
+        assert_eq!(stats[0].memory.free_kb, 781916);
+        assert_eq!(stats[0].memory.swap_free_kb, None);
+        assert_eq!(stats[0].system.context_switches_per_sec, 142.0);
+        assert_eq!(stats[0].cpu.idle, 98.0);
+    }
+
+    #[test]
+    fn test_parse_snapshot() {
+        let snapshot = VmstatSnapshot::parse(STAT_BEFORE, VMSTAT_BEFORE, MEMINFO).unwrap();
+        assert_eq!(snapshot.interrupts, 100000);
+        assert_eq!(snapshot.context_switches, 500000);
+        assert_eq!(snapshot.procs_running, 3);
+        assert_eq!(snapshot.pgpgout, 2000);
+        assert_eq!(snapshot.swap_free_kb, 2000000);
+
+        assert!(VmstatSnapshot::parse("intr 1\n", VMSTAT_BEFORE, MEMINFO).is_err());
+        assert!(VmstatSnapshot::parse(STAT_BEFORE, VMSTAT_BEFORE, "MemFree: 1 kB\n").is_err());
+    }
+
+    #[test]
+    fn test_vmstat_data_from_delta() {
+        let before = VmstatSnapshot::parse(STAT_BEFORE, VMSTAT_BEFORE, MEMINFO).unwrap();
+        let after = VmstatSnapshot::parse(STAT_AFTER, VMSTAT_AFTER, MEMINFO).unwrap();
+        let data = VmstatData::from_delta(&before, &after, Duration::from_secs(2), 4, 0);
+
+        assert_eq!(data.procs.running, 2);
+        assert_eq!(data.procs.blocked, 1);
+        assert_eq!(data.memory.free_kb, 781916);
+        assert_eq!(data.memory.cache_kb, 457784 + 42216);
+        assert_eq!(data.memory.swap_used_kb, 97148);
+        assert_eq!(data.memory.swap_free_kb, Some(2000000));
+        assert_eq!(data.swap.kb_in_per_sec, 8.0);
+        assert_eq!(data.swap.kb_out_per_sec, 80.0);
+        assert_eq!(data.io.blocks_in_per_sec, 200.0);
+        assert_eq!(data.io.blocks_out_per_sec, 4000.0);
+        assert_eq!(data.system.interrupts_per_sec, 1000.0);
+        assert_eq!(data.system.context_switches_per_sec, 2000.0);
+
+        // 1000 ticks: 200 user+nice, 90 sys+irq+soft, 660 idle, 50 iowait (guest stays in user)
+        assert_eq!(data.cpu.user, 20.0);
+        assert_eq!(data.cpu.system, 9.0);
+        assert_eq!(data.cpu.idle, 66.0);
+        assert_eq!(data.cpu.iowait, 5.0);
     }
 }
@@ -31,8 +31,15 @@
 //! };
 //! ```
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
 use std::path::PathBuf;
+use std::str::FromStr;
+use thiserror::Error;
+
+use super::units::{BlockSizes, BsSplit, KbBase, SizeSpec, UnitError};
 
 #[derive(Debug, Serialize, Clone, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -40,6 +47,7 @@ pub enum IoEngine {
     Sync,
     Psync,
     Libaio,
+    #[serde(rename = "io_uring", alias = "iouring")]
     IoUring,
     External(String),
     #[serde(other)]
@@ -55,9 +63,115 @@ pub enum IoPattern {
     RandRead,
     RandWrite,
     RandRW,
+    /// Sequential mixed reads and writes
+    #[serde(rename = "rw", alias = "readwrite")]
+    ReadWrite,
     Trim,
 }
 
+/// Error returned when an engine or pattern name is not recognised
+#[derive(Debug, Error)]
+#[error("Unknown FIO {kind}: {value}")]
+pub struct UnknownFioValue {
+    kind: &'static str,
+    value: String,
+}
+
+impl IoEngine {
+    /// Name of the engine as passed to `--ioengine`.
+    ///
+    /// [`IoEngine::Other`] carries no name, so it falls back to FIO's Linux default, `psync`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            IoEngine::Sync => "sync",
+            IoEngine::Psync | IoEngine::Other => "psync",
+            IoEngine::Libaio => "libaio",
+            IoEngine::IoUring => "io_uring",
+            IoEngine::External(name) => name,
+        }
+    }
+}
+
+impl FromStr for IoEngine {
+    type Err = UnknownFioValue;
+
+    /// Parses an engine name; names FIO knows but this enum does not model become
+    /// [`IoEngine::External`].
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" => Err(UnknownFioValue {
+                kind: "ioengine",
+                value: s.to_string(),
+            }),
+            "sync" => Ok(IoEngine::Sync),
+            "psync" => Ok(IoEngine::Psync),
+            "libaio" => Ok(IoEngine::Libaio),
+            "io_uring" | "iouring" => Ok(IoEngine::IoUring),
+            other => Ok(IoEngine::External(other.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for IoEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl IoPattern {
+    /// Name of the pattern as passed to `--rw`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IoPattern::Read => "read",
+            IoPattern::Write => "write",
+            IoPattern::RandRead => "randread",
+            IoPattern::RandWrite => "randwrite",
+            IoPattern::RandRW => "randrw",
+            IoPattern::ReadWrite => "rw",
+            IoPattern::Trim => "trim",
+        }
+    }
+
+    /// Whether the pattern issues reads.
+    pub fn reads(&self) -> bool {
+        matches!(
+            self,
+            IoPattern::Read | IoPattern::RandRead | IoPattern::RandRW | IoPattern::ReadWrite
+        )
+    }
+
+    /// Whether the pattern modifies data on the target (writes or trims).
+    pub fn writes(&self) -> bool {
+        !matches!(self, IoPattern::Read | IoPattern::RandRead)
+    }
+}
+
+impl FromStr for IoPattern {
+    type Err = UnknownFioValue;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "read" => Ok(IoPattern::Read),
+            "write" => Ok(IoPattern::Write),
+            "randread" => Ok(IoPattern::RandRead),
+            "randwrite" => Ok(IoPattern::RandWrite),
+            "randrw" => Ok(IoPattern::RandRW),
+            "rw" | "readwrite" => Ok(IoPattern::ReadWrite),
+            "trim" => Ok(IoPattern::Trim),
+            other => Err(UnknownFioValue {
+                kind: "rw pattern",
+                value: other.to_string(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for IoPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// Configuration for a single FIO job.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FioJobConfig {
@@ -89,7 +203,7 @@ pub struct FioJobConfig {
 }
 
 /// Storage target configuration for FIO testing.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageTarget {
     /// Path to the target (file, device, or directory)
     pub path: PathBuf,
@@ -102,12 +216,28 @@ pub struct StorageTarget {
     pub options: HashMap<String, String>,
 }
 
+/// Errors that can occur while interpreting FIO output
+#[derive(Debug, Error)]
+pub enum FioParseError {
+    /// The output did not contain a JSON document
+    #[error("No JSON document found in FIO output")]
+    MissingJson,
+
+    /// The JSON document did not match FIO's output schema
+    #[error("Failed to parse FIO JSON output: {0}")]
+    InvalidJson(String),
+
+    /// The document parsed but contained no job results
+    #[error("FIO output contains no jobs")]
+    NoJobs,
+}
+
 /// Statistics for a specific type of I/O operation.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct IoStats {
     /// Operations per second
     pub iops: f64,
-    /// Bandwidth in megabytes per second
+    /// Bandwidth in mebibytes per second
     pub bandwidth_mb: f64,
     /// Average latency in microseconds
     pub lat_usec: f64,
@@ -115,15 +245,104 @@ pub struct IoStats {
     pub lat_usec_p99: f64,
     /// Maximum observed latency in microseconds
     pub lat_usec_max: f64,
+    /// Minimum observed latency in microseconds
+    #[serde(default)]
+    pub lat_usec_min: f64,
+    /// Standard deviation of the total latency in microseconds
+    #[serde(default)]
+    pub lat_usec_stddev: f64,
+    /// Average completion latency in microseconds
+    #[serde(default)]
+    pub clat_usec: f64,
+    /// Completion latency percentiles in microseconds, keyed by FIO's
+    /// percentile label (e.g. "99.000000")
+    #[serde(default)]
+    pub clat_percentiles_usec: BTreeMap<String, f64>,
+    /// Total bytes transferred
+    #[serde(default)]
+    pub io_bytes: u64,
+    /// Total number of I/Os issued
+    #[serde(default)]
+    pub total_ios: u64,
+    /// Runtime of this direction in milliseconds
+    #[serde(default)]
+    pub runtime_ms: u64,
+}
+
+/// Results for a single FIO job (or reporting group).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FioJobResult {
+    /// Job name as given by `--name`
+    pub jobname: String,
+    /// Reporting group the job belongs to
+    pub groupid: u32,
+    /// FIO error code for this job (0 on success)
+    pub error: i32,
+    /// Read operation statistics
+    pub read: IoStats,
+    /// Write operation statistics
+    pub write: IoStats,
+    /// Trim operation statistics
+    pub trim: IoStats,
+    /// User CPU utilisation percentage
+    pub usr_cpu: f64,
+    /// System CPU utilisation percentage
+    pub sys_cpu: f64,
+    /// Job runtime in milliseconds
+    pub job_runtime_ms: u64,
+}
+
+/// Disk utilisation reported by FIO for each underlying device.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiskUtil {
+    /// Device name (e.g. "nvme0n1")
+    pub name: String,
+    /// Number of read I/Os completed by the device
+    pub read_ios: u64,
+    /// Number of write I/Os completed by the device
+    pub write_ios: u64,
+    /// Number of merged read requests
+    pub read_merges: u64,
+    /// Number of merged write requests
+    pub write_merges: u64,
+    /// Time spent servicing reads in milliseconds
+    pub read_ticks: u64,
+    /// Time spent servicing writes in milliseconds
+    pub write_ticks: u64,
+    /// Total time requests spent in the queue in milliseconds
+    pub in_queue: u64,
+    /// Device utilisation percentage
+    pub util: f64,
 }
 
 /// Results from a FIO benchmark run.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FioResult {
     /// Read operation statistics
     pub read: IoStats,
     /// Write operation statistics
     pub write: IoStats,
+    /// Trim operation statistics
+    #[serde(default)]
+    pub trim: IoStats,
+    /// Per-job results as reported by FIO
+    #[serde(default)]
+    pub jobs: Vec<FioJobResult>,
+    /// Average user CPU utilisation percentage across jobs
+    #[serde(default)]
+    pub usr_cpu: f64,
+    /// Average system CPU utilisation percentage across jobs
+    #[serde(default)]
+    pub sys_cpu: f64,
+    /// Utilisation of the devices backing the run
+    #[serde(default)]
+    pub disk_util: Vec<DiskUtil>,
+    /// FIO version that produced the results
+    #[serde(default)]
+    pub fio_version: Option<String>,
+    /// Unix timestamp at which FIO finished the run
+    #[serde(default)]
+    pub timestamp: i64,
     /// Error information if the benchmark failed
     pub error: Option<String>,
 }
@@ -147,28 +366,248 @@ impl Default for FioJobConfig {
     }
 }
 
-impl Default for IoStats {
-    fn default() -> Self {
+impl FioResult {
+    /// Parses the output of `fio --output-format=json`.
+    ///
+    /// FIO may print notes and warnings ahead of the JSON document, so
+    /// everything before the first `{` is ignored.  When the run was not
+    /// using `group_reporting`, the per-job statistics are aggregated:
+    /// IOPS and bandwidth are summed, mean latencies are weighted by the
+    /// number of I/Os, and maxima/percentiles take the worst job.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sysperf_svr::domain::storage::fio::FioResult;
+    ///
+    /// let json = r#"{"fio version": "fio-3.36", "jobs": [{"jobname": "t",
+    ///     "read": {"iops": 1000.0, "bw": 4000}}]}"#;
+    /// let result = FioResult::from_json(json).unwrap();
+    /// assert_eq!(result.read.iops, 1000.0);
+    /// ```
+    pub fn from_json(output: &str) -> Result<Self, FioParseError> {
+        let start = output.find('{').ok_or(FioParseError::MissingJson)?;
+        let raw: RawOutput = serde_json::from_str(&output[start..])
+            .map_err(|e| FioParseError::InvalidJson(e.to_string()))?;
+
+        if raw.jobs.is_empty() {
+            return Err(FioParseError::NoJobs);
+        }
+
+        let jobs: Vec<FioJobResult> = raw.jobs.iter().map(FioJobResult::from_raw).collect();
+
+        let failed: Vec<String> = jobs
+            .iter()
+            .filter(|job| job.error != 0)
+            .map(|job| format!("{} (error {})", job.jobname, job.error))
+            .collect();
+
+        let job_count = jobs.len() as f64;
+        Ok(Self {
+            read: IoStats::aggregate(jobs.iter().map(|j| &j.read)),
+            write: IoStats::aggregate(jobs.iter().map(|j| &j.write)),
+            trim: IoStats::aggregate(jobs.iter().map(|j| &j.trim)),
+            usr_cpu: jobs.iter().map(|j| j.usr_cpu).sum::<f64>() / job_count,
+            sys_cpu: jobs.iter().map(|j| j.sys_cpu).sum::<f64>() / job_count,
+            disk_util: raw.disk_util.into_iter().map(DiskUtil::from).collect(),
+            fio_version: raw.fio_version,
+            timestamp: raw.timestamp,
+            error: if failed.is_empty() {
+                None
+            } else {
+                Some(format!("FIO jobs failed: {}", failed.join(", ")))
+            },
+            jobs,
+        })
+    }
+
+    /// Read, write and trim statistics combined into a single set of totals.
+    ///
+    /// Useful for mixed workloads, where the device load is the sum of both directions.
+    pub fn combined(&self) -> IoStats {
+        IoStats::aggregate([&self.read, &self.write, &self.trim].into_iter())
+    }
+}
+
+impl FioJobResult {
+    fn from_raw(raw: &RawJob) -> Self {
         Self {
-            iops: 0.0,
-            bandwidth_mb: 0.0,
-            lat_usec: 0.0,
-            lat_usec_p99: 0.0,
-            lat_usec_max: 0.0,
+            jobname: raw.jobname.clone(),
+            groupid: raw.groupid,
+            error: raw.error,
+            read: IoStats::from(&raw.read),
+            write: IoStats::from(&raw.write),
+            trim: IoStats::from(&raw.trim),
+            usr_cpu: raw.usr_cpu,
+            sys_cpu: raw.sys_cpu,
+            job_runtime_ms: raw.job_runtime,
         }
     }
 }
 
-impl Default for FioResult {
-    fn default() -> Self {
+impl IoStats {
+    /// Combines the statistics of several jobs into one set of totals.
+    fn aggregate<'a>(stats: impl Iterator<Item = &'a IoStats>) -> IoStats {
+        let mut total = IoStats::default();
+        let mut weight_total = 0.0;
+
+        for s in stats {
+            let weight = s.total_ios as f64;
+            total.iops += s.iops;
+            total.bandwidth_mb += s.bandwidth_mb;
+            total.io_bytes += s.io_bytes;
+            total.total_ios += s.total_ios;
+            total.runtime_ms = total.runtime_ms.max(s.runtime_ms);
+            total.lat_usec += s.lat_usec * weight;
+            total.clat_usec += s.clat_usec * weight;
+            total.lat_usec_stddev = total.lat_usec_stddev.max(s.lat_usec_stddev);
+            total.lat_usec_p99 = total.lat_usec_p99.max(s.lat_usec_p99);
+            total.lat_usec_max = total.lat_usec_max.max(s.lat_usec_max);
+            if s.total_ios > 0 && (total.lat_usec_min == 0.0 || s.lat_usec_min < total.lat_usec_min)
+            {
+                total.lat_usec_min = s.lat_usec_min;
+            }
+            for (pct, value) in &s.clat_percentiles_usec {
+                let entry = total.clat_percentiles_usec.entry(pct.clone()).or_default();
+                *entry = entry.max(*value);
+            }
+            weight_total += weight;
+        }
+
+        if weight_total > 0.0 {
+            total.lat_usec /= weight_total;
+            total.clat_usec /= weight_total;
+        }
+        total
+    }
+}
+
+/// Nanoseconds per microsecond, used to convert FIO's `*_ns` sections.
+const NS_PER_USEC: f64 = 1000.0;
+
+impl From<&RawIo> for IoStats {
+    fn from(raw: &RawIo) -> Self {
+        let clat_percentiles_usec: BTreeMap<String, f64> = raw
+            .clat_ns
+            .percentile
+            .iter()
+            .map(|(pct, ns)| (pct.clone(), *ns / NS_PER_USEC))
+            .collect();
+
+        // Prefer the completion-latency p99; fall back to the total-latency
+        // percentiles when FIO was run with `lat_percentiles=1`.
+        let lat_usec_p99 = clat_percentiles_usec
+            .get("99.000000")
+            .copied()
+            .or_else(|| {
+                raw.lat_ns
+                    .percentile
+                    .get("99.000000")
+                    .map(|ns| ns / NS_PER_USEC)
+            })
+            .unwrap_or(0.0);
+
+        Self {
+            iops: raw.iops,
+            // `bw` is reported in KiB/s
+            bandwidth_mb: raw.bw / 1024.0,
+            lat_usec: raw.lat_ns.mean / NS_PER_USEC,
+            lat_usec_p99,
+            lat_usec_max: raw.lat_ns.max / NS_PER_USEC,
+            lat_usec_min: raw.lat_ns.min / NS_PER_USEC,
+            lat_usec_stddev: raw.lat_ns.stddev / NS_PER_USEC,
+            clat_usec: raw.clat_ns.mean / NS_PER_USEC,
+            clat_percentiles_usec,
+            io_bytes: raw.io_bytes,
+            total_ios: raw.total_ios,
+            runtime_ms: raw.runtime,
+        }
+    }
+}
+
+impl From<RawDiskUtil> for DiskUtil {
+    fn from(raw: RawDiskUtil) -> Self {
         Self {
-            read: IoStats::default(),
-            write: IoStats::default(),
-            error: None,
+            name: raw.name,
+            read_ios: raw.read_ios,
+            write_ios: raw.write_ios,
+            read_merges: raw.read_merges,
+            write_merges: raw.write_merges,
+            read_ticks: raw.read_ticks,
+            write_ticks: raw.write_ticks,
+            in_queue: raw.in_queue,
+            util: raw.util,
         }
     }
 }
 
+/// Top-level document produced by `fio --output-format=json`.
+#[derive(Debug, Deserialize)]
+struct RawOutput {
+    #[serde(rename = "fio version", default)]
+    fio_version: Option<String>,
+    #[serde(default)]
+    timestamp: i64,
+    #[serde(default)]
+    jobs: Vec<RawJob>,
+    #[serde(default)]
+    disk_util: Vec<RawDiskUtil>,
+}
+
+/// A single entry of the `jobs` array.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawJob {
+    jobname: String,
+    groupid: u32,
+    error: i32,
+    read: RawIo,
+    write: RawIo,
+    trim: RawIo,
+    job_runtime: u64,
+    usr_cpu: f64,
+    sys_cpu: f64,
+}
+
+/// The `read`, `write` and `trim` sections of a job.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawIo {
+    io_bytes: u64,
+    bw: f64,
+    iops: f64,
+    runtime: u64,
+    total_ios: u64,
+    clat_ns: RawLatency,
+    lat_ns: RawLatency,
+}
+
+/// Latency summary (`clat_ns`, `lat_ns`) with optional percentiles.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawLatency {
+    min: f64,
+    max: f64,
+    mean: f64,
+    stddev: f64,
+    percentile: BTreeMap<String, f64>,
+}
+
+/// A single entry of the `disk_util` array.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawDiskUtil {
+    name: String,
+    read_ios: u64,
+    write_ios: u64,
+    read_merges: u64,
+    write_merges: u64,
+    read_ticks: u64,
+    write_ticks: u64,
+    in_queue: u64,
+    util: f64,
+}
+
 impl FioJobConfig {
     /// Creates a new FIO job configuration for sequential read testing.
     ///
@@ -194,6 +633,74 @@ impl FioJobConfig {
         }
     }
 
+    /// Builds the FIO command-line arguments for this job against `target`.
+    ///
+    /// Block devices and files are addressed with `--filename`, directories and mounted
+    /// filesystems with `--directory`.  Boolean extra options follow FIO's flag convention:
+    /// `"true"` becomes a bare `--option` and `"false"` becomes `--option=0`, so that options
+    /// FIO enables by default (`randrepeat`, `invalidate`, ...) can be turned off.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sysperf_svr::domain::storage::fio::{FioJobConfig, StorageTarget};
+    ///
+    /// let job = FioJobConfig::new_sequential_read("1M", "4G", 1, 16);
+    /// let target = StorageTarget::new_device("/dev/nvme0n1", None);
+    /// let args = job.to_fio_args("seq_read", &target);
+    /// assert!(args.contains(&"--filename=/dev/nvme0n1".to_string()));
+    /// assert!(args.contains(&"--rw=read".to_string()));
+    /// ```
+    pub fn to_fio_args(&self, name: &str, target: &StorageTarget) -> Vec<String> {
+        let mut args = vec![format!("--name={}", name), target.fio_target_arg()];
+
+        for (key, value) in self.to_options() {
+            match value.as_str() {
+                "true" => args.push(format!("--{}", key)),
+                "false" => args.push(format!("--{}=0", key)),
+                _ => args.push(format!("--{}={}", key, value)),
+            }
+        }
+
+        args
+    }
+
+    /// Lists this job's FIO options as `(key, value)` pairs.
+    ///
+    /// Typed fields come first in a fixed order, followed by `extra_options` sorted by key so
+    /// that command lines and job files are stable between runs.  An empty `size` is omitted,
+    /// leaving FIO to use the whole file or device.
+    pub fn to_options(&self) -> Vec<(String, String)> {
+        let mut options = vec![
+            ("ioengine".to_string(), self.ioengine.to_string()),
+            ("rw".to_string(), self.rw.to_string()),
+            ("bs".to_string(), self.bs.clone()),
+        ];
+
+        if !self.size.is_empty() {
+            options.push(("size".into(), self.size.clone()));
+        }
+        options.push(("numjobs".into(), self.numjobs.to_string()));
+        options.push(("iodepth".into(), self.iodepth.to_string()));
+        options.push(("direct".into(), u8::from(self.direct).to_string()));
+        if self.buffered {
+            options.push(("buffered".into(), "1".into()));
+        }
+        if let Some(mix) = self.rwmixread {
+            options.push(("rwmixread".into(), mix.to_string()));
+        }
+
+        let mut extra: Vec<_> = self
+            .extra_options
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        extra.sort();
+        options.extend(extra);
+
+        options
+    }
+
     /// Creates a new FIO job configuration for random write testing.
     pub fn new_random_write(bs: &str, size: &str, numjobs: u32, iodepth: u32) -> Self {
         Self {
@@ -207,6 +714,71 @@ impl FioJobConfig {
             ..Default::default()
         }
     }
+
+    /// How this job's sizes are read, from its `kb_base` option.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if `kb_base` is neither 1000 nor 1024.
+    pub fn kb_base(&self) -> Result<KbBase, UnitError> {
+        self.extra_options
+            .get("kb_base")
+            .map_or(Ok(KbBase::default()), |base| base.parse())
+    }
+
+    /// Parses `bs` into read, write and trim block sizes.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if `bs` or `kb_base` is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sysperf_svr::domain::storage::fio::FioJobConfig;
+    ///
+    /// let job = FioJobConfig::new_random_write("4k,64k", "1G", 1, 32);
+    /// assert_eq!(job.block_sizes().unwrap().write.bytes(), 64 * 1024);
+    /// ```
+    pub fn block_sizes(&self) -> Result<BlockSizes, UnitError> {
+        BlockSizes::parse(&self.bs, self.kb_base()?)
+    }
+
+    /// Parses `size`, or `None` when it is empty and FIO uses the whole file or device.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if `size` or `kb_base` is invalid.
+    pub fn size_spec(&self) -> Result<Option<SizeSpec>, UnitError> {
+        if self.size.trim().is_empty() {
+            return Ok(None);
+        }
+        SizeSpec::parse(&self.size, self.kb_base()?).map(Some)
+    }
+
+    /// Parses the `bssplit` option, if set.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if `bssplit` or `kb_base` is invalid.
+    pub fn bssplit(&self) -> Result<Option<BsSplit>, UnitError> {
+        self.extra_options
+            .get("bssplit")
+            .map(|split| BsSplit::parse(split, self.kb_base()?))
+            .transpose()
+    }
+
+    /// Checks that every size option FIO would be given parses.
+    ///
+    /// # Errors
+    ///
+    /// Will return the first invalid `kb_base`, `bs`, `size` or `bssplit`.
+    pub fn validate_units(&self) -> Result<(), UnitError> {
+        self.block_sizes()?;
+        self.size_spec()?;
+        self.bssplit()?;
+        Ok(())
+    }
 }
 
 impl StorageTarget {
@@ -224,6 +796,23 @@ impl StorageTarget {
         }
     }
 
+    /// Returns the FIO argument selecting this target.
+    ///
+    /// Directory-like targets ("directory", "filesystem") use `--directory`, everything else
+    /// (devices, device-mapper volumes, files) uses `--filename`.
+    pub fn fio_target_arg(&self) -> String {
+        if self.is_directory() {
+            format!("--directory={}", self.path.display())
+        } else {
+            format!("--filename={}", self.path.display())
+        }
+    }
+
+    /// Whether FIO should create its data files inside this target.
+    pub fn is_directory(&self) -> bool {
+        matches!(self.target_type.as_str(), "directory" | "filesystem")
+    }
+
     /// Creates a new device-based storage target.
     pub fn new_device<P: Into<PathBuf>>(path: P, options: Option<HashMap<String, String>>) -> Self {
         Self {
@@ -232,4 +821,205 @@ impl StorageTarget {
             options: options.unwrap_or_default(),
         }
     }
+
+    /// Names the disk a benchmark on this target exercises.
+    ///
+    /// Block devices resolve to their whole disk, so a partition and its disk share a name, and
+    /// files and directories resolve to the disk of the filesystem holding them (or of their
+    /// nearest existing parent).  Paths on filesystems without a backing disk, such as tmpfs,
+    /// are named by their canonical path.
+    pub fn device_key(&self) -> String {
+        let mut path = self.path.as_path();
+        let metadata = loop {
+            match fs::metadata(path) {
+                Ok(metadata) => break Some(metadata),
+                Err(_) => match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => path = parent,
+                    _ => break None,
+                },
+            }
+        };
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| self.path.clone());
+
+        let dev = match metadata {
+            Some(m) if m.file_type().is_block_device() => m.rdev(),
+            Some(m) => m.dev(),
+            None => return canonical.display().to_string(),
+        };
+        let sysfs = PathBuf::from(format!(
+            "/sys/dev/block/{}:{}",
+            libc::major(dev),
+            libc::minor(dev)
+        ));
+        let Ok(sysfs) = fs::canonicalize(sysfs) else {
+            return canonical.display().to_string();
+        };
+
+        // A partition's sysfs directory sits inside its disk's
+        let disk = if sysfs.join("partition").exists() {
+            sysfs.parent().unwrap_or(&sysfs)
+        } else {
+            &sysfs
+        };
+        match disk.file_name() {
+            Some(name) => format!("/dev/{}", name.to_string_lossy()),
+            None => canonical.display().to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_OUTPUT: &str = r#"note: both iodepth >= 1 and synchronous I/O engine are selected
+{
+  "fio version" : "fio-3.36",
+  "timestamp" : 1714000000,
+  "jobs" : [
+    {
+      "jobname" : "fio_test_nvme",
+      "groupid" : 0,
+      "error" : 0,
+      "read" : {
+        "io_bytes" : 1073741824,
+        "bw" : 409600,
+        "iops" : 102400.5,
+        "runtime" : 2560,
+        "total_ios" : 262144,
+        "clat_ns" : {
+          "min" : 10000, "max" : 900000, "mean" : 150000.0, "stddev" : 2000.0,
+          "percentile" : { "50.000000" : 140000, "99.000000" : 310000 }
+        },
+        "lat_ns" : { "min" : 11000, "max" : 910000, "mean" : 152000.0, "stddev" : 2100.0 }
+      },
+      "write" : {
+        "io_bytes" : 0, "bw" : 0, "iops" : 0.0, "runtime" : 0, "total_ios" : 0,
+        "clat_ns" : { "min" : 0, "max" : 0, "mean" : 0.0, "stddev" : 0.0 },
+        "lat_ns" : { "min" : 0, "max" : 0, "mean" : 0.0, "stddev" : 0.0 }
+      },
+      "trim" : { "io_bytes" : 0, "bw" : 0, "iops" : 0.0 },
+      "job_runtime" : 2561,
+      "usr_cpu" : 12.5,
+      "sys_cpu" : 40.25
+    }
+  ],
+  "disk_util" : [
+    { "name" : "nvme0n1", "read_ios" : 262000, "write_ios" : 3, "read_merges" : 0,
+      "write_merges" : 0, "read_ticks" : 39000, "write_ticks" : 1, "in_queue" : 39001,
+      "util" : 98.7 }
+  ]
+}"#;
+
+    #[test]
+    fn test_parse_fio_json() {
+        let result = FioResult::from_json(SAMPLE_OUTPUT).unwrap();
+
+        assert_eq!(result.fio_version.as_deref(), Some("fio-3.36"));
+        assert_eq!(result.jobs.len(), 1);
+        assert_eq!(result.read.iops, 102400.5);
+        assert_eq!(result.read.bandwidth_mb, 400.0);
+        assert_eq!(result.read.lat_usec, 152.0);
+        assert_eq!(result.read.lat_usec_p99, 310.0);
+        assert_eq!(result.read.lat_usec_max, 910.0);
+        assert_eq!(result.read.clat_percentiles_usec["50.000000"], 140.0);
+        assert_eq!(result.write.iops, 0.0);
+        assert_eq!(result.sys_cpu, 40.25);
+        assert_eq!(result.disk_util[0].name, "nvme0n1");
+        assert_eq!(result.disk_util[0].util, 98.7);
+        assert!(result.error.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_jobs() {
+        let json = r#"{"jobs": [
+            {"jobname": "a", "read": {"iops": 100.0, "bw": 1024, "total_ios": 100,
+                "lat_ns": {"mean": 1000.0, "max": 5000}}},
+            {"jobname": "b", "error": 5, "read": {"iops": 300.0, "bw": 3072, "total_ios": 300,
+                "lat_ns": {"mean": 3000.0, "max": 9000}}}
+        ]}"#;
+        let result = FioResult::from_json(json).unwrap();
+
+        assert_eq!(result.read.iops, 400.0);
+        assert_eq!(result.read.bandwidth_mb, 4.0);
+        assert_eq!(result.read.lat_usec, 2.5);
+        assert_eq!(result.read.lat_usec_max, 9.0);
+        assert!(result.error.unwrap().contains("b (error 5)"));
+    }
+
+    #[test]
+    fn test_job_config_to_fio_args() {
+        let mut job = FioJobConfig {
+            ioengine: "io_uring".parse().unwrap(),
+            rw: "randrw".parse().unwrap(),
+            bs: "4k".into(),
+            size: "1G".into(),
+            numjobs: 2,
+            iodepth: 8,
+            rwmixread: Some(70),
+            ..Default::default()
+        };
+        job.extra_options.insert("time_based".into(), "true".into());
+        job.extra_options
+            .insert("randrepeat".into(), "false".into());
+        job.extra_options.insert("runtime".into(), "60".into());
+
+        let target = StorageTarget::new_file("/mnt/data", None);
+        let dir = StorageTarget {
+            target_type: "filesystem".into(),
+            ..target.clone()
+        };
+
+        let args = job.to_fio_args("mixed", &target);
+        assert!(args.contains(&"--filename=/mnt/data".to_string()));
+        assert!(args.contains(&"--ioengine=io_uring".to_string()));
+        assert!(args.contains(&"--rwmixread=70".to_string()));
+        assert!(args.contains(&"--time_based".to_string()));
+        assert!(args.contains(&"--runtime=60".to_string()));
+        assert!(args.contains(&"--randrepeat=0".to_string()));
+
+        let args = job.to_fio_args("mixed", &dir);
+        assert!(args.contains(&"--directory=/mnt/data".to_string()));
+    }
+
+    #[test]
+    fn test_engine_and_pattern_names() {
+        assert!(matches!(
+            "iouring".parse::<IoEngine>(),
+            Ok(IoEngine::IoUring)
+        ));
+        assert!(matches!(
+            "posixaio".parse::<IoEngine>(),
+            Ok(IoEngine::External(name)) if name == "posixaio"
+        ));
+        assert!("randomly".parse::<IoPattern>().is_err());
+        assert_eq!(IoPattern::ReadWrite.to_string(), "rw");
+        assert!(IoPattern::Trim.writes());
+        assert!(!IoPattern::RandRead.writes());
+    }
+
+    #[test]
+    fn test_device_key() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), b"").unwrap();
+        let key = |path: PathBuf| StorageTarget::new_file(path, None).device_key();
+
+        // Files on one filesystem share a key, including files that do not exist yet
+        let expected = key(dir.path().to_path_buf());
+        assert_eq!(key(dir.path().join("a")), expected);
+        assert_eq!(key(dir.path().join("b/c")), expected);
+        assert_ne!(key("/dev/null".into()), expected);
+    }
+
+    #[test]
+    fn test_parse_fio_json_errors() {
+        assert!(matches!(
+            FioResult::from_json("fio: no such file"),
+            Err(FioParseError::MissingJson)
+        ));
+        assert!(matches!(
+            FioResult::from_json(r#"{"jobs": []}"#),
+            Err(FioParseError::NoJobs)
+        ));
+    }
 }
@@ -0,0 +1,285 @@
+//! Benchmark run history
+//!
+//! Every completed benchmark is stored as a [`RunRecord`]: the parsed FIO results together with
+//! where, when and with what it ran (host, kernel, tool and FIO versions, target and profile).
+//! [`RunFilter`] selects runs by those fields so that results can be compared across time and
+//! machines.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sysperf_svr::domain::storage::history::RunFilter;
+//!
+//! // All runs of the 4k random read profile on nvme_direct in June 2024
+//! let filter = RunFilter {
+//!     target: Some("nvme_direct".into()),
+//!     profile: Some("randread_4k".into()),
+//!     since: Some(1717200000),
+//!     until: Some(1719792000),
+//!     ..Default::default()
+//! };
+//! assert!(filter.since.is_some());
+//! ```
+
+use super::fio::{FioJobConfig, FioResult, StorageTarget};
+use crate::domain::metrics::series::MetricSeries;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Version of this tool, recorded with every run
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Git revision the tool was built from, when built from a checkout
+pub const GIT_REVISION: Option<&str> = option_env!("SYSPERF_GIT_REVISION");
+
+/// Host the runs were taken on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostIdentity {
+    /// Host name
+    pub hostname: String,
+    /// Kernel release (`uname -r`)
+    pub kernel: String,
+}
+
+impl HostIdentity {
+    /// Reads the host name and kernel release from `/proc/sys/kernel`.
+    ///
+    /// Values that cannot be read are reported as "unknown".
+    pub fn detect() -> Self {
+        let read = |path: &str| {
+            fs::read_to_string(path)
+                .map(|s| s.trim().to_string())
+                .ok()
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "unknown".to_string())
+        };
+        Self {
+            hostname: read("/proc/sys/kernel/hostname"),
+            kernel: read("/proc/sys/kernel/osrelease"),
+        }
+    }
+}
+
+/// A completed benchmark run and the context it ran in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Unique, time-ordered run ID
+    pub id: String,
+    /// Host name of the machine that ran the benchmark
+    pub hostname: String,
+    /// Kernel release of that machine
+    pub kernel: String,
+    /// Unix timestamp (seconds) at which the run finished
+    pub timestamp: i64,
+    /// Version of this tool
+    pub tool_version: String,
+    /// Git revision of this tool, if known
+    #[serde(default)]
+    pub git_revision: Option<String>,
+    /// FIO version as reported by `fio --version`
+    #[serde(default)]
+    pub fio_version: Option<String>,
+    /// Name of the configured storage target, or `None` for the test directory
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Path the benchmark ran against
+    pub target_path: String,
+    /// Name of the FIO profile, or `None` for ad-hoc runs
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// FIO job name
+    pub name: String,
+    /// Job that was run, when it was built from a profile
+    #[serde(default)]
+    pub job: Option<FioJobConfig>,
+    /// Parsed FIO results
+    pub result: FioResult,
+    /// Pinned runs (such as baselines) are never removed by retention
+    #[serde(default)]
+    pub pinned: bool,
+    /// System metrics recorded during the run; stored separately and only loaded on request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_metrics: Option<MetricSeries>,
+}
+
+impl RunRecord {
+    /// Creates a record for a run that just finished on this host.
+    pub fn new(name: &str, storage_target: &StorageTarget, result: FioResult) -> Self {
+        let host = HostIdentity::detect();
+        let timestamp = chrono::Utc::now();
+        Self {
+            id: new_run_id(timestamp),
+            hostname: host.hostname,
+            kernel: host.kernel,
+            timestamp: timestamp.timestamp(),
+            tool_version: TOOL_VERSION.to_string(),
+            git_revision: GIT_REVISION.map(String::from),
+            fio_version: result.fio_version.clone(),
+            target: None,
+            target_path: storage_target.path.display().to_string(),
+            profile: None,
+            name: name.to_string(),
+            job: None,
+            result,
+            pinned: false,
+            system_metrics: None,
+        }
+    }
+
+    /// The job this run belongs to.
+    pub fn job(&self) -> JobKey {
+        JobKey {
+            hostname: self.hostname.clone(),
+            target: self.target.clone(),
+            profile: self.profile.clone(),
+            name: self.name.clone(),
+        }
+    }
+}
+
+/// A benchmark job: one run name on one host, target and profile
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct JobKey {
+    /// Host name of the machine that ran the job
+    pub hostname: String,
+    /// Name of the configured storage target, or `None` for the test directory
+    pub target: Option<String>,
+    /// Name of the FIO profile, or `None` for ad-hoc runs
+    pub profile: Option<String>,
+    /// FIO job name
+    pub name: String,
+}
+
+/// Keeps the first, i.e. newest, run of every job in `runs`, which is ordered newest first.
+pub fn latest_per_job(runs: impl IntoIterator<Item = RunRecord>) -> Vec<RunRecord> {
+    let mut seen = HashSet::new();
+    runs.into_iter()
+        .filter(|run| seen.insert(run.job()))
+        .collect()
+}
+
+/// Criteria for selecting stored runs; unset fields match every run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunFilter {
+    /// Only runs taken on this host
+    pub host: Option<String>,
+    /// Only runs against this configured target
+    pub target: Option<String>,
+    /// Only runs of this profile
+    pub profile: Option<String>,
+    /// Only runs at or after this Unix timestamp (seconds)
+    pub since: Option<i64>,
+    /// Only runs before this Unix timestamp (seconds)
+    pub until: Option<i64>,
+    /// Return at most this many runs, newest first
+    pub limit: Option<usize>,
+}
+
+impl RunFilter {
+    /// Whether `run` satisfies every criterion except `limit`.
+    pub fn matches(&self, run: &RunRecord) -> bool {
+        let eq = |wanted: &Option<String>, actual: Option<&str>| match wanted {
+            Some(wanted) => actual == Some(wanted.as_str()),
+            None => true,
+        };
+
+        eq(&self.host, Some(&run.hostname))
+            && eq(&self.target, run.target.as_deref())
+            && eq(&self.profile, run.profile.as_deref())
+            && self.since.is_none_or(|since| run.timestamp >= since)
+            && self.until.is_none_or(|until| run.timestamp < until)
+    }
+
+    /// Filters `runs`, orders them newest first and applies `limit`.
+    pub fn apply(&self, runs: impl IntoIterator<Item = RunRecord>) -> Vec<RunRecord> {
+        let mut runs: Vec<RunRecord> = runs.into_iter().filter(|r| self.matches(r)).collect();
+        runs.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(b.id.cmp(&a.id)));
+        if let Some(limit) = self.limit {
+            runs.truncate(limit);
+        }
+        runs
+    }
+}
+
+/// Creates a run ID that sorts by time, e.g. `20240601T120000.123Z-1a2b`.
+///
+/// The suffix mixes the process ID with a per-process counter so that runs started in the
+/// same millisecond, in this or another process, get distinct IDs.
+pub fn new_run_id(timestamp: chrono::DateTime<chrono::Utc>) -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let suffix = (std::process::id().wrapping_mul(31).wrapping_add(count)) & 0xffff;
+    format!("{}-{:04x}", timestamp.format("%Y%m%dT%H%M%S%.3fZ"), suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(host: &str, target: Option<&str>, profile: Option<&str>, timestamp: i64) -> RunRecord {
+        let mut run = RunRecord::new(
+            "job",
+            &StorageTarget::new_file("/tmp/x", None),
+            FioResult::default(),
+        );
+        run.hostname = host.into();
+        run.target = target.map(String::from);
+        run.profile = profile.map(String::from);
+        run.timestamp = timestamp;
+        run
+    }
+
+    #[test]
+    fn test_filter_matches_each_field() {
+        let runs = vec![
+            run("node1", Some("nvme_direct"), Some("randread_4k"), 100),
+            run("node1", Some("nvme_direct"), Some("seqwrite_1m"), 200),
+            run("node2", Some("nvme_direct"), Some("randread_4k"), 300),
+            run("node1", None, Some("randread_4k"), 400),
+        ];
+
+        let filter = RunFilter {
+            target: Some("nvme_direct".into()),
+            profile: Some("randread_4k".into()),
+            ..Default::default()
+        };
+        let found = filter.apply(runs.clone());
+        assert_eq!(
+            found.iter().map(|r| r.timestamp).collect::<Vec<_>>(),
+            vec![300, 100]
+        );
+
+        let filter = RunFilter {
+            host: Some("node1".into()),
+            since: Some(200),
+            until: Some(400),
+            ..Default::default()
+        };
+        assert_eq!(filter.apply(runs.clone())[0].timestamp, 200);
+        assert_eq!(filter.apply(runs.clone()).len(), 1);
+
+        let filter = RunFilter {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let found = filter.apply(runs);
+        assert_eq!(
+            found.iter().map(|r| r.timestamp).collect::<Vec<_>>(),
+            vec![400, 300]
+        );
+    }
+
+    #[test]
+    fn test_run_ids_are_unique_and_ordered() {
+        let early = chrono::DateTime::from_timestamp(1717243200, 0).unwrap();
+        let late = chrono::DateTime::from_timestamp(1717243201, 0).unwrap();
+
+        let a = new_run_id(early);
+        let b = new_run_id(early);
+        assert_ne!(a, b);
+        assert!(a.starts_with("20240601T120000.000Z-"));
+        assert!(new_run_id(late) > a.max(b));
+    }
+}
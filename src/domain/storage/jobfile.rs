@@ -0,0 +1,465 @@
+//! FIO job files (`.fio`) rendered from and parsed into [`FioJobConfig`]s.
+//!
+//! FIO job files are INI-like: a `[global]` section whose options apply to every job that
+//! follows it, then one section per job.  Options are written `key=value`, or as a bare `key`
+//! for flags such as `time_based`.  Lines starting with `;` or `#` are comments.
+//!
+//! # Example
+//!
+//! ```
+//! use sysperf_svr::domain::storage::fio::{FioJobConfig, StorageTarget};
+//! use sysperf_svr::domain::storage::jobfile::FioJobFile;
+//!
+//! let target = StorageTarget::new_device("/dev/nvme0n1", None);
+//! let mut file = FioJobFile::for_target(&target);
+//! file.set_global("runtime", "60");
+//! file.set_global("time_based", "true");
+//! file.add_job("seq_read", &FioJobConfig::new_sequential_read("1M", "", 1, 32));
+//! file.add_job("rand_write", &FioJobConfig::new_random_write("4k", "", 4, 64))
+//!     .stonewall = true;
+//!
+//! let text = file.render();
+//! assert!(text.contains("[rand_write]\nstonewall\n"));
+//!
+//! let parsed = FioJobFile::parse(&text).unwrap();
+//! let jobs = parsed.job_configs().unwrap();
+//! assert_eq!(jobs[1].1.iodepth, 64);
+//! ```
+
+use super::fio::{FioJobConfig, StorageTarget};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::Path;
+use thiserror::Error;
+
+/// Name of FIO's global section
+const GLOBAL_SECTION: &str = "global";
+
+/// Errors that can occur while reading or interpreting a job file
+#[derive(Debug, Error)]
+pub enum JobFileError {
+    /// Error reading or writing the job file
+    #[error("Failed to access job file: {0}")]
+    IoError(#[from] std::io::Error),
+
+    /// A line could not be parsed
+    #[error("Line {line}: {message}")]
+    SyntaxError { line: usize, message: String },
+
+    /// An option value could not be converted into a [`FioJobConfig`] field
+    #[error("Job [{job}]: invalid value for {key}: {message}")]
+    InvalidValue {
+        job: String,
+        key: String,
+        message: String,
+    },
+}
+
+/// A single job section of a job file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FioJobSection {
+    /// Section name, used by FIO as the job name
+    pub name: String,
+    /// Options set in this section, in file order
+    pub options: Vec<(String, String)>,
+    /// Wait for all previous jobs to finish before starting (`stonewall`)
+    pub stonewall: bool,
+    /// Start a new reporting group with this job (`new_group`)
+    pub new_group: bool,
+}
+
+/// A complete FIO job file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FioJobFile {
+    /// Options from the `[global]` section, in file order
+    pub global: Vec<(String, String)>,
+    /// Job sections, in file order
+    pub jobs: Vec<FioJobSection>,
+}
+
+impl FioJobSection {
+    /// Creates a section from a job configuration.
+    pub fn from_config(name: &str, config: &FioJobConfig) -> Self {
+        Self {
+            name: name.to_string(),
+            options: config.to_options(),
+            stonewall: false,
+            new_group: false,
+        }
+    }
+}
+
+impl FioJobFile {
+    /// Creates an empty job file.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a job file whose global section points every job at `target`.
+    pub fn for_target(target: &StorageTarget) -> Self {
+        let arg = target.fio_target_arg();
+        let (key, value) = arg
+            .trim_start_matches("--")
+            .split_once('=')
+            .unwrap_or(("filename", ""));
+
+        let mut file = Self::new();
+        file.set_global(key, value);
+        file
+    }
+
+    /// Sets a global option, replacing an earlier value for the same key.
+    pub fn set_global(&mut self, key: &str, value: &str) {
+        set_option(&mut self.global, key, value);
+    }
+
+    /// Appends a job section built from `config` and returns it for further tweaking.
+    pub fn add_job(&mut self, name: &str, config: &FioJobConfig) -> &mut FioJobSection {
+        self.jobs.push(FioJobSection::from_config(name, config));
+        self.jobs.last_mut().expect("job was just pushed")
+    }
+
+    /// Reads and parses a job file from disk.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, JobFileError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Renders the job file and writes it to disk.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), JobFileError> {
+        std::fs::write(path, self.render())?;
+        Ok(())
+    }
+
+    /// Renders the job file in FIO's INI format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        if !self.global.is_empty() {
+            out.push_str("[global]\n");
+            render_options(&mut out, &self.global);
+        }
+
+        for job in &self.jobs {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("[{}]\n", job.name));
+            if job.stonewall {
+                out.push_str("stonewall\n");
+            }
+            if job.new_group {
+                out.push_str("new_group\n");
+            }
+            render_options(&mut out, &job.options);
+        }
+
+        out
+    }
+
+    /// Parses FIO job file text.
+    ///
+    /// A `[global]` section appearing after jobs only affects the jobs that follow it, as in
+    /// FIO itself; its options are folded into those jobs' sections.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if:
+    /// - An option appears before any section header
+    /// - A section header is malformed
+    /// - The file uses `include`, which is not supported
+    pub fn parse(content: &str) -> Result<Self, JobFileError> {
+        let mut file = Self::new();
+        // Options from a [global] section that follows at least one job
+        let mut late_global: Vec<(String, String)> = Vec::new();
+        let mut in_global = false;
+
+        for (i, raw_line) in content.lines().enumerate() {
+            let line_no = i + 1;
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                let name = line
+                    .strip_prefix('[')
+                    .and_then(|l| l.strip_suffix(']'))
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| JobFileError::SyntaxError {
+                        line: line_no,
+                        message: format!("malformed section header: {}", line),
+                    })?;
+
+                in_global = name == GLOBAL_SECTION;
+                if !in_global {
+                    file.jobs.push(FioJobSection {
+                        name: name.to_string(),
+                        options: late_global.clone(),
+                        ..Default::default()
+                    });
+                }
+                continue;
+            }
+
+            if line.starts_with("include ") {
+                return Err(JobFileError::SyntaxError {
+                    line: line_no,
+                    message: "include directives are not supported".into(),
+                });
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (line, "true"),
+            };
+
+            if in_global {
+                if file.jobs.is_empty() {
+                    set_option(&mut file.global, key, value);
+                } else {
+                    set_option(&mut late_global, key, value);
+                }
+                continue;
+            }
+
+            let job = file
+                .jobs
+                .last_mut()
+                .ok_or_else(|| JobFileError::SyntaxError {
+                    line: line_no,
+                    message: format!("option {} appears outside of any section", key),
+                })?;
+
+            match key {
+                "stonewall" | "wait_for_previous" => job.stonewall = is_true(value),
+                "new_group" => job.new_group = is_true(value),
+                _ => set_option(&mut job.options, key, value),
+            }
+        }
+
+        Ok(file)
+    }
+
+    /// Resolves every job into a [`FioJobConfig`], applying the global section.
+    ///
+    /// Options FIO needs but the file leaves unset take FIO's own defaults (`psync`, `read`,
+    /// `4k`, one job, iodepth 1, buffered I/O) rather than [`FioJobConfig::default`], so that
+    /// imported files behave exactly as they would under FIO.
+    pub fn job_configs(&self) -> Result<Vec<(String, FioJobConfig)>, JobFileError> {
+        self.jobs
+            .iter()
+            .map(|job| {
+                let options: BTreeMap<String, String> = self
+                    .global
+                    .iter()
+                    .chain(job.options.iter())
+                    .cloned()
+                    .collect();
+                config_from_options(&job.name, options).map(|config| (job.name.clone(), config))
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for FioJobFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render())
+    }
+}
+
+/// Builds a [`FioJobConfig`] from resolved `key=value` options.
+fn config_from_options(
+    job: &str,
+    mut options: BTreeMap<String, String>,
+) -> Result<FioJobConfig, JobFileError> {
+    let invalid = |key: &str, message: String| JobFileError::InvalidValue {
+        job: job.to_string(),
+        key: key.to_string(),
+        message,
+    };
+    let mut take = |keys: &[&str], default: &str| {
+        take_option(&mut options, keys).unwrap_or_else(|| default.to_string())
+    };
+
+    let ioengine = take(&["ioengine"], "psync");
+    let rw = take(&["rw", "readwrite"], "read");
+    let bs = take(&["bs", "blocksize"], "4k");
+    let size = take(&["size"], "");
+    let numjobs = take(&["numjobs"], "1");
+    let iodepth = take(&["iodepth"], "1");
+    let direct = is_true(&take(&["direct"], "0"));
+    let buffered = is_true(&take(&["buffered"], "0"));
+    let rwmixread = take_option(&mut options, &["rwmixread"]);
+
+    Ok(FioJobConfig {
+        ioengine: ioengine
+            .parse()
+            .map_err(|e| invalid("ioengine", format!("{}", e)))?,
+        rw: rw.parse().map_err(|e| invalid("rw", format!("{}", e)))?,
+        bs,
+        size,
+        numjobs: numjobs
+            .parse()
+            .map_err(|e| invalid("numjobs", format!("{}", e)))?,
+        iodepth: iodepth
+            .parse()
+            .map_err(|e| invalid("iodepth", format!("{}", e)))?,
+        direct,
+        buffered,
+        rwmixread: rwmixread
+            .map(|mix| mix.parse())
+            .transpose()
+            .map_err(|e| invalid("rwmixread", format!("{}", e)))?,
+        extra_options: options.into_iter().collect::<HashMap<_, _>>(),
+    })
+}
+
+/// Removes and returns the first of `keys` present in `options`.
+fn take_option(options: &mut BTreeMap<String, String>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| options.remove(*key))
+}
+
+/// Writes options, rendering `true` as a bare flag and `false` as `key=0`.
+fn render_options(out: &mut String, options: &[(String, String)]) {
+    for (key, value) in options {
+        match value.as_str() {
+            "true" => out.push_str(&format!("{}\n", key)),
+            "false" => out.push_str(&format!("{}=0\n", key)),
+            _ => out.push_str(&format!("{}={}\n", key, value)),
+        }
+    }
+}
+
+fn set_option(options: &mut Vec<(String, String)>, key: &str, value: &str) {
+    match options.iter_mut().find(|(k, _)| k == key) {
+        Some(existing) => existing.1 = value.to_string(),
+        None => options.push((key.to_string(), value.to_string())),
+    }
+}
+
+fn is_true(value: &str) -> bool {
+    matches!(value, "1" | "true" | "yes" | "on")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::storage::fio::{IoEngine, IoPattern};
+
+    const HAND_WRITTEN: &str = "\
+; NVMe qualification
+[global]
+ioengine=libaio
+direct=1
+filename=/dev/nvme0n1
+runtime=60
+time_based
+
+[seq-read]
+rw=read
+bs=1M
+iodepth=32
+
+[rand-write]
+stonewall
+rw=randwrite
+bs=4k
+iodepth=64
+numjobs=4
+
+# a later global only applies to the jobs below it
+[global]
+rwmixread=70
+
+[mixed]
+new_group
+rw=randrw
+";
+
+    #[test]
+    fn test_parse_hand_written_file() {
+        let file = FioJobFile::parse(HAND_WRITTEN).unwrap();
+        assert_eq!(file.jobs.len(), 3);
+        assert!(file.jobs[1].stonewall);
+        assert!(file.jobs[2].new_group);
+
+        let jobs = file.job_configs().unwrap();
+        let (name, seq) = &jobs[0];
+        assert_eq!(name, "seq-read");
+        assert!(matches!(seq.ioengine, IoEngine::Libaio));
+        assert!(matches!(seq.rw, IoPattern::Read));
+        assert!(seq.direct);
+        assert_eq!(seq.bs, "1M");
+        assert_eq!(seq.numjobs, 1);
+        assert_eq!(seq.extra_options["time_based"], "true");
+        assert_eq!(seq.extra_options["filename"], "/dev/nvme0n1");
+        assert_eq!(seq.rwmixread, None);
+
+        assert_eq!(jobs[1].1.numjobs, 4);
+        assert_eq!(jobs[2].1.rwmixread, Some(70));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let file = FioJobFile::parse(HAND_WRITTEN).unwrap();
+        let reparsed = FioJobFile::parse(&file.render()).unwrap();
+
+        assert_eq!(file, reparsed);
+    }
+
+    #[test]
+    fn test_render_from_configs() {
+        let target = StorageTarget {
+            path: "/mnt/data".into(),
+            target_type: "filesystem".into(),
+            options: HashMap::new(),
+        };
+        let mut file = FioJobFile::for_target(&target);
+        let mut config = FioJobConfig::new_random_write("4k", "1G", 2, 16);
+        config.extra_options.insert("verify".into(), "md5".into());
+        config
+            .extra_options
+            .insert("randrepeat".into(), "false".into());
+        file.add_job("write", &config).new_group = true;
+
+        let text = file.render();
+        assert!(text.starts_with("[global]\ndirectory=/mnt/data\n"));
+        assert!(
+            text.contains("[write]\nnew_group\nioengine=io_uring\nrw=randwrite\nbs=4k\nsize=1G\n")
+        );
+        assert!(text.contains("verify=md5\n"));
+        assert!(text.contains("randrepeat=0\n"));
+
+        let (_, parsed) = FioJobFile::parse(&text)
+            .unwrap()
+            .job_configs()
+            .unwrap()
+            .remove(0);
+        assert!(matches!(parsed.rw, IoPattern::RandWrite));
+        assert_eq!(parsed.size, "1G");
+        assert_eq!(parsed.numjobs, 2);
+        assert_eq!(parsed.iodepth, 16);
+        assert!(parsed.direct);
+        assert_eq!(parsed.extra_options["verify"], "md5");
+        assert_eq!(parsed.extra_options["randrepeat"], "0");
+        assert_eq!(parsed.extra_options["directory"], "/mnt/data");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(
+            FioJobFile::parse("bs=4k\n[job]\n"),
+            Err(JobFileError::SyntaxError { line: 1, .. })
+        ));
+        assert!(matches!(
+            FioJobFile::parse("[job\n"),
+            Err(JobFileError::SyntaxError { line: 1, .. })
+        ));
+        let err = FioJobFile::parse("[job]\nrw=sideways\n")
+            .unwrap()
+            .job_configs()
+            .unwrap_err();
+        assert!(err.to_string().contains("rw"));
+    }
+}
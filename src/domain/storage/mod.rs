@@ -1 +1,11 @@
 pub mod fio;
+pub mod history;
+pub mod jobfile;
+pub mod preflight;
+pub mod retention;
+pub mod safety;
+pub mod saturation;
+pub mod scratch;
+pub mod steady_state;
+pub mod sweep;
+pub mod units;
@@ -0,0 +1,385 @@
+//! Pre-flight checks before FIO creates its data files
+//!
+//! FIO lays out `size` bytes per job before it starts measuring: one file per job in a
+//! directory target, or a single file shared by all jobs in a file target.  Nothing stops a
+//! profile from asking for more than the filesystem holds, so [`Preflight::check`] works out
+//! how much a job needs, compares it with the space `statvfs` reports as free, and probes
+//! whether the filesystem accepts `O_DIRECT` when the job asks for direct I/O.
+//!
+//! A job that does not fit fails, or, when [`PreflightConfig::scale_to_fit`] is set, has its
+//! size scaled down to what fits.  Device targets are not checked: FIO does not create files
+//! on them.  A directory target's `filename` must name files inside the directory.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysperf_svr::domain::storage::fio::{FioJobConfig, StorageTarget};
+//! use sysperf_svr::domain::storage::preflight::{Preflight, PreflightConfig};
+//!
+//! let job = FioJobConfig { size: "256G".into(), numjobs: 16, ..Default::default() };
+//! let target = StorageTarget::new_file("./benchmark/data", None);
+//! match Preflight::new(PreflightConfig::default()).check(&job, &target) {
+//!     Ok(Some(capacity)) => println!("{} bytes needed", capacity.required),
+//!     Ok(None) => println!("nothing to check"),
+//!     Err(e) => eprintln!("{}", e),
+//! }
+//! ```
+
+use super::fio::{FioJobConfig, StorageTarget};
+use super::units::{format_bytes, SizeSpec, UnitError};
+use std::ffi::CString;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use thiserror::Error;
+
+/// Smallest size a job is scaled down to; below this the run is not worth doing
+const MIN_SCALED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Scaled sizes are rounded down to a multiple of this, which any block size divides
+const SCALE_ALIGNMENT: u64 = 1024 * 1024;
+
+/// How strict the checks are
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreflightConfig {
+    /// Scale the per-job size down to fit the free space instead of failing
+    pub scale_to_fit: bool,
+    /// Share of the filesystem, in percent, that must stay free once the data files exist
+    pub reserve_percent: u32,
+}
+
+impl Default for PreflightConfig {
+    fn default() -> Self {
+        Self {
+            scale_to_fit: false,
+            reserve_percent: 5,
+        }
+    }
+}
+
+/// Space a job needs and what it gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capacity {
+    /// Bytes the job's data files take at the requested size
+    pub required: u64,
+    /// Bytes that may be used: free space less the reserve
+    pub available: u64,
+    /// Size of each job's data, in bytes; smaller than requested when scaled
+    pub size: u64,
+    /// Whether `size` was scaled down to fit
+    pub scaled: bool,
+}
+
+/// Why a job cannot run
+#[derive(Debug, Error)]
+pub enum PreflightError {
+    #[error(transparent)]
+    InvalidSize(#[from] UnitError),
+    #[error(
+        "Not enough space in {}: {files} × {} needs {} but only {} is free (keeping {reserve_percent}% of the filesystem free); reduce size or numjobs{}",
+        .path.display(), format_bytes(*.size), format_bytes(*.required), format_bytes(*.available),
+        if *.scaled { "" } else { ", or set storage.scale_to_fit" }
+    )]
+    InsufficientSpace {
+        path: PathBuf,
+        files: u32,
+        size: u64,
+        required: u64,
+        available: u64,
+        reserve_percent: u32,
+        /// Whether scaling was allowed but could not make the job fit
+        scaled: bool,
+    },
+    #[error("The filesystem holding {} does not support O_DIRECT; set direct = false or use another target", .0.display())]
+    NoDirectIo(PathBuf),
+    #[error("filename \"{file}\" is outside the directory target {}; use a relative path or a file or device target", .dir.display())]
+    OutsideTarget { file: String, dir: PathBuf },
+    #[error("Cannot check {}: {source}", .path.display())]
+    Io { path: PathBuf, source: io::Error },
+}
+
+/// Checks jobs against the filesystems they write to
+#[derive(Debug, Clone, Default)]
+pub struct Preflight {
+    config: PreflightConfig,
+}
+
+impl Preflight {
+    /// Creates a checker with `config`.
+    pub fn new(config: PreflightConfig) -> Self {
+        Self { config }
+    }
+
+    /// Checks that `job` fits on `target` and that the target supports its I/O mode.
+    ///
+    /// # Returns
+    ///
+    /// The space the job needs and gets, or `None` when there is nothing to check: device
+    /// targets, file targets that are not regular files, and sizes that are empty or given as a
+    /// percentage.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the size is invalid, a directory target's `filename` is absolute
+    /// or leaves the directory, the job does not fit (even scaled, when allowed), the
+    /// filesystem rejects `O_DIRECT` for a direct job, or the target's filesystem cannot be
+    /// inspected.
+    pub fn check(
+        &self,
+        job: &FioJobConfig,
+        target: &StorageTarget,
+    ) -> Result<Option<Capacity>, PreflightError> {
+        if !matches!(
+            target.target_type.as_str(),
+            "directory" | "filesystem" | "file"
+        ) {
+            return Ok(None);
+        }
+        if target.is_directory() {
+            let outside = |file: &&str| {
+                let path = Path::new(file);
+                path.is_absolute() || path.components().any(|c| c == Component::ParentDir)
+            };
+            if let Some(file) = job
+                .extra_options
+                .get("filename")
+                .and_then(|filename| filename.split(':').find(outside))
+            {
+                return Err(PreflightError::OutsideTarget {
+                    file: file.to_string(),
+                    dir: target.path.clone(),
+                });
+            }
+        }
+        let size = match job.size_spec()? {
+            Some(SizeSpec::Bytes(size)) => size.bytes(),
+            // A share of the file or device, or all of it, never outgrows it
+            Some(SizeSpec::Percent(_)) | None => return Ok(None),
+        };
+
+        // A directory gets a file per job; jobs sharing a file target share its size
+        let (dir, files, existing) = if target.is_directory() {
+            (target.path.clone(), job.numjobs.max(1), 0)
+        } else {
+            let metadata = fs::metadata(&target.path).ok();
+            // A "file" that is really a device node or pipe is not laid out by FIO
+            if metadata.as_ref().is_some_and(|m| !m.is_file()) {
+                return Ok(None);
+            }
+            let existing = metadata.map_or(0, |m| m.len());
+            let dir = target.path.parent().unwrap_or(Path::new(".")).to_path_buf();
+            (dir, 1, existing)
+        };
+
+        if job.direct {
+            self.check_direct_io(&dir)?;
+        }
+        self.check_space(&dir, size, files, existing).map(Some)
+    }
+
+    /// Checks that `files` files of `size` bytes fit in `dir`, counting `existing` bytes
+    /// already allocated to them as available.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the files do not fit (even scaled, when allowed), or `dir`'s
+    /// filesystem cannot be inspected.
+    pub fn check_space(
+        &self,
+        dir: &Path,
+        size: u64,
+        files: u32,
+        existing: u64,
+    ) -> Result<Capacity, PreflightError> {
+        let files = files.max(1);
+        let (free, total) = filesystem_space(dir)?;
+        let reserve = total / 100 * u64::from(self.config.reserve_percent);
+        let available = (free + existing).saturating_sub(reserve);
+        let required = size.saturating_mul(u64::from(files));
+
+        if required <= available {
+            return Ok(Capacity {
+                required,
+                available,
+                size,
+                scaled: false,
+            });
+        }
+
+        let scaled = available / u64::from(files) / SCALE_ALIGNMENT * SCALE_ALIGNMENT;
+        if self.config.scale_to_fit && scaled >= MIN_SCALED_SIZE.min(size) {
+            return Ok(Capacity {
+                required,
+                available,
+                size: scaled,
+                scaled: true,
+            });
+        }
+        Err(PreflightError::InsufficientSpace {
+            path: dir.to_path_buf(),
+            files,
+            size,
+            required,
+            available,
+            reserve_percent: self.config.reserve_percent,
+            scaled: self.config.scale_to_fit,
+        })
+    }
+
+    /// Checks that files in `dir` can be opened with `O_DIRECT`.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`PreflightError::NoDirectIo`] if the filesystem rejects `O_DIRECT`, and
+    /// [`PreflightError::Io`] if no file can be created in `dir`.
+    pub fn check_direct_io(&self, dir: &Path) -> Result<(), PreflightError> {
+        static PROBES: AtomicU32 = AtomicU32::new(0);
+        let dir = existing_ancestor(dir);
+        let probe = dir.join(format!(
+            ".sysperf-direct-probe-{}-{}",
+            std::process::id(),
+            PROBES.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let opened = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(&probe);
+        let _ = fs::remove_file(&probe);
+        match opened {
+            Ok(_) => Ok(()),
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+                Err(PreflightError::NoDirectIo(dir))
+            }
+            Err(source) => Err(PreflightError::Io { path: dir, source }),
+        }
+    }
+}
+
+/// Free and total bytes of the filesystem holding `path`, or its nearest existing parent.
+fn filesystem_space(path: &Path) -> Result<(u64, u64), PreflightError> {
+    let path = existing_ancestor(path);
+    let io_error = |source| PreflightError::Io {
+        path: path.clone(),
+        source,
+    };
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io_error(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid NUL-terminated string and stat a writable statvfs
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io_error(io::Error::last_os_error()));
+    }
+    let block = stat.f_frsize as u64;
+    Ok((stat.f_bavail as u64 * block, stat.f_blocks as u64 * block))
+}
+
+/// `path` if it exists, otherwise its nearest existing parent (FIO creates the rest).
+fn existing_ancestor(path: &Path) -> PathBuf {
+    let path = if path.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        path
+    };
+    path.ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.exists())
+        .unwrap_or(Path::new("."))
+        .to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let (free, _) = filesystem_space(dir.path()).unwrap();
+        let preflight = Preflight::new(PreflightConfig {
+            scale_to_fit: false,
+            reserve_percent: 0,
+        });
+
+        let fits = preflight.check_space(dir.path(), 1 << 20, 4, 0).unwrap();
+        assert_eq!(fits.required, 4 << 20);
+        assert!(!fits.scaled);
+
+        // The directory does not need to exist yet
+        let e = preflight
+            .check_space(&dir.path().join("new/dir"), free, 16, 0)
+            .unwrap_err();
+        assert!(matches!(
+            e,
+            PreflightError::InsufficientSpace { files: 16, .. }
+        ));
+        assert!(e.to_string().contains("storage.scale_to_fit"));
+
+        let scaling = Preflight::new(PreflightConfig {
+            scale_to_fit: true,
+            reserve_percent: 0,
+        });
+        let scaled = scaling.check_space(dir.path(), free, 2, 0).unwrap();
+        assert!(scaled.scaled);
+        assert!(scaled.size * 2 <= free);
+        assert_eq!(scaled.size % SCALE_ALIGNMENT, 0);
+    }
+
+    #[test]
+    fn test_check_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let preflight = Preflight::default();
+        let job = FioJobConfig {
+            size: "1M".into(),
+            numjobs: 8,
+            direct: false,
+            ..Default::default()
+        };
+
+        let directory = StorageTarget {
+            path: dir.path().join("fio"),
+            target_type: "directory".into(),
+            options: Default::default(),
+        };
+        assert_eq!(
+            preflight.check(&job, &directory).unwrap().unwrap().required,
+            8 << 20
+        );
+
+        // Jobs share a file target, and its existing bytes count as available
+        let file = StorageTarget::new_file(dir.path().join("data"), None);
+        assert_eq!(
+            preflight.check(&job, &file).unwrap().unwrap().required,
+            1 << 20
+        );
+
+        let device = StorageTarget::new_device("/dev/nvme0n1", None);
+        assert!(preflight.check(&job, &device).unwrap().is_none());
+        let null = StorageTarget::new_file("/dev/null", None);
+        assert!(preflight.check(&job, &null).unwrap().is_none());
+        let invalid = FioJobConfig {
+            size: "lots".into(),
+            ..job.clone()
+        };
+        assert!(matches!(
+            preflight.check(&invalid, &file),
+            Err(PreflightError::InvalidSize(_))
+        ));
+
+        // A directory target's files stay inside it
+        for filename in ["/dev/nvme0n1", "a:../b"] {
+            let mut named = job.clone();
+            named
+                .extra_options
+                .insert("filename".into(), filename.into());
+            assert!(matches!(
+                preflight.check(&named, &directory),
+                Err(PreflightError::OutsideTarget { .. })
+            ));
+        }
+    }
+}
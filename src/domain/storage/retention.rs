@@ -0,0 +1,129 @@
+//! Retention policy for stored results
+//!
+//! Runs and metric samples older than the retention window are pruned, except for runs that
+//! have been pinned (typically baselines that later runs are compared against), which are kept
+//! regardless of age.
+//!
+//! # Example
+//!
+//! ```rust
+//! use sysperf_svr::domain::storage::retention::RetentionPolicy;
+//!
+//! let policy = RetentionPolicy::new(30);
+//! let now = 1719792000;
+//! assert_eq!(policy.cutoff(now), Some(now - 30 * 86400));
+//! assert_eq!(RetentionPolicy::new(0).cutoff(now), None);
+//! ```
+
+use super::history::RunRecord;
+use serde::{Deserialize, Serialize};
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+/// How long stored results are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Days to keep runs and metric samples; 0 keeps them forever
+    pub retention_days: u32,
+}
+
+/// What a prune removed, or would remove on a dry run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneReport {
+    /// Unix timestamp (seconds) before which unpinned results expire
+    pub cutoff: Option<i64>,
+    /// Expired runs
+    pub runs: Vec<RunRecord>,
+    /// Runs older than the cutoff that were kept because they are pinned
+    pub pinned_kept: usize,
+    /// Keys of expired metric samples
+    pub metrics: Vec<String>,
+    /// Whether nothing was actually deleted
+    pub dry_run: bool,
+}
+
+impl RetentionPolicy {
+    /// Creates a policy keeping results for `retention_days` days (0 = forever).
+    pub fn new(retention_days: u32) -> Self {
+        Self { retention_days }
+    }
+
+    /// Unix timestamp before which results expire, or `None` when nothing expires.
+    pub fn cutoff(&self, now: i64) -> Option<i64> {
+        (self.retention_days > 0).then(|| now - i64::from(self.retention_days) * SECONDS_PER_DAY)
+    }
+
+    /// Splits `runs` into those that expire at `now` and the number of expired runs kept
+    /// because they are pinned.
+    pub fn expired_runs(&self, runs: Vec<RunRecord>, now: i64) -> (Vec<RunRecord>, usize) {
+        let Some(cutoff) = self.cutoff(now) else {
+            return (Vec::new(), 0);
+        };
+
+        let (old, _): (Vec<_>, Vec<_>) = runs.into_iter().partition(|r| r.timestamp < cutoff);
+        let (pinned, expired): (Vec<_>, Vec<_>) = old.into_iter().partition(|r| r.pinned);
+        (expired, pinned.len())
+    }
+
+    /// Whether the metric sample stored under `key` expires at `now`.
+    ///
+    /// Sample keys end in the sample's Unix timestamp in milliseconds
+    /// (`<metric>/<timestamp_ms>`); keys that do not are never expired.
+    pub fn metric_expired(&self, key: &str, now: i64) -> bool {
+        let Some(cutoff) = self.cutoff(now) else {
+            return false;
+        };
+        key.rsplit('/')
+            .next()
+            .and_then(|ts| ts.parse::<i64>().ok())
+            .is_some_and(|ts_ms| ts_ms < cutoff * 1000)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::storage::fio::{FioResult, StorageTarget};
+
+    const NOW: i64 = 1719792000;
+
+    fn run(age_days: i64, pinned: bool) -> RunRecord {
+        let mut run = RunRecord::new(
+            "job",
+            &StorageTarget::new_file("/tmp/x", None),
+            FioResult::default(),
+        );
+        run.timestamp = NOW - age_days * SECONDS_PER_DAY;
+        run.pinned = pinned;
+        run
+    }
+
+    #[test]
+    fn test_expired_runs_skip_pinned() {
+        let policy = RetentionPolicy::new(30);
+        let runs = vec![run(1, false), run(31, false), run(45, true), run(90, false)];
+
+        let (expired, pinned_kept) = policy.expired_runs(runs.clone(), NOW);
+        assert_eq!(expired.len(), 2);
+        assert!(expired
+            .iter()
+            .all(|r| !r.pinned && r.timestamp < NOW - 30 * 86400));
+        assert_eq!(pinned_kept, 1);
+
+        let (expired, pinned_kept) = RetentionPolicy::new(0).expired_runs(runs, NOW);
+        assert!(expired.is_empty());
+        assert_eq!(pinned_kept, 0);
+    }
+
+    #[test]
+    fn test_metric_expiry_from_key() {
+        let policy = RetentionPolicy::new(7);
+        let old = (NOW - 8 * SECONDS_PER_DAY) * 1000;
+        let recent = (NOW - 6 * SECONDS_PER_DAY) * 1000;
+
+        assert!(policy.metric_expired(&format!("mpstat/{}", old), NOW));
+        assert!(!policy.metric_expired(&format!("mpstat/{}", recent), NOW));
+        assert!(!policy.metric_expired("mpstat/latest", NOW));
+        assert!(!RetentionPolicy::new(0).metric_expired(&format!("mpstat/{}", old), NOW));
+    }
+}
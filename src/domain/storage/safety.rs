@@ -0,0 +1,654 @@
+//! Safety checks before benchmarking raw block devices
+//!
+//! A write or trim workload on a block device destroys whatever is stored on it.  Before such a
+//! workload runs, [`SafetyGuard::check`] inspects the device, refuses it outright while the
+//! device is in use (mounted, active swap, or held by an md array, LVM or another device-mapper
+//! volume), and otherwise requires the data loss to be acknowledged for that target.
+//!
+//! Inspection reads `/proc/mounts` (falling back to the device number of each mountpoint for
+//! sources such as `/dev/root`), `/proc/swaps`, `/sys/class/block` and the first bytes of the
+//! device itself.  Signatures cannot be read without permission to open the device; the other
+//! checks still apply then.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysperf_svr::domain::storage::fio::StorageTarget;
+//! use sysperf_svr::domain::storage::safety::SafetyGuard;
+//!
+//! let target = StorageTarget::new_device("/dev/nvme0n1", None);
+//! if let Some(report) = SafetyGuard::new().inspect(&target) {
+//!     for finding in &report.in_use {
+//!         println!("{}", finding);
+//!     }
+//! }
+//! ```
+
+use super::fio::StorageTarget;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Target types that name a raw device rather than a file or directory
+const DEVICE_TYPES: &[&str] = &["block_device", "device_mapper", "device"];
+
+/// Bytes read from the start of a device to look for signatures (covers btrfs at 64 KiB)
+const SIGNATURE_BYTES: usize = 68 * 1024;
+
+/// Something that keeps a device busy; writing to it would corrupt live data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceUse {
+    /// `device` (the target or a partition or holder of it) is mounted
+    Mounted { device: String, mountpoint: String },
+    /// `device` is an active swap area
+    Swap { device: String },
+    /// `holder` is built on `device`
+    Held {
+        device: String,
+        holder: String,
+        kind: HolderKind,
+    },
+}
+
+/// What a holder device is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HolderKind {
+    /// A software RAID array
+    Md,
+    /// An LVM logical volume, with its device-mapper name
+    Lvm(String),
+    /// Any other device-mapper volume (dm-crypt, multipath, ...), with its name
+    DeviceMapper(String),
+    Other,
+}
+
+/// Data found on a device that is not in use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceContent {
+    /// Partitions the kernel knows of
+    Partitions(Vec<String>),
+    /// An on-disk signature, such as "GPT partition table" or "ext4 filesystem"
+    Signature(&'static str),
+}
+
+/// What [`SafetyGuard::inspect`] found on a block device
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DeviceReport {
+    /// Kernel name of the device (e.g. "nvme0n1")
+    pub device: String,
+    /// Reasons the device must not be written to
+    pub in_use: Vec<DeviceUse>,
+    /// Data that writing would destroy
+    pub contents: Vec<DeviceContent>,
+}
+
+/// Why a workload may not run on a target
+#[derive(Debug, Error)]
+pub enum SafetyError {
+    #[error("Refusing to write to {target} ({path}): {}", join(.uses))]
+    InUse {
+        target: String,
+        path: PathBuf,
+        uses: Vec<DeviceUse>,
+    },
+    #[error(
+        "Writing to {target} ({path}) destroys the data on it{}; data loss on {target} has not been allowed with --i-understand-data-loss",
+        found(.contents)
+    )]
+    NotAcknowledged {
+        target: String,
+        path: PathBuf,
+        contents: Vec<DeviceContent>,
+    },
+}
+
+/// Inspects block devices through `/proc` and `/sys`
+#[derive(Debug, Clone)]
+pub struct SafetyGuard {
+    root: PathBuf,
+}
+
+impl Default for SafetyGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SafetyGuard {
+    /// Creates a guard inspecting the running system.
+    pub fn new() -> Self {
+        Self::with_root("/")
+    }
+
+    /// Creates a guard reading `proc`, `sys` and `dev` below `root` instead of `/`.
+    ///
+    /// Target paths are still resolved on the running system.
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Whether `target` names a raw device, so that writing to it overwrites its contents.
+    pub fn is_raw(&self, target: &StorageTarget) -> bool {
+        DEVICE_TYPES.contains(&target.target_type.as_str()) || self.device_name(target).is_some()
+    }
+
+    /// Checks that a workload may run on `target`, named `name` in the configuration.
+    ///
+    /// Workloads that only read, and workloads on files and directories, always pass.
+    ///
+    /// # Returns
+    ///
+    /// What was found on the device, when the target is a block device.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`SafetyError::InUse`] when a writing workload targets a device in use, and
+    /// [`SafetyError::NotAcknowledged`] when it targets any other raw device without
+    /// `acknowledged`.
+    pub fn check(
+        &self,
+        name: &str,
+        target: &StorageTarget,
+        writes: bool,
+        acknowledged: bool,
+    ) -> Result<Option<DeviceReport>, SafetyError> {
+        let report = self.inspect(target);
+        if !writes || !self.is_raw(target) {
+            return Ok(report);
+        }
+
+        let (uses, contents) = report
+            .as_ref()
+            .map(|r| (r.in_use.clone(), r.contents.clone()))
+            .unwrap_or_default();
+        if !uses.is_empty() {
+            return Err(SafetyError::InUse {
+                target: name.to_string(),
+                path: target.path.clone(),
+                uses,
+            });
+        }
+        if !acknowledged {
+            return Err(SafetyError::NotAcknowledged {
+                target: name.to_string(),
+                path: target.path.clone(),
+                contents,
+            });
+        }
+        Ok(report)
+    }
+
+    /// Inspects the block device `target` points at.
+    ///
+    /// Returns `None` when the target is not a block device.
+    pub fn inspect(&self, target: &StorageTarget) -> Option<DeviceReport> {
+        self.device_name(target)
+            .map(|name| self.inspect_device(&name))
+    }
+
+    /// Inspects the block device with kernel name `device`.
+    pub fn inspect_device(&self, device: &str) -> DeviceReport {
+        let partitions = self.partitions(device);
+
+        // Everything built on the device counts: its partitions and their holders, recursively
+        let mut related: BTreeSet<String> = BTreeSet::new();
+        let mut holders = Vec::new();
+        let mut pending: Vec<String> = std::iter::once(device.to_string())
+            .chain(partitions.iter().cloned())
+            .collect();
+        while let Some(name) = pending.pop() {
+            if !related.insert(name.clone()) {
+                continue;
+            }
+            for holder in self.holders(&name) {
+                holders.push((name.clone(), holder.clone()));
+                pending.push(holder);
+            }
+        }
+
+        let mut in_use = Vec::new();
+        for (source, mountpoint) in self.table("proc/mounts") {
+            // Sources such as /dev/root have no device node; the mountpoint's st_dev still
+            // names the device
+            let name = self
+                .resolve(&source)
+                .filter(|n| related.contains(n))
+                .or_else(|| {
+                    source
+                        .starts_with("/dev/")
+                        .then(|| self.mounted_device(&mountpoint))?
+                        .filter(|n| related.contains(n))
+                });
+            if let Some(name) = name {
+                in_use.push(DeviceUse::Mounted {
+                    device: name,
+                    mountpoint,
+                });
+            }
+        }
+        for (source, _) in self.table("proc/swaps").into_iter().skip(1) {
+            if let Some(name) = self.resolve(&source).filter(|n| related.contains(n)) {
+                in_use.push(DeviceUse::Swap { device: name });
+            }
+        }
+        for (device, holder) in holders {
+            let kind = self.holder_kind(&holder);
+            in_use.push(DeviceUse::Held {
+                device,
+                holder,
+                kind,
+            });
+        }
+
+        let mut contents = Vec::new();
+        if !partitions.is_empty() {
+            contents.push(DeviceContent::Partitions(partitions));
+        }
+        contents.extend(
+            self.signatures(device)
+                .into_iter()
+                .map(DeviceContent::Signature),
+        );
+
+        DeviceReport {
+            device: device.to_string(),
+            in_use,
+            contents,
+        }
+    }
+
+    /// Kernel name of the block device at the target's path.
+    fn device_name(&self, target: &StorageTarget) -> Option<String> {
+        let metadata = fs::metadata(&target.path).ok()?;
+        if !metadata.file_type().is_block_device() {
+            return None;
+        }
+        self.block_device(metadata.rdev())
+    }
+
+    /// Kernel name of the block device holding the filesystem mounted on `mountpoint`.
+    fn mounted_device(&self, mountpoint: &str) -> Option<String> {
+        let path = self.root.join(mountpoint.trim_start_matches('/'));
+        self.block_device(fs::metadata(path).ok()?.dev())
+    }
+
+    /// Kernel name of the block device numbered `dev`, looked up in `/sys/dev/block`.
+    fn block_device(&self, dev: u64) -> Option<String> {
+        let sysfs = self.root.join(format!(
+            "sys/dev/block/{}:{}",
+            libc::major(dev),
+            libc::minor(dev)
+        ));
+        let name = fs::canonicalize(sysfs).ok()?.file_name()?.to_owned();
+        Some(name.to_string_lossy().into_owned())
+    }
+
+    /// Partitions of `device`, which sysfs lists as subdirectories holding a `partition` file.
+    fn partitions(&self, device: &str) -> Vec<String> {
+        let mut partitions: Vec<String> = self
+            .entries(&format!("sys/class/block/{}", device))
+            .into_iter()
+            .filter(|name| {
+                self.root
+                    .join(format!("sys/class/block/{}/{}/partition", device, name))
+                    .exists()
+            })
+            .collect();
+        partitions.sort();
+        partitions
+    }
+
+    fn holders(&self, device: &str) -> Vec<String> {
+        let mut holders = self.entries(&format!("sys/class/block/{}/holders", device));
+        holders.sort();
+        holders
+    }
+
+    fn holder_kind(&self, holder: &str) -> HolderKind {
+        let dm = |file: &str| {
+            fs::read_to_string(
+                self.root
+                    .join(format!("sys/class/block/{}/dm/{}", holder, file)),
+            )
+            .map(|s| s.trim().to_string())
+        };
+        if holder.starts_with("md") {
+            HolderKind::Md
+        } else if let Ok(name) = dm("name") {
+            match dm("uuid") {
+                Ok(uuid) if uuid.starts_with("LVM-") => HolderKind::Lvm(name),
+                _ => HolderKind::DeviceMapper(name),
+            }
+        } else {
+            HolderKind::Other
+        }
+    }
+
+    /// Kernel name of the device at `source`, a path such as `/dev/sda1` or `/dev/mapper/vg-lv`.
+    fn resolve(&self, source: &str) -> Option<String> {
+        let relative = source.strip_prefix("/dev/")?;
+        let path = self.root.join("dev").join(relative);
+        let resolved = fs::canonicalize(&path).unwrap_or(path);
+        let name = resolved.file_name()?.to_string_lossy().into_owned();
+
+        // Without udev links, map a device-mapper name to its dm-N node through sysfs
+        if let Some(dm_name) = relative.strip_prefix("mapper/") {
+            if name == dm_name {
+                return self
+                    .entries("sys/class/block")
+                    .into_iter()
+                    .find(|entry| {
+                        fs::read_to_string(
+                            self.root.join(format!("sys/class/block/{}/dm/name", entry)),
+                        )
+                        .is_ok_and(|s| s.trim() == dm_name)
+                    })
+                    .or(Some(name));
+            }
+        }
+        Some(name)
+    }
+
+    /// Reads the first two columns of a whitespace-separated table such as `/proc/mounts`.
+    fn table(&self, file: &str) -> Vec<(String, String)> {
+        let content = fs::read_to_string(self.root.join(file)).unwrap_or_default();
+        content
+            .lines()
+            .filter_map(|line| {
+                let mut columns = line.split_whitespace();
+                Some((
+                    unescape(columns.next()?),
+                    unescape(columns.next().unwrap_or_default()),
+                ))
+            })
+            .collect()
+    }
+
+    fn entries(&self, dir: &str) -> Vec<String> {
+        fs::read_dir(self.root.join(dir))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Signatures found in the first bytes of `/dev/<device>`; none when it cannot be read.
+    fn signatures(&self, device: &str) -> Vec<&'static str> {
+        let mut buffer = Vec::with_capacity(SIGNATURE_BYTES);
+        let read = File::open(self.root.join("dev").join(device))
+            .and_then(|file| file.take(SIGNATURE_BYTES as u64).read_to_end(&mut buffer));
+        match read {
+            Ok(_) => signatures(&buffer),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Recognises partition tables, filesystems, LVM, md and swap signatures in the first bytes of
+/// a device.
+pub fn signatures(data: &[u8]) -> Vec<&'static str> {
+    let at = |offset: usize, magic: &[u8]| data.get(offset..offset + magic.len()) == Some(magic);
+    let mut found = Vec::new();
+
+    if at(512, b"EFI PART") || at(4096, b"EFI PART") {
+        found.push("GPT partition table");
+    } else if at(510, &[0x55, 0xaa]) {
+        found.push("MBR partition table");
+    }
+    if (0..4).any(|sector| at(sector * 512, b"LABELONE") && at(sector * 512 + 24, b"LVM2 001")) {
+        found.push("LVM physical volume");
+    }
+    if at(0, &[0xfc, 0x4e, 0x2b, 0xa9]) || at(4096, &[0xfc, 0x4e, 0x2b, 0xa9]) {
+        found.push("md RAID member");
+    }
+    if at(1080, &[0x53, 0xef]) {
+        found.push("ext2/3/4 filesystem");
+    }
+    if at(0, b"XFSB") {
+        found.push("XFS filesystem");
+    }
+    if at(65536 + 64, b"_BHRfS_M") {
+        found.push("btrfs filesystem");
+    }
+    if at(4086, b"SWAPSPACE2") || at(4086, b"SWAP-SPACE") {
+        found.push("swap area");
+    }
+    found
+}
+
+/// Decodes the octal escapes `/proc/mounts` uses for spaces and other special characters.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match (bytes[i], octal) {
+            (b'\\', Some(byte)) => {
+                decoded.push(byte);
+                i += 4;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+impl fmt::Display for DeviceUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceUse::Mounted { device, mountpoint } if mountpoint == "/" => {
+                write!(f, "{} backs the root filesystem", device)
+            }
+            DeviceUse::Mounted { device, mountpoint } => {
+                write!(f, "{} is mounted on {}", device, mountpoint)
+            }
+            DeviceUse::Swap { device } => write!(f, "{} is an active swap area", device),
+            DeviceUse::Held {
+                device,
+                holder,
+                kind,
+            } => match kind {
+                HolderKind::Md => write!(f, "{} is part of the md array {}", device, holder),
+                HolderKind::Lvm(name) => {
+                    write!(f, "{} backs the LVM volume {} ({})", device, name, holder)
+                }
+                HolderKind::DeviceMapper(name) => write!(
+                    f,
+                    "{} backs the device-mapper volume {} ({})",
+                    device, name, holder
+                ),
+                HolderKind::Other => write!(f, "{} is held by {}", device, holder),
+            },
+        }
+    }
+}
+
+impl fmt::Display for DeviceContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceContent::Partitions(partitions) => {
+                write!(f, "partitions {}", partitions.join(", "))
+            }
+            DeviceContent::Signature(signature) => write!(f, "a {}", signature),
+        }
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn found(contents: &[DeviceContent]) -> String {
+    if contents.is_empty() {
+        String::new()
+    } else {
+        format!(", including {}", join(contents))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Lays out /proc and /sys for a disk `sda` with partitions sda1 (mounted on /) and sda2
+    /// (an LVM physical volume), a disk `sdb` in the md array md0, and an unused disk `sdc`.
+    fn fake_system(root: &Path) {
+        let write = |path: &str, content: &[u8]| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        let mkdir = |path: &str| fs::create_dir_all(root.join(path)).unwrap();
+
+        write("proc/mounts", b"/dev/sda1 / ext4 rw 0 0\n/dev/mapper/vg-data /mnt/my\\040data xfs rw 0 0\nproc /proc proc rw 0 0\n");
+        write(
+            "proc/swaps",
+            b"Filename\tType\tSize\tUsed\tPriority\n/swapfile file 1024 0 -2\n",
+        );
+        write("sys/class/block/sda/sda1/partition", b"1");
+        write("sys/class/block/sda/sda2/partition", b"2");
+        mkdir("sys/class/block/sda/holders");
+        mkdir("sys/class/block/sda1/holders");
+        mkdir("sys/class/block/sda2/holders/dm-0");
+        write("sys/class/block/dm-0/dm/name", b"vg-data\n");
+        write("sys/class/block/dm-0/dm/uuid", b"LVM-abc\n");
+        mkdir("sys/class/block/sdb/holders/md0");
+        mkdir("sys/class/block/sdc/holders");
+
+        let mut gpt = vec![0u8; 8192];
+        gpt[512..520].copy_from_slice(b"EFI PART");
+        gpt[1080..1082].copy_from_slice(&[0x53, 0xef]);
+        write("dev/sdc", &gpt);
+    }
+
+    #[test]
+    fn test_inspect_device() {
+        let root = tempfile::tempdir().unwrap();
+        fake_system(root.path());
+        let guard = SafetyGuard::with_root(root.path());
+
+        let sda = guard.inspect_device("sda");
+        let uses: Vec<String> = sda.in_use.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            uses,
+            vec![
+                "sda1 backs the root filesystem",
+                "dm-0 is mounted on /mnt/my data",
+                "sda2 backs the LVM volume vg-data (dm-0)",
+            ]
+        );
+        assert_eq!(
+            sda.contents,
+            vec![DeviceContent::Partitions(vec![
+                "sda1".into(),
+                "sda2".into()
+            ])]
+        );
+
+        let sdb = guard.inspect_device("sdb");
+        assert_eq!(sdb.in_use[0].to_string(), "sdb is part of the md array md0");
+
+        let sdc = guard.inspect_device("sdc");
+        assert!(sdc.in_use.is_empty());
+        assert_eq!(
+            sdc.contents,
+            vec![
+                DeviceContent::Signature("GPT partition table"),
+                DeviceContent::Signature("ext2/3/4 filesystem"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mount_without_device_node() {
+        let root = tempfile::tempdir().unwrap();
+        fake_system(root.path());
+        let write = |path: &str, content: &[u8]| {
+            let path = root.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+
+        // /dev/root cannot be resolved; the device is found from the mountpoint's st_dev
+        write("proc/mounts", b"/dev/root /srv ext4 rw 0 0\n");
+        write("sys/class/block/sdd/sdd1/partition", b"1");
+        fs::create_dir_all(root.path().join("srv")).unwrap();
+        fs::create_dir_all(root.path().join("sys/class/block/sdd1")).unwrap();
+        let dev = fs::metadata(root.path().join("srv")).unwrap().dev();
+        let link = format!("sys/dev/block/{}:{}", libc::major(dev), libc::minor(dev));
+        fs::create_dir_all(root.path().join("sys/dev/block")).unwrap();
+        std::os::unix::fs::symlink(
+            root.path().join("sys/class/block/sdd1"),
+            root.path().join(link),
+        )
+        .unwrap();
+
+        let guard = SafetyGuard::with_root(root.path());
+        let uses: Vec<String> = guard
+            .inspect_device("sdd")
+            .in_use
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(uses, vec!["sdd1 is mounted on /srv"]);
+        assert!(guard.inspect_device("sdc").in_use.is_empty());
+    }
+
+    #[test]
+    fn test_check_requires_acknowledgement_for_raw_writes() {
+        let guard = SafetyGuard::new();
+        let device = StorageTarget::new_device("/nonexistent/nvme0n1", None);
+        let file = StorageTarget::new_file("/nonexistent/data", None);
+
+        assert!(guard.check("nvme", &device, false, false).is_ok());
+        assert!(guard.check("data", &file, true, false).is_ok());
+        assert!(guard.check("nvme", &device, true, true).is_ok());
+
+        let e = guard.check("nvme", &device, true, false).unwrap_err();
+        assert!(matches!(e, SafetyError::NotAcknowledged { .. }));
+        assert!(e
+            .to_string()
+            .contains("data loss on nvme has not been allowed"));
+    }
+
+    #[test]
+    fn test_signatures() {
+        let mut data = vec![0u8; 70 * 1024];
+        assert!(signatures(&data).is_empty());
+
+        data[510..512].copy_from_slice(&[0x55, 0xaa]);
+        data[512..520].copy_from_slice(b"LABELONE");
+        data[536..544].copy_from_slice(b"LVM2 001");
+        data[4086..4096].copy_from_slice(b"SWAPSPACE2");
+        data[65600..65608].copy_from_slice(b"_BHRfS_M");
+        assert_eq!(
+            signatures(&data),
+            vec![
+                "MBR partition table",
+                "LVM physical volume",
+                "btrfs filesystem",
+                "swap area"
+            ]
+        );
+        assert!(signatures(&data[..100]).is_empty());
+    }
+}
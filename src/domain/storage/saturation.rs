@@ -0,0 +1,323 @@
+//! Queue-depth saturation analysis.
+//!
+//! Given the results of an iodepth sweep, this module finds the queue depth at which a device
+//! saturates: the point after which adding more outstanding I/O no longer buys proportionally
+//! more IOPS and only makes requests wait longer.
+//!
+//! By Little's law the number of requests in flight equals throughput times latency
+//! (`L = λ·W`).  Below the knee, raising the queue depth raises IOPS while latency stays
+//! roughly flat.  Past the knee IOPS is pinned at the device's maximum, so every extra
+//! request only adds queueing delay and latency grows linearly with depth (`W ≈ L / λmax`).
+//!
+//! # Example
+//!
+//! ```
+//! use sysperf_svr::domain::storage::fio::IoStats;
+//! use sysperf_svr::domain::storage::saturation::{analyze_queue_depth, KneeCriteria};
+//!
+//! let stats = |iops: f64, lat_usec: f64| IoStats { iops, lat_usec, ..Default::default() };
+//! let series = vec![
+//!     (1, stats(10_000.0, 100.0)),
+//!     (8, stats(75_000.0, 105.0)),
+//!     (16, stats(90_000.0, 175.0)),
+//!     (32, stats(92_000.0, 345.0)),
+//! ];
+//!
+//! let report = analyze_queue_depth(&series, &KneeCriteria::default()).unwrap();
+//! assert_eq!(report.recommended_iodepth, 8);
+//! ```
+
+use super::fio::IoStats;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Microseconds per second, used to apply Little's law to FIO's latencies.
+const USEC_PER_SEC: f64 = 1_000_000.0;
+
+/// Errors that can occur during saturation analysis
+#[derive(Debug, Error)]
+pub enum SaturationError {
+    /// Fewer distinct queue depths than needed to measure a trend
+    #[error("Need at least 2 distinct queue depths, got {0}")]
+    NotEnoughPoints(usize),
+
+    /// A point cannot be used for the analysis
+    #[error("Invalid measurement at iodepth {iodepth}: {reason}")]
+    InvalidPoint { iodepth: u32, reason: String },
+}
+
+/// Thresholds deciding when a queue-depth step counts as saturated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KneeCriteria {
+    /// Minimum relative IOPS gain per relative queue-depth increase for a step to still count
+    /// as scaling (1.0 is perfectly linear scaling)
+    pub min_scaling_efficiency: f64,
+    /// Minimum relative latency increase for a step to count as queueing
+    pub min_latency_growth: f64,
+    /// Optional p99 latency budget in microseconds; depths above it are never recommended
+    pub max_p99_usec: Option<f64>,
+}
+
+impl Default for KneeCriteria {
+    fn default() -> Self {
+        Self {
+            min_scaling_efficiency: 0.25,
+            min_latency_growth: 0.10,
+            max_p99_usec: None,
+        }
+    }
+}
+
+/// Derived metrics for one queue depth
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueDepthPoint {
+    /// Queue depth of the run
+    pub iodepth: u32,
+    /// Measured IOPS
+    pub iops: f64,
+    /// Mean latency in microseconds
+    pub lat_usec: f64,
+    /// 99th percentile latency in microseconds
+    pub lat_usec_p99: f64,
+    /// Requests actually in flight according to Little's law (`IOPS × mean latency`)
+    pub effective_qd: f64,
+    /// Relative IOPS gain divided by relative depth increase since the previous point
+    pub scaling_efficiency: Option<f64>,
+    /// Relative latency increase since the previous point
+    pub latency_growth: Option<f64>,
+}
+
+/// Outcome of a queue-depth saturation analysis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaturationReport {
+    /// Per-depth metrics, ordered by queue depth
+    pub points: Vec<QueueDepthPoint>,
+    /// Last queue depth before IOPS flattened while latency rose, if the sweep reached it
+    pub knee_iodepth: Option<u32>,
+    /// Recommended operating queue depth
+    pub recommended_iodepth: u32,
+    /// Highest IOPS seen across the sweep
+    pub peak_iops: f64,
+}
+
+impl SaturationReport {
+    /// Whether the sweep went deep enough to saturate the device.
+    pub fn saturated(&self) -> bool {
+        self.knee_iodepth.is_some()
+    }
+
+    /// Metrics at the recommended queue depth.
+    pub fn recommended(&self) -> &QueueDepthPoint {
+        self.points
+            .iter()
+            .find(|p| p.iodepth == self.recommended_iodepth)
+            .expect("recommended depth is one of the points")
+    }
+}
+
+/// Finds the saturation knee in a series of results keyed by iodepth.
+///
+/// The series is sorted by queue depth first.  A step from one depth to the next is saturated
+/// when its scaling efficiency falls below [`KneeCriteria::min_scaling_efficiency`] while mean
+/// latency grows by at least [`KneeCriteria::min_latency_growth`].  The knee is the depth just
+/// before the first saturated step, and is the recommended operating depth unless a p99 budget
+/// lowers it.  If no step saturates, the deepest point within budget is recommended.
+///
+/// # Returns
+///
+/// Returns a Result containing the SaturationReport on success, or
+/// a SaturationError on failure
+///
+/// # Errors
+///
+/// Will return an error if:
+/// - The series has fewer than two distinct queue depths
+/// - A queue depth appears twice, is zero, or has no IOPS
+pub fn analyze_queue_depth(
+    series: &[(u32, IoStats)],
+    criteria: &KneeCriteria,
+) -> Result<SaturationReport, SaturationError> {
+    let mut sorted: Vec<&(u32, IoStats)> = series.iter().collect();
+    sorted.sort_by_key(|(iodepth, _)| *iodepth);
+
+    for pair in sorted.windows(2) {
+        if pair[0].0 == pair[1].0 {
+            return Err(SaturationError::InvalidPoint {
+                iodepth: pair[0].0,
+                reason: "measured more than once".into(),
+            });
+        }
+    }
+    if sorted.len() < 2 {
+        return Err(SaturationError::NotEnoughPoints(sorted.len()));
+    }
+
+    let mut points: Vec<QueueDepthPoint> = Vec::with_capacity(sorted.len());
+    for (iodepth, stats) in sorted {
+        if *iodepth == 0 {
+            return Err(SaturationError::InvalidPoint {
+                iodepth: 0,
+                reason: "iodepth must be at least 1".into(),
+            });
+        }
+        if stats.iops <= 0.0 {
+            return Err(SaturationError::InvalidPoint {
+                iodepth: *iodepth,
+                reason: "no I/O completed".into(),
+            });
+        }
+
+        let (scaling_efficiency, latency_growth) = match points.last() {
+            Some(prev) => {
+                let depth_growth = *iodepth as f64 / prev.iodepth as f64 - 1.0;
+                let iops_growth = stats.iops / prev.iops - 1.0;
+                let latency_growth = if prev.lat_usec > 0.0 {
+                    stats.lat_usec / prev.lat_usec - 1.0
+                } else {
+                    0.0
+                };
+                (Some(iops_growth / depth_growth), Some(latency_growth))
+            }
+            None => (None, None),
+        };
+
+        points.push(QueueDepthPoint {
+            iodepth: *iodepth,
+            iops: stats.iops,
+            lat_usec: stats.lat_usec,
+            lat_usec_p99: stats.lat_usec_p99,
+            effective_qd: stats.iops * stats.lat_usec / USEC_PER_SEC,
+            scaling_efficiency,
+            latency_growth,
+        });
+    }
+
+    let knee = points.windows(2).find_map(|pair| {
+        let step = &pair[1];
+        let flat = step
+            .scaling_efficiency
+            .is_some_and(|e| e < criteria.min_scaling_efficiency);
+        let queueing = step
+            .latency_growth
+            .is_some_and(|g| g >= criteria.min_latency_growth);
+        (flat && queueing).then_some(pair[0].iodepth)
+    });
+
+    let within_budget = |p: &QueueDepthPoint| match criteria.max_p99_usec {
+        Some(budget) => p.lat_usec_p99 <= budget,
+        None => true,
+    };
+    let ceiling = knee.unwrap_or(u32::MAX);
+    let recommended_iodepth = points
+        .iter()
+        .filter(|p| p.iodepth <= ceiling && within_budget(p))
+        .map(|p| p.iodepth)
+        .max()
+        .unwrap_or(points[0].iodepth);
+
+    let peak_iops = points.iter().map(|p| p.iops).fold(0.0, f64::max);
+
+    Ok(SaturationReport {
+        points,
+        knee_iodepth: knee,
+        recommended_iodepth,
+        peak_iops,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(iops: f64, lat_usec: f64, lat_usec_p99: f64) -> IoStats {
+        IoStats {
+            iops,
+            lat_usec,
+            lat_usec_p99,
+            ..Default::default()
+        }
+    }
+
+    /// An SSD-like curve: near-linear up to QD 16, flat at ~200k IOPS beyond it.
+    fn ssd_series() -> Vec<(u32, IoStats)> {
+        vec![
+            (64, stats(201_000.0, 318.0, 900.0)),
+            (1, stats(14_000.0, 70.0, 110.0)),
+            (8, stats(105_000.0, 75.0, 130.0)),
+            (16, stats(190_000.0, 84.0, 160.0)),
+            (32, stats(199_000.0, 160.0, 420.0)),
+        ]
+    }
+
+    #[test]
+    fn test_knee_detection() {
+        let report = analyze_queue_depth(&ssd_series(), &KneeCriteria::default()).unwrap();
+
+        let depths: Vec<u32> = report.points.iter().map(|p| p.iodepth).collect();
+        assert_eq!(depths, vec![1, 8, 16, 32, 64]);
+        assert_eq!(report.knee_iodepth, Some(16));
+        assert_eq!(report.recommended_iodepth, 16);
+        assert!(report.saturated());
+        assert_eq!(report.peak_iops, 201_000.0);
+
+        // Little's law: 190k IOPS at 84 µs keeps ~16 requests in flight
+        let point = report.recommended();
+        assert!((point.effective_qd - 15.96).abs() < 0.01);
+        assert!(report.points[0].scaling_efficiency.is_none());
+    }
+
+    #[test]
+    fn test_latency_budget_and_unsaturated() {
+        let criteria = KneeCriteria {
+            max_p99_usec: Some(150.0),
+            ..Default::default()
+        };
+        let report = analyze_queue_depth(&ssd_series(), &criteria).unwrap();
+        assert_eq!(report.knee_iodepth, Some(16));
+        assert_eq!(report.recommended_iodepth, 8);
+
+        // Still scaling at the deepest point: recommend the deepest tested depth
+        let series = vec![
+            (1, stats(10_000.0, 100.0, 150.0)),
+            (2, stats(19_500.0, 102.0, 155.0)),
+            (4, stats(38_000.0, 105.0, 160.0)),
+        ];
+        let report = analyze_queue_depth(&series, &KneeCriteria::default()).unwrap();
+        assert!(!report.saturated());
+        assert_eq!(report.recommended_iodepth, 4);
+    }
+
+    #[test]
+    fn test_flat_iops_without_latency_growth_is_not_a_knee() {
+        // IOPS stops growing but latency does not rise: the depth is not reaching the device
+        let series = vec![
+            (1, stats(10_000.0, 100.0, 150.0)),
+            (8, stats(10_100.0, 100.0, 150.0)),
+        ];
+        let report = analyze_queue_depth(&series, &KneeCriteria::default()).unwrap();
+        assert!(!report.saturated());
+    }
+
+    #[test]
+    fn test_invalid_series() {
+        let criteria = KneeCriteria::default();
+        assert!(matches!(
+            analyze_queue_depth(&[(1, stats(1.0, 1.0, 1.0))], &criteria),
+            Err(SaturationError::NotEnoughPoints(1))
+        ));
+        assert!(matches!(
+            analyze_queue_depth(
+                &[(4, stats(1.0, 1.0, 1.0)), (4, stats(2.0, 1.0, 1.0))],
+                &criteria
+            ),
+            Err(SaturationError::InvalidPoint { iodepth: 4, .. })
+        ));
+        assert!(matches!(
+            analyze_queue_depth(
+                &[(1, stats(1.0, 1.0, 1.0)), (8, stats(0.0, 0.0, 0.0))],
+                &criteria
+            ),
+            Err(SaturationError::InvalidPoint { iodepth: 8, .. })
+        ));
+    }
+}
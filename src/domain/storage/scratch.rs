@@ -0,0 +1,566 @@
+//! Lifecycle of FIO's scratch files
+//!
+//! FIO lays out its data files before it starts measuring and leaves them behind when it
+//! exits.  Every file a run is about to create is registered with [`ScratchFiles`] first; the
+//! returned [`ScratchGuard`] removes the files when it is dropped, so they go whether the run
+//! succeeds, fails, times out or is cancelled.
+//!
+//! Registered files are also listed in a manifest, `<state dir>/<pid>-<n>.json` for the `n`th
+//! registry of the process, so that a process that never got to drop its guards (it crashed,
+//! or was killed) leaves a record behind.
+//! [`find_orphans`] collects the files of manifests whose process is gone, together with the
+//! data files of the built-in suite (`fio_<name>_<timestamp>.dat`) that no running process
+//! owns.
+//!
+//! Targets with the [`REUSE_OPTION`] option keep their files, so the next run against the
+//! target finds them laid out (and preconditioned) already; see [`data_files`].
+//!
+//! # Example
+//!
+//! ```no_run
+//! use sysperf_svr::domain::storage::fio::{FioJobConfig, StorageTarget};
+//! use sysperf_svr::domain::storage::scratch::{data_files, ScratchFiles};
+//!
+//! let scratch = ScratchFiles::new("./benchmark/.scratch");
+//! let job = FioJobConfig { numjobs: 4, ..Default::default() };
+//! let target = StorageTarget::new_file("./benchmark/data", None);
+//!
+//! let guard = scratch.register(data_files("quick", &job, &target))?;
+//! // ... run FIO ...
+//! let freed = guard.remove();
+//! println!("freed {} bytes", freed.bytes);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use super::fio::{FioJobConfig, StorageTarget};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// Target option that keeps a target's data files between runs
+pub const REUSE_OPTION: &str = "reuse_files";
+
+/// Directory, inside the benchmark directory, that holds the manifests
+pub const STATE_DIR: &str = ".scratch";
+
+/// Numbers the registries of this process, so that each writes its own manifest
+static NEXT_REGISTRY: AtomicU32 = AtomicU32::new(0);
+
+/// Files registered by one process, as written to its manifest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Manifest {
+    pid: u32,
+    files: BTreeSet<PathBuf>,
+}
+
+/// Scratch files registered by this process
+#[derive(Debug)]
+pub struct ScratchFiles {
+    state_dir: PathBuf,
+    registry: u32,
+    files: Mutex<BTreeSet<PathBuf>>,
+}
+
+/// Removes registered files when dropped
+#[derive(Debug)]
+#[must_use = "the files are removed as soon as the guard is dropped"]
+pub struct ScratchGuard<'a> {
+    scratch: &'a ScratchFiles,
+    files: Vec<PathBuf>,
+}
+
+/// What removing scratch files freed
+#[derive(Debug, Default)]
+pub struct Removed {
+    /// Files removed
+    pub files: Vec<PathBuf>,
+    /// Bytes the removed files took up
+    pub bytes: u64,
+    /// Files that could not be removed, and why
+    pub errors: Vec<(PathBuf, io::Error)>,
+}
+
+/// A file left behind by a run that did not clean up after itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrphanFile {
+    pub path: PathBuf,
+    /// Size of the file in bytes
+    pub bytes: u64,
+}
+
+/// Files left behind by runs that did not clean up after themselves
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Orphans {
+    /// Scratch files of processes that have exited, and untracked data files of the built-in
+    /// suite
+    pub files: Vec<OrphanFile>,
+    /// Manifests of processes that have exited
+    #[serde(skip)]
+    pub manifests: Vec<PathBuf>,
+    /// Processes still running benchmarks, whose files were left alone
+    pub live_processes: usize,
+}
+
+/// What a clean removed, or would remove on a dry run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CleanReport {
+    /// Orphaned files
+    pub files: Vec<OrphanFile>,
+    /// Bytes the orphaned files take up
+    pub bytes: u64,
+    /// Processes still running benchmarks, whose files were left alone
+    pub live_processes: usize,
+    /// Files that could not be removed, with the reason
+    pub errors: Vec<String>,
+    /// Whether nothing was actually deleted
+    pub dry_run: bool,
+}
+
+impl ScratchFiles {
+    /// Creates a registry keeping its manifest in `state_dir`.
+    ///
+    /// Nothing is written until the first file is registered.
+    pub fn new<P: Into<PathBuf>>(state_dir: P) -> Self {
+        Self {
+            state_dir: state_dir.into(),
+            registry: NEXT_REGISTRY.fetch_add(1, Ordering::Relaxed),
+            files: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// The directory holding the manifests.
+    pub fn state_dir(&self) -> &Path {
+        &self.state_dir
+    }
+
+    /// Registers `files`, which a run is about to create.
+    ///
+    /// # Returns
+    ///
+    /// A guard that removes the files when it is dropped, or on
+    /// [`remove`](ScratchGuard::remove).
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the manifest cannot be written; nothing is registered then.
+    pub fn register(&self, files: Vec<PathBuf>) -> io::Result<ScratchGuard<'_>> {
+        if !files.is_empty() {
+            let mut registered = self.lock();
+            let added: Vec<_> = files
+                .iter()
+                .filter(|file| registered.insert((*file).clone()))
+                .cloned()
+                .collect();
+            if let Err(e) = self.write_manifest(&registered) {
+                for file in &added {
+                    registered.remove(file);
+                }
+                return Err(e);
+            }
+        }
+        Ok(ScratchGuard {
+            scratch: self,
+            files,
+        })
+    }
+
+    /// The files registered and not yet removed.
+    pub fn registered(&self) -> Vec<PathBuf> {
+        self.lock().iter().cloned().collect()
+    }
+
+    /// Removes `files` and forgets them.
+    fn release(&self, files: &[PathBuf]) -> Removed {
+        let mut removed = Removed::default();
+        for file in files {
+            let bytes = fs::symlink_metadata(file).map_or(0, |m| m.len());
+            match fs::remove_file(file) {
+                Ok(()) => {
+                    removed.files.push(file.clone());
+                    removed.bytes += bytes;
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => removed.errors.push((file.clone(), e)),
+            }
+        }
+
+        let mut registered = self.lock();
+        for file in files {
+            if !removed.errors.iter().any(|(failed, _)| failed == file) {
+                registered.remove(file);
+            }
+        }
+        // The files are gone either way; a stale manifest only costs `clean` a lookup
+        let _ = self.write_manifest(&registered);
+        removed
+    }
+
+    /// Writes the manifest of this process, or removes it once nothing is registered.
+    fn write_manifest(&self, files: &BTreeSet<PathBuf>) -> io::Result<()> {
+        let path = self.manifest_path();
+        if files.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => {
+                    // Only succeeds once no other process has a manifest left
+                    let _ = fs::remove_dir(&self.state_dir);
+                    Ok(())
+                }
+            };
+        }
+
+        fs::create_dir_all(&self.state_dir)?;
+        let manifest = Manifest {
+            pid: std::process::id(),
+            files: files.clone(),
+        };
+        let json = serde_json::to_vec_pretty(&manifest)?;
+        // Write then rename, so that a crash never leaves a truncated manifest
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &path)
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        self.state_dir
+            .join(format!("{}-{}.json", std::process::id(), self.registry))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeSet<PathBuf>> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ScratchGuard<'_> {
+    /// The files this guard removes.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Removes the files now, reporting what was freed.
+    pub fn remove(mut self) -> Removed {
+        let files = std::mem::take(&mut self.files);
+        self.scratch.release(&files)
+    }
+}
+
+impl Drop for ScratchGuard<'_> {
+    fn drop(&mut self) {
+        if !self.files.is_empty() {
+            self.scratch.release(&self.files);
+        }
+    }
+}
+
+/// Lists the data files FIO creates when running `job` as `name` against `target`.
+///
+/// FIO names the files of a directory target `<name>.<job>.<file>`, or uses the job's
+/// `filename` option; a file target is one file that all jobs share.  Only files the run
+/// creates inside the target are FIO's to remove, so anything that exists before the run is
+/// left out (devices and existing files), and so are `filename` entries that are absolute or
+/// leave the directory, and the files of targets with the [`REUSE_OPTION`] option set.
+pub fn data_files(name: &str, job: &FioJobConfig, target: &StorageTarget) -> Vec<PathBuf> {
+    if target
+        .options
+        .get(REUSE_OPTION)
+        .is_some_and(|reuse| reuse == "true")
+    {
+        return Vec::new();
+    }
+
+    if target.is_directory() {
+        let files: Vec<PathBuf> = match job.extra_options.get("filename") {
+            Some(filename) => filename
+                .split(':')
+                .map(Path::new)
+                .filter(|file| {
+                    file.is_relative() && !file.components().any(|c| c == Component::ParentDir)
+                })
+                .map(|file| target.path.join(file))
+                .collect(),
+            None => {
+                let nrfiles: u32 = job
+                    .extra_options
+                    .get("nrfiles")
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(1);
+                (0..job.numjobs.max(1))
+                    .flat_map(|j| (0..nrfiles.max(1)).map(move |f| (j, f)))
+                    .map(|(j, f)| target.path.join(format!("{}.{}.{}", name, j, f)))
+                    .collect()
+            }
+        };
+        // symlink_metadata, so that a dangling link is not followed and removed either
+        return files
+            .into_iter()
+            .filter(|file| fs::symlink_metadata(file).is_err())
+            .collect();
+    }
+
+    match target.target_type.as_str() {
+        "file" if !target.path.exists() => vec![target.path.clone()],
+        _ => Vec::new(),
+    }
+}
+
+/// Finds the files that runs left behind.
+///
+/// Scratch files of manifests in `state_dir` whose process has exited are orphans, and so are
+/// the built-in suite's `fio_*.dat` files in `benchmark_dir` that no running process has
+/// registered.  Files that no longer exist are skipped.
+///
+/// # Errors
+///
+/// Will return an error if `state_dir` or `benchmark_dir` exists but cannot be read.
+pub fn find_orphans(state_dir: &Path, benchmark_dir: &Path) -> io::Result<Orphans> {
+    let mut orphans = Orphans::default();
+    let mut owned = BTreeSet::new();
+    let mut files = BTreeSet::new();
+    let mut live = BTreeSet::new();
+
+    for path in read_dir(state_dir)? {
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Some(manifest) = fs::read(&path)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Manifest>(&json).ok())
+        else {
+            continue;
+        };
+        if process_alive(manifest.pid) {
+            live.insert(manifest.pid);
+            owned.extend(manifest.files);
+        } else {
+            files.extend(manifest.files);
+            orphans.manifests.push(path);
+        }
+    }
+
+    orphans.live_processes = live.len();
+
+    for path in read_dir(benchmark_dir)? {
+        let is_suite_file = path.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+            n.starts_with("fio_") && n.ends_with(".dat") && n.len() > "fio_.dat".len()
+        });
+        if is_suite_file && !owned.contains(&path) {
+            files.insert(path);
+        }
+    }
+
+    orphans.files = files
+        .into_iter()
+        .filter_map(|path| {
+            let metadata = fs::symlink_metadata(&path).ok()?;
+            metadata.is_file().then_some(OrphanFile {
+                path,
+                bytes: metadata.len(),
+            })
+        })
+        .collect();
+    Ok(orphans)
+}
+
+/// Removes `orphans`: their files first, then the manifests that listed them.
+///
+/// Manifests are kept when a file could not be removed, so that a later clean retries it.
+pub fn remove_orphans(orphans: &Orphans) -> Removed {
+    let mut removed = Removed::default();
+    for file in &orphans.files {
+        match fs::remove_file(&file.path) {
+            Ok(()) => {
+                removed.files.push(file.path.clone());
+                removed.bytes += file.bytes;
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => removed.errors.push((file.path.clone(), e)),
+        }
+    }
+    if removed.errors.is_empty() {
+        for manifest in &orphans.manifests {
+            let _ = fs::remove_file(manifest);
+            if let Some(state_dir) = manifest.parent() {
+                // Only succeeds once no running process has a manifest left
+                let _ = fs::remove_dir(state_dir);
+            }
+        }
+    }
+    removed
+}
+
+/// Entries of `dir`, or none when it does not exist.
+fn read_dir(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    match fs::read_dir(dir) {
+        Ok(entries) => Ok(entries.filter_map(|e| e.ok()).map(|e| e.path()).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether a process with `pid` exists.
+fn process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks whether the process exists and may be signalled
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_data_files() {
+        let job = FioJobConfig {
+            numjobs: 2,
+            ..Default::default()
+        };
+
+        let dir = StorageTarget {
+            path: "/mnt/fio".into(),
+            target_type: "directory".into(),
+            options: Default::default(),
+        };
+        assert_eq!(
+            data_files("quick", &job, &dir),
+            vec![
+                PathBuf::from("/mnt/fio/quick.0.0"),
+                PathBuf::from("/mnt/fio/quick.1.0")
+            ]
+        );
+
+        let named = FioJobConfig {
+            extra_options: HashMap::from([("filename".to_string(), "a:b".to_string())]),
+            ..job.clone()
+        };
+        assert_eq!(
+            data_files("quick", &named, &dir),
+            vec![PathBuf::from("/mnt/fio/a"), PathBuf::from("/mnt/fio/b")]
+        );
+
+        let reused = StorageTarget {
+            options: HashMap::from([(REUSE_OPTION.to_string(), "true".to_string())]),
+            ..dir
+        };
+        assert!(data_files("quick", &job, &reused).is_empty());
+
+        // Whatever exists before the run is not FIO's to remove
+        let tmp = tempfile::tempdir().unwrap();
+        let existing = StorageTarget {
+            path: tmp.path().into(),
+            target_type: "directory".into(),
+            options: Default::default(),
+        };
+        fs::write(tmp.path().join("a"), b"user data").unwrap();
+        assert_eq!(
+            data_files("quick", &named, &existing),
+            vec![tmp.path().join("b")]
+        );
+        let device = FioJobConfig {
+            extra_options: HashMap::from([("filename".to_string(), "/dev/null".to_string())]),
+            ..job.clone()
+        };
+        assert!(data_files("quick", &device, &existing).is_empty());
+
+        // Nor are files outside the directory, even when they do not exist yet
+        let outside = FioJobConfig {
+            extra_options: HashMap::from([(
+                "filename".to_string(),
+                format!("{}:../c:d", tmp.path().join("new").display()),
+            )]),
+            ..job.clone()
+        };
+        assert_eq!(
+            data_files("quick", &outside, &existing),
+            vec![tmp.path().join("d")]
+        );
+
+        let tmp = tempfile::tempdir().unwrap();
+        let file = StorageTarget::new_file(tmp.path().join("data"), None);
+        assert_eq!(data_files("quick", &job, &file), vec![file.path.clone()]);
+        fs::write(&file.path, b"user data").unwrap();
+        assert!(data_files("quick", &job, &file).is_empty());
+
+        let device = StorageTarget::new_device("/dev/nvme0n1", None);
+        assert!(data_files("quick", &job, &device).is_empty());
+    }
+
+    #[test]
+    fn test_guard_removes_files_and_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let scratch = ScratchFiles::new(dir.path().join(".scratch"));
+        let data = dir.path().join("quick.0.0");
+        let manifest = scratch.manifest_path();
+
+        let guard = scratch.register(vec![data.clone()]).unwrap();
+        fs::write(&data, vec![0u8; 4096]).unwrap();
+        assert!(manifest.exists());
+        assert_eq!(scratch.registered(), vec![data.clone()]);
+
+        let removed = guard.remove();
+        assert_eq!(removed.files, vec![data.clone()]);
+        assert_eq!(removed.bytes, 4096);
+        assert!(!data.exists());
+        assert!(!manifest.exists());
+
+        // Dropping the guard cleans up too, e.g. when the run fails
+        {
+            let _guard = scratch.register(vec![data.clone()]).unwrap();
+            fs::write(&data, b"x").unwrap();
+        }
+        assert!(!data.exists());
+        assert!(scratch.registered().is_empty());
+    }
+
+    #[test]
+    fn test_find_and_remove_orphans() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = dir.path().join(".scratch");
+        fs::create_dir_all(&state).unwrap();
+
+        // A crashed process, and this one, still running
+        let crashed = dir.path().join("crashed.0.0");
+        let running = dir.path().join("running.0.0");
+        fs::write(&crashed, vec![0u8; 100]).unwrap();
+        fs::write(&running, b"x").unwrap();
+        let dead = Manifest {
+            pid: i32::MAX as u32,
+            files: BTreeSet::from([crashed.clone(), dir.path().join("gone.0.0")]),
+        };
+        fs::write(state.join("dead.json"), serde_json::to_vec(&dead).unwrap()).unwrap();
+        let scratch = ScratchFiles::new(&state);
+        let _guard = scratch.register(vec![running.clone()]).unwrap();
+        // A second registry of this process keeps its own manifest
+        let held = dir.path().join("held.0.0");
+        fs::write(&held, b"x").unwrap();
+        let other = ScratchFiles::new(&state);
+        let _held = other.register(vec![held.clone()]).unwrap();
+
+        // Suite files from before tracking, and results that are not scratch files
+        let suite = dir.path().join("fio_ai_train_95r_5w_20250425_120000.dat");
+        fs::write(&suite, b"xx").unwrap();
+        fs::write(dir.path().join("results_ai_train.json"), b"{}").unwrap();
+
+        let orphans = find_orphans(&state, dir.path()).unwrap();
+        let paths: Vec<_> = orphans.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, vec![crashed.clone(), suite.clone()]);
+        assert_eq!(orphans.live_processes, 1);
+        assert_eq!(orphans.manifests, vec![state.join("dead.json")]);
+
+        let removed = remove_orphans(&orphans);
+        assert_eq!(removed.bytes, 102);
+        assert!(!crashed.exists() && !suite.exists());
+        assert!(running.exists() && held.exists());
+        assert!(!state.join("dead.json").exists());
+    }
+}
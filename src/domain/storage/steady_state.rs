@@ -0,0 +1,478 @@
+//! SSD preconditioning and steady-state detection, following the SNIA Solid State Storage
+//! Performance Test Specification (PTS)
+//!
+//! A fresh-out-of-box or freshly trimmed SSD writes into free flash and reports numbers it
+//! cannot sustain: once every block has been written, garbage collection competes with the
+//! workload and performance settles lower.  The PTS therefore measures in two steps:
+//!
+//! 1. **Workload-independent preconditioning**: write the test range twice over with 128 KiB
+//!    sequential writes, so that every LBA holds data ([`precondition_job`]).
+//! 2. **Measurement rounds**: run the test workload round after round until it reaches steady
+//!    state, and report the rounds of the measurement window ([`SteadyState`]).
+//!
+//! A tracking variable (IOPS by default) is in steady state when, over the last
+//! [`window`](SteadyStateCriteria::window) rounds:
+//!
+//! - the **data excursion**, the spread between the highest and lowest round, is within
+//!   20% of the window's average; and
+//! - the **slope excursion**, how far the least-squares line through the window moves from its
+//!   first round to its last, is within 10% of the average.
+//!
+//! Drives that do not settle within [`max_rounds`](SteadyStateCriteria::max_rounds) are
+//! reported as such rather than measured forever.
+//!
+//! # Example
+//!
+//! ```
+//! use sysperf_svr::domain::storage::fio::IoStats;
+//! use sysperf_svr::domain::storage::steady_state::{SteadyState, SteadyStateCriteria};
+//!
+//! let mut tracker = SteadyState::new(SteadyStateCriteria::default());
+//! for iops in [180_000.0, 120_000.0, 95_000.0, 91_000.0, 90_500.0, 89_800.0, 90_200.0, 90_100.0] {
+//!     tracker.record(IoStats { iops, ..Default::default() }, None);
+//! }
+//! assert!(tracker.is_steady());
+//! assert_eq!(tracker.window().unwrap().first_round, 4);
+//! ```
+
+use super::fio::{FioJobConfig, IoPattern, IoStats, StorageTarget};
+use super::units::ByteSize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Block size of the preconditioning writes
+const PRECONDITION_BLOCK_SIZE: u64 = 128 * 1024;
+
+/// Queue depth of the preconditioning writes
+const PRECONDITION_IODEPTH: u32 = 32;
+
+/// Times the test range is written over while preconditioning
+pub const PRECONDITION_LOOPS: u32 = 2;
+
+/// Job options that decide which files FIO lays out, kept when preconditioning so that the
+/// measurement rounds run on the files that were written
+const LAYOUT_OPTIONS: &[&str] = &["filename", "filesize", "kb_base", "nrfiles", "offset"];
+
+/// The value tracked for steady state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackingMetric {
+    /// Operations per second, all directions combined
+    #[default]
+    Iops,
+    /// Bandwidth in MiB/s, all directions combined
+    Bandwidth,
+    /// Mean latency in microseconds
+    Latency,
+}
+
+/// When a series of rounds counts as steady
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SteadyStateCriteria {
+    /// Value tracked across rounds
+    pub metric: TrackingMetric,
+    /// Rounds in the measurement window
+    pub window: usize,
+    /// Largest spread between the rounds of the window, in percent of its average
+    pub max_data_excursion: f64,
+    /// Largest change of the window's best-fit line, in percent of its average
+    pub max_slope_excursion: f64,
+    /// Rounds after which to give up on reaching steady state
+    pub max_rounds: u32,
+}
+
+impl Default for SteadyStateCriteria {
+    fn default() -> Self {
+        Self {
+            metric: TrackingMetric::Iops,
+            window: 5,
+            max_data_excursion: 20.0,
+            max_slope_excursion: 10.0,
+            max_rounds: 25,
+        }
+    }
+}
+
+/// One measurement round
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Round {
+    /// Round number, from 1
+    pub round: u32,
+    /// The tracked value of this round
+    pub value: f64,
+    /// Read, write and trim statistics combined
+    pub stats: IoStats,
+    /// ID of the round in the run history, when it was saved
+    pub run_id: Option<String>,
+}
+
+/// The measurement window and how it fares against the criteria
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowCheck {
+    /// First round of the window
+    pub first_round: u32,
+    /// Last round of the window
+    pub last_round: u32,
+    /// Mean of the tracked value over the window
+    pub average: f64,
+    /// Spread between the highest and lowest round, in percent of the average
+    pub data_excursion: f64,
+    /// Slope of the least-squares line, in units of the tracked value per round
+    pub slope: f64,
+    /// Change of the least-squares line across the window, in percent of the average
+    pub slope_excursion: f64,
+    /// Whether both excursions are within the criteria
+    pub steady: bool,
+}
+
+/// Tracks measurement rounds until they reach steady state
+#[derive(Debug, Clone)]
+pub struct SteadyState {
+    criteria: SteadyStateCriteria,
+    rounds: Vec<Round>,
+    window: Option<WindowCheck>,
+}
+
+/// Outcome of preconditioning and the measurement rounds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SteadyStateReport {
+    pub criteria: SteadyStateCriteria,
+    /// Statistics of the preconditioning writes; `None` when preconditioning was skipped
+    pub precondition: Option<IoStats>,
+    /// Every measurement round, in order
+    pub rounds: Vec<Round>,
+    /// The last measurement window; `None` with fewer rounds than the window holds
+    pub window: Option<WindowCheck>,
+    /// Whether the last window reached steady state
+    pub steady: bool,
+}
+
+impl TrackingMetric {
+    /// The tracked value of `stats`.
+    pub fn value(self, stats: &IoStats) -> f64 {
+        match self {
+            TrackingMetric::Iops => stats.iops,
+            TrackingMetric::Bandwidth => stats.bandwidth_mb,
+            TrackingMetric::Latency => stats.lat_usec,
+        }
+    }
+
+    /// Unit of the tracked value, for display.
+    pub fn unit(self) -> &'static str {
+        match self {
+            TrackingMetric::Iops => "IOPS",
+            TrackingMetric::Bandwidth => "MiB/s",
+            TrackingMetric::Latency => "µs",
+        }
+    }
+}
+
+impl FromStr for TrackingMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "iops" => Ok(TrackingMetric::Iops),
+            "bandwidth" => Ok(TrackingMetric::Bandwidth),
+            "latency" => Ok(TrackingMetric::Latency),
+            _ => Err(format!(
+                "unknown metric \"{}\"; use iops, bandwidth or latency",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TrackingMetric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TrackingMetric::Iops => "iops",
+            TrackingMetric::Bandwidth => "bandwidth",
+            TrackingMetric::Latency => "latency",
+        })
+    }
+}
+
+impl SteadyState {
+    /// Creates a tracker with no rounds.
+    pub fn new(criteria: SteadyStateCriteria) -> Self {
+        Self {
+            criteria,
+            rounds: Vec::new(),
+            window: None,
+        }
+    }
+
+    pub fn criteria(&self) -> &SteadyStateCriteria {
+        &self.criteria
+    }
+
+    /// Records the next round and checks the window ending with it.
+    ///
+    /// # Returns
+    ///
+    /// The window check, once there are enough rounds to fill a window.
+    pub fn record(&mut self, stats: IoStats, run_id: Option<String>) -> Option<&WindowCheck> {
+        let round = self.rounds.len() as u32 + 1;
+        self.rounds.push(Round {
+            round,
+            value: self.criteria.metric.value(&stats),
+            stats,
+            run_id,
+        });
+        self.window = check_window(&self.rounds, &self.criteria);
+        self.window.as_ref()
+    }
+
+    /// Whether the last window reached steady state.
+    pub fn is_steady(&self) -> bool {
+        self.window.as_ref().is_some_and(|w| w.steady)
+    }
+
+    /// Whether another round should run: not steady yet, and rounds remain.
+    pub fn needs_round(&self) -> bool {
+        !self.is_steady() && (self.rounds.len() as u32) < self.criteria.max_rounds
+    }
+
+    pub fn rounds(&self) -> &[Round] {
+        &self.rounds
+    }
+
+    /// The last window checked.
+    pub fn window(&self) -> Option<&WindowCheck> {
+        self.window.as_ref()
+    }
+
+    /// Ends tracking, with `precondition` the statistics of the preconditioning writes.
+    pub fn into_report(self, precondition: Option<IoStats>) -> SteadyStateReport {
+        SteadyStateReport {
+            steady: self.is_steady(),
+            criteria: self.criteria,
+            precondition,
+            rounds: self.rounds,
+            window: self.window,
+        }
+    }
+}
+
+impl SteadyStateReport {
+    /// The rounds of the measurement window, which the PTS reports as the result.
+    pub fn window_rounds(&self) -> &[Round] {
+        match &self.window {
+            Some(window) => &self.rounds[window.first_round as usize - 1..],
+            None => &[],
+        }
+    }
+
+    /// Formats the rounds as a table, marking those in the measurement window.
+    pub fn table(&self) -> String {
+        let metric = self.criteria.metric;
+        let first = self.window.as_ref().map_or(u32::MAX, |w| w.first_round);
+        let mut out = format!(
+            "{:>5} {:>12} {:>10} {:>10} {:>10}  {}\n",
+            "round",
+            metric.unit(),
+            "IOPS",
+            "MiB/s",
+            "mean µs",
+            "run"
+        );
+        for round in &self.rounds {
+            out.push_str(&format!(
+                "{:>4}{} {:>12.1} {:>10.0} {:>10.1} {:>10.1}  {}\n",
+                round.round,
+                if round.round >= first { "*" } else { " " },
+                round.value,
+                round.stats.iops,
+                round.stats.bandwidth_mb,
+                round.stats.lat_usec,
+                round.run_id.as_deref().unwrap_or("-")
+            ));
+        }
+        out
+    }
+}
+
+/// Checks the last `criteria.window` rounds against the steady-state criteria.
+///
+/// # Returns
+///
+/// `None` with fewer rounds than the window holds.
+pub fn check_window(rounds: &[Round], criteria: &SteadyStateCriteria) -> Option<WindowCheck> {
+    let size = criteria.window.max(2);
+    if rounds.len() < size {
+        return None;
+    }
+    let window = &rounds[rounds.len() - size..];
+    let n = size as f64;
+
+    let average = window.iter().map(|r| r.value).sum::<f64>() / n;
+    let (min, max) = window
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), r| {
+            (min.min(r.value), max.max(r.value))
+        });
+
+    // Least-squares fit of value against round number
+    let mean_x = window.iter().map(|r| f64::from(r.round)).sum::<f64>() / n;
+    let (covariance, variance) = window.iter().fold((0.0, 0.0), |(cov, var), r| {
+        let dx = f64::from(r.round) - mean_x;
+        (cov + dx * (r.value - average), var + dx * dx)
+    });
+    let slope = covariance / variance;
+
+    let percent_of_average = |value: f64| {
+        if average > 0.0 {
+            value / average * 100.0
+        } else {
+            f64::INFINITY
+        }
+    };
+    let data_excursion = percent_of_average(max - min);
+    let slope_excursion = percent_of_average(slope.abs() * (n - 1.0));
+
+    Some(WindowCheck {
+        first_round: window[0].round,
+        last_round: window[size - 1].round,
+        average,
+        data_excursion,
+        slope,
+        slope_excursion,
+        steady: data_excursion <= criteria.max_data_excursion
+            && slope_excursion <= criteria.max_slope_excursion,
+    })
+}
+
+/// Builds the workload-independent preconditioning job for measuring `job` on `target`.
+///
+/// The job writes the range `job` tests [`PRECONDITION_LOOPS`] times over with 128 KiB
+/// sequential direct writes: the whole device, or the device share `size` limits it to, for
+/// devices; the same data files `job` uses, for files and directories.
+pub fn precondition_job(job: &FioJobConfig, target: &StorageTarget) -> FioJobConfig {
+    let mut extra_options: std::collections::HashMap<String, String> = job
+        .extra_options
+        .iter()
+        .filter(|(key, _)| LAYOUT_OPTIONS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    extra_options.insert("loops".into(), PRECONDITION_LOOPS.to_string());
+
+    let is_file = target.is_directory() || target.target_type == "file";
+    FioJobConfig {
+        ioengine: job.ioengine.clone(),
+        rw: IoPattern::Write,
+        bs: ByteSize::from_bytes(PRECONDITION_BLOCK_SIZE).to_fio(job.kb_base().unwrap_or_default()),
+        size: job.size.clone(),
+        // Jobs sharing a device would each write all of it; a directory needs its file per job
+        numjobs: if is_file { job.numjobs } else { 1 },
+        iodepth: PRECONDITION_IODEPTH,
+        direct: true,
+        buffered: false,
+        rwmixread: None,
+        extra_options,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::storage::fio::IoEngine;
+
+    fn rounds(values: &[f64]) -> Vec<Round> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| Round {
+                round: i as u32 + 1,
+                value,
+                stats: IoStats::default(),
+                run_id: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_window_excursions() {
+        let criteria = SteadyStateCriteria::default();
+        assert!(check_window(&rounds(&[100.0; 4]), &criteria).is_none());
+
+        let flat = check_window(&rounds(&[100.0, 101.0, 99.0, 100.0, 100.0]), &criteria).unwrap();
+        assert!(flat.steady);
+        assert_eq!((flat.first_round, flat.last_round), (1, 5));
+        assert!((flat.data_excursion - 2.0).abs() < 1e-9);
+
+        // Every round within 20% of the average, but still falling steadily
+        let falling = check_window(&rounds(&[110.0, 105.0, 100.0, 95.0, 90.0]), &criteria).unwrap();
+        assert!((falling.slope + 5.0).abs() < 1e-9);
+        assert!((falling.slope_excursion - 20.0).abs() < 1e-9);
+        assert!(falling.data_excursion <= criteria.max_data_excursion);
+        assert!(!falling.steady);
+
+        // No trend, but too noisy
+        let noisy = check_window(&rounds(&[80.0, 120.0, 100.0, 120.0, 80.0]), &criteria).unwrap();
+        assert!(noisy.slope_excursion <= criteria.max_slope_excursion);
+        assert!(!noisy.steady);
+    }
+
+    #[test]
+    fn test_tracker_stops_at_steady_state_or_max_rounds() {
+        let criteria = SteadyStateCriteria {
+            max_rounds: 8,
+            ..Default::default()
+        };
+        let stats = |iops| IoStats {
+            iops,
+            ..Default::default()
+        };
+
+        let mut tracker = SteadyState::new(criteria.clone());
+        for iops in [300.0, 200.0, 102.0, 100.0, 99.0, 98.0, 101.0] {
+            assert!(tracker.needs_round());
+            tracker.record(stats(iops), Some(format!("r{}", iops)));
+        }
+        assert!(tracker.is_steady());
+        assert!(!tracker.needs_round());
+        let report = tracker.into_report(None);
+        assert!(report.steady);
+        assert_eq!(report.window_rounds().len(), 5);
+        assert_eq!(report.window_rounds()[0].round, 3);
+        assert!(report.table().contains("r98"));
+
+        let mut tracker = SteadyState::new(criteria);
+        for i in 0..8 {
+            tracker.record(stats(1000.0 / f64::from(i + 1)), None);
+        }
+        assert!(!tracker.is_steady());
+        assert!(!tracker.needs_round());
+    }
+
+    #[test]
+    fn test_precondition_job() {
+        let mut job = FioJobConfig::new_random_write("4k", "10G", 8, 64);
+        job.ioengine = IoEngine::Libaio;
+        job.rw = IoPattern::RandRW;
+        job.rwmixread = Some(70);
+        for (key, value) in [("runtime", "60"), ("time_based", "true"), ("nrfiles", "2")] {
+            job.extra_options.insert(key.into(), value.into());
+        }
+
+        let device = StorageTarget::new_device("/dev/nvme0n1", None);
+        let pre = precondition_job(&job, &device);
+        assert_eq!(pre.rw.as_str(), "write");
+        assert_eq!(pre.bs, "128k");
+        assert_eq!(pre.size, "10G");
+        assert_eq!((pre.numjobs, pre.iodepth, pre.rwmixread), (1, 32, None));
+        assert_eq!(pre.extra_options["loops"], "2");
+        assert!(!pre.extra_options.contains_key("time_based"));
+
+        let dir = StorageTarget {
+            path: "/mnt/xfs".into(),
+            target_type: "directory".into(),
+            options: Default::default(),
+        };
+        let pre = precondition_job(&job, &dir);
+        assert_eq!(pre.numjobs, 8);
+        assert_eq!(pre.extra_options["nrfiles"], "2");
+    }
+}
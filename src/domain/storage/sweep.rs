@@ -0,0 +1,479 @@
+//! Parameter sweeps over block size, queue depth and job count.
+//!
+//! A [`SweepPlan`] expands a base [`FioJobConfig`] into the cartesian product of its axes,
+//! optionally filtered and capped, and [`SweepResults`] collects the outcome of every point
+//! keyed by its axis values.  Running the points is left to the application layer so the plan
+//! stays independent of how FIO is invoked.
+//!
+//! # Example
+//!
+//! ```
+//! use sysperf_svr::domain::storage::fio::FioJobConfig;
+//! use sysperf_svr::domain::storage::sweep::{SweepAxes, SweepPlan};
+//!
+//! let base = FioJobConfig::new_random_write("4k", "1G", 1, 1);
+//! let plan = SweepPlan::new(
+//!     base,
+//!     SweepAxes {
+//!         block_sizes: vec!["4k".into(), "64k".into()],
+//!         iodepths: vec![1, 8, 16, 32, 64],
+//!         numjobs: vec![1, 4, 8],
+//!     },
+//! )
+//! .max_outstanding_ios(128);
+//!
+//! for point in plan.points() {
+//!     println!("{}", point.key);
+//! }
+//! ```
+
+use super::fio::{FioJobConfig, FioResult, IoStats};
+use super::saturation::{analyze_queue_depth, KneeCriteria, SaturationError, SaturationReport};
+use crate::domain::metrics::series::MetricSeries;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Values to sweep along each axis; an empty axis keeps the base config's value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SweepAxes {
+    /// Block sizes (e.g. "4k", "1M")
+    #[serde(default)]
+    pub block_sizes: Vec<String>,
+    /// Queue depths
+    #[serde(default)]
+    pub iodepths: Vec<u32>,
+    /// Job counts
+    #[serde(default)]
+    pub numjobs: Vec<u32>,
+}
+
+/// Axis values identifying a single sweep point
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SweepKey {
+    /// Block size
+    pub bs: String,
+    /// Queue depth
+    pub iodepth: u32,
+    /// Number of jobs
+    pub numjobs: u32,
+}
+
+impl SweepKey {
+    /// Total I/Os in flight at this point (`iodepth × numjobs`).
+    pub fn outstanding_ios(&self) -> u32 {
+        self.iodepth.saturating_mul(self.numjobs)
+    }
+
+    /// Job-name suffix identifying this point, e.g. `bs4k_qd32_j4`.
+    pub fn suffix(&self) -> String {
+        format!("bs{}_qd{}_j{}", self.bs, self.iodepth, self.numjobs)
+    }
+}
+
+impl fmt::Display for SweepKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bs={} iodepth={} numjobs={}",
+            self.bs, self.iodepth, self.numjobs
+        )
+    }
+}
+
+/// A single point of a sweep: its axis values and the job to run
+#[derive(Debug, Clone)]
+pub struct SweepPoint {
+    /// Axis values of this point
+    pub key: SweepKey,
+    /// Base config with the axis values applied
+    pub config: FioJobConfig,
+}
+
+/// Predicate deciding whether a point is kept
+type PointFilter = Box<dyn Fn(&SweepKey) -> bool + Send + Sync>;
+
+/// A sweep over the cartesian product of [`SweepAxes`]
+pub struct SweepPlan {
+    base: FioJobConfig,
+    axes: SweepAxes,
+    filters: Vec<PointFilter>,
+    limit: Option<usize>,
+}
+
+impl fmt::Debug for SweepPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SweepPlan")
+            .field("base", &self.base)
+            .field("axes", &self.axes)
+            .field("filters", &self.filters.len())
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+impl SweepPlan {
+    /// Creates a plan sweeping `axes` around `base`.
+    pub fn new(base: FioJobConfig, axes: SweepAxes) -> Self {
+        Self {
+            base,
+            axes,
+            filters: Vec::new(),
+            limit: None,
+        }
+    }
+
+    /// Keeps only points for which `filter` returns true.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&SweepKey) -> bool + Send + Sync + 'static,
+    {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Skips points with more than `max` I/Os in flight (`iodepth × numjobs`).
+    pub fn max_outstanding_ios(self, max: u32) -> Self {
+        self.filter(move |key| key.outstanding_ios() <= max)
+    }
+
+    /// Runs at most `limit` points, in plan order.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Expands the plan into its points.
+    ///
+    /// Points are ordered by block size, then job count, then queue depth, so that consecutive
+    /// runs walk up the queue-depth curve for a fixed block size and job count.
+    pub fn points(&self) -> Vec<SweepPoint> {
+        let block_sizes = or_base(&self.axes.block_sizes, self.base.bs.clone());
+        let numjobs = or_base(&self.axes.numjobs, self.base.numjobs);
+        let iodepths = or_base(&self.axes.iodepths, self.base.iodepth);
+
+        let mut points = Vec::new();
+        for bs in &block_sizes {
+            for &jobs in &numjobs {
+                for &iodepth in &iodepths {
+                    let key = SweepKey {
+                        bs: bs.clone(),
+                        iodepth,
+                        numjobs: jobs,
+                    };
+                    if !self.filters.iter().all(|filter| filter(&key)) {
+                        continue;
+                    }
+
+                    let config = FioJobConfig {
+                        bs: bs.clone(),
+                        iodepth,
+                        numjobs: jobs,
+                        ..self.base.clone()
+                    };
+                    points.push(SweepPoint { key, config });
+                }
+            }
+        }
+
+        if let Some(limit) = self.limit {
+            points.truncate(limit);
+        }
+        points
+    }
+}
+
+fn or_base<T: Clone>(values: &[T], base: T) -> Vec<T> {
+    if values.is_empty() {
+        vec![base]
+    } else {
+        values.to_vec()
+    }
+}
+
+/// Outcome of a single sweep point
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepRow {
+    /// Axis values of the point
+    pub key: SweepKey,
+    /// Parsed FIO results, if the run succeeded
+    pub result: Option<FioResult>,
+    /// Error message, if the run failed
+    pub error: Option<String>,
+    /// System metrics sampled while the point ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_metrics: Option<MetricSeries>,
+}
+
+/// Results of a sweep, in the order the points ran
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SweepResults {
+    /// One row per executed point
+    pub rows: Vec<SweepRow>,
+}
+
+impl SweepResults {
+    /// Records a successful point.
+    pub fn record(&mut self, key: SweepKey, result: FioResult) {
+        self.record_with_metrics(key, result, None);
+    }
+
+    /// Records a successful point together with the system metrics sampled during it.
+    pub fn record_with_metrics(
+        &mut self,
+        key: SweepKey,
+        result: FioResult,
+        system_metrics: Option<MetricSeries>,
+    ) {
+        self.rows.push(SweepRow {
+            key,
+            result: Some(result),
+            error: None,
+            system_metrics,
+        });
+    }
+
+    /// Records a failed point.
+    pub fn record_error(&mut self, key: SweepKey, error: String) {
+        self.rows.push(SweepRow {
+            key,
+            result: None,
+            error: Some(error),
+            system_metrics: None,
+        });
+    }
+
+    /// Looks up the result for a given combination of axis values.
+    pub fn get(&self, bs: &str, iodepth: u32, numjobs: u32) -> Option<&FioResult> {
+        self.rows
+            .iter()
+            .find(|row| {
+                row.key.bs == bs && row.key.iodepth == iodepth && row.key.numjobs == numjobs
+            })
+            .and_then(|row| row.result.as_ref())
+    }
+
+    /// Successful rows for one block size and job count, ordered by queue depth.
+    ///
+    /// This is the series a queue-depth saturation study looks at.
+    pub fn iodepth_series(&self, bs: &str, numjobs: u32) -> Vec<(u32, &FioResult)> {
+        let mut series: Vec<(u32, &FioResult)> = self
+            .rows
+            .iter()
+            .filter(|row| row.key.bs == bs && row.key.numjobs == numjobs)
+            .filter_map(|row| row.result.as_ref().map(|r| (row.key.iodepth, r)))
+            .collect();
+        series.sort_by_key(|(iodepth, _)| *iodepth);
+        series
+    }
+
+    /// Distinct block size and job count pairs, in the order they ran.
+    pub fn groups(&self) -> Vec<(String, u32)> {
+        let mut groups: Vec<(String, u32)> = Vec::new();
+        for row in &self.rows {
+            let group = (row.key.bs.clone(), row.key.numjobs);
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+        groups
+    }
+
+    /// Finds the queue-depth saturation knee for one block size and job count.
+    ///
+    /// Read, write and trim statistics are combined so that mixed workloads are judged by the
+    /// total load they put on the device.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if fewer than two queue depths succeeded for this pair.
+    pub fn saturation(
+        &self,
+        bs: &str,
+        numjobs: u32,
+        criteria: &KneeCriteria,
+    ) -> Result<SaturationReport, SaturationError> {
+        let series: Vec<(u32, IoStats)> = self
+            .iodepth_series(bs, numjobs)
+            .into_iter()
+            .map(|(iodepth, result)| (iodepth, result.combined()))
+            .collect();
+        analyze_queue_depth(&series, criteria)
+    }
+
+    /// Renders the results as a plain-text table.
+    pub fn table(&self) -> String {
+        let mut out = format!(
+            "{:>6} {:>7} {:>7} {:>12} {:>11} {:>10} {:>12} {:>11} {:>10}\n",
+            "bs",
+            "iodepth",
+            "numjobs",
+            "read IOPS",
+            "read MiB/s",
+            "read p99",
+            "write IOPS",
+            "write MiB/s",
+            "write p99"
+        );
+
+        for row in &self.rows {
+            let key = &row.key;
+            match (&row.result, &row.error) {
+                (Some(r), _) => out.push_str(&format!(
+                    "{:>6} {:>7} {:>7} {:>12.0} {:>11.1} {:>10.1} {:>12.0} {:>11.1} {:>10.1}\n",
+                    key.bs,
+                    key.iodepth,
+                    key.numjobs,
+                    r.read.iops,
+                    r.read.bandwidth_mb,
+                    r.read.lat_usec_p99,
+                    r.write.iops,
+                    r.write.bandwidth_mb,
+                    r.write.lat_usec_p99
+                )),
+                (None, error) => out.push_str(&format!(
+                    "{:>6} {:>7} {:>7} failed: {}\n",
+                    key.bs,
+                    key.iodepth,
+                    key.numjobs,
+                    error.as_deref().unwrap_or("unknown error")
+                )),
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn axes() -> SweepAxes {
+        SweepAxes {
+            block_sizes: vec!["4k".into(), "1M".into()],
+            iodepths: vec![1, 8, 16, 32, 64],
+            numjobs: vec![1, 4, 8],
+        }
+    }
+
+    #[test]
+    fn test_cartesian_product() {
+        let plan = SweepPlan::new(FioJobConfig::default(), axes());
+        let points = plan.points();
+
+        assert_eq!(points.len(), 2 * 5 * 3);
+        assert_eq!(points[0].key.suffix(), "bs4k_qd1_j1");
+        assert_eq!(points[1].key.iodepth, 8);
+        assert_eq!(points[5].key.numjobs, 4);
+        assert_eq!(points[29].config.bs, "1M");
+        assert_eq!(points[29].config.iodepth, 64);
+        assert_eq!(points[29].config.numjobs, 8);
+    }
+
+    #[test]
+    fn test_empty_axes_use_base() {
+        let base = FioJobConfig::new_sequential_read("128k", "1G", 2, 4);
+        let plan = SweepPlan::new(
+            base,
+            SweepAxes {
+                iodepths: vec![1, 2],
+                ..Default::default()
+            },
+        );
+        let points = plan.points();
+
+        assert_eq!(points.len(), 2);
+        assert!(points
+            .iter()
+            .all(|p| p.key.bs == "128k" && p.key.numjobs == 2));
+    }
+
+    #[test]
+    fn test_filters_and_limit() {
+        let plan = SweepPlan::new(FioJobConfig::default(), axes())
+            .max_outstanding_ios(64)
+            .filter(|key| key.bs == "4k");
+        assert!(plan
+            .points()
+            .iter()
+            .all(|p| p.key.outstanding_ios() <= 64 && p.key.bs == "4k"));
+        assert_eq!(plan.points().len(), 5 + 3 + 2);
+
+        let plan = SweepPlan::new(FioJobConfig::default(), axes()).limit(3);
+        assert_eq!(plan.points().len(), 3);
+    }
+
+    #[test]
+    fn test_results_lookup_and_series() {
+        let mut results = SweepResults::default();
+        for iodepth in [32, 1, 8] {
+            let mut result = FioResult::default();
+            result.read.iops = iodepth as f64 * 1000.0;
+            results.record(
+                SweepKey {
+                    bs: "4k".into(),
+                    iodepth,
+                    numjobs: 1,
+                },
+                result,
+            );
+        }
+        results.record_error(
+            SweepKey {
+                bs: "4k".into(),
+                iodepth: 64,
+                numjobs: 1,
+            },
+            "device busy".into(),
+        );
+
+        assert_eq!(results.get("4k", 8, 1).unwrap().read.iops, 8000.0);
+        assert!(results.get("4k", 64, 1).is_none());
+
+        let series = results.iodepth_series("4k", 1);
+        let depths: Vec<u32> = series.iter().map(|(qd, _)| *qd).collect();
+        assert_eq!(depths, vec![1, 8, 32]);
+
+        let table = results.table();
+        assert!(table.contains("failed: device busy"));
+        assert_eq!(table.lines().count(), 5);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0].len(), lines[1].len(), "header and rows must line up");
+        assert!(lines[0].ends_with(" write MiB/s  write p99"));
+        assert_eq!(results.groups(), vec![("4k".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_saturation_combines_directions() {
+        let mut results = SweepResults::default();
+        for (iodepth, iops, lat_usec) in [
+            (1, 10_000.0, 100.0),
+            (8, 70_000.0, 110.0),
+            (16, 72_000.0, 220.0),
+        ] {
+            let mut result = FioResult::default();
+            for io in [&mut result.read, &mut result.write] {
+                io.iops = iops / 2.0;
+                io.lat_usec = lat_usec;
+                io.total_ios = 1000;
+            }
+            results.record(
+                SweepKey {
+                    bs: "4k".into(),
+                    iodepth,
+                    numjobs: 1,
+                },
+                result,
+            );
+        }
+
+        let report = results
+            .saturation("4k", 1, &KneeCriteria::default())
+            .unwrap();
+        assert_eq!(report.points[1].iops, 70_000.0);
+        assert_eq!(report.recommended_iodepth, 8);
+        assert!(results
+            .saturation("64k", 1, &KneeCriteria::default())
+            .is_err());
+    }
+}
@@ -0,0 +1,641 @@
+//! Sizes and block sizes in FIO's notation
+//!
+//! FIO options such as `size`, `bs` and `bssplit` take byte counts with unit suffixes.  Suffixes
+//! are case-insensitive and their meaning depends on the job's `kb_base`:
+//!
+//! | Suffix             | `kb_base=1024` (default) | `kb_base=1000` |
+//! |--------------------|--------------------------|----------------|
+//! | `k`, `kb`          | 1024                     | 1000           |
+//! | `kib`              | 1000                     | 1024           |
+//! | `m`, `g`, `t`, `p` | powers of 1024           | powers of 1000 |
+//!
+//! The inversion of the IEC suffixes in the default mode is FIO's own, kept for compatibility
+//! with old job files.  A bare number is bytes.
+//!
+//! Beyond plain sizes:
+//!
+//! - `size` may be a percentage of the file or device ([`SizeSpec`]);
+//! - `bs` may give separate read, write and trim block sizes, as in `4k,128k` ([`BlockSizes`]);
+//! - `bssplit` weighs several block sizes per direction, as in `4k/10:64k/90` ([`BsSplit`]).
+//!
+//! # Example
+//!
+//! ```rust
+//! use sysperf_svr::domain::storage::units::{BlockSizes, ByteSize, KbBase};
+//!
+//! assert_eq!(ByteSize::parse("4k", KbBase::Base1024).unwrap().bytes(), 4096);
+//! assert_eq!(ByteSize::parse("4k", KbBase::Base1000).unwrap().bytes(), 4000);
+//! assert_eq!(ByteSize::from_bytes(1 << 20).to_string(), "1m");
+//!
+//! let bs = BlockSizes::parse("4k,128k", KbBase::default()).unwrap();
+//! assert_eq!(bs.read.bytes(), 4096);
+//! assert_eq!(bs.write.bytes(), 128 * 1024);
+//! assert_eq!(bs.to_string(), "4k,128k");
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// FIO's block size when `bs` leaves a direction out
+const DEFAULT_BLOCK_SIZE: u64 = 4096;
+
+/// Unit suffixes by power, as FIO accepts them
+const SI_SUFFIXES: [&str; 7] = ["", "k", "m", "g", "t", "p", "e"];
+const IEC_SUFFIXES: [&str; 7] = ["", "kib", "mib", "gib", "tib", "pib", "eib"];
+
+/// How FIO interprets unit suffixes, from a job's `kb_base` option
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KbBase {
+    /// `k` is 1024 and `KiB` 1000 (FIO's compatibility default)
+    #[default]
+    #[serde(rename = "1024")]
+    Base1024,
+    /// `k` is 1000 and `KiB` 1024
+    #[serde(rename = "1000")]
+    Base1000,
+}
+
+/// Why a size could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum UnitError {
+    #[error(
+        "Invalid size \"{0}\": expected a number with an optional unit such as 4k, 1MiB or 2g"
+    )]
+    InvalidSize(String),
+    #[error(
+        "Unknown unit \"{unit}\" in \"{value}\"; use k, m, g, t or p, optionally with B or iB"
+    )]
+    UnknownUnit { value: String, unit: String },
+    #[error("Size \"{0}\" does not fit in 64 bits")]
+    Overflow(String),
+    #[error("Invalid percentage \"{0}\": must be between 1% and 100%")]
+    InvalidPercent(String),
+    #[error("Block size \"{0}\" must be greater than zero")]
+    ZeroBlockSize(String),
+    #[error("Invalid block sizes \"{0}\": give at most read, write and trim sizes")]
+    TooManyDirections(String),
+    #[error("Invalid bssplit \"{value}\": {reason}")]
+    InvalidSplit { value: String, reason: String },
+    #[error("Invalid kb_base \"{0}\": must be 1000 or 1024")]
+    InvalidKbBase(String),
+}
+
+/// A byte count
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct ByteSize(u64);
+
+/// The `size` of a job: bytes, or a share of the file or device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeSpec {
+    Bytes(ByteSize),
+    /// Percentage of the file or device, 1–100
+    Percent(u32),
+}
+
+/// Block sizes per direction, as given to `bs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockSizes {
+    pub read: ByteSize,
+    pub write: ByteSize,
+    pub trim: ByteSize,
+}
+
+/// One block size of a [`BsSplit`] and the share of I/Os that use it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SplitEntry {
+    pub size: ByteSize,
+    /// Percentage of I/Os in this direction
+    pub percent: f64,
+}
+
+/// Weighted block sizes per direction, as given to `bssplit`
+///
+/// Entries without a percentage share whatever the others leave, in equal parts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BsSplit {
+    pub read: Vec<SplitEntry>,
+    pub write: Vec<SplitEntry>,
+    pub trim: Vec<SplitEntry>,
+}
+
+impl KbBase {
+    /// The multiplier of one unit step for a suffix family
+    fn multiplier(self, iec: bool) -> u64 {
+        match (self, iec) {
+            (KbBase::Base1024, false) | (KbBase::Base1000, true) => 1024,
+            (KbBase::Base1024, true) | (KbBase::Base1000, false) => 1000,
+        }
+    }
+}
+
+impl FromStr for KbBase {
+    type Err = UnitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "1024" => Ok(KbBase::Base1024),
+            "1000" => Ok(KbBase::Base1000),
+            other => Err(UnitError::InvalidKbBase(other.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for KbBase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KbBase::Base1024 => "1024",
+            KbBase::Base1000 => "1000",
+        })
+    }
+}
+
+impl ByteSize {
+    pub const fn from_bytes(bytes: u64) -> Self {
+        Self(bytes)
+    }
+
+    pub const fn bytes(self) -> u64 {
+        self.0
+    }
+
+    /// Parses a size such as "4k", "1MiB", "256G" or "0x1000" under `kb_base`.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the number or unit is invalid, or the size overflows.
+    pub fn parse(value: &str, kb_base: KbBase) -> Result<Self, UnitError> {
+        let trimmed = value.trim();
+        let (digits, radix, rest) = match trimmed
+            .strip_prefix("0x")
+            .or_else(|| trimmed.strip_prefix("0X"))
+        {
+            Some(hex) => {
+                let end = hex
+                    .find(|c: char| !c.is_ascii_hexdigit())
+                    .unwrap_or(hex.len());
+                (&hex[..end], 16, &hex[end..])
+            }
+            None => {
+                let end = trimmed
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(trimmed.len());
+                (&trimmed[..end], 10, &trimmed[end..])
+            }
+        };
+        if digits.is_empty() {
+            return Err(UnitError::InvalidSize(value.to_string()));
+        }
+        let number = u64::from_str_radix(digits, radix)
+            .map_err(|_| UnitError::Overflow(value.to_string()))?;
+
+        let unit = rest.trim().to_ascii_lowercase();
+        let (power, iec) = unit_power(&unit).ok_or_else(|| {
+            if unit.starts_with('.') {
+                UnitError::InvalidSize(value.to_string())
+            } else {
+                UnitError::UnknownUnit {
+                    value: value.to_string(),
+                    unit: rest.trim().to_string(),
+                }
+            }
+        })?;
+        kb_base
+            .multiplier(iec)
+            .checked_pow(power)
+            .and_then(|multiplier| number.checked_mul(multiplier))
+            .map(Self)
+            .ok_or_else(|| UnitError::Overflow(value.to_string()))
+    }
+
+    /// Formats the size the way FIO reads it under `kb_base`, in the largest unit that
+    /// represents it exactly: "4k", "1m" or "1536k" by default, "4KiB" under `kb_base=1000`.
+    pub fn to_fio(self, kb_base: KbBase) -> String {
+        let mut value = self.0;
+        let mut power = 0;
+        while value != 0 && value.is_multiple_of(1024) && power < SI_SUFFIXES.len() - 1 {
+            value /= 1024;
+            power += 1;
+        }
+        match (kb_base, power) {
+            (_, 0) => value.to_string(),
+            (KbBase::Base1024, _) => format!("{}{}", value, SI_SUFFIXES[power]),
+            (KbBase::Base1000, _) => format!("{}{}iB", value, SI_SUFFIXES[power].to_uppercase()),
+        }
+    }
+
+    /// Formats the size for people, e.g. "256 GiB" or "1.5 TiB".
+    pub fn human(self) -> String {
+        format_bytes(self.0)
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = UnitError;
+
+    /// Parses a size under FIO's default `kb_base`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, KbBase::default())
+    }
+}
+
+impl fmt::Display for ByteSize {
+    /// Formats the size as FIO reads it under its default `kb_base`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_fio(KbBase::default()))
+    }
+}
+
+impl From<u64> for ByteSize {
+    fn from(bytes: u64) -> Self {
+        Self(bytes)
+    }
+}
+
+impl SizeSpec {
+    /// Parses a `size` value: a byte count, or a percentage such as "50%".
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the size is invalid or the percentage is not between 1 and 100.
+    pub fn parse(value: &str, kb_base: KbBase) -> Result<Self, UnitError> {
+        match value.trim().strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<u32>() {
+                Ok(percent @ 1..=100) => Ok(SizeSpec::Percent(percent)),
+                _ => Err(UnitError::InvalidPercent(value.to_string())),
+            },
+            None => ByteSize::parse(value, kb_base).map(SizeSpec::Bytes),
+        }
+    }
+
+    /// Bytes this size covers of a file or device holding `capacity` bytes.
+    pub fn resolve(self, capacity: u64) -> u64 {
+        match self {
+            SizeSpec::Bytes(size) => size.bytes(),
+            SizeSpec::Percent(percent) => (u128::from(capacity) * u128::from(percent) / 100) as u64,
+        }
+    }
+
+    /// Formats the size as FIO reads it under `kb_base`.
+    pub fn to_fio(self, kb_base: KbBase) -> String {
+        match self {
+            SizeSpec::Bytes(size) => size.to_fio(kb_base),
+            SizeSpec::Percent(percent) => format!("{}%", percent),
+        }
+    }
+}
+
+impl fmt::Display for SizeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_fio(KbBase::default()))
+    }
+}
+
+impl BlockSizes {
+    /// The same block size in every direction.
+    pub fn uniform(size: ByteSize) -> Self {
+        Self {
+            read: size,
+            write: size,
+            trim: size,
+        }
+    }
+
+    /// Parses a `bs` value such as "4k", "4k,128k" or ",8k,".
+    ///
+    /// Like FIO, a single size applies to every direction, a missing trim size copies the
+    /// write size, and an empty entry is FIO's default of 4 KiB.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if a size is invalid or zero, or more than three are given.
+    pub fn parse(value: &str, kb_base: KbBase) -> Result<Self, UnitError> {
+        let sizes = per_direction(value)?
+            .into_iter()
+            .map(|entry| match entry.trim() {
+                "" => Ok(ByteSize(DEFAULT_BLOCK_SIZE)),
+                size => match ByteSize::parse(size, kb_base)? {
+                    ByteSize(0) => Err(UnitError::ZeroBlockSize(value.to_string())),
+                    size => Ok(size),
+                },
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(match sizes[..] {
+            [size] => Self::uniform(size),
+            [read, write] => Self {
+                read,
+                write,
+                trim: write,
+            },
+            [read, write, trim] => Self { read, write, trim },
+            _ => unreachable!("per_direction returns one to three entries"),
+        })
+    }
+
+    /// Formats the sizes as FIO reads them under `kb_base`, as briefly as possible.
+    pub fn to_fio(&self, kb_base: KbBase) -> String {
+        let (read, write, trim) = (
+            self.read.to_fio(kb_base),
+            self.write.to_fio(kb_base),
+            self.trim.to_fio(kb_base),
+        );
+        if self.read == self.write && self.write == self.trim {
+            read
+        } else if self.write == self.trim {
+            format!("{},{}", read, write)
+        } else {
+            format!("{},{},{}", read, write, trim)
+        }
+    }
+
+    /// The largest block size in any direction.
+    pub fn max(&self) -> ByteSize {
+        self.read.max(self.write).max(self.trim)
+    }
+}
+
+impl fmt::Display for BlockSizes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_fio(KbBase::default()))
+    }
+}
+
+impl BsSplit {
+    /// Parses a `bssplit` value such as "4k/10:64k/50:32k/40" or "2k/50:4k/50,4k/90:8k/10".
+    ///
+    /// Directions are separated as in `bs`.  Entries without a percentage ("4k/" or "4k")
+    /// share what the others leave equally.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if a size is invalid or zero, a percentage is not a number, or
+    /// the percentages of a direction do not add up to 100.
+    pub fn parse(value: &str, kb_base: KbBase) -> Result<Self, UnitError> {
+        let invalid = |reason: String| UnitError::InvalidSplit {
+            value: value.to_string(),
+            reason,
+        };
+
+        let mut directions = Vec::new();
+        for direction in per_direction(value)? {
+            let mut entries = Vec::new();
+            let mut unset = 0;
+            for entry in direction.split(':').map(str::trim) {
+                let (size, percent) = entry.split_once('/').unwrap_or((entry, ""));
+                let size = ByteSize::parse(size, kb_base)?;
+                if size.bytes() == 0 {
+                    return Err(UnitError::ZeroBlockSize(value.to_string()));
+                }
+                let percent = match percent.trim() {
+                    "" => {
+                        unset += 1;
+                        None
+                    }
+                    percent => Some(
+                        percent
+                            .parse::<f64>()
+                            .ok()
+                            .filter(|p| (0.0..=100.0).contains(p))
+                            .ok_or_else(|| {
+                                invalid(format!("\"{}\" is not a percentage", percent))
+                            })?,
+                    ),
+                };
+                entries.push((size, percent));
+            }
+
+            let set: f64 = entries.iter().filter_map(|(_, p)| *p).sum();
+            if set > 100.0 + f64::EPSILON {
+                return Err(invalid(format!("percentages add up to {}", set)));
+            }
+            if unset == 0 && (set - 100.0).abs() > 1e-6 {
+                return Err(invalid(format!(
+                    "percentages add up to {} instead of 100",
+                    set
+                )));
+            }
+            let share = if unset > 0 {
+                (100.0 - set) / f64::from(unset)
+            } else {
+                0.0
+            };
+            directions.push(
+                entries
+                    .into_iter()
+                    .map(|(size, percent)| SplitEntry {
+                        size,
+                        percent: percent.unwrap_or(share),
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        let mut directions = directions.into_iter();
+        let read = directions.next().unwrap_or_default();
+        let write = directions.next().unwrap_or_else(|| read.clone());
+        let trim = directions.next().unwrap_or_else(|| write.clone());
+        Ok(Self { read, write, trim })
+    }
+
+    /// Mean block size of each direction's I/Os, weighted by their percentages.
+    pub fn mean(&self) -> BlockSizes {
+        let mean = |entries: &[SplitEntry]| {
+            let bytes: f64 = entries
+                .iter()
+                .map(|e| e.size.bytes() as f64 * e.percent / 100.0)
+                .sum();
+            ByteSize(bytes.round() as u64)
+        };
+        BlockSizes {
+            read: mean(&self.read),
+            write: mean(&self.write),
+            trim: mean(&self.trim),
+        }
+    }
+}
+
+/// Formats a byte count with a binary unit, e.g. "256 GiB" or "1.5 TiB".
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 || value.fract() == 0.0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Splits a per-direction value into its read, write and trim parts.
+fn per_direction(value: &str) -> Result<Vec<&str>, UnitError> {
+    let parts: Vec<&str> = value.split(',').collect();
+    if parts.len() > 3 {
+        return Err(UnitError::TooManyDirections(value.to_string()));
+    }
+    Ok(parts)
+}
+
+/// The power of a lower-case unit suffix and whether it is an IEC (`kib`) suffix.
+fn unit_power(unit: &str) -> Option<(u32, bool)> {
+    if unit.is_empty() || unit == "b" {
+        return Some((0, false));
+    }
+    if let Some(power) = IEC_SUFFIXES.iter().skip(1).position(|s| *s == unit) {
+        return Some((power as u32 + 1, true));
+    }
+    let prefix = unit.strip_suffix('b').unwrap_or(unit);
+    SI_SUFFIXES
+        .iter()
+        .skip(1)
+        .position(|s| *s == prefix)
+        .map(|power| (power as u32 + 1, false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(value: &str) -> u64 {
+        ByteSize::parse(value, KbBase::Base1024).unwrap().bytes()
+    }
+
+    #[test]
+    fn test_parse_byte_sizes() {
+        assert_eq!(size("4096"), 4096);
+        assert_eq!(size("4k"), 4096);
+        assert_eq!(size("4K"), 4096);
+        assert_eq!(size("4KB"), 4096);
+        assert_eq!(size("256G"), 256 << 30);
+        assert_eq!(size("1t"), 1 << 40);
+        assert_eq!(size("0x1000"), 4096);
+        assert_eq!(size("512b"), 512);
+
+        // FIO's compatibility mode inverts IEC suffixes; kb_base=1000 restores them
+        assert_eq!(size("4KiB"), 4000);
+        let si = |v| ByteSize::parse(v, KbBase::Base1000).unwrap().bytes();
+        assert_eq!(si("4k"), 4000);
+        assert_eq!(si("4KiB"), 4096);
+        assert_eq!(si("1MiB"), 1 << 20);
+
+        assert!(matches!(
+            ByteSize::from_str("4x"),
+            Err(UnitError::UnknownUnit { .. })
+        ));
+        assert!(matches!(
+            ByteSize::from_str("1.5g"),
+            Err(UnitError::InvalidSize(_))
+        ));
+        assert!(matches!(
+            ByteSize::from_str("G"),
+            Err(UnitError::InvalidSize(_))
+        ));
+        assert!(matches!(
+            ByteSize::from_str("20e"),
+            Err(UnitError::Overflow(_))
+        ));
+    }
+
+    #[test]
+    fn test_format_byte_sizes() {
+        assert_eq!(ByteSize::from_bytes(4096).to_string(), "4k");
+        assert_eq!(ByteSize::from_bytes(3 << 19).to_string(), "1536k");
+        assert_eq!(ByteSize::from_bytes(256 << 30).to_string(), "256g");
+        assert_eq!(ByteSize::from_bytes(4000).to_string(), "4000");
+        assert_eq!(ByteSize::from_bytes(0).to_string(), "0");
+        assert_eq!(
+            ByteSize::from_bytes(1 << 20).to_fio(KbBase::Base1000),
+            "1MiB"
+        );
+
+        // Formatting round-trips under either base
+        for kb_base in [KbBase::Base1024, KbBase::Base1000] {
+            for bytes in [1, 4096, 3 << 19, 1 << 40, 123_456_789] {
+                let text = ByteSize::from_bytes(bytes).to_fio(kb_base);
+                assert_eq!(ByteSize::parse(&text, kb_base).unwrap().bytes(), bytes);
+            }
+        }
+
+        assert_eq!(ByteSize::from_bytes(3 << 39).human(), "1.5 TiB");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(256 << 30), "256 GiB");
+    }
+
+    #[test]
+    fn test_size_spec() {
+        let spec = |v| SizeSpec::parse(v, KbBase::default());
+        assert_eq!(spec("10G").unwrap(), SizeSpec::Bytes(ByteSize(10 << 30)));
+        assert_eq!(spec("50%").unwrap(), SizeSpec::Percent(50));
+        assert_eq!(spec("50%").unwrap().resolve(1 << 30), 1 << 29);
+        assert_eq!(spec("50%").unwrap().to_string(), "50%");
+        assert!(matches!(spec("0%"), Err(UnitError::InvalidPercent(_))));
+        assert!(matches!(spec("150%"), Err(UnitError::InvalidPercent(_))));
+    }
+
+    #[test]
+    fn test_block_sizes() {
+        let bs = |v| BlockSizes::parse(v, KbBase::default());
+        assert_eq!(bs("64k").unwrap(), BlockSizes::uniform(ByteSize(64 << 10)));
+
+        let split = bs("4k,128k").unwrap();
+        assert_eq!(
+            (split.read.bytes(), split.write.bytes(), split.trim.bytes()),
+            (4096, 128 << 10, 128 << 10)
+        );
+        assert_eq!(split.max().bytes(), 128 << 10);
+        assert_eq!(split.to_string(), "4k,128k");
+
+        // Empty entries are FIO's default block size
+        let defaults = bs(",8k,").unwrap();
+        assert_eq!(
+            (
+                defaults.read.bytes(),
+                defaults.write.bytes(),
+                defaults.trim.bytes()
+            ),
+            (4096, 8192, 4096)
+        );
+        assert_eq!(defaults.to_string(), "4k,8k,4k");
+
+        assert!(matches!(bs("0"), Err(UnitError::ZeroBlockSize(_))));
+        assert!(matches!(
+            bs("4k,4k,4k,4k"),
+            Err(UnitError::TooManyDirections(_))
+        ));
+    }
+
+    #[test]
+    fn test_bssplit() {
+        let split = BsSplit::parse("4k/10:64k/50:32k/40", KbBase::default()).unwrap();
+        assert_eq!(split.read.len(), 3);
+        assert_eq!(split.write, split.read);
+        assert_eq!(split.mean().read.bytes(), 410 + 32768 + 13107);
+
+        let split = BsSplit::parse("2k/50:4k/50,4k/90:8k/10", KbBase::default()).unwrap();
+        assert_eq!(split.mean().read.bytes(), 3072);
+        assert_eq!(split.write[1].size.bytes(), 8192);
+        assert_eq!(split.trim, split.write);
+
+        // Entries without a percentage share the rest
+        let split = BsSplit::parse("4k/50:8k/:16k", KbBase::default()).unwrap();
+        assert_eq!(split.read[1].percent, 25.0);
+        assert_eq!(split.read[2].percent, 25.0);
+
+        for invalid in ["4k/60:8k/60", "4k/10:8k/10", "4k/x", "0/100"] {
+            assert!(
+                BsSplit::parse(invalid, KbBase::default()).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+}
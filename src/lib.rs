@@ -1,7 +1,9 @@
 pub mod adapters;
 pub mod application;
 pub mod cli;
+pub mod config;
 pub mod database;
 pub mod domain;
 pub mod ports;
+pub mod server;
 // pub mod wasm;
@@ -1,6 +1,12 @@
+use crate::domain::metrics::series::MetricSeries;
+use crate::domain::storage::fio::{FioJobConfig, FioResult, StorageTarget};
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BenchmarkConfig {
@@ -53,6 +59,12 @@ pub struct BenchmarkResult {
     pub tool: BenchmarkTool,
     pub metrics: BenchmarkMetrics,
     pub raw_output: String,
+    /// System metrics sampled while the benchmark ran, when recording was enabled
+    #[serde(default)]
+    pub system_metrics: Option<MetricSeries>,
+    /// ID under which the run was saved to the history
+    #[serde(default)]
+    pub run_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -63,9 +75,34 @@ pub enum BenchmarkMetrics {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FIOMetrics {
+    /// Combined read and write operations per second
     pub iops: f64,
+    /// Combined read and write bandwidth in MiB/s
     pub bandwidth: f64,
+    /// Mean latency across reads and writes in microseconds
     pub latency: f64,
+    /// Full parsed FIO report
+    pub details: Box<FioResult>,
+}
+
+impl From<FioResult> for FIOMetrics {
+    fn from(result: FioResult) -> Self {
+        let ios = (result.read.total_ios + result.write.total_ios) as f64;
+        let latency = if ios > 0.0 {
+            (result.read.lat_usec * result.read.total_ios as f64
+                + result.write.lat_usec * result.write.total_ios as f64)
+                / ios
+        } else {
+            0.0
+        };
+
+        Self {
+            iops: result.read.iops + result.write.iops,
+            bandwidth: result.read.bandwidth_mb + result.write.bandwidth_mb,
+            latency,
+            details: Box::new(result),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -74,13 +111,99 @@ pub struct StressNgMetrics {
     pub cpu_usage: f64,
 }
 
+/// Limits on a benchmark in progress
+///
+/// Adapters stop the benchmark once `timeout` has passed, or once [`cancel`](Self::cancel) has
+/// been called on any clone of the control, and fail with [`BenchmarkStopped`].  The
+/// application refuses write workloads on raw devices unless the control acknowledges the data
+/// loss.
+#[derive(Debug, Clone, Default)]
+pub struct RunControl {
+    timeout: Option<Duration>,
+    cancelled: Arc<AtomicBool>,
+    data_loss_acknowledged: bool,
+}
+
+impl RunControl {
+    /// Creates a control without a timeout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops the benchmark after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Removes the timeout, keeping cancellation shared with the control this was cloned from.
+    pub fn without_timeout(mut self) -> Self {
+        self.timeout = None;
+        self
+    }
+
+    /// Allows write workloads on raw devices, destroying the data on them.
+    pub fn acknowledge_data_loss(mut self) -> Self {
+        self.data_loss_acknowledged = true;
+        self
+    }
+
+    /// Whether [`acknowledge_data_loss`](Self::acknowledge_data_loss) has been called.
+    pub fn data_loss_acknowledged(&self) -> bool {
+        self.data_loss_acknowledged
+    }
+
+    /// How long the benchmark may run.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Asks the adapter to stop the benchmark.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether [`cancel`](Self::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Why a benchmark was stopped before it finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum BenchmarkStopped {
+    #[error("timed out after {}s", .0.as_secs())]
+    TimedOut(Duration),
+    #[error("cancelled")]
+    Cancelled,
+}
+
+impl BenchmarkStopped {
+    /// Finds the reason a benchmark was stopped anywhere in `error`'s chain.
+    pub fn find(error: &anyhow::Error) -> Option<Self> {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<Self>())
+            .copied()
+    }
+}
+
 #[async_trait]
 
-pub trait BenchmarkPort {
-    fn run(&self) -> Result<()>;
+pub trait BenchmarkPort: Send + Sync {
+    fn run(&self) -> Result<Vec<FioResult>>;
+    fn run_config(&self, config: &BenchmarkConfig, control: &RunControl)
+        -> Result<BenchmarkResult>;
+    fn run_job(
+        &self,
+        name: &str,
+        job: &FioJobConfig,
+        target: &StorageTarget,
+        control: &RunControl,
+    ) -> Result<BenchmarkResult>;
     //    fn run_stress_ng(&self) -> Result<String>;
     fn run_fio(&self) -> Result<String>;
     fn validate(&self) -> Result<()>;
     fn run_command(&self, command: &str, args: &str) -> Result<String>;
-    fn check_fio_installation(&self) -> Result<()>;
+    fn check_fio_installation(&self) -> Result<String>;
 }
@@ -1,3 +1,3 @@
 use crate::database::KeyValueStore;
 
-pub trait DatabasePort: KeyValueStore {}
+pub trait DatabasePort: KeyValueStore + Send + Sync {}
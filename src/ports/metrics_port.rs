@@ -1,9 +1,61 @@
 use anyhow::Result;
 use serde_json::Value;
+use std::thread;
 
-pub trait MetricsPort {
+pub use crate::domain::metrics::series::MetricKind;
+
+pub trait MetricsPort: Send + Sync {
     fn collect_mpstat(&self) -> Result<Value>;
     fn collect_vmstat(&self) -> Result<Value>;
     fn collect_cpuinfo(&self) -> Result<Value>;
     fn collect_memoryinfo(&self) -> Result<Value>;
+    fn collect_iostat(&self) -> Result<Value>;
+    fn collect_loadavg(&self) -> Result<Value>;
+
+    /// Collects the metric selected by `kind`.
+    fn collect(&self, kind: MetricKind) -> Result<Value> {
+        match kind {
+            MetricKind::Cpu => self.collect_cpuinfo(),
+            MetricKind::Mem => self.collect_memoryinfo(),
+            MetricKind::Vmstat => self.collect_vmstat(),
+            MetricKind::Mpstat => self.collect_mpstat(),
+            MetricKind::Iostat => self.collect_iostat(),
+            MetricKind::Loadavg => self.collect_loadavg(),
+        }
+    }
+}
+
+/// Collects every metric in `kinds` concurrently, in order.
+///
+/// Rate-based metrics (mpstat, vmstat, iostat) then cover the same sampling window, and the
+/// call takes roughly one window however many metrics are selected.
+pub fn collect_all(
+    port: &dyn MetricsPort,
+    kinds: &[MetricKind],
+) -> Vec<(MetricKind, Result<Value>)> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = kinds
+            .iter()
+            .map(|&kind| scope.spawn(move || (kind, port.collect(kind))))
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().ok())
+            .collect()
+    })
+}
+
+/// The metrics in `requested` without duplicates, or `defaults` when none were requested.
+pub fn selected_metrics(defaults: &[MetricKind], requested: &[MetricKind]) -> Vec<MetricKind> {
+    let mut selected = Vec::new();
+    for &kind in if requested.is_empty() {
+        defaults
+    } else {
+        requested
+    } {
+        if !selected.contains(&kind) {
+            selected.push(kind);
+        }
+    }
+    selected
 }
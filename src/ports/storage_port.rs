@@ -1,7 +1,40 @@
+use crate::domain::storage::history::{latest_per_job, RunFilter, RunRecord};
 use anyhow::Result;
 use serde_json::Value;
 
-pub trait StoragePort {
+pub trait StoragePort: Send + Sync {
     fn store_metrics(&self, key: &str, value: &Value) -> Result<()>;
     fn retrieve_metrics(&self, key: &str) -> Result<Option<Value>>;
+
+    /// Lists the keys of all stored metric samples, ordered by key.
+    fn metric_keys(&self) -> Result<Vec<String>>;
+
+    /// Removes a metric sample.
+    fn delete_metrics(&self, key: &str) -> Result<()>;
+
+    /// Stores a run, including its recorded system metrics.
+    fn save_run(&self, run: &RunRecord) -> Result<()>;
+
+    /// Loads a run by ID, including its recorded system metrics.
+    fn get_run(&self, id: &str) -> Result<Option<RunRecord>>;
+
+    /// Lists the runs matching `filter`, newest first, without their system metrics.
+    fn list_runs(&self, filter: &RunFilter) -> Result<Vec<RunRecord>>;
+
+    /// Returns the newest run of every job, newest first, without their system metrics.
+    ///
+    /// The default lists every run; stores that keep an index should answer from it.
+    fn latest_runs(&self) -> Result<Vec<RunRecord>> {
+        Ok(latest_per_job(self.list_runs(&RunFilter::default())?))
+    }
+
+    /// Removes a run and its system metrics.
+    fn delete_run(&self, id: &str) -> Result<()>;
+
+    /// Pins or unpins a run, protecting it from retention.
+    ///
+    /// # Returns
+    ///
+    /// `false` if there is no run with that ID.
+    fn set_pinned(&self, id: &str, pinned: bool) -> Result<bool>;
 }
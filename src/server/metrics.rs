@@ -0,0 +1,149 @@
+//! Prometheus exposition of node metrics and benchmark summaries.
+
+use crate::domain::metrics::line_protocol::{run_points, sample_points, BENCHMARK_MEASUREMENT};
+use crate::domain::metrics::prometheus::{Exposition, MetricType};
+use crate::domain::metrics::series::{MetricKind, MetricSample};
+use crate::domain::storage::history::{RunRecord, GIT_REVISION, TOOL_VERSION};
+use std::collections::{BTreeMap, HashMap};
+
+/// Content type of the Prometheus text format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Builds the exposition served on `/metrics`.
+///
+/// Every collector sample becomes a set of `sysperf_<metric>_<field>` gauges labelled with
+/// `host` and the sample's own labels (CPU, device).  Each run in `runs` becomes a set of
+/// `sysperf_benchmark_<field>` gauges labelled with its host, target, profile, job name and
+/// job parameters; pass only the latest run of each job.
+pub fn exposition(
+    host: &str,
+    samples: &[MetricSample],
+    errors: &HashMap<MetricKind, u64>,
+    collect: &[MetricKind],
+    runs: &[RunRecord],
+) -> Exposition {
+    let mut exposition = Exposition::new();
+    let host_labels = BTreeMap::from([("host".to_string(), host.to_string())]);
+
+    let mut build_labels = host_labels.clone();
+    build_labels.insert("version".into(), TOOL_VERSION.into());
+    build_labels.insert("revision".into(), GIT_REVISION.unwrap_or("unknown").into());
+    exposition.add(
+        "sysperf_build_info",
+        "Version of sysperf-svr serving these metrics",
+        MetricType::Gauge,
+        &build_labels,
+        1.0,
+    );
+
+    for sample in samples {
+        let points = sample_points(
+            sample.metric,
+            sample.timestamp_ms,
+            &sample.data,
+            &host_labels,
+        );
+        exposition.add_points(&points, |source, field| {
+            format!("{} reported by the {} collector", field, source)
+        });
+        exposition.add(
+            "sysperf_collector_last_sample_timestamp_seconds",
+            "Unix time of the latest sample of each collector",
+            MetricType::Gauge,
+            &collector_labels(&host_labels, sample.metric),
+            sample.timestamp_ms as f64 / 1000.0,
+        );
+    }
+    for &kind in collect {
+        exposition.add(
+            "sysperf_collector_errors_total",
+            "Failed collections since the server started",
+            MetricType::Counter,
+            &collector_labels(&host_labels, kind),
+            errors.get(&kind).copied().unwrap_or(0) as f64,
+        );
+    }
+
+    for run in runs {
+        let summary: Vec<_> = run_points(run)
+            .into_iter()
+            .filter(|point| point.measurement == BENCHMARK_MEASUREMENT)
+            .collect();
+        exposition.add_points(&summary, |_, field| {
+            format!("{} of the latest run of each benchmark job", field)
+        });
+        if let Some(point) = summary.first() {
+            exposition.add(
+                "sysperf_benchmark_timestamp_seconds",
+                "Unix time at which the latest run of each benchmark job finished",
+                MetricType::Gauge,
+                &point.tags,
+                run.timestamp as f64,
+            );
+        }
+    }
+
+    exposition
+}
+
+fn collector_labels(host: &BTreeMap<String, String>, kind: MetricKind) -> BTreeMap<String, String> {
+    let mut labels = host.clone();
+    labels.insert("collector".into(), kind.to_string());
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::storage::fio::{FioJobConfig, FioResult, StorageTarget};
+    use serde_json::json;
+
+    #[test]
+    fn test_exposition_of_samples_and_runs() {
+        let samples = vec![MetricSample {
+            metric: MetricKind::Iostat,
+            timestamp_ms: 1_700_000_000_000,
+            data: json!([{"device": "nvme0n1", "util": 87.5}]),
+        }];
+        let errors = HashMap::from([(MetricKind::Mpstat, 2)]);
+
+        let mut run = RunRecord::new(
+            "randread_4k_nvme_direct",
+            &StorageTarget::new_file("/dev/nvme0n1", None),
+            FioResult::default(),
+        );
+        run.hostname = "node1".into();
+        run.target = Some("nvme_direct".into());
+        run.profile = Some("randread_4k".into());
+        run.job = Some(FioJobConfig::default());
+        run.result.read.iops = 250000.0;
+
+        let text = exposition(
+            "node1",
+            &samples,
+            &errors,
+            &[MetricKind::Mpstat, MetricKind::Iostat],
+            &[run],
+        )
+        .render();
+
+        assert!(text.contains("sysperf_iostat_util{device=\"nvme0n1\",host=\"node1\"} 87.5\n"));
+        assert!(text.contains("# TYPE sysperf_collector_errors_total counter\n"));
+        assert!(text
+            .contains("sysperf_collector_errors_total{collector=\"mpstat\",host=\"node1\"} 2\n"));
+        assert!(text
+            .contains("sysperf_collector_errors_total{collector=\"iostat\",host=\"node1\"} 0\n"));
+        assert!(text.contains(
+            "sysperf_collector_last_sample_timestamp_seconds{collector=\"iostat\",host=\"node1\"} 1700000000\n"
+        ));
+
+        let iops = text
+            .lines()
+            .find(|l| l.starts_with("sysperf_benchmark_read_iops{"))
+            .unwrap();
+        assert!(iops.contains("profile=\"randread_4k\""));
+        assert!(iops.contains("target=\"nvme_direct\""));
+        assert!(iops.ends_with("} 250000"));
+        assert!(!text.contains("run_id"));
+    }
+}
@@ -0,0 +1,649 @@
+//! HTTP server mode
+//!
+//! `sysperf-svr serve` keeps sampling the node's system metrics in the background, runs
+//! benchmark jobs submitted over HTTP and answers queries about them:
+//!
+//! | Route                   | Response                                                        |
+//! |-------------------------|-----------------------------------------------------------------|
+//! | `GET /`                 | A short index of the routes                                     |
+//! | `GET /metrics`          | Collector samples and the latest benchmark summaries for Prometheus |
+//! | `POST /jobs`            | Queues `{"profile": ..., "target": ..., "timeout": ...}`; `202` with the job |
+//! | `GET /jobs`             | Every job known to this server, newest first                    |
+//! | `GET /jobs/{id}`        | Status of one job                                               |
+//! | `GET /jobs/{id}/log`    | The job's log, streamed until the job finishes                  |
+//! | `GET /jobs/{id}/result` | The run a succeeded job saved; `409` while it has not           |
+//! | `POST /jobs/{id}/cancel`| Cancels a queued or running job; `409` once it has finished     |
+//! | `GET /runs`             | Stored runs, filtered by `host`, `target`, `profile`, `since`, `until` and `limit` |
+//! | `GET /runs/{id}`        | One stored run                                                  |
+//!
+//! API responses are JSON; errors are `{"error": "..."}`.  Up to `storage.max_concurrent_tests`
+//! jobs run at once, never two on the same disk, see [`JobQueue`].
+//!
+//! Submitting and cancelling jobs require `Authorization: Bearer <token>` with the token set as
+//! `[server] api_token`; without a token those routes answer `403` and the API is read-only.
+
+pub mod metrics;
+
+use crate::application::jobs::{JobError, JobQueue, JobSpec, JobState};
+use crate::application::sampler::{MetricsSampler, Sampling};
+use crate::application::Application;
+use crate::domain::storage::history::{HostIdentity, RunFilter};
+use crate::ports::metrics_port::MetricKind;
+use anyhow::anyhow;
+use serde::Serialize;
+use std::io::{self, Cursor, Read, Write};
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+use tiny_http::{Header, Method, Request, Response};
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+const INDEX: &str = "sysperf-svr\n\n\
+    GET  /metrics           Prometheus metrics\n\
+    POST /jobs              Submit a benchmark job: {\"profile\": ..., \"target\": ..., \"timeout\": ...} (bearer token)\n\
+    GET  /jobs              List jobs\n\
+    GET  /jobs/{id}         Job status\n\
+    GET  /jobs/{id}/log     Follow a job's log\n\
+    GET  /jobs/{id}/result  Run saved by a job\n\
+    POST /jobs/{id}/cancel  Cancel a job (bearer token)\n\
+    GET  /runs              List stored runs (?host=&target=&profile=&since=&until=&limit=)\n\
+    GET  /runs/{id}         One stored run\n";
+
+/// Largest request body accepted
+const MAX_BODY_BYTES: u64 = 64 * 1024;
+
+/// How long a log stream waits for new lines before checking the job again
+const LOG_POLL: Duration = Duration::from_secs(1);
+
+/// An HTTP server bound to its address
+pub struct Server<'a> {
+    app: &'a Application,
+    http: tiny_http::Server,
+    sampling: Sampling,
+    collect: Vec<MetricKind>,
+    jobs: JobQueue,
+    host: String,
+}
+
+impl<'a> Server<'a> {
+    /// Binds to `addr` and starts sampling `collect` every `interval`.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the address cannot be bound.
+    pub fn bind(
+        app: &'a Application,
+        addr: SocketAddr,
+        collect: Vec<MetricKind>,
+        interval: Duration,
+    ) -> anyhow::Result<Self> {
+        let http = tiny_http::Server::http(addr)
+            .map_err(|e| anyhow!("Failed to listen on {}: {}", addr, e))?;
+        let sampling = MetricsSampler::new(app.metrics.clone(), collect.clone(), interval).start();
+
+        Ok(Self {
+            app,
+            http,
+            sampling,
+            collect,
+            jobs: JobQueue::new(),
+            host: HostIdentity::detect().hostname,
+        })
+    }
+
+    /// Lets jobs write to the raw-device `targets`; see [`JobQueue::allow_data_loss`].
+    pub fn allow_data_loss(mut self, targets: Vec<String>) -> Self {
+        self.jobs = self.jobs.allow_data_loss(targets);
+        self
+    }
+
+    /// The address the server listens on.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Answers requests and runs submitted jobs until [`stop`](Self::stop) is called.
+    ///
+    /// Every request is answered on its own thread, so following a job's log does not hold up
+    /// other clients.  After `stop`, returns once the running jobs have stopped.
+    pub fn run(&self) {
+        thread::scope(|scope| {
+            scope.spawn(|| self.jobs.work(self.app));
+            for request in self.http.incoming_requests() {
+                scope.spawn(move || self.handle(request));
+            }
+            self.jobs.close();
+        });
+    }
+
+    /// Stops accepting requests; running jobs are cancelled and queued ones are failed.
+    pub fn stop(&self) {
+        self.http.unblock();
+    }
+
+    fn handle(&self, mut request: Request) {
+        let method = request.method().clone();
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        self.app.logger.log_debug(&format!("{} {}", method, path));
+
+        let response = match (&method, segments.as_slice()) {
+            (Method::Get, []) => text(200, INDEX, "text/plain; charset=utf-8"),
+            (Method::Get, ["metrics"]) => self.metrics(),
+            (Method::Get, ["jobs"]) => json(200, &self.jobs.list()),
+            (Method::Post, ["jobs"]) => match self.authorize(&request) {
+                Ok(()) => self.submit(&mut request),
+                Err(denied) => denied,
+            },
+            (Method::Get, ["jobs", id]) => match self.jobs.get(id) {
+                Some(job) => json(200, &job),
+                None => error(404, &format!("No job with ID {}", id)),
+            },
+            (Method::Get, ["jobs", id, "log"]) => return self.stream_log(request, id),
+            (Method::Get, ["jobs", id, "result"]) => self.job_result(id),
+            (Method::Post, ["jobs", id, "cancel"]) => match self.authorize(&request) {
+                Ok(()) => match self.jobs.cancel(id) {
+                    Ok(job) => json(200, &job),
+                    Err(e @ JobError::NotFound(_)) => error(404, &e.to_string()),
+                    Err(e @ JobError::Finished { .. }) => error(409, &e.to_string()),
+                },
+                Err(denied) => denied,
+            },
+            (Method::Get, ["runs"]) => self.list_runs(query),
+            (Method::Get, ["runs", id]) => self.get_run(id),
+            (
+                _,
+                []
+                | ["metrics"]
+                | ["jobs"]
+                | ["jobs", _]
+                | ["jobs", _, "log" | "result" | "cancel"]
+                | ["runs"]
+                | ["runs", _],
+            ) => error(405, "Method not allowed"),
+            _ => error(404, "Not found"),
+        };
+        if let Err(e) = request.respond(response) {
+            self.app
+                .logger
+                .log_warn(&format!("Failed to send response: {}", e));
+        }
+    }
+
+    fn metrics(&self) -> HttpResponse {
+        // Node metrics are still worth serving when the database is unavailable
+        let runs = self.app.latest_runs().unwrap_or_else(|e| {
+            self.app
+                .logger
+                .log_warn(&format!("Failed to read stored runs: {:#}", e));
+            Vec::new()
+        });
+        let exposition = metrics::exposition(
+            &self.host,
+            &self.sampling.latest(),
+            &self.sampling.errors(),
+            &self.collect,
+            &runs,
+        );
+        text(200, &exposition.render(), metrics::CONTENT_TYPE)
+    }
+
+    /// Checks the bearer token that routes changing jobs require.
+    fn authorize(&self, request: &Request) -> Result<(), HttpResponse> {
+        let Some(token) = &self.app.config.server.api_token else {
+            return Err(error(
+                403,
+                "The job API is read-only; set [server] api_token to submit and cancel jobs",
+            ));
+        };
+        let given = request
+            .headers()
+            .iter()
+            .find(|header| header.field.equiv("Authorization"))
+            .and_then(|header| header.value.as_str().strip_prefix("Bearer "));
+        match given {
+            Some(given) if same_secret(given.trim().as_bytes(), token.as_bytes()) => Ok(()),
+            _ => {
+                let challenge =
+                    Header::from_bytes("WWW-Authenticate", "Bearer").expect("valid header");
+                Err(error(401, "Missing or wrong bearer token").with_header(challenge))
+            }
+        }
+    }
+
+    fn submit(&self, request: &mut Request) -> HttpResponse {
+        let mut body = String::new();
+        if let Err(e) = request
+            .as_reader()
+            .take(MAX_BODY_BYTES)
+            .read_to_string(&mut body)
+        {
+            return error(400, &format!("Failed to read request body: {}", e));
+        }
+        let spec: JobSpec = match serde_json::from_str(&body) {
+            Ok(spec) => spec,
+            Err(e) => return error(400, &format!("Invalid job: {}", e)),
+        };
+        let job = match self.jobs.submit(self.app, spec) {
+            Ok(job) => job,
+            Err(e) => return error(400, &format!("{:#}", e)),
+        };
+        let location =
+            Header::from_bytes("Location", format!("/jobs/{}", job.id)).expect("valid header");
+        json(202, &job).with_header(location)
+    }
+
+    fn job_result(&self, id: &str) -> HttpResponse {
+        let Some(job) = self.jobs.get(id) else {
+            return error(404, &format!("No job with ID {}", id));
+        };
+        match (job.state, job.run_id) {
+            (JobState::Succeeded, Some(run_id)) => self.get_run(&run_id),
+            (JobState::Succeeded, None) => error(
+                404,
+                &format!("Job {} succeeded but its run was not saved", id),
+            ),
+            (state @ (JobState::Failed | JobState::TimedOut | JobState::Cancelled), _) => error(
+                409,
+                &format!("Job {} {}: {}", id, state, job.error.unwrap_or_default()),
+            ),
+            (state, _) => error(409, &format!("Job {} is {}", id, state)),
+        }
+    }
+
+    fn list_runs(&self, query: &str) -> HttpResponse {
+        let filter = match run_filter(query) {
+            Ok(filter) => filter,
+            Err(e) => return error(400, &e),
+        };
+        match self.app.storage.list_runs(&filter) {
+            Ok(runs) => json(200, &runs),
+            Err(e) => error(500, &format!("Failed to list runs: {:#}", e)),
+        }
+    }
+
+    fn get_run(&self, id: &str) -> HttpResponse {
+        match self.app.storage.get_run(id) {
+            Ok(Some(run)) => json(200, &run),
+            Ok(None) => error(404, &format!("No run with ID {}", id)),
+            Err(e) => error(500, &format!("Failed to read run {}: {:#}", id, e)),
+        }
+    }
+
+    /// Streams the log of job `id` with chunked encoding, one chunk per batch of new lines.
+    ///
+    /// tiny_http buffers chunked bodies, so the response is written to the connection directly
+    /// to get every line to the client as soon as it is logged.
+    fn stream_log(&self, request: Request, id: &str) {
+        if self.jobs.get(id).is_none() {
+            let response = error(404, &format!("No job with ID {}", id));
+            if let Err(e) = request.respond(response) {
+                self.app
+                    .logger
+                    .log_warn(&format!("Failed to send response: {}", e));
+            }
+            return;
+        }
+
+        let mut writer = request.into_writer();
+        let mut stream = || -> io::Result<()> {
+            writer.write_all(
+                b"HTTP/1.1 200 OK\r\n\
+                  Content-Type: text/plain; charset=utf-8\r\n\
+                  Transfer-Encoding: chunked\r\n\
+                  Connection: close\r\n\r\n",
+            )?;
+            writer.flush()?;
+
+            let mut next = 0;
+            while let Some((lines, finished)) = self.jobs.read_log(id, next, LOG_POLL) {
+                if !lines.is_empty() {
+                    next += lines.len();
+                    let chunk = lines.join("\n") + "\n";
+                    write!(writer, "{:x}\r\n{}\r\n", chunk.len(), chunk)?;
+                    writer.flush()?;
+                }
+                if finished {
+                    break;
+                }
+            }
+            writer.write_all(b"0\r\n\r\n")?;
+            writer.flush()
+        };
+        if let Err(e) = stream() {
+            self.app
+                .logger
+                .log_debug(&format!("Stopped streaming the log of job {}: {}", id, e));
+        }
+    }
+}
+
+/// Parses the query string of `GET /runs` into a filter.
+fn run_filter(query: &str) -> Result<RunFilter, String> {
+    let mut filter = RunFilter::default();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value);
+        let invalid = |_| format!("Invalid {} \"{}\"", key, value);
+        match key {
+            "host" => filter.host = Some(value),
+            "target" => filter.target = Some(value),
+            "profile" => filter.profile = Some(value),
+            "since" => filter.since = Some(value.parse().map_err(invalid)?),
+            "until" => filter.until = Some(value.parse().map_err(invalid)?),
+            "limit" => filter.limit = Some(value.parse().map_err(invalid)?),
+            _ => return Err(format!("Unknown parameter \"{}\"", key)),
+        }
+    }
+    Ok(filter)
+}
+
+/// Decodes `%XX` escapes and `+` in a query string value.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Compares two secrets in a time that does not depend on where they differ.
+fn same_secret(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn text(status: u16, body: &str, content_type: &str) -> HttpResponse {
+    let header = Header::from_bytes("Content-Type", content_type).expect("valid header");
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header)
+}
+
+fn json<T: Serialize>(status: u16, body: &T) -> HttpResponse {
+    match serde_json::to_string_pretty(body) {
+        Ok(body) => text(status, &(body + "\n"), "application/json"),
+        Err(e) => error(500, &format!("Failed to encode response: {}", e)),
+    }
+}
+
+fn error(status: u16, message: &str) -> HttpResponse {
+    let body = serde_json::json!({ "error": message });
+    text(status, &format!("{}\n", body), "application/json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::benchmark_adapter::BenchmarkAdapter;
+    use crate::adapters::database_adapter::DatabaseAdapter;
+    use crate::adapters::log_adapter::FernLogger;
+    use crate::adapters::storage_adapter::StorageAdapter;
+    use crate::application::jobs::JobLogger;
+    use crate::config::Config;
+    use crate::domain::storage::fio::{FioResult, StorageTarget};
+    use crate::domain::storage::history::RunRecord;
+    use crate::ports::log_port::LoggerPort;
+    use crate::ports::metrics_port::MetricsPort;
+    use anyhow::{anyhow, Result};
+    use serde_json::{json, Value};
+    use std::path::Path;
+    use std::sync::Arc;
+
+    const TOKEN: &str = "s3cret";
+
+    struct LoadOnly;
+
+    impl MetricsPort for LoadOnly {
+        fn collect_mpstat(&self) -> Result<Value> {
+            Err(anyhow!("no mpstat"))
+        }
+        fn collect_vmstat(&self) -> Result<Value> {
+            Err(anyhow!("no vmstat"))
+        }
+        fn collect_cpuinfo(&self) -> Result<Value> {
+            Err(anyhow!("no cpuinfo"))
+        }
+        fn collect_memoryinfo(&self) -> Result<Value> {
+            Err(anyhow!("no meminfo"))
+        }
+        fn collect_iostat(&self) -> Result<Value> {
+            Err(anyhow!("no iostat"))
+        }
+        fn collect_loadavg(&self) -> Result<Value> {
+            Ok(json!({"one": 0.5, "five": 0.25, "fifteen": 0.125}))
+        }
+    }
+
+    /// An application whose "fio" always fails, storing runs under `dir`
+    fn app(dir: &Path) -> Application {
+        let config = Config::parse(
+            &format!(
+                "[storage]\ntest_directory = \"{}\"\n\
+                 [storage.fio.defaults]\nsize = \"1M\"\n\
+                 [storage.fio.profiles.quick]\nrw = \"randread\"\n\
+                 [metrics]\nrecord_benchmarks = false\n\
+                 [server]\napi_token = \"{}\"\n",
+                dir.join("scratch").display(),
+                TOKEN
+            ),
+            Path::new("t.toml"),
+        )
+        .unwrap();
+        let logger: Arc<dyn LoggerPort> = Arc::new(JobLogger::new(Arc::new(FernLogger::new())));
+        let db = Arc::new(DatabaseAdapter::new(dir.join("db")));
+        Application::new(
+            config,
+            db.clone(),
+            Arc::new(
+                BenchmarkAdapter::new("false".into(), vec![], logger.clone())
+                    .with_benchmark_dir(dir.join("scratch")),
+            ),
+            Arc::new(LoadOnly),
+            Arc::new(StorageAdapter::new(db)),
+            logger,
+        )
+    }
+
+    fn status(result: std::result::Result<ureq::Response, ureq::Error>) -> u16 {
+        match result {
+            Ok(response) => response.status(),
+            Err(ureq::Error::Status(status, _)) => status,
+            Err(e) => panic!("request failed: {}", e),
+        }
+    }
+
+    fn get_json(url: &str) -> Value {
+        let body = ureq::get(url).call().unwrap().into_string().unwrap();
+        serde_json::from_str(&body).unwrap()
+    }
+
+    /// Posts `body` with the API token and returns the status and the JSON response
+    fn post_json(url: &str, body: Value) -> (u16, Value) {
+        let response = match ureq::post(url)
+            .set("Authorization", &format!("Bearer {}", TOKEN))
+            .set("Content-Type", "application/json")
+            .send_string(&body.to_string())
+        {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => panic!("request failed: {}", e),
+        };
+        let status = response.status();
+        (
+            status,
+            serde_json::from_str(&response.into_string().unwrap()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_serves_metrics() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = app(dir.path());
+        app.config.server.api_token = None;
+
+        let server = Server::bind(
+            &app,
+            "127.0.0.1:0".parse().unwrap(),
+            vec![MetricKind::Loadavg, MetricKind::Iostat],
+            Duration::from_millis(20),
+        )
+        .unwrap();
+        let base = format!("http://{}", server.local_addr().unwrap());
+        thread::sleep(Duration::from_millis(100));
+
+        thread::scope(|scope| {
+            scope.spawn(|| server.run());
+
+            let response = ureq::get(&format!("{}/metrics", base)).call().unwrap();
+            assert_eq!(response.header("Content-Type"), Some(metrics::CONTENT_TYPE));
+            let body = response.into_string().unwrap();
+            assert!(body.contains("sysperf_loadavg_one{host="));
+            assert!(body.contains("sysperf_collector_errors_total{collector=\"iostat\""));
+            assert!(body.contains("# TYPE sysperf_build_info gauge\n"));
+
+            assert_eq!(status(ureq::get(&format!("{}/nope", base)).call()), 404);
+            assert_eq!(status(ureq::post(&format!("{}/metrics", base)).call()), 405);
+
+            // Without a token the job API is read-only
+            let submit =
+                ureq::post(&format!("{}/jobs", base)).send_string("{\"profile\": \"quick\"}");
+            assert_eq!(status(submit), 403);
+            assert_eq!(status(ureq::get(&format!("{}/jobs", base)).call()), 200);
+
+            server.stop();
+        });
+    }
+
+    #[test]
+    fn test_jobs_and_runs_api() {
+        let dir = tempfile::tempdir().unwrap();
+        let app = app(dir.path());
+        let mut stored = RunRecord::new(
+            "quick",
+            &StorageTarget::new_file("/dev/nvme0n1", None),
+            FioResult::default(),
+        );
+        stored.profile = Some("quick".into());
+        app.storage.save_run(&stored).unwrap();
+
+        let server = Server::bind(
+            &app,
+            "127.0.0.1:0".parse().unwrap(),
+            vec![MetricKind::Loadavg],
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        let base = format!("http://{}", server.local_addr().unwrap());
+
+        thread::scope(|scope| {
+            scope.spawn(|| server.run());
+
+            // Changing jobs takes the token
+            let jobs_url = format!("{}/jobs", base);
+            let quick = "{\"profile\": \"quick\"}";
+            assert_eq!(status(ureq::post(&jobs_url).send_string(quick)), 401);
+            let wrong = ureq::post(&jobs_url).set("Authorization", "Bearer s3cre7");
+            assert_eq!(status(wrong.send_string(quick)), 401);
+            assert_eq!(
+                status(ureq::post(&format!("{}/jobs/x/cancel", base)).call()),
+                401
+            );
+
+            // Unknown profiles and fields are rejected up front
+            assert_eq!(post_json(&jobs_url, json!({"profile": "nope"})).0, 400);
+            assert_eq!(
+                post_json(&jobs_url, json!({"profile": "quick", "bs": "4k"})).0,
+                400
+            );
+            let unsafe_job = json!({"profile": "quick", "i_understand_data_loss": true});
+            assert_eq!(post_json(&jobs_url, unsafe_job).0, 400);
+            let (code, body) = post_json(&jobs_url, json!({"profile": "quick", "target": "nope"}));
+            assert_eq!(code, 400);
+            assert!(body["error"].as_str().unwrap().contains("nope"));
+
+            let (code, job) = post_json(&jobs_url, json!({"profile": "quick"}));
+            assert_eq!(code, 202);
+            let id = job["id"].as_str().unwrap().to_string();
+            assert_eq!(job["profile"], "quick");
+
+            // The log streams until the job has finished
+            let log = ureq::get(&format!("{}/jobs/{}/log", base, id))
+                .call()
+                .unwrap()
+                .into_string()
+                .unwrap();
+            assert!(log.contains(&format!("[INFO] - Job {} started", id)));
+            assert!(log.contains(&format!("[ERROR] - Job {} failed", id)));
+
+            let job: Value = get_json(&format!("{}/jobs/{}", base, id));
+            assert_eq!(job["state"], "failed");
+            assert_eq!(
+                status(ureq::get(&format!("{}/jobs/{}/result", base, id)).call()),
+                409
+            );
+            let cancel = |id: &str| {
+                let request = ureq::post(&format!("{}/jobs/{}/cancel", base, id));
+                status(
+                    request
+                        .set("Authorization", &format!("Bearer {}", TOKEN))
+                        .call(),
+                )
+            };
+            assert_eq!(cancel(&id), 409);
+            assert_eq!(cancel("nope"), 404);
+            let jobs: Value = get_json(&format!("{}/jobs", base));
+            assert_eq!(jobs.as_array().unwrap().len(), 1);
+            assert_eq!(
+                status(ureq::get(&format!("{}/jobs/nope", base)).call()),
+                404
+            );
+
+            let runs: Value = get_json(&format!("{}/runs?profile=quick&limit=5", base));
+            assert_eq!(runs[0]["id"], stored.id.as_str());
+            let run: Value = get_json(&format!("{}/runs/{}", base, stored.id));
+            assert_eq!(run["name"], "quick");
+            assert_eq!(
+                status(ureq::get(&format!("{}/runs?limit=x", base)).call()),
+                400
+            );
+            assert_eq!(
+                status(ureq::get(&format!("{}/runs/nope", base)).call()),
+                404
+            );
+
+            server.stop();
+        });
+    }
+
+    #[test]
+    fn test_run_filter_from_query() {
+        let filter =
+            run_filter("host=node%201&target=nvme_direct&since=1717200000&limit=3").unwrap();
+        assert_eq!(filter.host.as_deref(), Some("node 1"));
+        assert_eq!(filter.target.as_deref(), Some("nvme_direct"));
+        assert_eq!(filter.since, Some(1717200000));
+        assert_eq!(filter.limit, Some(3));
+
+        assert_eq!(run_filter("").unwrap(), RunFilter::default());
+        assert!(run_filter("since=yesterday").is_err());
+        assert!(run_filter("sort=asc").is_err());
+        assert_eq!(percent_decode("a+b%2Fc%"), "a b/c%");
+    }
+}